@@ -10,7 +10,9 @@ use blame::BlameRoot;
 use blobrepo::BlobRepo;
 use blobrepo_errors::*;
 use blobstore::Blobstore;
-use blobstore_factory::{make_blobstore, make_metadata_sql_factory, MetadataSqlFactory};
+use blobstore_factory::{
+    make_blobstore, make_local_cache, make_metadata_sql_factory, MetadataSqlFactory,
+};
 use bonsai_git_mapping::SqlBonsaiGitMappingConnection;
 use bonsai_globalrev_mapping::SqlBonsaiGlobalrevMapping;
 use bonsai_hg_mapping::{CachingBonsaiHgMapping, SqlBonsaiHgMapping};
@@ -159,6 +161,7 @@ impl<'a> BlobrepoBuilder<'a> {
         );
 
         let (sql_factory, blobstore) = future::try_join(sql_factory, blobstore).await?;
+        let blobstore = make_local_cache(blobstore, &blobstore_options.local_cache_options)?;
 
         open_blobrepo_given_datasources(
             fb,
@@ -395,6 +398,7 @@ pub fn init_all_derived_data() -> DerivedDataConfig {
             TreeHandle::NAME.to_string(),
         },
         unode_version: UnodeVersion::V2,
+        blame_filesize_limit: None,
     }
 }
 
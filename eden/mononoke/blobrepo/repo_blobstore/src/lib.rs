@@ -6,10 +6,10 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use context::CoreContext;
 use context_concurrency_blobstore::ContextConcurrencyBlobstore;
-use futures_ext::BoxFuture;
+use futures_ext::{BoxFuture, BoxStream};
 use mononoke_types::{BlobstoreBytes, RepositoryId};
 use prefixblob::PrefixBlobstore;
 use redactedblobstore::{RedactedBlobstore, RedactedBlobstoreConfig};
@@ -69,6 +69,20 @@ where
     fn assert_present(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
         self.0.assert_present(ctx, key)
     }
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.0.enumerate(ctx, range)
+    }
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.0.delete(ctx, key)
+    }
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.0.overwrite(ctx, key, value)
+    }
 }
 
 pub type RepoBlobstore = AbstractRepoBlobstore<Arc<dyn Blobstore>>;
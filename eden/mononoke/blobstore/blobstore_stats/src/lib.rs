@@ -31,6 +31,8 @@ pub enum OperationType {
     Get,
     Put,
     ScrubGet,
+    Delete,
+    Overwrite,
 }
 
 impl From<OperationType> for ScubaValue {
@@ -39,6 +41,8 @@ impl From<OperationType> for ScubaValue {
             OperationType::Get => ScubaValue::from("get"),
             OperationType::Put => ScubaValue::from("put"),
             OperationType::ScrubGet => ScubaValue::from("scrub_get"),
+            OperationType::Delete => ScubaValue::from("delete"),
+            OperationType::Overwrite => ScubaValue::from("overwrite"),
         }
     }
 }
@@ -118,3 +122,29 @@ pub fn record_put_stats(
 
     scuba.log();
 }
+
+pub fn record_delete_stats(
+    scuba: &mut ScubaSampleBuilder,
+    stats: FutureStats,
+    result: Result<&(), &Error>,
+    key: String,
+    session: String,
+    blobstore_id: Option<BlobstoreId>,
+) {
+    // Deletes are rare and can't be undone, so always log them
+    scuba.unsampled();
+    add_common_values(
+        scuba,
+        key,
+        session,
+        stats,
+        OperationType::Delete,
+        blobstore_id,
+    );
+
+    if let Err(error) = result {
+        scuba.add(ERROR, error.to_string());
+    }
+
+    scuba.log();
+}
@@ -0,0 +1,412 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::convert::TryInto;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Error;
+use bytes::{BufMut, Bytes, BytesMut};
+use filetime::FileTime;
+use futures::future::{FutureExt as NewFutureExt, TryFutureExt};
+use futures_ext::{BoxFuture, FutureExt};
+use mononoke_types::hash::Context;
+use tokio::task;
+
+use blobstore::{Blobstore, BlobstoreBytes, BlobstoreGetData, BlobstoreMetadata, CountedBlobstore};
+
+use crate::in_process_lease::InProcessLease;
+use crate::locking_cache::{CacheBlobstore, CacheOps};
+
+const MAGIC: &[u8; 8] = b"MNCACHE\x01";
+const BLOBS_DIR: &str = "blobs";
+const TMP_DIR: &str = "tmp";
+// Temporary files this old were left behind by a writer that crashed
+const STALE_TMP_AGE: Duration = Duration::from_secs(3600);
+
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A caching layer over an existing blobstore, backed by a directory on local disk. The directory
+/// can be shared by every process on the host. Entries are written to a temporary file and renamed
+/// into place, so a crashed writer never leaves a partial entry behind, and the least recently
+/// used entries are evicted once the directory grows past `max_bytes`.
+#[derive(Clone)]
+pub struct DiskCacheOps {
+    inner: Arc<DiskCache>,
+}
+
+struct DiskCache {
+    root: PathBuf,
+    max_bytes: u64,
+    // Size of the directory at the last scan. Other processes' writes are only seen by rescanning,
+    // which is why a rescan is also forced after a tenth of `max_bytes` of our own writes.
+    scanned_bytes: AtomicU64,
+    written_bytes: AtomicU64,
+    evicting: AtomicBool,
+}
+
+impl DiskCacheOps {
+    pub fn new(root: impl AsRef<Path>, max_bytes: u64) -> Result<Self, Error> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join(BLOBS_DIR))?;
+        fs::create_dir_all(root.join(TMP_DIR))?;
+
+        let cache = DiskCache {
+            root,
+            max_bytes,
+            scanned_bytes: AtomicU64::new(0),
+            written_bytes: AtomicU64::new(0),
+            evicting: AtomicBool::new(false),
+        };
+        cache.evict()?;
+
+        Ok(Self {
+            inner: Arc::new(cache),
+        })
+    }
+
+    #[cfg(test)]
+    fn size_on_disk(&self) -> u64 {
+        self.inner.scan().unwrap().1
+    }
+}
+
+pub fn new_disk_cache_blobstore<T>(
+    blobstore: T,
+    root: impl AsRef<Path>,
+    max_bytes: u64,
+) -> Result<CountedBlobstore<CacheBlobstore<DiskCacheOps, InProcessLease, T>>, Error>
+where
+    T: Blobstore + Clone,
+{
+    let cache_ops = DiskCacheOps::new(root, max_bytes)?;
+    Ok(CountedBlobstore::new(
+        "disk_cache".to_string(),
+        CacheBlobstore::new(cache_ops, InProcessLease::new(), blobstore),
+    ))
+}
+
+impl DiskCache {
+    fn entry_path(&self, key: &str) -> PathBuf {
+        let mut context = Context::new(b"cacheblob.disk");
+        context.update(key);
+        let name = context.finish().to_hex();
+        self.root
+            .join(BLOBS_DIR)
+            .join(&name.as_str()[..2])
+            .join(name.as_str())
+    }
+
+    fn read(&self, key: &str) -> Result<Option<BlobstoreGetData>, Error> {
+        let path = self.entry_path(key);
+        let data = match fs::read(&path) {
+            Ok(data) => Bytes::from(data),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match decode_entry(key, data) {
+            Some(value) => {
+                // Hits count as uses for eviction
+                let _ = filetime::set_file_mtime(&path, FileTime::now());
+                Ok(Some(value))
+            }
+            None => {
+                // Truncated by a crash or left by an older format, so let it be refilled
+                let _ = fs::remove_file(&path);
+                Ok(None)
+            }
+        }
+    }
+
+    fn write(&self, key: &str, value: BlobstoreGetData) -> Result<(), Error> {
+        let path = self.entry_path(key);
+        let entry = encode_entry(key, value);
+
+        let tmp_path = self.root.join(TMP_DIR).join(format!(
+            "{}.{}",
+            process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let res = fs::File::create(&tmp_path)
+            .and_then(|mut file| file.write_all(&entry))
+            .and_then(|()| match path.parent() {
+                Some(parent) => fs::create_dir_all(parent),
+                None => Ok(()),
+            })
+            .and_then(|()| fs::rename(&tmp_path, &path));
+        if let Err(e) = res {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
+
+        let written = self
+            .written_bytes
+            .fetch_add(entry.len() as u64, Ordering::Relaxed)
+            + entry.len() as u64;
+        if written > self.max_bytes / 10
+            || self.scanned_bytes.load(Ordering::Relaxed) + written > self.max_bytes
+        {
+            self.maybe_evict()?;
+        }
+        Ok(())
+    }
+
+    fn maybe_evict(&self) -> Result<(), Error> {
+        // Another task is already on it
+        if self.evicting.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let res = self.evict();
+        self.evicting.store(false, Ordering::Release);
+        res
+    }
+
+    // List every entry as (last use, size, path), and the total size. Entries can be removed by
+    // other processes while we look at them, so anything that disappears is skipped.
+    fn scan(&self) -> Result<(Vec<(SystemTime, u64, PathBuf)>, u64), Error> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for shard in fs::read_dir(self.root.join(BLOBS_DIR))? {
+            let shard = shard?;
+            let files = match fs::read_dir(shard.path()) {
+                Ok(files) => files,
+                Err(_) => continue,
+            };
+            for file in files.filter_map(Result::ok) {
+                if let Ok(meta) = file.metadata() {
+                    let mtime = meta.modified()?;
+                    total += meta.len();
+                    entries.push((mtime, meta.len(), file.path()));
+                }
+            }
+        }
+        Ok((entries, total))
+    }
+
+    // Remove the least recently used entries until the cache is at 90% of its limit, and clear out
+    // temporary files abandoned by crashed writers.
+    fn evict(&self) -> Result<(), Error> {
+        let now = SystemTime::now();
+        for tmp in fs::read_dir(self.root.join(TMP_DIR))?.filter_map(Result::ok) {
+            let stale = tmp
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .map(|mtime| now.duration_since(mtime).unwrap_or_default() > STALE_TMP_AGE)
+                .unwrap_or(false);
+            if stale {
+                let _ = fs::remove_file(tmp.path());
+            }
+        }
+
+        let (mut entries, mut total) = self.scan()?;
+        self.written_bytes.store(0, Ordering::Relaxed);
+        let target = self.max_bytes / 10 * 9;
+        if total > target {
+            entries.sort_by_key(|(mtime, _, _)| *mtime);
+            for (_, len, path) in entries {
+                if total <= target {
+                    break;
+                }
+                match fs::remove_file(&path) {
+                    Ok(()) => total -= len,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => total -= len,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        self.scanned_bytes.store(total, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Entries are MAGIC, the key, the ctime and the value, with lengths so that truncation is
+// detected. The key is stored so that a read can't return the value of another key.
+fn encode_entry(key: &str, value: BlobstoreGetData) -> Bytes {
+    let ctime = *value.as_meta().as_ctime();
+    let bytes = value.into_raw_bytes();
+    let mut entry = BytesMut::with_capacity(MAGIC.len() + 4 + key.len() + 9 + 8 + bytes.len());
+    entry.put_slice(MAGIC);
+    entry.put_u32(key.len() as u32);
+    entry.put_slice(key.as_bytes());
+    match ctime {
+        Some(ctime) => {
+            entry.put_u8(1);
+            entry.put_i64(ctime);
+        }
+        None => {
+            entry.put_u8(0);
+            entry.put_i64(0);
+        }
+    }
+    entry.put_u64(bytes.len() as u64);
+    entry.put_slice(&bytes);
+    entry.freeze()
+}
+
+fn decode_entry(key: &str, mut entry: Bytes) -> Option<BlobstoreGetData> {
+    fn split(entry: &mut Bytes, len: usize) -> Option<Bytes> {
+        if entry.len() < len {
+            None
+        } else {
+            Some(entry.split_to(len))
+        }
+    }
+
+    if split(&mut entry, MAGIC.len())?.as_ref() != MAGIC {
+        return None;
+    }
+    let key_len = u32::from_be_bytes(split(&mut entry, 4)?.as_ref().try_into().ok()?);
+    if split(&mut entry, key_len as usize)?.as_ref() != key.as_bytes() {
+        return None;
+    }
+    let has_ctime = split(&mut entry, 1)?[0] == 1;
+    let ctime = i64::from_be_bytes(split(&mut entry, 8)?.as_ref().try_into().ok()?);
+    let len = u64::from_be_bytes(split(&mut entry, 8)?.as_ref().try_into().ok()?);
+    if entry.len() as u64 != len {
+        return None;
+    }
+    Some(BlobstoreGetData::new(
+        BlobstoreMetadata::new(if has_ctime { Some(ctime) } else { None }),
+        BlobstoreBytes::from_bytes(entry),
+    ))
+}
+
+// Filesystem access blocks, so keep it off the executor threads
+fn blocking<T, F>(f: F) -> BoxFuture<T, ()>
+where
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+    T: Send + 'static,
+{
+    async move {
+        match task::spawn_blocking(f).await {
+            Ok(Ok(res)) => Ok(res),
+            _ => Err(()),
+        }
+    }
+    .boxed()
+    .compat()
+    .boxify()
+}
+
+impl CacheOps for DiskCacheOps {
+    const CACHE_NAME: &'static str = "disk";
+
+    fn get(&self, key: &str) -> BoxFuture<Option<BlobstoreGetData>, ()> {
+        let inner = self.inner.clone();
+        let key = key.to_string();
+        blocking(move || inner.read(&key))
+    }
+
+    fn put(&self, key: &str, value: BlobstoreGetData) -> BoxFuture<(), ()> {
+        let inner = self.inner.clone();
+        let key = key.to_string();
+        blocking(move || inner.write(&key, value))
+    }
+
+    fn check_present(&self, key: &str) -> BoxFuture<bool, ()> {
+        let inner = self.inner.clone();
+        let key = key.to_string();
+        blocking(move || Ok(inner.entry_path(&key).exists()))
+    }
+}
+
+impl fmt::Debug for DiskCacheOps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskCacheOps")
+            .field("root", &self.inner.root)
+            .field("max_bytes", &self.inner.max_bytes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fbinit::FacebookInit;
+    use futures::compat::Future01CompatExt;
+    use tempdir::TempDir;
+
+    fn value(bytes: &'static [u8], ctime: Option<i64>) -> BlobstoreGetData {
+        BlobstoreGetData::new(
+            BlobstoreMetadata::new(ctime),
+            BlobstoreBytes::from_bytes(bytes),
+        )
+    }
+
+    #[fbinit::compat_test]
+    async fn get_put(_fb: FacebookInit) -> Result<(), ()> {
+        let dir = TempDir::new("disk_cache").unwrap();
+        let cache = DiskCacheOps::new(dir.path(), 1024 * 1024).unwrap();
+
+        assert_eq!(cache.get("foo").compat().await?, None);
+        assert!(!cache.check_present("foo").compat().await?);
+
+        cache
+            .put("foo", value(b"foobar", Some(10)))
+            .compat()
+            .await?;
+        assert_eq!(
+            cache.get("foo").compat().await?,
+            Some(value(b"foobar", Some(10)))
+        );
+        assert!(cache.check_present("foo").compat().await?);
+
+        // A second cache on the same directory, as another process would have, sees the entry
+        let other = DiskCacheOps::new(dir.path(), 1024 * 1024).unwrap();
+        assert_eq!(
+            other.get("foo").compat().await?,
+            Some(value(b"foobar", Some(10)))
+        );
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn truncated_entry(_fb: FacebookInit) -> Result<(), ()> {
+        let dir = TempDir::new("disk_cache").unwrap();
+        let cache = DiskCacheOps::new(dir.path(), 1024 * 1024).unwrap();
+
+        cache.put("foo", value(b"foobar", None)).compat().await?;
+        let path = cache.inner.entry_path("foo");
+        let entry = fs::read(&path).unwrap();
+        fs::write(&path, &entry[..entry.len() - 1]).unwrap();
+
+        assert_eq!(cache.get("foo").compat().await?, None);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn eviction(_fb: FacebookInit) -> Result<(), ()> {
+        let dir = TempDir::new("disk_cache").unwrap();
+        let max_bytes = 1000;
+        let cache = DiskCacheOps::new(dir.path(), max_bytes).unwrap();
+
+        cache.put("first", value(&[0; 100], None)).compat().await?;
+        // Make sure "first" is the least recently used, whatever the mtime resolution
+        filetime::set_file_mtime(
+            cache.inner.entry_path("first"),
+            FileTime::from_unix_time(0, 0),
+        )
+        .unwrap();
+        for i in 0..10 {
+            cache
+                .put(&format!("key{}", i), value(&[0; 100], None))
+                .compat()
+                .await?;
+        }
+
+        assert!(cache.size_on_disk() <= max_bytes);
+        assert_eq!(cache.get("first").compat().await?, None);
+        Ok(())
+    }
+}
@@ -10,6 +10,9 @@
 mod cachelib_cache;
 pub use crate::cachelib_cache::{new_cachelib_blobstore, new_cachelib_blobstore_no_lease};
 
+mod disk_cache;
+pub use crate::disk_cache::{new_disk_cache_blobstore, DiskCacheOps};
+
 pub mod dummy;
 
 mod in_process_lease;
@@ -27,3 +30,6 @@ pub use crate::memcache_cache_lease::{
 
 mod mem_writes;
 pub use crate::mem_writes::MemWritesBlobstore;
+
+mod memory_cache;
+pub use crate::memory_cache::{new_memory_lru_blobstore, MemoryLruOps};
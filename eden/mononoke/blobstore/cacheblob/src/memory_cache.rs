@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use futures_ext::{BoxFuture, FutureExt};
+use futures_old::IntoFuture;
+use lru_cache::LruCache;
+
+use blobstore::{Blobstore, BlobstoreGetData, CountedBlobstore};
+
+use crate::in_process_lease::InProcessLease;
+use crate::locking_cache::{CacheBlobstore, CacheOps};
+
+/// A caching layer over an existing blobstore, backed by an in-process LRU holding at most
+/// `max_bytes` of keys and values.
+#[derive(Clone)]
+pub struct MemoryLruOps {
+    inner: Arc<Mutex<MemoryLru>>,
+}
+
+struct MemoryLru {
+    entries: LruCache<String, BlobstoreGetData>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+fn entry_size(key: &str, value: &BlobstoreGetData) -> usize {
+    key.len() + value.as_raw_bytes().len()
+}
+
+impl MemoryLruOps {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MemoryLru {
+                entries: LruCache::new(usize::max_value()),
+                bytes: 0,
+                max_bytes,
+            })),
+        }
+    }
+}
+
+pub fn new_memory_lru_blobstore<T>(
+    blobstore: T,
+    max_bytes: usize,
+) -> CountedBlobstore<CacheBlobstore<MemoryLruOps, InProcessLease, T>>
+where
+    T: Blobstore + Clone,
+{
+    let cache_ops = MemoryLruOps::new(max_bytes);
+    CountedBlobstore::new(
+        "memory_lru".to_string(),
+        CacheBlobstore::new(cache_ops, InProcessLease::new(), blobstore),
+    )
+}
+
+impl CacheOps for MemoryLruOps {
+    const CACHE_NAME: &'static str = "memory_lru";
+
+    fn get(&self, key: &str) -> BoxFuture<Option<BlobstoreGetData>, ()> {
+        let mut lru = self.inner.lock().expect("lock poisoned");
+        Ok(lru.entries.get_mut(key).cloned()).into_future().boxify()
+    }
+
+    fn put(&self, key: &str, value: BlobstoreGetData) -> BoxFuture<(), ()> {
+        let size = entry_size(key, &value);
+        let mut lru = self.inner.lock().expect("lock poisoned");
+        // Don't flush the whole cache for a single blob
+        if size <= lru.max_bytes {
+            if let Some(old) = lru.entries.insert(key.to_string(), value) {
+                lru.bytes -= entry_size(key, &old);
+            }
+            lru.bytes += size;
+            while lru.bytes > lru.max_bytes {
+                match lru.entries.remove_lru() {
+                    Some((key, value)) => lru.bytes -= entry_size(&key, &value),
+                    None => break,
+                }
+            }
+        }
+        Ok(()).into_future().boxify()
+    }
+
+    fn check_present(&self, key: &str) -> BoxFuture<bool, ()> {
+        let mut lru = self.inner.lock().expect("lock poisoned");
+        Ok(lru.entries.contains_key(key)).into_future().boxify()
+    }
+}
+
+impl fmt::Debug for MemoryLruOps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lru = self.inner.lock().expect("lock poisoned");
+        f.debug_struct("MemoryLruOps")
+            .field("bytes", &lru.bytes)
+            .field("max_bytes", &lru.max_bytes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures_old::Future;
+
+    #[test]
+    fn lru_eviction() {
+        let cache = MemoryLruOps::new(30);
+        let value = || BlobstoreGetData::from_bytes(&b"0123456789"[..]);
+
+        for key in &["k1", "k2"] {
+            cache.put(key, value()).wait().unwrap();
+        }
+        // Use k1, so k2 is the least recently used
+        assert_eq!(cache.get("k1").wait().unwrap(), Some(value()));
+        cache.put("k3", value()).wait().unwrap();
+
+        assert!(cache.check_present("k1").wait().unwrap());
+        assert!(!cache.check_present("k2").wait().unwrap());
+        assert!(cache.check_present("k3").wait().unwrap());
+    }
+
+    #[test]
+    fn oversized_value() {
+        let cache = MemoryLruOps::new(30);
+        cache
+            .put("small", BlobstoreGetData::from_bytes(&b"0123456789"[..]))
+            .wait()
+            .unwrap();
+        cache
+            .put("big", BlobstoreGetData::from_bytes(vec![0; 100]))
+            .wait()
+            .unwrap();
+
+        assert!(cache.check_present("small").wait().unwrap());
+        assert!(!cache.check_present("big").wait().unwrap());
+    }
+}
@@ -6,10 +6,10 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use context::CoreContext;
 use futures::future;
-use futures_ext::{BoxFuture, FutureExt};
+use futures_ext::{BoxFuture, BoxStream, FutureExt};
 use mononoke_types::BlobstoreBytes;
 use rand::{thread_rng, Rng};
 use std::num::NonZeroU32;
@@ -109,6 +109,31 @@ impl<T: Blobstore + Clone> Blobstore for ChaosBlobstore<T> {
             self.blobstore.is_present(ctx, key)
         }
     }
+
+    #[inline]
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.blobstore.enumerate(ctx, range)
+    }
+
+    #[inline]
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.blobstore.delete(ctx, key)
+    }
+
+    #[inline]
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        let should_error = thread_rng().gen::<f32>() > self.sample_threshold_write;
+        if should_error {
+            future::err(ErrorKind::InjectedChaosPut(key).into()).boxify()
+        } else {
+            self.blobstore.overwrite(ctx, key, value)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,156 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt;
+use std::hash::Hasher;
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use twox_hash::XxHash32;
+
+use crate::errors::ErrorKind;
+
+/// Identifies the codec a blob was encoded with. The discriminants are written into every
+/// compressed blob, so they must never be changed or reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CodecId {
+    Uncompressed = 0,
+    Zstd = 1,
+    ZstdWithDictionary = 2,
+}
+
+impl CodecId {
+    pub fn from_u8(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(CodecId::Uncompressed),
+            1 => Some(CodecId::Zstd),
+            2 => Some(CodecId::ZstdWithDictionary),
+            _ => None,
+        }
+    }
+}
+
+/// A reversible transformation of blob contents. The output of `encode` must be self-contained:
+/// anything `decode` needs other than the codec's own configuration has to be in the output.
+pub trait Codec: fmt::Debug + Send + Sync + 'static {
+    fn id(&self) -> CodecId;
+
+    fn encode(&self, raw: &[u8]) -> Result<Vec<u8>>;
+
+    fn decode(&self, encoded: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Stores blobs as they are. Used for blobs that do not compress well.
+#[derive(Debug)]
+pub struct Uncompressed;
+
+impl Codec for Uncompressed {
+    fn id(&self) -> CodecId {
+        CodecId::Uncompressed
+    }
+
+    fn encode(&self, raw: &[u8]) -> Result<Vec<u8>> {
+        Ok(raw.to_vec())
+    }
+
+    fn decode(&self, encoded: &[u8]) -> Result<Vec<u8>> {
+        Ok(encoded.to_vec())
+    }
+}
+
+/// Plain zstd compression at a fixed level.
+#[derive(Debug)]
+pub struct Zstd {
+    level: i32,
+}
+
+impl Zstd {
+    pub fn new(level: i32) -> Self {
+        Self { level }
+    }
+}
+
+impl Codec for Zstd {
+    fn id(&self) -> CodecId {
+        CodecId::Zstd
+    }
+
+    fn encode(&self, raw: &[u8]) -> Result<Vec<u8>> {
+        Ok(zstd::stream::encode_all(raw, self.level)?)
+    }
+
+    fn decode(&self, encoded: &[u8]) -> Result<Vec<u8>> {
+        Ok(zstd::stream::decode_all(encoded)?)
+    }
+}
+
+/// Zstd compression with a pre-trained dictionary. Small blobs of similar structure (e.g.
+/// serialized manifests) compress much better with a dictionary than on their own.
+///
+/// The encoded form starts with a 4 byte big-endian dictionary id, so that blobs written with a
+/// different dictionary are detected rather than silently corrupted.
+pub struct ZstdWithDictionary {
+    level: i32,
+    dictionary_id: u32,
+    dictionary: Vec<u8>,
+}
+
+impl ZstdWithDictionary {
+    pub fn new(level: i32, dictionary: Vec<u8>) -> Self {
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(&dictionary);
+        Self {
+            level,
+            dictionary_id: hasher.finish() as u32,
+            dictionary,
+        }
+    }
+
+    pub fn dictionary_id(&self) -> u32 {
+        self.dictionary_id
+    }
+}
+
+impl fmt::Debug for ZstdWithDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstdWithDictionary")
+            .field("level", &self.level)
+            .field("dictionary_id", &self.dictionary_id)
+            .finish()
+    }
+}
+
+impl Codec for ZstdWithDictionary {
+    fn id(&self) -> CodecId {
+        CodecId::ZstdWithDictionary
+    }
+
+    fn encode(&self, raw: &[u8]) -> Result<Vec<u8>> {
+        let mut encoded = self.dictionary_id.to_be_bytes().to_vec();
+        let mut encoder =
+            zstd::stream::Encoder::with_dictionary(&mut encoded, self.level, &self.dictionary)?;
+        encoder.write_all(raw)?;
+        encoder.finish()?;
+        Ok(encoded)
+    }
+
+    fn decode(&self, encoded: &[u8]) -> Result<Vec<u8>> {
+        if encoded.len() < 4 {
+            return Err(ErrorKind::Truncated.into());
+        }
+        let (id, frame) = encoded.split_at(4);
+        let dictionary_id = u32::from_be_bytes([id[0], id[1], id[2], id[3]]);
+        if dictionary_id != self.dictionary_id {
+            return Err(ErrorKind::UnknownDictionary(dictionary_id).into());
+        }
+
+        let mut decoded = Vec::new();
+        zstd::stream::Decoder::with_dictionary(frame, &self.dictionary)?
+            .read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+}
@@ -0,0 +1,22 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use thiserror::Error;
+
+use crate::codec::CodecId;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Compressed blob {0} uses unknown codec {1}")]
+    UnknownCodec(String, u8),
+    #[error("Compressed blob {0} uses codec {1:?}, which is not configured")]
+    CodecNotConfigured(String, CodecId),
+    #[error("Compressed blob was written with unknown dictionary {0:08x}")]
+    UnknownDictionary(u32),
+    #[error("Compressed blob is truncated")]
+    Truncated,
+}
@@ -0,0 +1,280 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod codec;
+mod errors;
+
+pub use crate::codec::{Codec, CodecId, Uncompressed, Zstd, ZstdWithDictionary};
+pub use crate::errors::ErrorKind;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::{Error, Result};
+use bytes::Bytes;
+use futures::Future;
+use futures_ext::{try_boxfuture, BoxFuture, BoxStream, FutureExt};
+
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+
+/// Every blob written by CompressedBlobstore starts with this, followed by a byte identifying
+/// the codec. Blobs that do not start with it were written before compression was enabled,
+/// and are returned unchanged.
+const MAGIC: &[u8] = b"\x89MNCBLB\n";
+const HEADER_LEN: usize = 9;
+/// Blobs smaller than this are stored uncompressed without trying the codec.
+const MIN_COMPRESS_SIZE: usize = 64;
+
+/// A layer over an existing blobstore that compresses values on put and decompresses them on
+/// get. Values the codec cannot shrink are stored uncompressed.
+#[derive(Clone)]
+pub struct CompressedBlobstore<T> {
+    blobstore: T,
+    codec: Arc<dyn Codec>,
+    decoders: Arc<HashMap<CodecId, Arc<dyn Codec>>>,
+}
+
+impl<T: Blobstore + Clone> CompressedBlobstore<T> {
+    /// Compress new blobs with `codec`. Blobs written with `codec`, uncompressed or with plain
+    /// zstd can be read back.
+    pub fn new(blobstore: T, codec: Arc<dyn Codec>) -> Self {
+        let mut decoders: HashMap<CodecId, Arc<dyn Codec>> = HashMap::new();
+        decoders.insert(CodecId::Uncompressed, Arc::new(Uncompressed));
+        decoders.insert(CodecId::Zstd, Arc::new(Zstd::new(0)));
+        decoders.insert(codec.id(), codec.clone());
+
+        Self {
+            blobstore,
+            codec,
+            decoders: Arc::new(decoders),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.blobstore
+    }
+
+    pub fn as_inner(&self) -> &T {
+        &self.blobstore
+    }
+
+    fn encode(&self, value: BlobstoreBytes) -> Result<BlobstoreBytes> {
+        let raw = value.as_bytes();
+        let (codec_id, encoded) = if raw.len() < MIN_COMPRESS_SIZE {
+            (CodecId::Uncompressed, None)
+        } else {
+            let encoded = self.codec.encode(raw)?;
+            if encoded.len() < raw.len() {
+                (self.codec.id(), Some(encoded))
+            } else {
+                (CodecId::Uncompressed, None)
+            }
+        };
+        let payload = encoded.as_ref().map_or(raw.as_ref(), Vec::as_slice);
+
+        let mut envelope = Vec::with_capacity(HEADER_LEN + payload.len());
+        envelope.extend_from_slice(MAGIC);
+        envelope.push(codec_id as u8);
+        envelope.extend_from_slice(payload);
+        Ok(BlobstoreBytes::from_bytes(envelope))
+    }
+}
+
+fn decode(decoders: &HashMap<CodecId, Arc<dyn Codec>>, key: &str, bytes: Bytes) -> Result<Bytes> {
+    if !bytes.starts_with(MAGIC) {
+        return Ok(bytes);
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(ErrorKind::Truncated.into());
+    }
+
+    let codec_byte = bytes[MAGIC.len()];
+    let codec_id = CodecId::from_u8(codec_byte)
+        .ok_or_else(|| ErrorKind::UnknownCodec(key.to_string(), codec_byte))?;
+    if codec_id == CodecId::Uncompressed {
+        return Ok(bytes.slice(HEADER_LEN..));
+    }
+
+    let codec = decoders
+        .get(&codec_id)
+        .ok_or_else(|| ErrorKind::CodecNotConfigured(key.to_string(), codec_id))?;
+    Ok(Bytes::from(codec.decode(&bytes[HEADER_LEN..])?))
+}
+
+impl<T: Blobstore + Clone> Blobstore for CompressedBlobstore<T> {
+    fn get(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+        let decoders = self.decoders.clone();
+        self.blobstore
+            .get(ctx, key.clone())
+            .and_then(move |maybe_data| {
+                maybe_data
+                    .map(|data| -> Result<_> {
+                        let meta = data.as_meta().clone();
+                        let bytes = decode(&decoders, &key, data.into_raw_bytes())?;
+                        Ok(BlobstoreGetData::new(
+                            meta,
+                            BlobstoreBytes::from_bytes(bytes),
+                        ))
+                    })
+                    .transpose()
+            })
+            .boxify()
+    }
+
+    fn put(&self, ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
+        let value = try_boxfuture!(self.encode(value));
+        self.blobstore.put(ctx, key, value)
+    }
+
+    fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
+        self.blobstore.is_present(ctx, key)
+    }
+
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.blobstore.enumerate(ctx, range)
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.blobstore.delete(ctx, key)
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        let value = try_boxfuture!(self.encode(value));
+        self.blobstore.overwrite(ctx, key, value)
+    }
+}
+
+impl<T: Blobstore + Clone> fmt::Debug for CompressedBlobstore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressedBlobstore")
+            .field("blobstore", &self.blobstore)
+            .field("codec", &self.codec)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use fbinit::FacebookInit;
+    use rand::{thread_rng, RngCore};
+
+    use memblob::EagerMemblob;
+
+    fn roundtrip(
+        ctx: &CoreContext,
+        store: &CompressedBlobstore<EagerMemblob>,
+        key: &str,
+        value: Bytes,
+    ) -> Bytes {
+        // We're using EagerMemblob (immediate future completion) so calling wait() is fine.
+        store
+            .put(
+                ctx.clone(),
+                key.to_string(),
+                BlobstoreBytes::from_bytes(value.clone()),
+            )
+            .wait()
+            .expect("put should succeed");
+        let out = store
+            .get(ctx.clone(), key.to_string())
+            .wait()
+            .expect("get should succeed")
+            .expect("value should be present")
+            .into_raw_bytes();
+        assert_eq!(out, value);
+
+        store
+            .as_inner()
+            .get(ctx.clone(), key.to_string())
+            .wait()
+            .expect("get should succeed")
+            .expect("value should be present")
+            .into_raw_bytes()
+    }
+
+    #[fbinit::test]
+    fn test_compressible(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let store = CompressedBlobstore::new(EagerMemblob::new(), Arc::new(Zstd::new(3)));
+
+        let value = Bytes::from(vec![b'a'; 4096]);
+        let stored = roundtrip(&ctx, &store, "compressible", value.clone());
+        assert!(stored.starts_with(MAGIC));
+        assert_eq!(stored[MAGIC.len()], CodecId::Zstd as u8);
+        assert!(stored.len() < value.len());
+    }
+
+    #[fbinit::test]
+    fn test_incompressible(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let store = CompressedBlobstore::new(EagerMemblob::new(), Arc::new(Zstd::new(3)));
+
+        let mut random = vec![0u8; 4096];
+        thread_rng().fill_bytes(&mut random);
+        let stored = roundtrip(&ctx, &store, "random", Bytes::from(random));
+        assert_eq!(stored[MAGIC.len()], CodecId::Uncompressed as u8);
+
+        let stored = roundtrip(&ctx, &store, "small", Bytes::from_static(b"small"));
+        assert_eq!(stored[MAGIC.len()], CodecId::Uncompressed as u8);
+    }
+
+    #[fbinit::test]
+    fn test_dictionary(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let dictionary = b"mononoke manifest entry ".repeat(16);
+        let codec = Arc::new(ZstdWithDictionary::new(3, dictionary.clone()));
+        let store = CompressedBlobstore::new(EagerMemblob::new(), codec);
+
+        let value = Bytes::from(b"mononoke manifest entry 1234 ".repeat(8));
+        let stored = roundtrip(&ctx, &store, "dict", value);
+        assert_eq!(stored[MAGIC.len()], CodecId::ZstdWithDictionary as u8);
+
+        // A store configured with a different dictionary cannot read it.
+        let other = CompressedBlobstore::new(
+            store.as_inner().clone(),
+            Arc::new(ZstdWithDictionary::new(3, b"other".repeat(16))),
+        );
+        assert!(other.get(ctx, "dict".to_string()).wait().is_err());
+    }
+
+    #[fbinit::test]
+    fn test_legacy_uncompressed(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let store = CompressedBlobstore::new(base.clone(), Arc::new(Zstd::new(3)));
+
+        base.put(
+            ctx.clone(),
+            "legacy".to_string(),
+            BlobstoreBytes::from_bytes("written before compression"),
+        )
+        .wait()
+        .expect("put should succeed");
+
+        assert_eq!(
+            store
+                .get(ctx, "legacy".to_string())
+                .wait()
+                .expect("get should succeed")
+                .expect("value should be present")
+                .into_raw_bytes(),
+            Bytes::from("written before compression"),
+        );
+    }
+}
@@ -6,11 +6,12 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use cloned::cloned;
 use context::{CoreContext, PerfCounterType};
 use futures::{compat::Future01CompatExt, FutureExt as _, TryFutureExt};
-use futures_ext::{BoxFuture, FutureExt};
+use futures_ext::{BoxFuture, BoxStream, FutureExt, StreamExt};
+use futures_old::Future;
 use futures_stats::TimedTryFutureExt;
 use mononoke_types::BlobstoreBytes;
 use time_ext::DurationExt;
@@ -102,6 +103,46 @@ impl<T: Blobstore + Clone> Blobstore for ContextConcurrencyBlobstore<T> {
         .compat()
         .boxify()
     }
+
+    // Enumeration is limited as a single read, as it is only used by offline tooling.
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        cloned!(self.blobstore);
+        async move {
+            access(&ctx, AccessReason::Read).await?;
+            Ok(blobstore.enumerate(ctx, range))
+        }
+        .boxed()
+        .compat()
+        .flatten_stream()
+        .boxify()
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        cloned!(self.blobstore);
+        async move {
+            access(&ctx, AccessReason::Write).await?;
+            blobstore.delete(ctx, key).compat().await
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        cloned!(self.blobstore);
+        async move {
+            access(&ctx, AccessReason::Write).await?;
+            blobstore.overwrite(ctx, key, value).compat().await
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,34 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use thiserror::Error;
+
+use crate::keys::KeyId;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Blob {0} is not encrypted")]
+    NotEncrypted(String),
+    #[error("Encrypted blob {0} is truncated")]
+    Truncated(String),
+    #[error("Blob {0} is encrypted with unknown key {1}")]
+    UnknownKey(String, KeyId),
+    #[error("Blob {0} failed to decrypt: it is corrupt or has been tampered with")]
+    DecryptionFailed(String),
+    #[error("Blob {0} is missing")]
+    Missing(String),
+    #[error("Blob {0} is still encrypted with key {1} after being re-encrypted")]
+    NotReencrypted(String, KeyId),
+    #[error("Invalid keyfile line {0}: expected '<key id> <hex key>'")]
+    InvalidKeyfileLine(usize),
+    #[error("Key {0} is {1} bytes long, expected {2}")]
+    InvalidKeyLength(KeyId, usize, usize),
+    #[error("Key {0} is defined more than once")]
+    DuplicateKey(KeyId),
+    #[error("Key {0} is configured for encryption, but is not in the keyfile")]
+    CurrentKeyMissing(KeyId),
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::errors::ErrorKind;
+
+/// Identifies a key. Written into every encrypted blob, so that blobs written before a key
+/// rotation can still be decrypted.
+pub type KeyId = u32;
+
+/// Length of an AES-256 key.
+pub const KEY_LEN: usize = 32;
+
+/// The keys an EncryptedBlobstore can decrypt with, and the one it encrypts new blobs with.
+pub struct EncryptionKeys {
+    current: KeyId,
+    keys: HashMap<KeyId, Vec<u8>>,
+}
+
+impl EncryptionKeys {
+    pub fn new(current: KeyId, keys: HashMap<KeyId, Vec<u8>>) -> Result<Self> {
+        for (id, key) in keys.iter() {
+            if key.len() != KEY_LEN {
+                return Err(ErrorKind::InvalidKeyLength(*id, key.len(), KEY_LEN).into());
+            }
+        }
+        if !keys.contains_key(&current) {
+            return Err(ErrorKind::CurrentKeyMissing(current).into());
+        }
+        Ok(Self { current, keys })
+    }
+
+    /// Load keys from a keyfile. Each line of the keyfile holds a numeric key id and the
+    /// hex-encoded key, separated by whitespace. Empty lines and lines starting with `#` are
+    /// ignored. Old keys should be kept in the keyfile until all blobs have been re-encrypted.
+    pub fn from_keyfile(path: &Path, current: KeyId) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("While reading keyfile {}", path.display()))?;
+        Self::parse(&contents, current)
+            .with_context(|| format!("While parsing keyfile {}", path.display()))
+    }
+
+    fn parse(contents: &str, current: KeyId) -> Result<Self> {
+        let mut keys = HashMap::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || ErrorKind::InvalidKeyfileLine(index + 1);
+            let mut parts = line.split_whitespace();
+            let (id, key) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(id), Some(key), None) => (id, key),
+                _ => return Err(invalid().into()),
+            };
+            let id: KeyId = id.parse().map_err(|_| invalid())?;
+            let key = hex::decode(key).map_err(|_| invalid())?;
+            if keys.insert(id, key).is_some() {
+                return Err(ErrorKind::DuplicateKey(id).into());
+            }
+        }
+        Self::new(current, keys)
+    }
+
+    pub fn current_id(&self) -> KeyId {
+        self.current
+    }
+
+    pub(crate) fn current(&self) -> (KeyId, &[u8]) {
+        (self.current, &self.keys[&self.current])
+    }
+
+    pub(crate) fn get(&self, id: KeyId) -> Option<&[u8]> {
+        self.keys.get(&id).map(Vec::as_slice)
+    }
+}
+
+impl fmt::Debug for EncryptionKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print key material.
+        let mut ids: Vec<_> = self.keys.keys().collect();
+        ids.sort();
+        f.debug_struct("EncryptionKeys")
+            .field("current", &self.current)
+            .field("ids", &ids)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY_A: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const KEY_B: &str = "1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100";
+
+    #[test]
+    fn test_parse_keyfile() -> Result<()> {
+        let contents = format!("# rotated 2020-06\n1 {}\n\n2 {}\n", KEY_A, KEY_B);
+        let keys = EncryptionKeys::parse(&contents, 2)?;
+        assert_eq!(keys.current_id(), 2);
+        assert_eq!(keys.get(1), Some(hex::decode(KEY_A)?.as_slice()));
+        assert!(keys.get(3).is_none());
+        assert!(!format!("{:?}", keys).contains(KEY_A));
+
+        assert!(EncryptionKeys::parse(&contents, 3).is_err());
+        assert!(EncryptionKeys::parse("1 abcd\n", 1).is_err());
+        assert!(EncryptionKeys::parse(&format!("1 {} extra\n", KEY_A), 1).is_err());
+        assert!(EncryptionKeys::parse(&format!("1 {}\n1 {}\n", KEY_A, KEY_B), 1).is_err());
+        Ok(())
+    }
+}
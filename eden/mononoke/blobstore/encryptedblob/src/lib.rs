@@ -0,0 +1,477 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod errors;
+mod keys;
+
+pub use crate::errors::ErrorKind;
+pub use crate::keys::{EncryptionKeys, KeyId, KEY_LEN};
+
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::{Error, Result};
+use bytes::Bytes;
+use cloned::cloned;
+use futures::{future, Future};
+use futures_ext::{try_boxfuture, BoxFuture, BoxStream, FutureExt};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+
+/// Every blob written by EncryptedBlobstore starts with this, followed by the big-endian id of
+/// the key it is encrypted with, the nonce, the ciphertext and finally the authentication tag.
+const MAGIC: &[u8] = b"\x89MNENCB\n";
+const KEY_ID_LEN: usize = 4;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = 24;
+
+/// A layer over an existing blobstore that encrypts values with AES-256-GCM before storing
+/// them, so that the underlying blobstore never sees plaintext.
+///
+/// The blobstore key and the envelope header are authenticated along with the value, so a
+/// value moved to a different key, or tampered with in any way, fails to decrypt.
+#[derive(Clone)]
+pub struct EncryptedBlobstore<T> {
+    blobstore: T,
+    keys: Arc<EncryptionKeys>,
+}
+
+impl<T: Blobstore + Clone> EncryptedBlobstore<T> {
+    pub fn new(blobstore: T, keys: Arc<EncryptionKeys>) -> Self {
+        Self { blobstore, keys }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.blobstore
+    }
+
+    pub fn as_inner(&self) -> &T {
+        &self.blobstore
+    }
+
+    /// Re-encrypt the blob stored under `key` with the current key. Returns false if it was
+    /// already encrypted with the current key.
+    ///
+    /// The blob is replaced with `Blobstore::overwrite`, so the underlying blobstore must support
+    /// it. As a safety net the blob is read back afterwards, and `ErrorKind::NotReencrypted` is
+    /// returned if it is still encrypted with the old key.
+    pub fn reencrypt(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
+        self.blobstore
+            .get(ctx.clone(), key.clone())
+            .and_then({
+                cloned!(self.blobstore, self.keys);
+                move |maybe_data| {
+                    let data =
+                        try_boxfuture!(maybe_data.ok_or_else(|| ErrorKind::Missing(key.clone())));
+                    let bytes = data.into_raw_bytes();
+                    if try_boxfuture!(key_id(&key, &bytes)) == keys.current_id() {
+                        return future::ok(false).boxify();
+                    }
+
+                    let plaintext = try_boxfuture!(decrypt(&keys, &key, &bytes));
+                    let encrypted = try_boxfuture!(encrypt(&keys, &key, &plaintext));
+                    blobstore
+                        .overwrite(ctx.clone(), key.clone(), encrypted)
+                        .and_then({
+                            cloned!(blobstore, key);
+                            move |()| blobstore.get(ctx, key)
+                        })
+                        .and_then(move |maybe_data| {
+                            let data = maybe_data.ok_or_else(|| ErrorKind::Missing(key.clone()))?;
+                            let stored_id = key_id(&key, data.as_raw_bytes())?;
+                            if stored_id != keys.current_id() {
+                                return Err(ErrorKind::NotReencrypted(key, stored_id).into());
+                            }
+                            Ok(true)
+                        })
+                        .boxify()
+                }
+            })
+            .boxify()
+    }
+}
+
+/// AES-GCM additional data: the envelope header followed by the blobstore key.
+fn additional_data(header: &[u8], key: &str) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(header.len() + key.len());
+    aad.extend_from_slice(header);
+    aad.extend_from_slice(key.as_bytes());
+    aad
+}
+
+fn encrypt(keys: &EncryptionKeys, key: &str, plaintext: &[u8]) -> Result<BlobstoreBytes> {
+    let (key_id, secret) = keys.current();
+
+    let mut envelope = Vec::with_capacity(HEADER_LEN + plaintext.len() + TAG_LEN);
+    envelope.extend_from_slice(MAGIC);
+    envelope.extend_from_slice(&key_id.to_be_bytes());
+    let mut nonce = [0u8; NONCE_LEN];
+    openssl::rand::rand_bytes(&mut nonce)?;
+    envelope.extend_from_slice(&nonce);
+
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        secret,
+        Some(&nonce),
+        &additional_data(&envelope, key),
+        plaintext,
+        &mut tag,
+    )?;
+    envelope.extend_from_slice(&ciphertext);
+    envelope.extend_from_slice(&tag);
+    Ok(BlobstoreBytes::from_bytes(envelope))
+}
+
+fn key_id(key: &str, bytes: &[u8]) -> Result<KeyId> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(ErrorKind::NotEncrypted(key.to_string()).into());
+    }
+    if bytes.len() < HEADER_LEN + TAG_LEN {
+        return Err(ErrorKind::Truncated(key.to_string()).into());
+    }
+    let id = &bytes[MAGIC.len()..MAGIC.len() + KEY_ID_LEN];
+    Ok(KeyId::from_be_bytes([id[0], id[1], id[2], id[3]]))
+}
+
+fn decrypt(keys: &EncryptionKeys, key: &str, bytes: &[u8]) -> Result<Bytes> {
+    let key_id = key_id(key, bytes)?;
+    let secret = keys
+        .get(key_id)
+        .ok_or_else(|| ErrorKind::UnknownKey(key.to_string(), key_id))?;
+
+    let (header, rest) = bytes.split_at(HEADER_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+    let nonce = &header[MAGIC.len() + KEY_ID_LEN..];
+    let plaintext = decrypt_aead(
+        Cipher::aes_256_gcm(),
+        secret,
+        Some(nonce),
+        &additional_data(header, key),
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| ErrorKind::DecryptionFailed(key.to_string()))?;
+    Ok(Bytes::from(plaintext))
+}
+
+impl<T: Blobstore + Clone> Blobstore for EncryptedBlobstore<T> {
+    fn get(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+        let keys = self.keys.clone();
+        self.blobstore
+            .get(ctx, key.clone())
+            .and_then(move |maybe_data| {
+                maybe_data
+                    .map(|data| -> Result<_> {
+                        let meta = data.as_meta().clone();
+                        let bytes = decrypt(&keys, &key, data.as_raw_bytes())?;
+                        Ok(BlobstoreGetData::new(
+                            meta,
+                            BlobstoreBytes::from_bytes(bytes),
+                        ))
+                    })
+                    .transpose()
+            })
+            .boxify()
+    }
+
+    fn put(&self, ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
+        let value = try_boxfuture!(encrypt(&self.keys, &key, value.as_bytes()));
+        self.blobstore.put(ctx, key, value)
+    }
+
+    fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
+        self.blobstore.is_present(ctx, key)
+    }
+
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.blobstore.enumerate(ctx, range)
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.blobstore.delete(ctx, key)
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        let value = try_boxfuture!(encrypt(&self.keys, &key, value.as_bytes()));
+        self.blobstore.overwrite(ctx, key, value)
+    }
+}
+
+impl<T: Blobstore + Clone> fmt::Debug for EncryptedBlobstore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedBlobstore")
+            .field("blobstore", &self.blobstore)
+            .field("keys", &self.keys)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use fbinit::FacebookInit;
+
+    use memblob::EagerMemblob;
+    use sqlblob::Sqlblob;
+    use tokio_compat::runtime::Runtime;
+
+    fn keys(current: KeyId, ids: &[KeyId]) -> Arc<EncryptionKeys> {
+        let keys: HashMap<_, _> = ids
+            .iter()
+            .map(|id| (*id, vec![*id as u8; KEY_LEN]))
+            .collect();
+        Arc::new(EncryptionKeys::new(current, keys).expect("keys should be valid"))
+    }
+
+    fn raw_get(ctx: &CoreContext, store: &EagerMemblob, key: &str) -> Bytes {
+        // We're using EagerMemblob (immediate future completion) so calling wait() is fine.
+        store
+            .get(ctx.clone(), key.to_string())
+            .wait()
+            .expect("get should succeed")
+            .expect("value should be present")
+            .into_raw_bytes()
+    }
+
+    #[fbinit::test]
+    fn test_roundtrip(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let store = EncryptedBlobstore::new(base.clone(), keys(1, &[1]));
+
+        let value = Bytes::from_static(b"top secret contents of a file");
+        store
+            .put(
+                ctx.clone(),
+                "key".to_string(),
+                BlobstoreBytes::from_bytes(value.clone()),
+            )
+            .wait()
+            .expect("put should succeed");
+        assert_eq!(
+            store
+                .get(ctx.clone(), "key".to_string())
+                .wait()
+                .expect("get should succeed")
+                .map(BlobstoreGetData::into_raw_bytes),
+            Some(value.clone()),
+        );
+
+        let stored = raw_get(&ctx, &base, "key");
+        assert!(stored.starts_with(MAGIC));
+        assert!(!stored.windows(value.len()).any(|w| w == value.as_ref()));
+    }
+
+    #[fbinit::test]
+    fn test_tampering(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let store = EncryptedBlobstore::new(base.clone(), keys(1, &[1]));
+
+        store
+            .put(
+                ctx.clone(),
+                "key".to_string(),
+                BlobstoreBytes::from_bytes("value"),
+            )
+            .wait()
+            .expect("put should succeed");
+        let stored = raw_get(&ctx, &base, "key");
+
+        // Moving a value to a different key is detected.
+        base.put(
+            ctx.clone(),
+            "other".to_string(),
+            BlobstoreBytes::from_bytes(stored.clone()),
+        )
+        .wait()
+        .expect("put should succeed");
+        assert!(store.get(ctx.clone(), "other".to_string()).wait().is_err());
+
+        // So is flipping a bit of the ciphertext.
+        let mut corrupt = stored.to_vec();
+        corrupt[HEADER_LEN] ^= 1;
+        base.put(
+            ctx.clone(),
+            "key".to_string(),
+            BlobstoreBytes::from_bytes(corrupt),
+        )
+        .wait()
+        .expect("put should succeed");
+        assert!(store.get(ctx.clone(), "key".to_string()).wait().is_err());
+
+        // And plaintext values are refused.
+        base.put(
+            ctx.clone(),
+            "plain".to_string(),
+            BlobstoreBytes::from_bytes("value"),
+        )
+        .wait()
+        .expect("put should succeed");
+        assert!(store.get(ctx, "plain".to_string()).wait().is_err());
+    }
+
+    #[fbinit::test]
+    fn test_rotation(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let old = EncryptedBlobstore::new(base.clone(), keys(1, &[1]));
+        let new = EncryptedBlobstore::new(base.clone(), keys(2, &[1, 2]));
+
+        old.put(
+            ctx.clone(),
+            "key".to_string(),
+            BlobstoreBytes::from_bytes("value"),
+        )
+        .wait()
+        .expect("put should succeed");
+
+        // The new store can read blobs written with the old key, and re-encrypt them.
+        assert!(new.get(ctx.clone(), "key".to_string()).wait().is_ok());
+        assert!(new
+            .reencrypt(ctx.clone(), "key".to_string())
+            .wait()
+            .expect("reencrypt should succeed"));
+        assert_eq!(
+            key_id("key", &raw_get(&ctx, &base, "key")).expect("should be encrypted"),
+            2
+        );
+        assert!(!new
+            .reencrypt(ctx.clone(), "key".to_string())
+            .wait()
+            .expect("reencrypt should succeed"));
+
+        // The old store does not know the new key.
+        assert!(old.get(ctx.clone(), "key".to_string()).wait().is_err());
+        assert_eq!(
+            new.get(ctx, "key".to_string())
+                .wait()
+                .expect("get should succeed")
+                .map(BlobstoreGetData::into_raw_bytes),
+            Some(Bytes::from("value")),
+        );
+    }
+
+    /// A blobstore that keeps the existing value even when asked to overwrite it.
+    #[derive(Clone, Debug)]
+    struct InsertOnlyBlobstore(EagerMemblob);
+
+    impl Blobstore for InsertOnlyBlobstore {
+        fn get(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+            self.0.get(ctx, key)
+        }
+
+        fn put(
+            &self,
+            ctx: CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> BoxFuture<(), Error> {
+            let inner = self.0.clone();
+            self.0
+                .is_present(ctx.clone(), key.clone())
+                .and_then(move |present| {
+                    if present {
+                        future::ok(()).boxify()
+                    } else {
+                        inner.put(ctx, key, value)
+                    }
+                })
+                .boxify()
+        }
+
+        fn overwrite(
+            &self,
+            ctx: CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> BoxFuture<(), Error> {
+            self.put(ctx, key, value)
+        }
+    }
+
+    #[fbinit::test]
+    fn test_reencrypt_not_replaced(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = InsertOnlyBlobstore(EagerMemblob::new());
+        let old = EncryptedBlobstore::new(base.clone(), keys(1, &[1]));
+        let new = EncryptedBlobstore::new(base.clone(), keys(2, &[1, 2]));
+
+        old.put(
+            ctx.clone(),
+            "key".to_string(),
+            BlobstoreBytes::from_bytes("value"),
+        )
+        .wait()
+        .expect("put should succeed");
+
+        // The re-encrypted blob isn't stored, and that is reported rather than ignored.
+        let error = new
+            .reencrypt(ctx.clone(), "key".to_string())
+            .wait()
+            .expect_err("reencrypt should fail");
+        match error.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::NotReencrypted(key, 1)) if key == "key" => {}
+            _ => panic!("unexpected error: {:?}", error),
+        }
+        assert_eq!(
+            key_id("key", &raw_get(&ctx, &base.0, "key")).expect("should be encrypted"),
+            1
+        );
+    }
+
+    #[fbinit::test]
+    fn test_reencrypt_sqlblob(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let mut rt = Runtime::new()?;
+        let base = Arc::new(Sqlblob::with_sqlite_in_memory()?);
+        let old = EncryptedBlobstore::new(base.clone(), keys(1, &[1]));
+        let new = EncryptedBlobstore::new(base.clone(), keys(2, &[1, 2]));
+
+        // Sqlblob keeps existing values on put, so this relies on overwrite. The large value is
+        // stored in chunks.
+        let values = vec![
+            ("small", Bytes::from_static(b"value")),
+            ("large", Bytes::from(vec![7u8; 2 * 1024 * 1024])),
+        ];
+        for (key, value) in &values {
+            rt.block_on(old.put(
+                ctx.clone(),
+                key.to_string(),
+                BlobstoreBytes::from_bytes(value.clone()),
+            ))?;
+            assert!(rt.block_on(new.reencrypt(ctx.clone(), key.to_string()))?);
+
+            let stored = rt
+                .block_on(base.get(ctx.clone(), key.to_string()))?
+                .expect("value should be present");
+            assert_eq!(key_id(key, stored.as_raw_bytes())?, 2);
+            let reread = rt.block_on(new.get(ctx.clone(), key.to_string()))?;
+            assert_eq!(
+                reread.map(BlobstoreGetData::into_raw_bytes),
+                Some(value.clone())
+            );
+        }
+
+        Ok(())
+    }
+}
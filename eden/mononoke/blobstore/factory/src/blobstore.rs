@@ -5,10 +5,13 @@
  * GNU General Public License version 2.
  */
 
-use anyhow::{Context, Error};
+use anyhow::{format_err, Context, Error};
 use blobstore::{Blobstore, DisabledBlob, ErrorKind};
 use blobstore_sync_queue::SqlBlobstoreSyncQueue;
+use cacheblob::{new_disk_cache_blobstore, new_memory_lru_blobstore};
 use chaosblob::{ChaosBlobstore, ChaosOptions};
+use compressedblob::{Codec, CompressedBlobstore, Zstd, ZstdWithDictionary};
+use encryptedblob::{EncryptedBlobstore, EncryptionKeys};
 use fbinit::FacebookInit;
 use fileblob::Fileblob;
 use futures::{
@@ -17,27 +20,41 @@ use futures::{
 };
 use logblob::LogBlob;
 use metaconfig_types::{
-    BlobConfig, BlobstoreId, DatabaseConfig, MultiplexId, ScrubAction,
+    BlobConfig, BlobstoreId, DatabaseConfig, MultiplexId, MultiplexedStoreType, ScrubAction,
     ShardableRemoteDatabaseConfig,
 };
 use multiplexedblob::{LoggingScrubHandler, MultiplexedBlobstore, ScrubBlobstore, ScrubHandler};
+use packblob::PackBlob;
 use readonlyblob::ReadOnlyBlobstore;
+use s3blob::{S3Blob, S3Credentials, S3Options};
 use scuba::ScubaSampleBuilder;
 use slog::Logger;
 use sql_construct::SqlConstructFromDatabaseConfig;
 use sql_ext::facebook::MysqlOptions;
-use sqlblob::Sqlblob;
-use std::num::NonZeroU64;
+use sqlblob::{CountedSqlblob, Sqlblob};
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::PathBuf;
 use std::sync::Arc;
 use throttledblob::{ThrottleOptions, ThrottledBlob};
+use tieredblob::TieredBlobstore;
 
 use crate::ReadOnlyStorage;
 
+/// A cache on the local host in front of a repo's blobstore, for deployments that don't have
+/// cachelib and memcache.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LocalCacheOptions {
+    Disabled,
+    Memory { max_bytes: usize },
+    Disk { path: PathBuf, max_bytes: u64 },
+}
+
 #[derive(Clone, Debug)]
 pub struct BlobstoreOptions {
     pub chaos_options: ChaosOptions,
     pub throttle_options: ThrottleOptions,
     pub manifold_api_key: Option<String>,
+    pub local_cache_options: LocalCacheOptions,
 }
 
 impl BlobstoreOptions {
@@ -45,11 +62,13 @@ impl BlobstoreOptions {
         chaos_options: ChaosOptions,
         throttle_options: ThrottleOptions,
         manifold_api_key: Option<String>,
+        local_cache_options: LocalCacheOptions,
     ) -> Self {
         Self {
             chaos_options,
             throttle_options,
             manifold_api_key,
+            local_cache_options,
         }
     }
 }
@@ -60,10 +79,30 @@ impl Default for BlobstoreOptions {
             ChaosOptions::new(None, None),
             ThrottleOptions::new(None, None),
             None,
+            LocalCacheOptions::Disabled,
         )
     }
 }
 
+/// Wrap a repo's blobstore in the local cache described by `options`, if any.
+pub fn make_local_cache(
+    blobstore: Arc<dyn Blobstore>,
+    options: &LocalCacheOptions,
+) -> Result<Arc<dyn Blobstore>, Error> {
+    let store = match options {
+        LocalCacheOptions::Disabled => blobstore,
+        LocalCacheOptions::Memory { max_bytes } => {
+            Arc::new(new_memory_lru_blobstore(blobstore, *max_bytes)) as Arc<dyn Blobstore>
+        }
+        LocalCacheOptions::Disk { path, max_bytes } => {
+            new_disk_cache_blobstore(blobstore, path, *max_bytes)
+                .with_context(|| format!("Opening local cache at {}", path.display()))
+                .map(|store| Arc::new(store) as Arc<dyn Blobstore>)?
+        }
+    };
+    Ok(store)
+}
+
 /// Construct a blobstore according to the specification. The multiplexed blobstore
 /// needs an SQL DB for its queue, as does the MySQL blobstore.
 /// If `throttling.read_qps` or `throttling.write_qps` are Some then ThrottledBlob will be used to limit
@@ -90,64 +129,19 @@ pub fn make_blobstore<'a>(
                 .context(ErrorKind::StateOpen)
                 .map(|store| Arc::new(store) as Arc<dyn Blobstore>)?,
 
-            Sqlite { path } => Sqlblob::with_sqlite_path(path.join("blobs"), readonly_storage.0)
-                .context(ErrorKind::StateOpen)
-                .map(|store| Arc::new(store) as Arc<dyn Blobstore>)?,
-
-            Mysql { remote } => match remote {
-                ShardableRemoteDatabaseConfig::Unsharded(config) => {
-                    if let Some(myrouter_port) = mysql_options.myrouter_port {
-                        Sqlblob::with_myrouter_unsharded(
-                            fb,
-                            config.db_address,
-                            myrouter_port,
-                            mysql_options.read_connection_type(),
-                            readonly_storage.0,
-                        )
-                        .compat()
-                        .await
-                    } else {
-                        Sqlblob::with_raw_xdb_unsharded(
-                            fb,
-                            config.db_address,
-                            mysql_options.read_connection_type(),
-                            readonly_storage.0,
-                        )
-                        .compat()
-                        .await
-                    }
-                }
-                ShardableRemoteDatabaseConfig::Sharded(config) => {
-                    if let Some(myrouter_port) = mysql_options.myrouter_port {
-                        Sqlblob::with_myrouter(
-                            fb,
-                            config.shard_map.clone(),
-                            myrouter_port,
-                            mysql_options.read_connection_type(),
-                            config.shard_num,
-                            readonly_storage.0,
-                        )
-                        .compat()
-                        .await
-                    } else {
-                        Sqlblob::with_raw_xdb_shardmap(
-                            fb,
-                            config.shard_map.clone(),
-                            mysql_options.read_connection_type(),
-                            config.shard_num,
-                            readonly_storage.0,
-                        )
-                        .compat()
-                        .await
-                    }
-                }
+            Sqlite { .. } | Mysql { .. } => {
+                make_sqlblob(fb, blobconfig, mysql_options, readonly_storage)
+                    .await
+                    .map(|store| Arc::new(store) as Arc<dyn Blobstore>)?
             }
-            .map(|store| Arc::new(store) as Arc<dyn Blobstore>)?,
+
             Multiplexed {
                 multiplex_id,
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                minimum_successful_writes,
+                read_repair,
                 queue_db,
             } => {
                 has_components = true;
@@ -158,6 +152,8 @@ pub fn make_blobstore<'a>(
                     scuba_table,
                     scuba_sample_rate,
                     blobstores,
+                    minimum_successful_writes,
+                    read_repair,
                     None,
                     mysql_options,
                     readonly_storage,
@@ -171,6 +167,7 @@ pub fn make_blobstore<'a>(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                minimum_successful_writes,
                 scrub_action,
                 queue_db,
             } => {
@@ -182,6 +179,8 @@ pub fn make_blobstore<'a>(
                     scuba_table,
                     scuba_sample_rate,
                     blobstores,
+                    minimum_successful_writes,
+                    false,
                     Some((
                         Arc::new(LoggingScrubHandler::new(false)) as Arc<dyn ScrubHandler>,
                         scrub_action,
@@ -214,6 +213,94 @@ pub fn make_blobstore<'a>(
 
                 Arc::new(LogBlob::new(store, scuba, scuba_sample_rate)) as Arc<dyn Blobstore>
             }
+            Compressed {
+                blobconfig,
+                level,
+                dictionary_path,
+            } => {
+                let codec = match dictionary_path {
+                    Some(path) => {
+                        let dictionary = std::fs::read(&path).with_context(|| {
+                            format!("While reading compression dictionary {}", path.display())
+                        })?;
+                        Arc::new(ZstdWithDictionary::new(level, dictionary)) as Arc<dyn Codec>
+                    }
+                    None => Arc::new(Zstd::new(level)) as Arc<dyn Codec>,
+                };
+
+                let store = make_blobstore(
+                    fb,
+                    *blobconfig,
+                    mysql_options,
+                    readonly_storage,
+                    &blobstore_options,
+                    logger,
+                )
+                .await?;
+
+                Arc::new(CompressedBlobstore::new(store, codec)) as Arc<dyn Blobstore>
+            }
+            Pack { blobconfig } => {
+                let store = make_blobstore(
+                    fb,
+                    *blobconfig,
+                    mysql_options,
+                    readonly_storage,
+                    &blobstore_options,
+                    logger,
+                )
+                .await?;
+
+                Arc::new(PackBlob::new(store)) as Arc<dyn Blobstore>
+            }
+            Encrypted {
+                blobconfig,
+                keyfile,
+                key_id,
+            } => {
+                let keys = EncryptionKeys::from_keyfile(&keyfile, key_id)?;
+
+                let store = make_blobstore(
+                    fb,
+                    *blobconfig,
+                    mysql_options,
+                    readonly_storage,
+                    &blobstore_options,
+                    logger,
+                )
+                .await?;
+
+                Arc::new(EncryptedBlobstore::new(store, Arc::new(keys))) as Arc<dyn Blobstore>
+            }
+            Tiered {
+                hot,
+                cold,
+                promote_on_read,
+                demote_after: _,
+            } => {
+                let hot = make_blobstore(
+                    fb,
+                    *hot,
+                    mysql_options,
+                    readonly_storage,
+                    &blobstore_options,
+                    logger,
+                )
+                .await?;
+                let cold = make_blobstore(
+                    fb,
+                    *cold,
+                    mysql_options,
+                    readonly_storage,
+                    &blobstore_options,
+                    logger,
+                )
+                .await?;
+
+                // Demotion is left to `mononoke_admin blobstore-demote`, so that only one
+                // process runs it however many open the blobstore
+                Arc::new(TieredBlobstore::new(hot, cold, promote_on_read)) as Arc<dyn Blobstore>
+            }
             Manifold { bucket, prefix } => {
                 #[cfg(fbcode_build)]
                 {
@@ -256,6 +343,28 @@ pub fn make_blobstore<'a>(
                     unimplemented!("This is implemented only for fbcode_build")
                 }
             }
+            S3 {
+                endpoint,
+                bucket,
+                prefix,
+                region,
+                credentials_path,
+            } => {
+                let credentials = match credentials_path {
+                    Some(path) => S3Credentials::from_file(&path)?,
+                    None => S3Credentials::from_env()?,
+                };
+                S3Blob::new(
+                    &endpoint,
+                    bucket,
+                    prefix,
+                    region,
+                    credentials,
+                    S3Options::default(),
+                )
+                .context(ErrorKind::StateOpen)
+                .map(|store| Arc::new(store) as Arc<dyn Blobstore>)?
+            }
         };
 
         let store = if readonly_storage.0 {
@@ -288,13 +397,81 @@ pub fn make_blobstore<'a>(
     .boxed()
 }
 
+/// Construct the SQL blobstore for a `BlobConfig::Sqlite` or `BlobConfig::Mysql`, for callers that
+/// need the `Sqlblob` itself rather than a `Blobstore`.
+pub async fn make_sqlblob(
+    fb: FacebookInit,
+    blobconfig: BlobConfig,
+    mysql_options: MysqlOptions,
+    readonly_storage: ReadOnlyStorage,
+) -> Result<CountedSqlblob, Error> {
+    use BlobConfig::*;
+
+    match blobconfig {
+        Sqlite { path } => Sqlblob::with_sqlite_path(path.join("blobs"), readonly_storage.0)
+            .context(ErrorKind::StateOpen),
+
+        Mysql { remote } => match remote {
+            ShardableRemoteDatabaseConfig::Unsharded(config) => {
+                if let Some(myrouter_port) = mysql_options.myrouter_port {
+                    Sqlblob::with_myrouter_unsharded(
+                        fb,
+                        config.db_address,
+                        myrouter_port,
+                        mysql_options.read_connection_type(),
+                        readonly_storage.0,
+                    )
+                    .compat()
+                    .await
+                } else {
+                    Sqlblob::with_raw_xdb_unsharded(
+                        fb,
+                        config.db_address,
+                        mysql_options.read_connection_type(),
+                        readonly_storage.0,
+                    )
+                    .compat()
+                    .await
+                }
+            }
+            ShardableRemoteDatabaseConfig::Sharded(config) => {
+                if let Some(myrouter_port) = mysql_options.myrouter_port {
+                    Sqlblob::with_myrouter(
+                        fb,
+                        config.shard_map.clone(),
+                        myrouter_port,
+                        mysql_options.read_connection_type(),
+                        config.shard_num,
+                        readonly_storage.0,
+                    )
+                    .compat()
+                    .await
+                } else {
+                    Sqlblob::with_raw_xdb_shardmap(
+                        fb,
+                        config.shard_map.clone(),
+                        mysql_options.read_connection_type(),
+                        config.shard_num,
+                        readonly_storage.0,
+                    )
+                    .compat()
+                    .await
+                }
+            }
+        },
+        other => Err(format_err!("Not a SQL blobstore: {:?}", other)),
+    }
+}
+
 pub fn make_blobstore_multiplexed<'a>(
     fb: FacebookInit,
     multiplex_id: MultiplexId,
     queue_db: DatabaseConfig,
     scuba_table: Option<String>,
     scuba_sample_rate: NonZeroU64,
-    inner_config: Vec<(BlobstoreId, BlobConfig)>,
+    inner_config: Vec<(BlobstoreId, MultiplexedStoreType, BlobConfig)>,
+    minimum_successful_writes: NonZeroUsize,
+    read_repair: bool,
     scrub_args: Option<(Arc<dyn ScrubHandler>, ScrubAction)>,
     mysql_options: MysqlOptions,
     readonly_storage: ReadOnlyStorage,
@@ -311,7 +488,7 @@ pub fn make_blobstore_multiplexed<'a>(
         let mut applied_chaos = false;
 
         let components = future::try_join_all(inner_config.into_iter().map({
-            move |(blobstoreid, config)| {
+            move |(blobstoreid, store_type, config)| {
                 let mut blobstore_options = blobstore_options.clone();
 
                 if blobstore_options.chaos_options.has_chaos() {
@@ -336,7 +513,7 @@ pub fn make_blobstore_multiplexed<'a>(
                     )
                     .await?;
 
-                    Ok((blobstoreid, store))
+                    Ok((blobstoreid, store_type, store))
                 }
             }
         }));
@@ -350,10 +527,21 @@ pub fn make_blobstore_multiplexed<'a>(
 
         let (components, queue) = future::try_join(components, queue).await?;
 
+        let mut normal_components = vec![];
+        let mut write_only_components = vec![];
+        for (blobstoreid, store_type, store) in components {
+            match store_type {
+                MultiplexedStoreType::Normal => normal_components.push((blobstoreid, store)),
+                MultiplexedStoreType::WriteOnly => write_only_components.push((blobstoreid, store)),
+            }
+        }
+
         let blobstore = match scrub_args {
             Some((scrub_handler, scrub_action)) => Arc::new(ScrubBlobstore::new(
                 multiplex_id,
-                components,
+                normal_components,
+                write_only_components,
+                minimum_successful_writes,
                 Arc::new(queue),
                 scuba_table.map_or(ScubaSampleBuilder::with_discard(), |table| {
                     ScubaSampleBuilder::new(fb, table)
@@ -364,7 +552,10 @@ pub fn make_blobstore_multiplexed<'a>(
             )) as Arc<dyn Blobstore>,
             None => Arc::new(MultiplexedBlobstore::new(
                 multiplex_id,
-                components,
+                normal_components,
+                write_only_components,
+                minimum_successful_writes,
+                read_repair,
                 Arc::new(queue),
                 scuba_table.map_or(ScubaSampleBuilder::with_discard(), |table| {
                     ScubaSampleBuilder::new(fb, table)
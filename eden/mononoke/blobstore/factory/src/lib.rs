@@ -13,7 +13,10 @@ mod sql;
 pub use chaosblob::ChaosOptions;
 pub use throttledblob::ThrottleOptions;
 
-pub use crate::blobstore::{make_blobstore, make_blobstore_multiplexed, BlobstoreOptions};
+pub use crate::blobstore::{
+    make_blobstore, make_blobstore_multiplexed, make_local_cache, make_sqlblob, BlobstoreOptions,
+    LocalCacheOptions,
+};
 pub use crate::sql::{make_metadata_sql_factory, MetadataSqlFactory};
 
 #[derive(Copy, Clone, PartialEq)]
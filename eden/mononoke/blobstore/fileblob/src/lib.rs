@@ -8,18 +8,18 @@
 #![deny(warnings)]
 
 use std::convert::TryFrom;
-use std::fs::{create_dir_all, File};
+use std::fs::{create_dir_all, read_dir, remove_file, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 use anyhow::{bail, Error, Result};
 use futures::future::{poll_fn, Future};
-use futures::Async;
-use futures_ext::{BoxFuture, FutureExt};
-use percent_encoding::{percent_encode, AsciiSet, CONTROLS};
+use futures::{stream, Async, Stream};
+use futures_ext::{BoxFuture, BoxStream, FutureExt, StreamExt};
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, CONTROLS};
 
-use blobstore::{Blobstore, BlobstoreGetData, BlobstoreMetadata};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange, BlobstoreMetadata};
 use context::CoreContext;
 use mononoke_types::BlobstoreBytes;
 use tempfile::NamedTempFile;
@@ -58,6 +58,17 @@ impl Fileblob {
         let key = percent_encode(key.as_bytes(), PATH);
         self.base.join(format!("{}-{}", PREFIX, key))
     }
+
+    /// Inverse of `path`: recover the key from a file name in the base directory, or None if
+    /// the file is not a blob.
+    fn key(name: &str) -> Result<Option<String>> {
+        let prefix = format!("{}-", PREFIX);
+        if !name.starts_with(&prefix) {
+            return Ok(None);
+        }
+        let key = percent_decode_str(&name[prefix.len()..]).decode_utf8()?;
+        Ok(Some(key.into_owned()))
+    }
 }
 
 fn ctime(file: &File) -> Option<i64> {
@@ -117,4 +128,49 @@ impl Blobstore for Fileblob {
         .from_err()
         .boxify()
     }
+
+    fn enumerate(&self, _ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        let base = self.base.clone();
+
+        poll_fn(move || -> io::Result<_> { Ok(Async::Ready(read_dir(&base)?)) })
+            .from_err()
+            .map(move |entries| {
+                stream::iter_result(entries.map(move |entry| -> Result<Option<String>> {
+                    let name = entry?.file_name();
+                    let key = match name.to_str() {
+                        Some(name) => Self::key(name)?,
+                        None => None,
+                    };
+                    Ok(key.filter(|key| range.contains(key)))
+                }))
+            })
+            .flatten_stream()
+            .filter_map(|key| key)
+            .boxify()
+    }
+
+    fn delete(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        let p = self.path(&key);
+
+        poll_fn(move || {
+            match remove_file(&p) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+                Ok(()) => {}
+            };
+            Ok(Async::Ready(()))
+        })
+        .from_err()
+        .boxify()
+    }
+
+    // Put always replaces the file, atomically.
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.put(ctx, key, value)
+    }
 }
@@ -9,13 +9,13 @@
 use std::num::NonZeroU64;
 
 use anyhow::Error;
-use futures_ext::BoxFuture;
+use futures_ext::{BoxFuture, BoxStream};
 use futures_old::future::{self};
 use futures_stats::Timed;
 use scuba::ScubaSampleBuilder;
 
-use blobstore::{Blobstore, BlobstoreGetData};
-use blobstore_stats::{record_get_stats, record_put_stats, OperationType};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
+use blobstore_stats::{record_delete_stats, record_get_stats, record_put_stats, OperationType};
 use context::{CoreContext, PerfCounterType};
 use mononoke_types::BlobstoreBytes;
 
@@ -97,4 +97,56 @@ impl<B: Blobstore> Blobstore for LogBlob<B> {
             .increment_counter(PerfCounterType::BlobPresenceChecks);
         self.inner.assert_present(ctx, key)
     }
+
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.inner.enumerate(ctx, range)
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        let mut scuba = self.scuba.clone();
+
+        self.inner
+            .delete(ctx.clone(), key.clone())
+            .timed(move |stats, result| {
+                record_delete_stats(
+                    &mut scuba,
+                    stats,
+                    result,
+                    key,
+                    ctx.session_id().to_string(),
+                    None,
+                );
+                Ok(())
+            })
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        let mut scuba = self.scuba.clone();
+        let size = value.len();
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::BlobPuts);
+
+        self.inner
+            .overwrite(ctx.clone(), key.clone(), value)
+            .timed(move |stats, result| {
+                record_put_stats(
+                    &mut scuba,
+                    stats,
+                    result,
+                    key,
+                    ctx.session_id().to_string(),
+                    OperationType::Overwrite,
+                    size,
+                    None,
+                    None,
+                );
+                Ok(())
+            })
+    }
 }
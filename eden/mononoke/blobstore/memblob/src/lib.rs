@@ -10,10 +10,11 @@ use std::fmt;
 use std::sync::{Arc, Mutex};
 
 use anyhow::Error;
-use futures::future::{lazy, IntoFuture};
-use futures_ext::{BoxFuture, FutureExt};
+use futures::future::{lazy, Future, IntoFuture};
+use futures::stream;
+use futures_ext::{BoxFuture, BoxStream, FutureExt, StreamExt};
 
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use context::CoreContext;
 use mononoke_types::BlobstoreBytes;
 
@@ -57,6 +58,16 @@ impl LazyMemblob {
     }
 }
 
+fn keys_in_range(hash: &HashMap<String, BlobstoreBytes>, range: &BlobstoreKeyRange) -> Vec<String> {
+    let mut keys: Vec<_> = hash
+        .keys()
+        .filter(|key| range.contains(key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys
+}
+
 impl Blobstore for EagerMemblob {
     fn put(&self, _ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
         let mut inner = self.hash.lock().expect("lock poison");
@@ -72,6 +83,26 @@ impl Blobstore for EagerMemblob {
             .into_future()
             .boxify()
     }
+
+    fn enumerate(&self, _ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        let inner = self.hash.lock().expect("lock poison");
+
+        stream::iter_ok(keys_in_range(&inner, &range)).boxify()
+    }
+
+    fn delete(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.remove(&key);
+        Ok(()).into_future().boxify()
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.put(ctx, key, value)
+    }
 }
 
 impl Blobstore for LazyMemblob {
@@ -96,6 +127,37 @@ impl Blobstore for LazyMemblob {
         })
         .boxify()
     }
+
+    fn enumerate(&self, _ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        let hash = self.hash.clone();
+
+        lazy(move || {
+            let inner = hash.lock().expect("lock poison");
+            Ok::<_, Error>(stream::iter_ok(keys_in_range(&inner, &range)))
+        })
+        .flatten_stream()
+        .boxify()
+    }
+
+    fn delete(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        let hash = self.hash.clone();
+
+        lazy(move || {
+            let mut inner = hash.lock().expect("lock poison");
+            inner.remove(&key);
+            Ok(()).into_future()
+        })
+        .boxify()
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.put(ctx, key, value)
+    }
 }
 
 impl fmt::Debug for EagerMemblob {
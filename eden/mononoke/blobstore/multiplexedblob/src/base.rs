@@ -6,21 +6,28 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use blobstore_stats::{record_get_stats, record_put_stats, OperationType};
 use blobstore_sync_queue::OperationKey;
 use cloned::cloned;
 use context::{CoreContext, PerfCounterType};
-use futures_ext::{BoxFuture, FutureExt};
+use futures::{
+    compat::Future01CompatExt,
+    future::{FutureExt as NewFutureExt, TryFutureExt},
+    stream::{FuturesUnordered, StreamExt as NewStreamExt},
+};
+use futures_ext::{BoxFuture, BoxStream, FutureExt, StreamExt};
 use futures_old::future::{self, Future, Loop};
+use futures_old::stream::{self, Stream};
 use futures_stats::Timed;
 use itertools::{Either, Itertools};
 use metaconfig_types::{BlobstoreId, MultiplexId};
 use mononoke_types::BlobstoreBytes;
 use scuba::ScubaSampleBuilder;
+use slog::warn;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::num::NonZeroU64;
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
@@ -33,6 +40,7 @@ use tokio::prelude::FutureExt as TokioFutureExt;
 use tokio::timer::timeout::Error as TimeoutError;
 
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+const ENUMERATE_CHECK_CONCURRENCY: usize = 100;
 
 type BlobstoresWithEntry = HashSet<BlobstoreId>;
 type BlobstoresReturnedNone = HashSet<BlobstoreId>;
@@ -44,6 +52,12 @@ pub enum ErrorKind {
     SomeFailedOthersNone(Arc<BlobstoresReturnedError>),
     #[error("All blobstores failed: {0:?}")]
     AllFailed(Arc<BlobstoresReturnedError>),
+    #[error("Delete failed in some blobstores: {0:?}")]
+    DeleteFailed(Arc<BlobstoresReturnedError>),
+    #[error("Overwrite failed in some blobstores: {0:?}")]
+    OverwriteFailed(Arc<BlobstoresReturnedError>),
+    #[error("Put needs {0} blobstores to succeed, but some failed: {1:?}")]
+    NotEnoughSuccessfulWrites(usize, Arc<BlobstoresReturnedError>),
     // Errors below this point are from ScrubBlobstore only. If they include an
     // Option<BlobstoreBytes>, this implies that this error is recoverable
     #[error(
@@ -71,6 +85,13 @@ pub trait MultiplexedBlobstorePutHandler: Send + Sync {
 pub struct MultiplexedBlobstoreBase {
     multiplex_id: MultiplexId,
     blobstores: Arc<[(BlobstoreId, Arc<dyn Blobstore>)]>,
+    /// Blobstores that are written to, but never read from by get and is_present (e.g. because
+    /// they are still being populated)
+    write_only_blobstores: Arc<[(BlobstoreId, Arc<dyn Blobstore>)]>,
+    /// Number of blobstores a put must succeed in before it is successful
+    minimum_successful_writes: NonZeroUsize,
+    /// Whether a get that finds the blob writes it to the blobstores that returned None
+    read_repair: bool,
     handler: Arc<dyn MultiplexedBlobstorePutHandler>,
     scuba: ScubaSampleBuilder,
     scuba_sample_rate: NonZeroU64,
@@ -80,6 +101,9 @@ impl MultiplexedBlobstoreBase {
     pub fn new(
         multiplex_id: MultiplexId,
         blobstores: Vec<(BlobstoreId, Arc<dyn Blobstore>)>,
+        write_only_blobstores: Vec<(BlobstoreId, Arc<dyn Blobstore>)>,
+        minimum_successful_writes: NonZeroUsize,
+        read_repair: bool,
         handler: Arc<dyn MultiplexedBlobstorePutHandler>,
         mut scuba: ScubaSampleBuilder,
         scuba_sample_rate: NonZeroU64,
@@ -89,12 +113,21 @@ impl MultiplexedBlobstoreBase {
         Self {
             multiplex_id,
             blobstores: blobstores.into(),
+            write_only_blobstores: write_only_blobstores.into(),
+            minimum_successful_writes,
+            read_repair,
             handler,
             scuba,
             scuba_sample_rate,
         }
     }
 
+    fn all_blobstores(&self) -> impl Iterator<Item = &(BlobstoreId, Arc<dyn Blobstore>)> {
+        self.blobstores
+            .iter()
+            .chain(self.write_only_blobstores.iter())
+    }
+
     pub fn scrub_get(
         &self,
         ctx: CoreContext,
@@ -103,15 +136,10 @@ impl MultiplexedBlobstoreBase {
         let mut scuba = self.scuba.clone();
         scuba.sampled(self.scuba_sample_rate);
 
-        let requests = multiplexed_get(
-            &ctx,
-            self.blobstores.as_ref(),
-            &key,
-            OperationType::ScrubGet,
-            scuba,
-        )
-        .into_iter()
-        .map(|f| f.then(Ok));
+        let blobstores: Vec<_> = self.all_blobstores().cloned().collect();
+        let requests = multiplexed_get(&ctx, &blobstores, &key, OperationType::ScrubGet, scuba)
+            .into_iter()
+            .map(|f| f.then(Ok));
 
         future::join_all(requests)
             .and_then(|results| {
@@ -165,55 +193,14 @@ impl MultiplexedBlobstoreBase {
             })
             .boxify()
     }
-}
-
-fn remap_timeout_error(err: TimeoutError<Error>) -> Error {
-    match err.into_inner() {
-        Some(err) => err,
-        None => Error::msg("blobstore operation timeout"),
-    }
-}
-
-pub fn inner_put(
-    ctx: CoreContext,
-    mut scuba: ScubaSampleBuilder,
-    write_order: Arc<AtomicUsize>,
-    blobstore_id: BlobstoreId,
-    blobstore: Arc<dyn Blobstore>,
-    key: String,
-    value: BlobstoreBytes,
-) -> impl Future<Item = BlobstoreId, Error = Error> {
-    let size = value.len();
-    let session = ctx.session_id().clone();
-    blobstore
-        .put(ctx, key.clone(), value.clone())
-        .timeout(REQUEST_TIMEOUT)
-        .map({ move |_| blobstore_id })
-        .map_err(remap_timeout_error)
-        .timed(move |stats, result| {
-            record_put_stats(
-                &mut scuba,
-                stats,
-                result.map(|_| &()),
-                key,
-                session.to_string(),
-                OperationType::Put,
-                size,
-                Some(blobstore_id),
-                Some(write_order.fetch_add(1, Ordering::Relaxed) + 1),
-            );
-            Ok(())
-        })
-}
-
-impl Blobstore for MultiplexedBlobstoreBase {
-    fn get(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
-        ctx.perf_counters()
-            .increment_counter(PerfCounterType::BlobGets);
-
-        let mut scuba = self.scuba.clone();
-        scuba.sampled(self.scuba_sample_rate);
 
+    // Returns the first blob found, without waiting for the other blobstores.
+    fn get_first(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        scuba: ScubaSampleBuilder,
+    ) -> BoxFuture<Option<BlobstoreGetData>, Error> {
         let is_logged = scuba.sampling().is_logged();
 
         let requests = multiplexed_get(
@@ -268,7 +255,146 @@ impl Blobstore for MultiplexedBlobstoreBase {
                 }
             })
         })
-        .timed(move |stats, _| {
+        .boxify()
+    }
+
+    // Waits for every blobstore to answer, and writes the blob to the ones that returned None
+    // before returning it.
+    fn get_with_repair(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        scuba: ScubaSampleBuilder,
+    ) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+        let requests = multiplexed_get(
+            &ctx,
+            self.blobstores.as_ref(),
+            &key,
+            OperationType::Get,
+            scuba.clone(),
+        )
+        .into_iter()
+        .map(|f| f.then(Ok::<_, Error>));
+        let blobstores: HashMap<_, _> = self.blobstores.iter().cloned().collect();
+
+        future::join_all(requests)
+            .and_then(move |results| {
+                let (successes, errors): (HashMap<_, _>, HashMap<_, _>) =
+                    results.into_iter().partition_map(|r| match r {
+                        Ok(v) => Either::Left(v),
+                        Err(v) => Either::Right(v),
+                    });
+                let value = successes.values().find_map(|value| value.clone());
+
+                let mut value = match value {
+                    Some(value) => value,
+                    None if errors.is_empty() => return future::ok(None).left_future(),
+                    None => {
+                        let error = if successes.is_empty() {
+                            ErrorKind::AllFailed(errors.into())
+                        } else {
+                            ErrorKind::SomeFailedOthersNone(errors.into())
+                        };
+                        return future::err(error.into()).left_future();
+                    }
+                };
+                value.remove_ctime();
+
+                let write_order = Arc::new(AtomicUsize::new(0));
+                let repairs = successes
+                    .into_iter()
+                    .filter(|(_, value)| value.is_none())
+                    .map(|(blobstore_id, _)| {
+                        inner_put(
+                            ctx.clone(),
+                            scuba.clone(),
+                            write_order.clone(),
+                            blobstore_id,
+                            blobstores[&blobstore_id].clone(),
+                            key.clone(),
+                            value.as_bytes().clone(),
+                        )
+                        .then({
+                            cloned!(ctx, key);
+                            move |result| {
+                                // The healer will get another chance at it if the repair fails
+                                if let Err(error) = result {
+                                    warn!(
+                                        ctx.logger(),
+                                        "read repair of {} in blobstore {:?} failed: {:?}",
+                                        key,
+                                        blobstore_id,
+                                        error
+                                    );
+                                }
+                                Ok(())
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                future::join_all(repairs)
+                    .map(move |_| Some(value))
+                    .right_future()
+            })
+            .boxify()
+    }
+}
+
+fn remap_timeout_error(err: TimeoutError<Error>) -> Error {
+    match err.into_inner() {
+        Some(err) => err,
+        None => Error::msg("blobstore operation timeout"),
+    }
+}
+
+pub fn inner_put(
+    ctx: CoreContext,
+    mut scuba: ScubaSampleBuilder,
+    write_order: Arc<AtomicUsize>,
+    blobstore_id: BlobstoreId,
+    blobstore: Arc<dyn Blobstore>,
+    key: String,
+    value: BlobstoreBytes,
+) -> impl Future<Item = BlobstoreId, Error = Error> {
+    let size = value.len();
+    let session = ctx.session_id().clone();
+    blobstore
+        .put(ctx, key.clone(), value.clone())
+        .timeout(REQUEST_TIMEOUT)
+        .map({ move |_| blobstore_id })
+        .map_err(remap_timeout_error)
+        .timed(move |stats, result| {
+            record_put_stats(
+                &mut scuba,
+                stats,
+                result.map(|_| &()),
+                key,
+                session.to_string(),
+                OperationType::Put,
+                size,
+                Some(blobstore_id),
+                Some(write_order.fetch_add(1, Ordering::Relaxed) + 1),
+            );
+            Ok(())
+        })
+}
+
+impl Blobstore for MultiplexedBlobstoreBase {
+    fn get(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::BlobGets);
+
+        let mut scuba = self.scuba.clone();
+        scuba.sampled(self.scuba_sample_rate);
+
+        let get = if self.read_repair {
+            self.get_with_repair(ctx.clone(), key, scuba)
+        } else {
+            self.get_first(ctx.clone(), key, scuba)
+        };
+
+        get.timed(move |stats, _| {
             ctx.perf_counters().set_max_counter(
                 PerfCounterType::BlobGetsMaxLatency,
                 stats.completion_time.as_millis_unchecked() as i64,
@@ -282,41 +408,58 @@ impl Blobstore for MultiplexedBlobstoreBase {
         ctx.perf_counters()
             .increment_counter(PerfCounterType::BlobPuts);
         let write_order = Arc::new(AtomicUsize::new(0));
-        let operation_key = OperationKey::gen();
-        let puts = self
+        // Write-only blobstores get the blob too, but don't count towards the write quorum
+        let puts: PutEvents = self
             .blobstores
             .iter()
-            .map({
-                |(blobstore_id, blobstore)| {
-                    inner_put(
-                        ctx.clone(),
-                        self.scuba.clone(),
-                        write_order.clone(),
-                        *blobstore_id,
-                        blobstore.clone(),
-                        key.clone(),
-                        value.clone(),
-                    )
-                }
+            .map(|blobstore| (blobstore, false))
+            .chain(
+                self.write_only_blobstores
+                    .iter()
+                    .map(|blobstore| (blobstore, true)),
+            )
+            .map(|(&(blobstore_id, ref blobstore), write_only)| {
+                inner_put(
+                    ctx.clone(),
+                    self.scuba.clone(),
+                    write_order.clone(),
+                    blobstore_id,
+                    blobstore.clone(),
+                    key.clone(),
+                    value.clone(),
+                )
+                .map_err(move |error| (blobstore_id, error))
+                .compat()
+                .map(move |result| PutEvent::Put { result, write_only })
+                .boxed()
             })
             .collect();
-
-        multiplexed_put(
-            ctx.clone(),
-            self.handler.clone(),
+        let state = PutState {
+            ctx: ctx.clone(),
+            handler: self.handler.clone(),
             key,
-            self.multiplex_id,
-            operation_key,
-            puts,
-        )
-        .timed(move |stats, _| {
-            ctx.perf_counters().set_max_counter(
-                PerfCounterType::BlobPutsMaxLatency,
-                stats.completion_time.as_millis_unchecked() as i64,
-            );
-            Ok(())
-        })
-        .boxify()
+            multiplex_id: self.multiplex_id,
+            operation_key: OperationKey::gen(),
+            minimum_successful_writes: self.minimum_successful_writes.get(),
+            blobstores_count: self.blobstores.len(),
+            in_flight: self.blobstores.len(),
+            succeeded: 0,
+            logged: false,
+            put_errors: HashMap::new(),
+            queue_error: None,
+        };
+
+        multiplexed_put(state, puts)
+            .boxed()
+            .compat()
+            .timed(move |stats, _| {
+                ctx.perf_counters().set_max_counter(
+                    PerfCounterType::BlobPutsMaxLatency,
+                    stats.completion_time.as_millis_unchecked() as i64,
+                );
+                Ok(())
+            })
+            .boxify()
     }
 
     fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
@@ -373,6 +516,102 @@ impl Blobstore for MultiplexedBlobstoreBase {
         })
         .boxify()
     }
+
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        // Components may be out of sync until the healer catches up, so the answer is the union
+        // of all their keys. Rather than remembering every key produced, which would grow with
+        // the size of the store, each key is produced by the first component that has it: keys
+        // of later components are skipped if an earlier one has them too.
+        let blobstores: Vec<_> = self
+            .all_blobstores()
+            .map(|(_, blobstore)| blobstore.clone())
+            .collect();
+
+        stream::iter_ok::<_, Error>(0..blobstores.len())
+            .map(move |index| {
+                let earlier = blobstores[..index].to_vec();
+                blobstores[index]
+                    .enumerate(ctx.clone(), range.clone())
+                    .map({
+                        cloned!(ctx);
+                        move |key| {
+                            let checks = earlier
+                                .iter()
+                                .map(|blobstore| blobstore.is_present(ctx.clone(), key.clone()))
+                                .collect::<Vec<_>>();
+                            future::join_all(checks).map(move |present| {
+                                if present.into_iter().any(|present| present) {
+                                    None
+                                } else {
+                                    Some(key)
+                                }
+                            })
+                        }
+                    })
+                    .buffered(ENUMERATE_CHECK_CONCURRENCY)
+                    .filter_map(|key| key)
+            })
+            .flatten()
+            .boxify()
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        // A key is only gone once every component has dropped it, otherwise the healer or a
+        // read from a lagging component could bring it back. Failures are reported together so
+        // that the caller can retry the whole delete.
+        let deletes = self.all_blobstores().map(|&(blobstore_id, ref blobstore)| {
+            blobstore
+                .delete(ctx.clone(), key.clone())
+                .then(move |result| Ok::<_, Error>((blobstore_id, result)))
+        });
+
+        future::join_all(deletes)
+            .and_then(|results| {
+                let errors: HashMap<_, _> = results
+                    .into_iter()
+                    .filter_map(|(blobstore_id, result)| {
+                        result.err().map(|error| (blobstore_id, error))
+                    })
+                    .collect();
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(ErrorKind::DeleteFailed(errors.into()).into())
+                }
+            })
+            .boxify()
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        // Like delete, every component has to be overwritten: a component left with the old
+        // value keeps serving it, and the healer only copies keys that are missing.
+        let overwrites = self.all_blobstores().map(|&(blobstore_id, ref blobstore)| {
+            blobstore
+                .overwrite(ctx.clone(), key.clone(), value.clone())
+                .then(move |result| Ok::<_, Error>((blobstore_id, result)))
+        });
+
+        future::join_all(overwrites)
+            .and_then(|results| {
+                let errors: HashMap<_, _> = results
+                    .into_iter()
+                    .filter_map(|(blobstore_id, result)| {
+                        result.err().map(|error| (blobstore_id, error))
+                    })
+                    .collect();
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(ErrorKind::OverwriteFailed(errors.into()).into())
+                }
+            })
+            .boxify()
+    }
 }
 
 impl fmt::Debug for MultiplexedBlobstoreBase {
@@ -383,7 +622,7 @@ impl fmt::Debug for MultiplexedBlobstoreBase {
             &self.multiplex_id
         )?;
         f.debug_map()
-            .entries(self.blobstores.iter().map(|(ref k, ref v)| (k, v)))
+            .entries(self.all_blobstores().map(|(ref k, ref v)| (k, v)))
             .finish()
     }
 }
@@ -429,82 +668,117 @@ fn multiplexed_get(
         .collect()
 }
 
-fn multiplexed_put<F: Future<Item = BlobstoreId, Error = Error> + Send + 'static>(
-    ctx: CoreContext,
-    handler: Arc<dyn MultiplexedBlobstorePutHandler>,
-    key: String,
-    multiplex_id: MultiplexId,
-    operation_key: OperationKey,
-    puts: Vec<F>,
-) -> impl Future<Item = (), Error = Error> {
-    future::select_ok(puts).and_then(move |(blobstore_id, other_puts)| {
-        finish_put(
-            ctx,
-            handler,
-            key,
-            blobstore_id,
-            multiplex_id,
-            operation_key,
-            other_puts,
-        )
-    })
+enum PutEvent {
+    Put {
+        result: Result<BlobstoreId, (BlobstoreId, Error)>,
+        write_only: bool,
+    },
+    Logged(Result<(), Error>),
 }
 
-fn finish_put<F: Future<Item = BlobstoreId, Error = Error> + Send + 'static>(
+type PutEvents = FuturesUnordered<futures::future::BoxFuture<'static, PutEvent>>;
+
+struct PutState {
     ctx: CoreContext,
     handler: Arc<dyn MultiplexedBlobstorePutHandler>,
     key: String,
-    blobstore_id: BlobstoreId,
     multiplex_id: MultiplexId,
     operation_key: OperationKey,
-    other_puts: Vec<F>,
-) -> BoxFuture<(), Error> {
-    // Ocne we finished a put in one blobstore, we want to return once this blob is in a position
-    // to be replicated properly to the multiplexed stores. This can happen in two cases:
-    // - We wrote it to the SQL queue that will replicate it to other blobstores.
-    // - We wrote it to all the blobstores.
-    // As soon as either of those things happen, we can report the put as successful.
-    use futures_old::future::Either;
-
-    let queue_write = handler.on_put(
-        ctx.clone(),
-        blobstore_id,
-        multiplex_id,
-        operation_key.clone(),
-        key.clone(),
-    );
-
-    let rest_put = if other_puts.len() > 0 {
-        multiplexed_put(ctx, handler, key, multiplex_id, operation_key, other_puts).left_future()
-    } else {
-        // We have no remaining puts to perform, which means we've successfully written to all
-        // blobstores.
-        future::ok(()).right_future()
-    };
-
-    queue_write
-        .select2(rest_put)
-        .then(|res| match res {
-            Ok(Either::A((_, rest_put))) => {
-                // Blobstore queue write succeeded. Spawn the rest of the puts to give them a
-                // chance to complete, but we're done.
-                spawn(rest_put.discard());
-                future::ok(()).boxify()
-            }
-            Ok(Either::B((_, queue_write))) => {
-                // Remaininig puts succeeded (note that this might mean one of them and its
-                // corresponding SQL write succeeded). Spawn the queue write, but we're done.
-                spawn(queue_write.discard());
-                future::ok(()).boxify()
+    minimum_successful_writes: usize,
+    // These only count the normal blobstores, as write-only ones don't make up the quorum
+    blobstores_count: usize,
+    in_flight: usize,
+    succeeded: usize,
+    logged: bool,
+    put_errors: HashMap<BlobstoreId, Error>,
+    queue_error: Option<Error>,
+}
+
+impl PutState {
+    fn handle(&mut self, event: PutEvent, events: &mut PutEvents) {
+        match event {
+            PutEvent::Put {
+                result: Ok(blobstore_id),
+                write_only,
+            } => {
+                if !write_only {
+                    self.in_flight -= 1;
+                    self.succeeded += 1;
+                }
+                events.push(
+                    self.handler
+                        .on_put(
+                            self.ctx.clone(),
+                            blobstore_id,
+                            self.multiplex_id,
+                            self.operation_key.clone(),
+                            self.key.clone(),
+                        )
+                        .compat()
+                        .map(PutEvent::Logged)
+                        .boxed(),
+                );
             }
-            Err(Either::A((_, rest_put))) => {
-                // Blobstore queue write failed. We might still succeed if the other puts succeed.
-                rest_put.boxify()
+            PutEvent::Put {
+                result: Err((blobstore_id, error)),
+                write_only,
+            } => {
+                if !write_only {
+                    self.in_flight -= 1;
+                }
+                self.put_errors.insert(blobstore_id, error);
             }
-            Err(Either::B((_, queue_write))) => {
-                // Remaining puts failed. We might sitll succeed if the queue write succeeds.
-                queue_write
+            PutEvent::Logged(Ok(())) => self.logged = true,
+            PutEvent::Logged(Err(error)) => self.queue_error = Some(error),
+        }
+    }
+
+    // Once enough blobstores have the blob, we want to return once it is in a position to be
+    // replicated properly to the rest. This can happen in two cases:
+    // - We wrote it to the SQL queue that will replicate it to other blobstores.
+    // - We wrote it to all the blobstores.
+    // Returns None while the outcome depends on events still to come.
+    fn outcome(&mut self, events_done: bool) -> Option<Result<(), Error>> {
+        if self.succeeded >= self.minimum_successful_writes
+            && (self.logged || self.succeeded == self.blobstores_count)
+        {
+            return Some(Ok(()));
+        }
+        if self.succeeded + self.in_flight < self.minimum_successful_writes {
+            let put_errors = std::mem::replace(&mut self.put_errors, HashMap::new());
+            return Some(Err(ErrorKind::NotEnoughSuccessfulWrites(
+                self.minimum_successful_writes,
+                put_errors.into(),
+            )
+            .into()));
+        }
+        if events_done {
+            // Enough puts succeeded, but none of them could be queued for replication
+            return Some(Err(self.queue_error.take().unwrap_or_else(|| {
+                Error::msg("blobstore put could not be queued for replication")
+            })));
+        }
+        None
+    }
+}
+
+async fn multiplexed_put(mut state: PutState, mut events: PutEvents) -> Result<(), Error> {
+    loop {
+        if let Some(result) = state.outcome(events.is_empty()) {
+            if !events.is_empty() {
+                // Give the remaining puts a chance to complete and be queued, but we're done.
+                spawn(finish_puts(state, events).unit_error().boxed().compat());
             }
-        })
-        .boxify()
+            return result;
+        }
+        if let Some(event) = events.next().await {
+            state.handle(event, &mut events);
+        }
+    }
+}
+
+async fn finish_puts(mut state: PutState, mut events: PutEvents) {
+    while let Some(event) = events.next().await {
+        state.handle(event, &mut events);
+    }
 }
@@ -9,11 +9,11 @@ use crate::base::{inner_put, ErrorKind};
 use crate::queue::MultiplexedBlobstore;
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData, BlobstoreMetadata};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange, BlobstoreMetadata};
 use blobstore_sync_queue::BlobstoreSyncQueue;
 use cloned::cloned;
 use context::CoreContext;
-use futures_ext::{BoxFuture, FutureExt};
+use futures_ext::{BoxFuture, BoxStream, FutureExt};
 use futures_old::future::{self, Future};
 use metaconfig_types::{BlobstoreId, MultiplexId, ScrubAction};
 use mononoke_types::BlobstoreBytes;
@@ -21,7 +21,7 @@ use scuba::ScubaSampleBuilder;
 use slog::{info, warn};
 use std::collections::HashMap;
 use std::fmt;
-use std::num::NonZeroU64;
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::sync::{atomic::AtomicUsize, Arc};
 
 pub trait ScrubHandler: Send + Sync {
@@ -85,6 +85,8 @@ impl ScrubBlobstore {
     pub fn new(
         multiplex_id: MultiplexId,
         blobstores: Vec<(BlobstoreId, Arc<dyn Blobstore>)>,
+        write_only_blobstores: Vec<(BlobstoreId, Arc<dyn Blobstore>)>,
+        minimum_successful_writes: NonZeroUsize,
         queue: Arc<dyn BlobstoreSyncQueue>,
         scuba: ScubaSampleBuilder,
         scuba_sample_rate: NonZeroU64,
@@ -94,6 +96,10 @@ impl ScrubBlobstore {
         let inner = MultiplexedBlobstore::new(
             multiplex_id,
             blobstores.clone(),
+            write_only_blobstores.clone(),
+            minimum_successful_writes,
+            // Scrubbing does its own repairs
+            false,
             queue.clone(),
             scuba.clone(),
             scuba_sample_rate,
@@ -103,9 +109,11 @@ impl ScrubBlobstore {
             scrub_handler,
             scrub_action,
             scuba,
+            // Write-only blobstores are scrubbed too, which is how they get populated
             scrub_stores: Arc::new(
                 blobstores
                     .into_iter()
+                    .chain(write_only_blobstores.into_iter())
                     .collect::<HashMap<BlobstoreId, Arc<dyn Blobstore>>>(),
             ),
             queue,
@@ -253,4 +261,21 @@ impl Blobstore for ScrubBlobstore {
     fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
         self.inner.is_present(ctx, key)
     }
+
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.inner.enumerate(ctx, range)
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.inner.delete(ctx, key)
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.inner.overwrite(ctx, key, value)
+    }
 }
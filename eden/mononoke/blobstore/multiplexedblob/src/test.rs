@@ -13,7 +13,7 @@ use crate::base::{MultiplexedBlobstoreBase, MultiplexedBlobstorePutHandler};
 use crate::queue::MultiplexedBlobstore;
 use crate::scrub::{LoggingScrubHandler, ScrubBlobstore, ScrubHandler};
 use anyhow::{bail, Error};
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use blobstore_sync_queue::{
     BlobstoreSyncQueue, BlobstoreSyncQueueEntry, OperationKey, SqlBlobstoreSyncQueue,
 };
@@ -28,6 +28,7 @@ use futures::{
 };
 use futures_ext::{BoxFuture, FutureExt};
 use futures_old::future::{Future, IntoFuture};
+use futures_old::stream::Stream;
 use futures_old::sync::oneshot;
 use lock_ext::LockExt;
 use memblob::LazyMemblob;
@@ -169,6 +170,8 @@ async fn scrub_blobstore_fetch_none(fb: FacebookInit) -> Result<(), Error> {
     let bs = ScrubBlobstore::new(
         MultiplexId::new(1),
         vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(1usize),
         queue.clone(),
         ScubaSampleBuilder::with_discard(),
         nonzero!(1u64),
@@ -213,6 +216,9 @@ fn base(fb: FacebookInit) {
                 (BlobstoreId::new(0), bs0.clone()),
                 (BlobstoreId::new(1), bs1.clone()),
             ],
+            vec![],
+            nonzero!(1usize),
+            false,
             log.clone(),
             ScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
@@ -367,6 +373,9 @@ fn multiplexed(fb: FacebookInit) {
         let bs = MultiplexedBlobstore::new(
             MultiplexId::new(1),
             vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+            vec![],
+            nonzero!(1usize),
+            false,
             queue.clone(),
             ScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
@@ -463,6 +472,9 @@ async fn multiplexed_operation_keys(fb: FacebookInit) -> Result<(), Error> {
             (bid1, bs1.clone()),
             (bid2, bs2.clone()),
         ],
+        vec![],
+        nonzero!(1usize),
+        false,
         queue.clone(),
         ScubaSampleBuilder::with_discard(),
         nonzero!(1u64),
@@ -512,6 +524,8 @@ fn scrubbed(fb: FacebookInit) {
         let bs = ScrubBlobstore::new(
             MultiplexId::new(1),
             vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+            vec![],
+            nonzero!(1usize),
             queue.clone(),
             ScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
@@ -599,6 +613,8 @@ fn scrubbed(fb: FacebookInit) {
         let bs = ScrubBlobstore::new(
             MultiplexId::new(1),
             vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+            vec![],
+            nonzero!(1usize),
             queue.clone(),
             ScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
@@ -706,6 +722,9 @@ fn queue_waits(fb: FacebookInit) {
                 (BlobstoreId::new(1), bs1.clone()),
                 (BlobstoreId::new(2), bs2.clone()),
             ],
+            vec![],
+            nonzero!(1usize),
+            false,
             log.clone(),
             ScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
@@ -806,3 +825,217 @@ fn queue_waits(fb: FacebookInit) {
         }
     });
 }
+
+#[fbinit::compat_test]
+async fn write_quorum(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory().unwrap());
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(LazyMemblob::new());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(LazyMemblob::new());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(ReadOnlyBlobstore::new(LazyMemblob::new()));
+
+    // Two of three blobstores succeeding is enough
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone()),
+            (bid1, bs1.clone()),
+            (bid2, bs2.clone()),
+        ],
+        vec![],
+        nonzero!(2usize),
+        false,
+        queue.clone(),
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+    bs.put(ctx.clone(), "k0".to_string(), make_value("v0"))
+        .compat()
+        .await?;
+    assert!(bs0
+        .get(ctx.clone(), "k0".to_string())
+        .compat()
+        .await?
+        .is_some());
+    assert!(bs1
+        .get(ctx.clone(), "k0".to_string())
+        .compat()
+        .await?
+        .is_some());
+
+    // A single success isn't, even though it is queued for replication
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid2, bs2.clone())],
+        vec![],
+        nonzero!(2usize),
+        false,
+        queue.clone(),
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+    let res = bs
+        .put(ctx.clone(), "k1".to_string(), make_value("v1"))
+        .compat()
+        .await;
+    assert!(res.is_err(), "quorum of 2 reached with 1 write");
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn read_repair(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory().unwrap());
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(LazyMemblob::new());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(LazyMemblob::new());
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![],
+        nonzero!(1usize),
+        true,
+        queue.clone(),
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+
+    let k = "k".to_string();
+    let v = make_value("v");
+    bs0.put(ctx.clone(), k.clone(), v.clone()).compat().await?;
+    assert!(bs1.get(ctx.clone(), k.clone()).compat().await?.is_none());
+
+    let fetched = bs.get(ctx.clone(), k.clone()).compat().await?;
+    assert_eq!(fetched.map(|data| data.into_raw_bytes()), Some(v.clone()));
+    // The get has written the blob back to the blobstore missing it
+    let repaired = bs1.get(ctx.clone(), k.clone()).compat().await?;
+    assert_eq!(repaired.map(|data| data.into_raw_bytes()), Some(v));
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn write_only(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory().unwrap());
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(LazyMemblob::new());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(LazyMemblob::new());
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone())],
+        vec![(bid1, bs1.clone())],
+        nonzero!(1usize),
+        false,
+        queue.clone(),
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+
+    // Puts go to the write-only blobstore too
+    bs.put(ctx.clone(), "k0".to_string(), make_value("v0"))
+        .compat()
+        .await?;
+    assert!(bs0
+        .get(ctx.clone(), "k0".to_string())
+        .compat()
+        .await?
+        .is_some());
+    assert!(bs1
+        .get(ctx.clone(), "k0".to_string())
+        .compat()
+        .await?
+        .is_some());
+
+    // But it is never read from
+    bs1.put(ctx.clone(), "k1".to_string(), make_value("v1"))
+        .compat()
+        .await?;
+    assert!(bs
+        .get(ctx.clone(), "k1".to_string())
+        .compat()
+        .await?
+        .is_none());
+    assert!(
+        !bs.is_present(ctx.clone(), "k1".to_string())
+            .compat()
+            .await?
+    );
+
+    // Nor does it count towards the write quorum
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(ReadOnlyBlobstore::new(LazyMemblob::new()));
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid2, bs2.clone())],
+        vec![(bid1, bs1.clone())],
+        nonzero!(1usize),
+        false,
+        queue.clone(),
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+    let res = bs
+        .put(ctx.clone(), "k2".to_string(), make_value("v2"))
+        .compat()
+        .await;
+    assert!(res.is_err(), "quorum of 1 reached with a write-only write");
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn enumerate(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory().unwrap());
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(LazyMemblob::new());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(LazyMemblob::new());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(LazyMemblob::new());
+    let bs = MultiplexedBlobstore::new(
+        MultiplexId::new(1),
+        vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
+        vec![(bid2, bs2.clone())],
+        nonzero!(1usize),
+        false,
+        queue.clone(),
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+
+    // Keys are produced once, whichever components have them
+    for (blobstore, key) in &[
+        (&bs0, "both"),
+        (&bs1, "both"),
+        (&bs0, "first"),
+        (&bs1, "second"),
+        (&bs2, "second"),
+        (&bs2, "write_only"),
+    ] {
+        blobstore
+            .put(ctx.clone(), key.to_string(), make_value(key))
+            .compat()
+            .await?;
+    }
+    let mut keys = bs
+        .enumerate(ctx.clone(), BlobstoreKeyRange::all())
+        .collect()
+        .compat()
+        .await?;
+    keys.sort();
+    assert_eq!(keys, vec!["both", "first", "second", "write_only"]);
+
+    Ok(())
+}
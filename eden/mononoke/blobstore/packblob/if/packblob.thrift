@@ -0,0 +1,42 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+// Values written by PackBlob are normally stored unchanged. A value is only
+// wrapped in a StorageEnvelope (after the envelope magic) if it has been moved
+// into a pack, or if it happens to start with the envelope magic itself.
+union StorageEnvelope {
+  1: binary single,
+  2: PackedValueRef packed,
+}
+
+// Points from a key to the pack that now holds its value.
+struct PackedValueRef {
+  1: string pack_key,
+}
+
+// A zstd delta (see scm/lib/zstdelta) against the value of an earlier entry in
+// the same pack.
+struct ZstdDelta {
+  1: i32 base_index,
+  2: binary delta,
+}
+
+union PackedEntryValue {
+  // zstd compressed full value
+  1: binary zstd,
+  2: ZstdDelta delta,
+}
+
+struct PackedEntry {
+  1: string key,
+  2: PackedEntryValue value,
+}
+
+// Stored under the pack key. Entries can only refer to earlier entries.
+struct PackedFormat {
+  1: list<PackedEntry> entries,
+}
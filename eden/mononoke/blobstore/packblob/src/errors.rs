@@ -0,0 +1,22 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Packed blob {0} has an invalid envelope")]
+    InvalidEnvelope(String),
+    #[error("Pack {1} for blob {0} is missing")]
+    PackMissing(String, String),
+    #[error("Pack {1} does not contain blob {0}")]
+    NotInPack(String, String),
+    #[error("Pack {0} is corrupt: entry {1} refers to invalid base {2}")]
+    InvalidDeltaBase(String, usize, i32),
+    #[error("Cannot create an empty pack")]
+    EmptyPack,
+}
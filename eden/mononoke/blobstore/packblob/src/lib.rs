@@ -0,0 +1,519 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod errors;
+mod pack;
+
+pub use crate::errors::ErrorKind;
+
+use std::collections::HashSet;
+use std::fmt;
+
+use anyhow::Error;
+use cloned::cloned;
+use fbthrift::compact_protocol;
+use futures::{future, Future};
+use futures_ext::{try_boxfuture, BoxFuture, BoxStream, FutureExt};
+
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
+use context::CoreContext;
+use mononoke_types::{hash::Context, BlobstoreBytes};
+use packblob_thrift::{PackedFormat, PackedValueRef, StorageEnvelope};
+
+/// Values that have been moved into a pack, or that would otherwise be mistaken for one, are
+/// stored as this magic followed by a serialized `StorageEnvelope`. Everything else is stored
+/// unchanged, so PackBlob can be put in front of an existing blobstore.
+const MAGIC: &[u8] = b"\x89MNPACK\n";
+/// Infix of the keys packs are stored under.
+const PACK_KEY_INFIX: &str = "packblob.";
+
+/// A layer over an existing blobstore that can store groups of related values (e.g. successive
+/// versions of the same file) together in a pack, delta-encoded against each other.
+///
+/// Puts go to the underlying blobstore as normal; values are moved into packs offline with
+/// `put_packed` (see the repacker tool). Once a value has been packed, its key holds a small
+/// reference to the pack instead of the value. Packing replaces values with
+/// `Blobstore::overwrite`, so the underlying blobstore must support it.
+#[derive(Clone)]
+pub struct PackBlob<T> {
+    blobstore: T,
+}
+
+/// The outcome of `PackBlob::put_packed`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PutPacked {
+    /// Key of the new pack.
+    pub pack_key: String,
+    /// Packs that held some of the entries before, and that no key refers to any more. They
+    /// can be deleted.
+    pub superseded: Vec<String>,
+}
+
+impl<T: Blobstore + Clone> PackBlob<T> {
+    pub fn new(blobstore: T) -> Self {
+        Self { blobstore }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.blobstore
+    }
+
+    pub fn as_inner(&self) -> &T {
+        &self.blobstore
+    }
+
+    /// Returns the key of the pack holding the value of `key`, or None if it isn't packed.
+    pub fn pack_key(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<String>, Error> {
+        self.blobstore
+            .get(ctx, key.clone())
+            .and_then(move |maybe_data| {
+                let envelope = match maybe_data {
+                    Some(data) => decode_envelope(&key, data.as_raw_bytes())?,
+                    None => None,
+                };
+                match envelope {
+                    Some(StorageEnvelope::packed(PackedValueRef { pack_key })) => {
+                        Ok(Some(pack_key))
+                    }
+                    _ => Ok(None),
+                }
+            })
+            .boxify()
+    }
+
+    /// Returns true if any of the keys stored in the pack `pack_key` still refers to it. A
+    /// missing pack is not referenced.
+    pub fn is_pack_referenced(&self, ctx: CoreContext, pack_key: String) -> BoxFuture<bool, Error> {
+        let this = self.clone();
+        self.blobstore
+            .get(ctx.clone(), pack_key.clone())
+            .and_then(move |maybe_pack| {
+                let pack: PackedFormat = match maybe_pack {
+                    Some(pack) => {
+                        try_boxfuture!(compact_protocol::deserialize(pack.as_raw_bytes().as_ref()))
+                    }
+                    None => return future::ok(false).boxify(),
+                };
+                future::join_all(
+                    pack.entries
+                        .into_iter()
+                        .map(move |entry| this.pack_key(ctx.clone(), entry.key)),
+                )
+                .map(move |pack_keys| {
+                    pack_keys
+                        .into_iter()
+                        .any(|current| current.as_ref() == Some(&pack_key))
+                })
+                .boxify()
+            })
+            .boxify()
+    }
+
+    /// Store `entries` in a single pack under a key starting with `key_prefix`, and point each
+    /// entry's key at it. Entries should be ordered so that similar values are close together:
+    /// each value is delta-encoded against one of the values preceding it.
+    ///
+    /// Entries that were already packed are moved to the new pack, and the packs they were in
+    /// are returned as superseded once nothing refers to them.
+    pub fn put_packed(
+        &self,
+        ctx: CoreContext,
+        key_prefix: &str,
+        entries: Vec<(String, BlobstoreBytes)>,
+    ) -> BoxFuture<PutPacked, Error> {
+        let keys: Vec<_> = entries.iter().map(|(key, _)| key.clone()).collect();
+        let pack = try_boxfuture!(pack::build_pack(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, value.into_bytes()))
+                .collect()
+        ));
+        let pack = compact_protocol::serialize(&pack);
+
+        let mut hash = Context::new(b"packblob");
+        hash.update(&pack);
+        let pack_key = format!("{}{}{}", key_prefix, PACK_KEY_INFIX, hash.finish().to_hex());
+        let reference = encode_envelope(&StorageEnvelope::packed(PackedValueRef {
+            pack_key: pack_key.clone(),
+        }));
+
+        let old_pack_keys = future::join_all(
+            keys.iter()
+                .map(|key| self.pack_key(ctx.clone(), key.clone()))
+                .collect::<Vec<_>>(),
+        );
+
+        // The pack must be durable before any key refers to it.
+        old_pack_keys
+            .and_then({
+                cloned!(self.blobstore, ctx, pack_key);
+                move |old_pack_keys| {
+                    blobstore
+                        .put(ctx, pack_key, BlobstoreBytes::from_bytes(pack))
+                        .map(move |()| old_pack_keys)
+                }
+            })
+            .and_then({
+                cloned!(self.blobstore, ctx);
+                move |old_pack_keys| {
+                    future::join_all(
+                        keys.into_iter().map(move |key| {
+                            blobstore.overwrite(ctx.clone(), key, reference.clone())
+                        }),
+                    )
+                    .map(move |_| old_pack_keys)
+                }
+            })
+            .and_then({
+                let this = self.clone();
+                move |old_pack_keys| {
+                    let old_pack_keys: HashSet<_> = old_pack_keys
+                        .into_iter()
+                        .filter_map(|old| old.filter(|old| old != &pack_key))
+                        .collect();
+                    future::join_all(old_pack_keys.into_iter().map(move |old| {
+                        this.is_pack_referenced(ctx.clone(), old.clone())
+                            .map(move |referenced| (old, referenced))
+                    }))
+                    .map(move |old_packs| PutPacked {
+                        pack_key,
+                        superseded: old_packs
+                            .into_iter()
+                            .filter(|(_, referenced)| !referenced)
+                            .map(|(old, _)| old)
+                            .collect(),
+                    })
+                }
+            })
+            .boxify()
+    }
+}
+
+/// Returns the envelope stored in `bytes`, or None if they hold a value stored unchanged.
+fn decode_envelope(key: &str, bytes: &[u8]) -> Result<Option<StorageEnvelope>, Error> {
+    if !bytes.starts_with(MAGIC) {
+        return Ok(None);
+    }
+    match compact_protocol::deserialize(&bytes[MAGIC.len()..]) {
+        Ok(envelope) => Ok(Some(envelope)),
+        Err(_) => Err(ErrorKind::InvalidEnvelope(key.to_string()).into()),
+    }
+}
+
+fn encode_envelope(envelope: &StorageEnvelope) -> BlobstoreBytes {
+    let serialized = compact_protocol::serialize(envelope);
+    let mut encoded = Vec::with_capacity(MAGIC.len() + serialized.len());
+    encoded.extend_from_slice(MAGIC);
+    encoded.extend_from_slice(&serialized);
+    BlobstoreBytes::from_bytes(encoded)
+}
+
+/// Values that happen to start with the envelope magic are wrapped in a single-value envelope,
+/// so that they aren't mistaken for one when read back.
+fn escape_single(value: BlobstoreBytes) -> BlobstoreBytes {
+    if value.as_bytes().starts_with(MAGIC) {
+        encode_envelope(&StorageEnvelope::single(value.into_bytes().to_vec()))
+    } else {
+        value
+    }
+}
+
+impl<T: Blobstore + Clone> Blobstore for PackBlob<T> {
+    fn get(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+        self.blobstore
+            .get(ctx.clone(), key.clone())
+            .and_then({
+                cloned!(self.blobstore);
+                move |maybe_data| {
+                    let data = match maybe_data {
+                        Some(data) => data,
+                        None => return future::ok(None).left_future(),
+                    };
+                    let envelope = match decode_envelope(&key, data.as_raw_bytes()) {
+                        Ok(Some(envelope)) => envelope,
+                        Ok(None) => return future::ok(Some(data)).left_future(),
+                        Err(error) => return future::err(error).left_future(),
+                    };
+                    let meta = data.as_meta().clone();
+
+                    match envelope {
+                        StorageEnvelope::single(value) => future::ok(Some(BlobstoreGetData::new(
+                            meta,
+                            BlobstoreBytes::from_bytes(value),
+                        )))
+                        .left_future(),
+                        StorageEnvelope::packed(PackedValueRef { pack_key }) => blobstore
+                            .get(ctx, pack_key.clone())
+                            .and_then(move |maybe_pack| {
+                                let pack = maybe_pack.ok_or_else(|| {
+                                    ErrorKind::PackMissing(key.clone(), pack_key.clone())
+                                })?;
+                                let pack: PackedFormat =
+                                    compact_protocol::deserialize(pack.as_raw_bytes().as_ref())?;
+                                let value = pack::unpack(&pack_key, &pack, &key)?
+                                    .ok_or_else(|| ErrorKind::NotInPack(key, pack_key))?;
+                                Ok(Some(BlobstoreGetData::new(
+                                    meta,
+                                    BlobstoreBytes::from_bytes(value),
+                                )))
+                            })
+                            .right_future(),
+                        StorageEnvelope::UnknownField(_) => {
+                            future::err(ErrorKind::InvalidEnvelope(key).into()).left_future()
+                        }
+                    }
+                }
+            })
+            .boxify()
+    }
+
+    fn put(&self, ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
+        self.blobstore.put(ctx, key, escape_single(value))
+    }
+
+    fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
+        self.blobstore.is_present(ctx, key)
+    }
+
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.blobstore.enumerate(ctx, range)
+    }
+
+    // Only the envelope goes. A pack it pointed into is left alone, as other keys may still be
+    // stored in it.
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.blobstore.delete(ctx, key)
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.blobstore.overwrite(ctx, key, escape_single(value))
+    }
+}
+
+impl<T: Blobstore + Clone> fmt::Debug for PackBlob<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PackBlob")
+            .field("blobstore", &self.blobstore)
+            .finish()
+    }
+}
+
+/// Returns true if `key` is the key of a pack written by `PackBlob::put_packed`.
+pub fn is_pack_key(key: &str) -> bool {
+    key.contains(PACK_KEY_INFIX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use bytes::Bytes;
+    use fbinit::FacebookInit;
+
+    use memblob::EagerMemblob;
+    use sqlblob::Sqlblob;
+    use std::sync::Arc;
+    use tokio_compat::runtime::Runtime;
+
+    fn get(ctx: &CoreContext, store: &PackBlob<EagerMemblob>, key: &str) -> Option<Bytes> {
+        // We're using EagerMemblob (immediate future completion) so calling wait() is fine.
+        store
+            .get(ctx.clone(), key.to_string())
+            .wait()
+            .expect("get should succeed")
+            .map(BlobstoreGetData::into_raw_bytes)
+    }
+
+    #[fbinit::test]
+    fn test_single_passthrough(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let store = PackBlob::new(EagerMemblob::new());
+
+        let plain = Bytes::from_static(b"plain value");
+        store
+            .put(
+                ctx.clone(),
+                "plain".to_string(),
+                BlobstoreBytes::from_bytes(plain.clone()),
+            )
+            .wait()
+            .expect("put should succeed");
+        assert_eq!(get(&ctx, &store, "plain"), Some(plain.clone()));
+        // Ordinary values are stored unchanged.
+        assert_eq!(
+            store
+                .as_inner()
+                .get(ctx.clone(), "plain".to_string())
+                .wait()
+                .expect("get should succeed")
+                .map(BlobstoreGetData::into_raw_bytes),
+            Some(plain),
+        );
+
+        let mut tricky = MAGIC.to_vec();
+        tricky.extend_from_slice(b"not an envelope");
+        let tricky = Bytes::from(tricky);
+        store
+            .put(
+                ctx.clone(),
+                "tricky".to_string(),
+                BlobstoreBytes::from_bytes(tricky.clone()),
+            )
+            .wait()
+            .expect("put should succeed");
+        assert_eq!(get(&ctx, &store, "tricky"), Some(tricky));
+        assert_eq!(get(&ctx, &store, "missing"), None);
+    }
+
+    #[fbinit::test]
+    fn test_put_packed(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let store = PackBlob::new(EagerMemblob::new());
+
+        let base = b"fn main() {\n    println!(\"hello\");\n}\n".repeat(64);
+        let entries: Vec<_> = (0..5)
+            .map(|i| {
+                let mut value = base.clone();
+                value.extend_from_slice(format!("// version {}\n", i).as_bytes());
+                (format!("repo0000.v{}", i), Bytes::from(value))
+            })
+            .collect();
+        for (key, value) in entries.iter() {
+            store
+                .put(
+                    ctx.clone(),
+                    key.clone(),
+                    BlobstoreBytes::from_bytes(value.clone()),
+                )
+                .wait()
+                .expect("put should succeed");
+        }
+
+        let PutPacked {
+            pack_key,
+            superseded,
+        } = store
+            .put_packed(
+                ctx.clone(),
+                "repo0000.",
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), BlobstoreBytes::from_bytes(value.clone())))
+                    .collect(),
+            )
+            .wait()
+            .expect("put_packed should succeed");
+        assert!(pack_key.starts_with("repo0000.packblob."));
+        assert!(superseded.is_empty());
+        assert!(is_pack_key(&pack_key));
+
+        let total: usize = entries.iter().map(|(_, value)| value.len()).sum();
+        let pack_len = store
+            .as_inner()
+            .get(ctx.clone(), pack_key.clone())
+            .wait()
+            .expect("get should succeed")
+            .expect("pack should be present")
+            .as_raw_bytes()
+            .len();
+        assert!(pack_len < total / 4);
+
+        for (key, value) in entries {
+            assert_eq!(get(&ctx, &store, &key), Some(value));
+        }
+    }
+
+    #[fbinit::test]
+    fn test_superseded_packs(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let store = PackBlob::new(EagerMemblob::new());
+
+        let entries: Vec<_> = (0..3)
+            .map(|i| {
+                let value = format!("version {}", i).repeat(16);
+                (
+                    format!("repo0000.v{}", i),
+                    BlobstoreBytes::from_bytes(value),
+                )
+            })
+            .collect();
+        let put_packed = |entries: &[(String, BlobstoreBytes)]| {
+            store
+                .put_packed(ctx.clone(), "repo0000.", entries.to_vec())
+                .wait()
+                .expect("put_packed should succeed")
+        };
+        let pack_key = |key: &str| {
+            store
+                .pack_key(ctx.clone(), key.to_string())
+                .wait()
+                .expect("pack_key should succeed")
+        };
+
+        assert_eq!(pack_key("repo0000.v0"), None);
+        let first = put_packed(&entries[..2]);
+        assert_eq!(pack_key("repo0000.v0"), Some(first.pack_key.clone()));
+
+        // v0 still refers to the first pack.
+        let second = put_packed(&entries[1..]);
+        assert!(second.superseded.is_empty());
+        assert_eq!(pack_key("repo0000.v1"), Some(second.pack_key.clone()));
+
+        // Now nothing does.
+        let third = put_packed(&entries[..1]);
+        assert_eq!(third.superseded, vec![first.pack_key]);
+
+        for (key, value) in entries {
+            assert_eq!(get(&ctx, &store, &key), Some(value.into_bytes()));
+        }
+    }
+
+    #[fbinit::test]
+    fn test_put_packed_sqlblob(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let mut rt = Runtime::new()?;
+        let store = PackBlob::new(Arc::new(Sqlblob::with_sqlite_in_memory()?));
+
+        let entries: Vec<_> = (0..3)
+            .map(|i| {
+                let value = format!("version {}", i).repeat(16);
+                (
+                    format!("repo0000.v{}", i),
+                    BlobstoreBytes::from_bytes(value),
+                )
+            })
+            .collect();
+        for (key, value) in &entries {
+            rt.block_on(store.put(ctx.clone(), key.clone(), value.clone()))?;
+        }
+
+        // Sqlblob keeps existing values on put, so the values must be overwritten for their
+        // space to be reclaimed.
+        let packed = rt.block_on(store.put_packed(ctx.clone(), "repo0000.", entries.clone()))?;
+        for (key, value) in entries {
+            let raw = rt
+                .block_on(store.as_inner().get(ctx.clone(), key.clone()))?
+                .expect("reference should be present");
+            assert!(raw.as_raw_bytes().starts_with(MAGIC));
+            assert_eq!(
+                rt.block_on(store.pack_key(ctx.clone(), key.clone()))?,
+                Some(packed.pack_key.clone())
+            );
+            let stored = rt.block_on(store.get(ctx.clone(), key))?;
+            assert_eq!(stored.map(BlobstoreGetData::into_bytes), Some(value));
+        }
+
+        Ok(())
+    }
+}
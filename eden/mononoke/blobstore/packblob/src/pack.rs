@@ -0,0 +1,140 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use bytes::Bytes;
+use packblob_thrift::{PackedEntry, PackedEntryValue, PackedFormat, ZstdDelta};
+
+use crate::errors::ErrorKind;
+
+/// Longest chain of deltas that has to be applied to reconstruct a single value. Bounds the
+/// cost of a read at the expense of a slightly larger pack.
+const MAX_DELTA_CHAIN: usize = 16;
+/// How many of the preceding entries are tried as a delta base for each new entry. Callers are
+/// expected to order entries so that similar values are close together.
+const DELTA_CANDIDATES: usize = 8;
+
+/// Encode `entries` as a pack. Each value is stored either zstd compressed on its own, or as a
+/// zstd delta against one of the entries preceding it, whichever is smaller.
+pub(crate) fn build_pack(entries: Vec<(String, Bytes)>) -> Result<PackedFormat> {
+    if entries.is_empty() {
+        return Err(ErrorKind::EmptyPack.into());
+    }
+
+    let mut packed = Vec::with_capacity(entries.len());
+    let mut chain_depth: Vec<usize> = Vec::with_capacity(entries.len());
+
+    for (index, (key, data)) in entries.iter().enumerate() {
+        let mut best = PackedEntryValue::zstd(zstdelta::diff(b"", data)?);
+        let mut best_len = encoded_len(&best);
+        let mut best_depth = 0;
+
+        for base_index in index.saturating_sub(DELTA_CANDIDATES)..index {
+            let depth = chain_depth[base_index] + 1;
+            if depth > MAX_DELTA_CHAIN {
+                continue;
+            }
+            let delta = zstdelta::diff(&entries[base_index].1, data)?;
+            if delta.len() < best_len {
+                best_len = delta.len();
+                best_depth = depth;
+                best = PackedEntryValue::delta(ZstdDelta {
+                    base_index: base_index as i32,
+                    delta,
+                });
+            }
+        }
+
+        chain_depth.push(best_depth);
+        packed.push(PackedEntry {
+            key: key.clone(),
+            value: best,
+        });
+    }
+
+    Ok(PackedFormat { entries: packed })
+}
+
+fn encoded_len(value: &PackedEntryValue) -> usize {
+    match value {
+        PackedEntryValue::zstd(data) => data.len(),
+        PackedEntryValue::delta(ZstdDelta { delta, .. }) => delta.len(),
+        PackedEntryValue::UnknownField(_) => usize::MAX,
+    }
+}
+
+/// Reconstruct the value stored under `key` in a pack, by applying its chain of deltas.
+pub(crate) fn unpack(pack_key: &str, pack: &PackedFormat, key: &str) -> Result<Option<Bytes>> {
+    let index = match pack.entries.iter().position(|entry| entry.key == key) {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+
+    // Walk back to a full value, then apply the deltas forwards.
+    let mut chain = vec![];
+    let mut current = index;
+    let base = loop {
+        match &pack.entries[current].value {
+            PackedEntryValue::zstd(data) => break zstdelta::apply(b"", data)?,
+            PackedEntryValue::delta(ZstdDelta { base_index, delta }) => {
+                if *base_index < 0 || *base_index as usize >= current {
+                    return Err(ErrorKind::InvalidDeltaBase(
+                        pack_key.to_string(),
+                        current,
+                        *base_index,
+                    )
+                    .into());
+                }
+                chain.push(delta);
+                current = *base_index as usize;
+            }
+            PackedEntryValue::UnknownField(_) => {
+                return Err(ErrorKind::InvalidEnvelope(pack_key.to_string()).into());
+            }
+        }
+    };
+
+    let value = chain
+        .into_iter()
+        .rev()
+        .try_fold(base, |base, delta| zstdelta::apply(&base, delta))?;
+    Ok(Some(Bytes::from(value)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pack_roundtrip() -> Result<()> {
+        let base = b"the quick brown fox jumps over the lazy dog\n".repeat(32);
+        let entries: Vec<_> = (0..40)
+            .map(|i| {
+                let mut value = base.clone();
+                value.extend_from_slice(format!("revision {}\n", i).as_bytes());
+                (format!("key{}", i), Bytes::from(value))
+            })
+            .collect();
+
+        let pack = build_pack(entries.clone())?;
+        match &pack.entries[1].value {
+            PackedEntryValue::delta(ZstdDelta { base_index, .. }) => assert_eq!(*base_index, 0),
+            _ => panic!("similar values should be delta encoded"),
+        }
+
+        for (key, value) in entries {
+            assert_eq!(unpack("pack", &pack, &key)?, Some(value));
+        }
+        assert_eq!(unpack("pack", &pack, "missing")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_pack() {
+        assert!(build_pack(vec![]).is_err());
+    }
+}
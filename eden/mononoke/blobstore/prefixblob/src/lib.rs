@@ -8,11 +8,12 @@
 use anyhow::Error;
 use inlinable_string::InlinableString;
 
-use futures_ext::BoxFuture;
+use futures::Stream;
+use futures_ext::{BoxFuture, BoxStream, StreamExt};
 
 use context::CoreContext;
 
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use mononoke_types::BlobstoreBytes;
 
 /// A layer over an existing blobstore that prepends a fixed string to each get and put.
@@ -60,6 +61,29 @@ impl<T: Blobstore + Clone> Blobstore for PrefixBlobstore<T> {
     fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
         self.blobstore.is_present(ctx, self.prepend(key))
     }
+
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        let prefix_len = self.prefix.len();
+        self.blobstore
+            .enumerate(ctx, range.prepend(&self.prefix))
+            .map(move |key| key[prefix_len..].to_string())
+            .boxify()
+    }
+
+    #[inline]
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.blobstore.delete(ctx, self.prepend(key))
+    }
+
+    #[inline]
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.blobstore.overwrite(ctx, self.prepend(key), value)
+    }
 }
 
 #[cfg(test)]
@@ -119,4 +143,45 @@ mod test {
             .wait()
             .expect("is_present should succeed"));
     }
+
+    #[fbinit::test]
+    fn test_prefix_enumerate(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let prefixed = PrefixBlobstore::new(base.clone(), "prefix123-");
+
+        for key in &["foo", "bar"] {
+            prefixed
+                .put(
+                    ctx.clone(),
+                    key.to_string(),
+                    BlobstoreBytes::from_bytes("x"),
+                )
+                .wait()
+                .expect("put should succeed");
+        }
+        base.put(
+            ctx.clone(),
+            "unprefixed".to_string(),
+            BlobstoreBytes::from_bytes("x"),
+        )
+        .wait()
+        .expect("put should succeed");
+
+        // Keys come back without the prefix, and keys outside the prefix are not included.
+        let mut keys = prefixed
+            .enumerate(ctx.clone(), BlobstoreKeyRange::all())
+            .collect()
+            .wait()
+            .expect("enumerate should succeed");
+        keys.sort();
+        assert_eq!(keys, vec!["bar".to_string(), "foo".to_string()]);
+
+        let keys = prefixed
+            .enumerate(ctx.clone(), BlobstoreKeyRange::with_prefix("f"))
+            .collect()
+            .wait()
+            .expect("enumerate should succeed");
+        assert_eq!(keys, vec!["foo".to_string()]);
+    }
 }
@@ -11,4 +11,6 @@ use thiserror::Error;
 pub enum ErrorKind {
     #[error("Attempt to put to ReadOnlyBlobstore for key {0}")]
     ReadOnlyPut(String),
+    #[error("Attempt to delete from ReadOnlyBlobstore for key {0}")]
+    ReadOnlyDelete(String),
 }
@@ -6,10 +6,10 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use context::CoreContext;
 use futures::future;
-use futures_ext::{BoxFuture, FutureExt};
+use futures_ext::{BoxFuture, BoxStream, FutureExt};
 use mononoke_types::BlobstoreBytes;
 mod errors;
 pub use crate::errors::ErrorKind;
@@ -41,6 +41,26 @@ impl<T: Blobstore + Clone> Blobstore for ReadOnlyBlobstore<T> {
     fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
         self.blobstore.is_present(ctx, key)
     }
+
+    #[inline]
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.blobstore.enumerate(ctx, range)
+    }
+
+    #[inline]
+    fn delete(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        future::err(ErrorKind::ReadOnlyDelete(key).into()).boxify()
+    }
+
+    #[inline]
+    fn overwrite(
+        &self,
+        _ctx: CoreContext,
+        key: String,
+        _value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        future::err(ErrorKind::ReadOnlyPut(key).into()).boxify()
+    }
 }
 
 #[cfg(test)]
@@ -70,4 +90,24 @@ mod test {
         let base_present = base.is_present(ctx, key.clone()).wait().unwrap();
         assert!(!base_present);
     }
+
+    #[fbinit::test]
+    fn test_error_on_delete(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let wrapper = ReadOnlyBlobstore::new(base.clone());
+        let key = "foobar".to_string();
+
+        base.put(
+            ctx.clone(),
+            key.clone(),
+            BlobstoreBytes::from_bytes("test foobar"),
+        )
+        .wait()
+        .unwrap();
+        let r = wrapper.delete(ctx.clone(), key.clone()).wait();
+        assert!(!r.is_ok());
+        let base_present = base.is_present(ctx, key.clone()).wait().unwrap();
+        assert!(base_present);
+    }
 }
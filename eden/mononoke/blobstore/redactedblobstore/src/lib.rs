@@ -8,10 +8,10 @@
 #![deny(warnings)]
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use context::CoreContext;
 use futures::future::{Future, IntoFuture};
-use futures_ext::{BoxFuture, FutureExt};
+use futures_ext::{BoxFuture, BoxStream, FutureExt};
 use mononoke_types::{BlobstoreBytes, Timestamp};
 use scuba_ext::ScubaSampleBuilder;
 use slog::debug;
@@ -204,6 +204,40 @@ impl<T: Blobstore + Clone> Blobstore for RedactedBlobstoreInner<T> {
     fn assert_present(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
         self.blobstore.assert_present(ctx, key)
     }
+
+    // Listing and removing keys don't expose their content, so redaction doesn't apply.
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.blobstore.enumerate(ctx, range)
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.blobstore.delete(ctx, key)
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.access_blobstore(&key)
+            .map_err({
+                cloned!(ctx, key);
+                move |err| {
+                    debug!(
+                        ctx.logger(),
+                        "Overwriting redacted blobstore with key {:?}", key
+                    );
+
+                    self.to_scuba_redacted_blob_accessed(&ctx, &key, config::PUT_OPERATION);
+                    err
+                }
+            })
+            .map({ move |blobstore| blobstore.overwrite(ctx, key, value) })
+            .into_future()
+            .flatten()
+            .boxify()
+    }
 }
 
 impl<B> Blobstore for RedactedBlobstore<B>
@@ -222,6 +256,20 @@ where
     fn assert_present(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
         self.inner.assert_present(ctx, key)
     }
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.inner.enumerate(ctx, range)
+    }
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.inner.delete(ctx, key)
+    }
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.inner.overwrite(ctx, key, value)
+    }
 }
 
 pub fn has_redaction_root_cause(e: &Error) -> bool {
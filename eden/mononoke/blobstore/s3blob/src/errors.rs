@@ -0,0 +1,23 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use http::{Method, StatusCode};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("S3 request {0} {1} failed with status {2}: {3}")]
+    UnexpectedStatus(Method, String, StatusCode, String),
+    #[error("S3 request {0} {1} failed after {2} attempts")]
+    RetriesExhausted(Method, String, usize),
+    #[error("S3 response is missing {0}")]
+    MalformedResponse(&'static str),
+    #[error("Invalid S3 endpoint {0}")]
+    InvalidEndpoint(String),
+    #[error("Invalid S3 credentials file: expected '<access key id> <secret access key>'")]
+    InvalidCredentials,
+}
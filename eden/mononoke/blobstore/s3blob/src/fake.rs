@@ -0,0 +1,249 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! An in-process stand-in for S3, serving the subset of the API S3Blob uses.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use bytes::Bytes;
+use http::header::{AUTHORIZATION, ETAG};
+use http::{Method, Request, Response, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Server};
+use percent_encoding::percent_decode_str;
+
+use crate::xml;
+
+#[derive(Default)]
+struct FakeState {
+    objects: BTreeMap<String, Bytes>,
+    uploads: HashMap<String, BTreeMap<u32, Bytes>>,
+    next_upload_id: u64,
+    completed_uploads: usize,
+    fail_next: usize,
+}
+
+pub(crate) struct FakeS3 {
+    addr: SocketAddr,
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeS3 {
+    /// Start serving on a local port. Must be called from within a Tokio runtime.
+    pub(crate) async fn start() -> Result<Self> {
+        let state = Arc::new(Mutex::new(FakeState::default()));
+        let make_service = make_service_fn({
+            let state = state.clone();
+            move |_| {
+                let state = state.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        let state = state.clone();
+                        async move { Ok::<_, Infallible>(handle(&state, request).await) }
+                    }))
+                }
+            }
+        });
+        let server = Server::try_bind(&([127, 0, 0, 1], 0).into())?.serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        Ok(Self { addr, state })
+    }
+
+    pub(crate) fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The object stored under `path` (`<bucket>/<key>`).
+    pub(crate) fn object(&self, path: &str) -> Option<Bytes> {
+        self.state.lock().unwrap().objects.get(path).cloned()
+    }
+
+    pub(crate) fn completed_uploads(&self) -> usize {
+        self.state.lock().unwrap().completed_uploads
+    }
+
+    /// Fail the next `count` requests with 503 Slow Down.
+    pub(crate) fn fail_next(&self, count: usize) {
+        self.state.lock().unwrap().fail_next = count;
+    }
+}
+
+fn respond(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    let mut response = Response::new(body.into());
+    *response.status_mut() = status;
+    response
+}
+
+async fn handle(state: &Mutex<FakeState>, request: Request<Body>) -> Response<Body> {
+    if !request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|auth| auth.to_str().ok())
+        .map_or(false, |auth| auth.starts_with("AWS4-HMAC-SHA256 "))
+    {
+        return respond(StatusCode::FORBIDDEN, "<Error>unsigned</Error>");
+    }
+
+    let method = request.method().clone();
+    let path = percent_decode_str(request.uri().path().trim_start_matches('/'))
+        .decode_utf8_lossy()
+        .into_owned();
+    let query: HashMap<String, String> = request
+        .uri()
+        .query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let mut parts = param.splitn(2, '=');
+            let decode = |s: Option<&str>| {
+                percent_decode_str(s.unwrap_or(""))
+                    .decode_utf8_lossy()
+                    .into_owned()
+            };
+            (decode(parts.next()), decode(parts.next()))
+        })
+        .collect();
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(_) => return respond(StatusCode::BAD_REQUEST, Body::empty()),
+    };
+
+    let mut state = state.lock().unwrap();
+    if state.fail_next > 0 {
+        state.fail_next -= 1;
+        return respond(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "<Error><Code>SlowDown</Code></Error>",
+        );
+    }
+
+    match method {
+        Method::GET if query.get("list-type").map(String::as_str) == Some("2") => {
+            list(&state, &path, &query)
+        }
+        Method::GET => match state.objects.get(&path) {
+            Some(value) => respond(StatusCode::OK, value.clone()),
+            None => respond(
+                StatusCode::NOT_FOUND,
+                "<Error><Code>NoSuchKey</Code></Error>",
+            ),
+        },
+        Method::HEAD => match state.objects.get(&path) {
+            Some(_) => respond(StatusCode::OK, Body::empty()),
+            None => respond(StatusCode::NOT_FOUND, Body::empty()),
+        },
+        Method::PUT => match (query.get("uploadId"), query.get("partNumber")) {
+            (Some(upload_id), Some(part_number)) => {
+                let part_number: u32 = part_number.parse().unwrap_or(0);
+                match state.uploads.get_mut(upload_id) {
+                    Some(parts) => {
+                        parts.insert(part_number, body);
+                        let mut response = respond(StatusCode::OK, Body::empty());
+                        response
+                            .headers_mut()
+                            .insert(ETAG, format!("\"part-{}\"", part_number).parse().unwrap());
+                        response
+                    }
+                    None => respond(StatusCode::NOT_FOUND, "<Error>NoSuchUpload</Error>"),
+                }
+            }
+            _ => {
+                state.objects.insert(path, body);
+                respond(StatusCode::OK, Body::empty())
+            }
+        },
+        Method::POST if query.contains_key("uploads") => {
+            state.next_upload_id += 1;
+            let upload_id = format!("upload/{}", state.next_upload_id);
+            state.uploads.insert(upload_id.clone(), BTreeMap::new());
+            respond(
+                StatusCode::OK,
+                format!(
+                    "<InitiateMultipartUploadResult><UploadId>{}</UploadId>\
+                     </InitiateMultipartUploadResult>",
+                    xml::escape(&upload_id)
+                ),
+            )
+        }
+        Method::POST if query.contains_key("uploadId") => {
+            match state.uploads.remove(&query["uploadId"]) {
+                Some(parts) => {
+                    let requested = xml::elements(std::str::from_utf8(&body).unwrap_or(""), "ETag");
+                    if requested.len() != parts.len() {
+                        return respond(StatusCode::BAD_REQUEST, "<Error>InvalidPart</Error>");
+                    }
+                    let value: Vec<u8> = parts
+                        .values()
+                        .flat_map(|part| part.iter().copied())
+                        .collect();
+                    state.objects.insert(path, Bytes::from(value));
+                    state.completed_uploads += 1;
+                    respond(
+                        StatusCode::OK,
+                        "<CompleteMultipartUploadResult></CompleteMultipartUploadResult>",
+                    )
+                }
+                None => respond(StatusCode::NOT_FOUND, "<Error>NoSuchUpload</Error>"),
+            }
+        }
+        Method::DELETE if query.contains_key("uploadId") => {
+            state.uploads.remove(&query["uploadId"]);
+            respond(StatusCode::NO_CONTENT, Body::empty())
+        }
+        Method::DELETE => {
+            state.objects.remove(&path);
+            respond(StatusCode::NO_CONTENT, Body::empty())
+        }
+        _ => respond(StatusCode::NOT_IMPLEMENTED, Body::empty()),
+    }
+}
+
+/// ListObjectsV2. Continuation tokens are simply the last key of the previous page.
+fn list(state: &FakeState, bucket: &str, query: &HashMap<String, String>) -> Response<Body> {
+    let bucket_prefix = format!("{}/", bucket);
+    let prefix = query.get("prefix").cloned().unwrap_or_default();
+    let max_keys: usize = query
+        .get("max-keys")
+        .and_then(|max_keys| max_keys.parse().ok())
+        .unwrap_or(1000);
+    let after = query.get("continuation-token");
+
+    let mut keys = state
+        .objects
+        .keys()
+        .filter(|path| path.starts_with(bucket_prefix.as_str()))
+        .map(|path| &path[bucket_prefix.len()..])
+        .filter(|key| key.starts_with(prefix.as_str()))
+        .filter(|key| after.map_or(true, |after| *key > after.as_str()));
+    let page: Vec<_> = keys.by_ref().take(max_keys).collect();
+    let truncated = keys.next().is_some();
+
+    let mut body = String::from("<ListBucketResult>");
+    body.push_str(&format!("<IsTruncated>{}</IsTruncated>", truncated));
+    for key in page.iter() {
+        body.push_str(&format!(
+            "<Contents><Key>{}</Key></Contents>",
+            xml::escape(key)
+        ));
+    }
+    if truncated {
+        if let Some(last) = page.last() {
+            body.push_str(&format!(
+                "<NextContinuationToken>{}</NextContinuationToken>",
+                xml::escape(last)
+            ));
+        }
+    }
+    body.push_str("</ListBucketResult>");
+    respond(StatusCode::OK, body)
+}
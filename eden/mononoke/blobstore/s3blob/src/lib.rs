@@ -0,0 +1,696 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod errors;
+#[cfg(test)]
+mod fake;
+mod sigv4;
+mod xml;
+
+pub use crate::errors::ErrorKind;
+
+use std::cmp::min;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{format_err, Context, Error, Result};
+use bytes::Bytes;
+use chrono::Utc;
+use futures::{
+    future::{self, FutureExt as _, TryFutureExt},
+    stream::{self, StreamExt as _, TryStreamExt},
+};
+use futures_ext::{BoxFuture, BoxStream, FutureExt as _, StreamExt as _};
+use http::header::{HeaderMap, AUTHORIZATION, CONTENT_LENGTH, ETAG, HOST};
+use http::uri::{Authority, Scheme, Uri};
+use http::{Method, Request, StatusCode};
+use hyper::{client::HttpConnector, Body, Client};
+use hyper_tls::HttpsConnector;
+
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+
+use crate::sigv4::RequestToSign;
+
+/// Credentials used to sign requests.
+#[derive(Clone)]
+pub struct S3Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl S3Credentials {
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+        }
+    }
+
+    /// Read credentials from a file holding the access key id and the secret access key,
+    /// separated by whitespace.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("While reading S3 credentials {}", path.display()))?;
+        let mut parts = contents.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(access_key_id), Some(secret_access_key), None) => {
+                Ok(Self::new(access_key_id, secret_access_key))
+            }
+            _ => Err(ErrorKind::InvalidCredentials.into()),
+        }
+    }
+
+    /// Read credentials from the standard `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`
+    /// environment variables.
+    pub fn from_env() -> Result<Self> {
+        Ok(Self::new(
+            env::var("AWS_ACCESS_KEY_ID").context("While reading AWS_ACCESS_KEY_ID")?,
+            env::var("AWS_SECRET_ACCESS_KEY").context("While reading AWS_SECRET_ACCESS_KEY")?,
+        ))
+    }
+}
+
+impl fmt::Debug for S3Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct S3Options {
+    /// Number of times a request is attempted before giving up. Only connection errors,
+    /// throttling and server errors are retried.
+    pub max_attempts: usize,
+    /// Delay before the first retry. Doubles with every retry, up to `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Values larger than this are uploaded in parts.
+    pub multipart_threshold: usize,
+    /// Size of each part of a multipart upload. S3 requires at least 5MiB.
+    pub part_size: usize,
+    /// Number of keys to request per page when enumerating.
+    pub list_page_size: usize,
+}
+
+impl Default for S3Options {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multipart_threshold: 16 * 1024 * 1024,
+            part_size: 8 * 1024 * 1024,
+            list_page_size: 1000,
+        }
+    }
+}
+
+struct S3Response {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl S3Response {
+    fn body_str(&self) -> Result<&str> {
+        Ok(std::str::from_utf8(&self.body)?)
+    }
+}
+
+/// A blobstore backed by a bucket in S3 or an S3-compatible object store. Objects are
+/// addressed path-style (`<endpoint>/<bucket>/<prefix><key>`), which every S3-compatible store
+/// supports.
+#[derive(Clone)]
+pub struct S3Blob {
+    inner: Arc<S3BlobInner>,
+}
+
+struct S3BlobInner {
+    client: Client<HttpsConnector<HttpConnector>>,
+    scheme: Scheme,
+    authority: Authority,
+    bucket: String,
+    prefix: String,
+    region: String,
+    credentials: S3Credentials,
+    options: S3Options,
+}
+
+impl S3Blob {
+    pub fn new(
+        endpoint: &str,
+        bucket: impl Into<String>,
+        prefix: impl Into<String>,
+        region: impl Into<String>,
+        credentials: S3Credentials,
+        options: S3Options,
+    ) -> Result<Self> {
+        let endpoint: Uri = endpoint
+            .parse()
+            .with_context(|| ErrorKind::InvalidEndpoint(endpoint.to_string()))?;
+        let parts = endpoint.into_parts();
+        let (scheme, authority) = match (parts.scheme, parts.authority) {
+            (Some(scheme), Some(authority)) => (scheme, authority),
+            (scheme, authority) => {
+                return Err(
+                    ErrorKind::InvalidEndpoint(format!("{:?}://{:?}", scheme, authority)).into(),
+                )
+            }
+        };
+
+        Ok(Self {
+            inner: Arc::new(S3BlobInner {
+                client: Client::builder().build(HttpsConnector::new()),
+                scheme,
+                authority,
+                bucket: bucket.into(),
+                prefix: prefix.into(),
+                region: region.into(),
+                credentials,
+                options,
+            }),
+        })
+    }
+
+    /// Send a signed request, retrying with exponential backoff on connection errors,
+    /// throttling and server errors.
+    async fn request(
+        &self,
+        method: Method,
+        key: Option<&str>,
+        query: &[(&str, &str)],
+        body: Bytes,
+    ) -> Result<S3Response> {
+        let inner = &self.inner;
+        let path = match key {
+            Some(key) => format!("/{}/{}{}", inner.bucket, inner.prefix, key),
+            None => format!("/{}", inner.bucket),
+        };
+        let canonical_uri = sigv4::encode_path(&path);
+        let canonical_query = sigv4::canonical_query(query);
+        let path_and_query = if canonical_query.is_empty() {
+            canonical_uri.clone()
+        } else {
+            format!("{}?{}", canonical_uri, canonical_query)
+        };
+        let uri = Uri::builder()
+            .scheme(inner.scheme.clone())
+            .authority(inner.authority.clone())
+            .path_and_query(path_and_query.as_str())
+            .build()?;
+
+        let mut backoff = inner.options.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let signed = sigv4::sign(
+                &inner.credentials,
+                &inner.region,
+                Utc::now(),
+                &RequestToSign {
+                    method: method.as_str(),
+                    host: inner.authority.as_str(),
+                    canonical_uri: &canonical_uri,
+                    canonical_query: &canonical_query,
+                    payload: &body,
+                },
+            )?;
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(uri.clone())
+                .header(HOST, inner.authority.as_str())
+                .header("x-amz-date", signed.amz_date)
+                .header("x-amz-content-sha256", signed.content_sha256)
+                .header(AUTHORIZATION, signed.authorization)
+                .header(CONTENT_LENGTH, body.len())
+                .body(Body::from(body.clone()))?;
+
+            let error = match inner.client.request(request).await {
+                Ok(response) => {
+                    let (parts, response_body) = response.into_parts();
+                    match hyper::body::to_bytes(response_body).await {
+                        Ok(response_body) if !is_retriable(parts.status) => {
+                            return Ok(S3Response {
+                                status: parts.status,
+                                headers: parts.headers,
+                                body: response_body,
+                            });
+                        }
+                        Ok(_) => format_err!("Retriable status {}", parts.status),
+                        Err(e) => e.into(),
+                    }
+                }
+                Err(e) => e.into(),
+            };
+
+            if attempt >= inner.options.max_attempts {
+                return Err(error.context(ErrorKind::RetriesExhausted(method, path, attempt)));
+            }
+            tokio::time::delay_for(backoff).await;
+            backoff = min(backoff * 2, inner.options.max_backoff);
+        }
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Option<Bytes>> {
+        let response = self
+            .request(Method::GET, Some(key), &[], Bytes::new())
+            .await?;
+        if response.status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(check(Method::GET, key, response)?.body))
+    }
+
+    async fn head_object(&self, key: &str) -> Result<bool> {
+        let response = self
+            .request(Method::HEAD, Some(key), &[], Bytes::new())
+            .await?;
+        if response.status == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        check(Method::HEAD, key, response)?;
+        Ok(true)
+    }
+
+    /// S3 reports success for keys that don't exist, so this is idempotent.
+    async fn delete_object(&self, key: &str) -> Result<()> {
+        let response = self
+            .request(Method::DELETE, Some(key), &[], Bytes::new())
+            .await?;
+        check(Method::DELETE, key, response)?;
+        Ok(())
+    }
+
+    async fn put_object(&self, key: &str, value: Bytes) -> Result<()> {
+        if value.len() > self.inner.options.multipart_threshold {
+            return self.put_object_multipart(key, value).await;
+        }
+        let response = self.request(Method::PUT, Some(key), &[], value).await?;
+        check(Method::PUT, key, response)?;
+        Ok(())
+    }
+
+    async fn put_object_multipart(&self, key: &str, value: Bytes) -> Result<()> {
+        let response = self
+            .request(Method::POST, Some(key), &[("uploads", "")], Bytes::new())
+            .await?;
+        let response = check(Method::POST, key, response)?;
+        let upload_id = xml::element(response.body_str()?, "UploadId")
+            .ok_or(ErrorKind::MalformedResponse("UploadId"))?;
+
+        let result = self.upload_parts(key, &upload_id, value).await;
+        if result.is_err() {
+            // Best effort: the upload is garbage either way, and the original error is more
+            // interesting than a failure to clean up.
+            let _ = self
+                .request(
+                    Method::DELETE,
+                    Some(key),
+                    &[("uploadId", &upload_id)],
+                    Bytes::new(),
+                )
+                .await;
+        }
+        result
+    }
+
+    async fn upload_parts(&self, key: &str, upload_id: &str, value: Bytes) -> Result<()> {
+        let part_size = self.inner.options.part_size;
+        let num_parts = (value.len() + part_size - 1) / part_size;
+        let etags = future::try_join_all((0..num_parts).map(|index| {
+            let part = value.slice(index * part_size..min((index + 1) * part_size, value.len()));
+            async move {
+                let part_number = (index + 1).to_string();
+                let response = self
+                    .request(
+                        Method::PUT,
+                        Some(key),
+                        &[("partNumber", &part_number), ("uploadId", upload_id)],
+                        part,
+                    )
+                    .await?;
+                let response = check(Method::PUT, key, response)?;
+                let etag = response
+                    .headers
+                    .get(ETAG)
+                    .ok_or(ErrorKind::MalformedResponse("ETag"))?
+                    .to_str()?
+                    .to_string();
+                Ok::<_, Error>(etag)
+            }
+        }))
+        .await?;
+
+        let mut complete = String::from("<CompleteMultipartUpload>");
+        for (index, etag) in etags.iter().enumerate() {
+            complete.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                index + 1,
+                xml::escape(etag)
+            ));
+        }
+        complete.push_str("</CompleteMultipartUpload>");
+
+        let response = self
+            .request(
+                Method::POST,
+                Some(key),
+                &[("uploadId", upload_id)],
+                Bytes::from(complete),
+            )
+            .await?;
+        let response = check(Method::POST, key, response)?;
+        // Completing an upload can fail after a 200 OK has been sent.
+        if response.body_str()?.contains("<Error>") {
+            return Err(ErrorKind::UnexpectedStatus(
+                Method::POST,
+                key.to_string(),
+                response.status,
+                response.body_str()?.to_string(),
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// List one page of keys starting with `prefix`. Returns the keys without our own prefix,
+    /// and the token for the next page if there is one.
+    async fn list_objects(
+        &self,
+        prefix: &str,
+        continuation_token: Option<&str>,
+    ) -> Result<(Vec<String>, Option<String>)> {
+        let inner = &self.inner;
+        let full_prefix = format!("{}{}", inner.prefix, prefix);
+        let page_size = inner.options.list_page_size.to_string();
+        let mut query = vec![
+            ("list-type", "2"),
+            ("prefix", full_prefix.as_str()),
+            ("max-keys", page_size.as_str()),
+        ];
+        if let Some(token) = continuation_token {
+            query.push(("continuation-token", token));
+        }
+
+        let response = self
+            .request(Method::GET, None, &query, Bytes::new())
+            .await?;
+        let response = check(Method::GET, &full_prefix, response)?;
+        let body = response.body_str()?;
+
+        let keys = xml::elements(body, "Key")
+            .into_iter()
+            .filter_map(|key| {
+                if key.starts_with(inner.prefix.as_str()) {
+                    Some(key[inner.prefix.len()..].to_string())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let next = if xml::element(body, "IsTruncated").as_deref() == Some("true") {
+            Some(
+                xml::element(body, "NextContinuationToken")
+                    .ok_or(ErrorKind::MalformedResponse("NextContinuationToken"))?,
+            )
+        } else {
+            None
+        };
+        Ok((keys, next))
+    }
+}
+
+fn is_retriable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn check(method: Method, key: &str, response: S3Response) -> Result<S3Response> {
+    if response.status.is_success() {
+        return Ok(response);
+    }
+    let body = String::from_utf8_lossy(&response.body).into_owned();
+    Err(ErrorKind::UnexpectedStatus(method, key.to_string(), response.status, body).into())
+}
+
+impl fmt::Debug for S3Blob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Blob")
+            .field("endpoint", &self.inner.authority)
+            .field("bucket", &self.inner.bucket)
+            .field("prefix", &self.inner.prefix)
+            .field("region", &self.inner.region)
+            .field("credentials", &self.inner.credentials)
+            .finish()
+    }
+}
+
+impl Blobstore for S3Blob {
+    fn get(&self, _ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+        let this = self.clone();
+        async move {
+            let value = this.get_object(&key).await?;
+            Ok(value.map(BlobstoreGetData::from_bytes))
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+
+    fn put(&self, _ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
+        let this = self.clone();
+        async move { this.put_object(&key, value.into_bytes()).await }
+            .boxed()
+            .compat()
+            .boxify()
+    }
+
+    fn is_present(&self, _ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
+        let this = self.clone();
+        async move { this.head_object(&key).await }
+            .boxed()
+            .compat()
+            .boxify()
+    }
+
+    fn enumerate(&self, _ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        let this = self.clone();
+        let prefix = range.prefix().to_string();
+        // The state is the continuation token of the next page, or None once the last page has
+        // been listed.
+        stream::try_unfold(Some(None), move |token: Option<Option<String>>| {
+            let this = this.clone();
+            let prefix = prefix.clone();
+            async move {
+                let token = match token {
+                    Some(token) => token,
+                    None => return Ok(None),
+                };
+                let (keys, next) = this.list_objects(&prefix, token.as_deref()).await?;
+                Ok::<_, Error>(Some((
+                    stream::iter(keys.into_iter().map(Ok::<_, Error>)),
+                    next.map(Some),
+                )))
+            }
+        })
+        .try_flatten()
+        .try_filter(move |key| future::ready(range.contains(key)))
+        .boxed()
+        .compat()
+        .boxify()
+    }
+
+    fn delete(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        let this = self.clone();
+        async move { this.delete_object(&key).await }
+            .boxed()
+            .compat()
+            .boxify()
+    }
+
+    // S3 PUTs always replace the object.
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        self.put(ctx, key, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use fbinit::FacebookInit;
+    use futures::compat::Future01CompatExt;
+
+    use crate::fake::FakeS3;
+
+    async fn setup(options: S3Options) -> Result<(FakeS3, S3Blob)> {
+        let fake = FakeS3::start().await?;
+        let blob = S3Blob::new(
+            &fake.endpoint(),
+            "bucket",
+            "mononoke/",
+            "us-east-1",
+            S3Credentials::new("access", "secret"),
+            options,
+        )?;
+        Ok((fake, blob))
+    }
+
+    #[fbinit::compat_test]
+    async fn test_put_get(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let (fake, blob) = setup(S3Options::default()).await?;
+
+        let key = "repo0000.content.blake2.00".to_string();
+        assert!(!blob.is_present(ctx.clone(), key.clone()).compat().await?);
+        assert!(blob.get(ctx.clone(), key.clone()).compat().await?.is_none());
+
+        blob.put(
+            ctx.clone(),
+            key.clone(),
+            BlobstoreBytes::from_bytes("hello world"),
+        )
+        .compat()
+        .await?;
+        assert!(blob.is_present(ctx.clone(), key.clone()).compat().await?);
+        assert_eq!(
+            blob.get(ctx, key.clone())
+                .compat()
+                .await?
+                .map(BlobstoreGetData::into_raw_bytes),
+            Some(Bytes::from("hello world")),
+        );
+        assert_eq!(
+            fake.object(&format!("bucket/mononoke/{}", key)),
+            Some(Bytes::from("hello world"))
+        );
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_delete(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let (fake, blob) = setup(S3Options::default()).await?;
+
+        let key = "repo0000.content.blake2.00".to_string();
+        blob.put(ctx.clone(), key.clone(), BlobstoreBytes::from_bytes("v"))
+            .compat()
+            .await?;
+        blob.delete(ctx.clone(), key.clone()).compat().await?;
+        assert!(!blob.is_present(ctx.clone(), key.clone()).compat().await?);
+        assert_eq!(fake.object(&format!("bucket/mononoke/{}", key)), None);
+
+        // Deleting again is fine.
+        blob.delete(ctx, key).compat().await?;
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_multipart(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let (fake, blob) = setup(S3Options {
+            multipart_threshold: 100,
+            part_size: 64,
+            ..S3Options::default()
+        })
+        .await?;
+
+        let value: Bytes = (0..1000u32).map(|i| i as u8).collect::<Vec<_>>().into();
+        blob.put(
+            ctx.clone(),
+            "large".to_string(),
+            BlobstoreBytes::from_bytes(value.clone()),
+        )
+        .compat()
+        .await?;
+        assert_eq!(
+            blob.get(ctx, "large".to_string())
+                .compat()
+                .await?
+                .map(BlobstoreGetData::into_raw_bytes),
+            Some(value),
+        );
+        assert_eq!(fake.completed_uploads(), 1);
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_retry(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let (fake, blob) = setup(S3Options {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            ..S3Options::default()
+        })
+        .await?;
+
+        fake.fail_next(2);
+        blob.put(
+            ctx.clone(),
+            "key".to_string(),
+            BlobstoreBytes::from_bytes("v"),
+        )
+        .compat()
+        .await?;
+
+        fake.fail_next(3);
+        assert!(blob.get(ctx, "key".to_string()).compat().await.is_err());
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_enumerate(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let (_fake, blob) = setup(S3Options {
+            list_page_size: 2,
+            ..S3Options::default()
+        })
+        .await?;
+
+        for key in &["repo0000.a", "repo0000.b", "repo0000.c", "repo0001.a"] {
+            blob.put(
+                ctx.clone(),
+                key.to_string(),
+                BlobstoreBytes::from_bytes("v"),
+            )
+            .compat()
+            .await?;
+        }
+
+        let keys: Vec<_> = blob
+            .enumerate(ctx.clone(), BlobstoreKeyRange::with_prefix("repo0000."))
+            .compat()
+            .try_collect()
+            .await?;
+        assert_eq!(keys, vec!["repo0000.a", "repo0000.b", "repo0000.c"]);
+
+        let keys: Vec<_> = blob
+            .enumerate(
+                ctx,
+                BlobstoreKeyRange::between(Some("repo0000.b".to_string()), None),
+            )
+            .compat()
+            .try_collect()
+            .await?;
+        assert_eq!(keys, vec!["repo0000.b", "repo0000.c", "repo0001.a"]);
+        Ok(())
+    }
+}
@@ -6,11 +6,11 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use cloned::cloned;
 use context::CoreContext;
 use futures::future::Future;
-use futures_ext::{BoxFuture, FutureExt};
+use futures_ext::{BoxFuture, BoxStream, FutureExt};
 use mononoke_types::BlobstoreBytes;
 use std::sync::Arc;
 
@@ -94,6 +94,30 @@ impl<T: Blobstore + Clone> Blobstore for SamplingBlobstore<T> {
             })
             .boxify()
     }
+
+    #[inline]
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.inner.enumerate(ctx, range)
+    }
+
+    #[inline]
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.inner.delete(ctx, key)
+    }
+
+    #[inline]
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        let sample_res = self.handler.sample_put(&ctx, &key, &value);
+        self.inner
+            .overwrite(ctx, key, value)
+            .and_then(|()| sample_res)
+            .boxify()
+    }
 }
 
 #[cfg(test)]
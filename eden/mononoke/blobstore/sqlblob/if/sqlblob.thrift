@@ -6,7 +6,10 @@
  */
 
 union InChunk {
+  // Legacy layout: the chunks are in the chunk table, under the same id
   1: i32 num_of_chunks,
+  // Ids of the chunks in the content_chunk table, in order
+  2: list<string> content_chunk_ids,
 }
 
 union DataCacheEntry {
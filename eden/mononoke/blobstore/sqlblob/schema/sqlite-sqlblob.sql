@@ -5,16 +5,29 @@
  * GNU General Public License version 2.
  */
 
-CREATE TABLE `data` (
+CREATE TABLE IF NOT EXISTS `data` (
   `id` VARCHAR(255) NOT NULL,
   `type` TINYINT NOT NULL,
   `value` BLOB NOT NULL,
   PRIMARY KEY (`id`)
 );
 
-CREATE TABLE `chunk` (
+CREATE TABLE IF NOT EXISTS `chunk` (
   `id` VARCHAR(255) NOT NULL,
   `chunk_id` INT UNSIGNED NOT NULL,
   `value` BLOB NOT NULL,
   PRIMARY KEY (`id`, `chunk_id`)
 );
+
+-- Chunks of large values, keyed by a hash of their content so that values
+-- sharing chunks store them once. refcount is the number of data rows that
+-- point at the chunk; a chunk is removed once it drops to zero.
+--
+-- Values written before this table existed keep their chunks in `chunk`,
+-- and stay readable. `mononoke_admin sqlblob-migrate-chunks` moves them over.
+CREATE TABLE IF NOT EXISTS `content_chunk` (
+  `id` VARCHAR(255) NOT NULL,
+  `refcount` BIGINT NOT NULL,
+  `value` BLOB NOT NULL,
+  PRIMARY KEY (`id`)
+);
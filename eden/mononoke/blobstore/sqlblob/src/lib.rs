@@ -19,17 +19,17 @@ use crate::delay::BlobDelay;
 use crate::facebook::myadmin_delay;
 #[cfg(not(fbcode_build))]
 use crate::myadmin_delay_dummy as myadmin_delay;
-use crate::store::{ChunkSqlStore, DataSqlStore};
+use crate::store::{ChunkSqlStore, ContentChunkSqlStore, DataSqlStore};
 use anyhow::{format_err, Error, Result};
-use blobstore::{Blobstore, BlobstoreGetData, CountedBlobstore};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange, CountedBlobstore};
 use cloned::cloned;
 use context::CoreContext;
 use fbinit::FacebookInit;
-use futures_ext::{try_boxfuture, BoxFuture, FutureExt as _};
+use futures_ext::{try_boxfuture, BoxFuture, BoxStream, FutureExt as _, StreamExt as _};
 use futures_old::future::{self, join_all};
 use futures_old::prelude::*;
 use memcache::MEMCACHE_VALUE_MAX_SIZE;
-use mononoke_types::BlobstoreBytes;
+use mononoke_types::{hash::Context as HashContext, BlobstoreBytes};
 use sql::{rusqlite::Connection as SqliteConnection, Connection};
 use sql_ext::{
     facebook::{
@@ -48,13 +48,38 @@ const MAX_KEY_SIZE: usize = 200;
 // does, but leave some extra bytes for metadata
 const CHUNK_SIZE: usize = MEMCACHE_VALUE_MAX_SIZE - 1000;
 const SQLITE_SHARD_NUM: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(2) };
+// Number of keys fetched per query when enumerating a shard
+const ENUMERATE_PAGE_SIZE: u64 = 10_000;
 
 const COUNTED_ID: &str = "sqlblob";
 pub type CountedSqlblob = CountedBlobstore<Sqlblob>;
 
 enum DataEntry {
     Data(BlobstoreGetData),
+    // Legacy layout, with the chunks stored under the same key in the chunk table
     InChunk(NonZeroUsize),
+    InContentChunks(Vec<String>),
+}
+
+fn content_chunk_id(chunk: &[u8]) -> String {
+    let mut context = HashContext::new(b"sqlblob.chunk");
+    context.update(chunk);
+    context.finish().to_hex().to_string()
+}
+
+// Run the writes one after another, waiting for replication lag before each of them
+fn delayed_sequence(
+    delay: BlobDelay,
+    writes: Vec<BoxFuture<(), Error>>,
+) -> BoxFuture<(), Error> {
+    writes
+        .into_iter()
+        .fold(future::ok(()).boxify(), move |chain, next| {
+            let delay = delay.clone();
+            chain
+                .and_then(move |_| delay.delay().and_then(|()| next))
+                .boxify()
+        })
 }
 
 fn i32_to_non_zero_usize(val: i32) -> Option<NonZeroUsize> {
@@ -68,6 +93,7 @@ fn i32_to_non_zero_usize(val: i32) -> Option<NonZeroUsize> {
 pub struct Sqlblob {
     data_store: DataSqlStore,
     chunk_store: ChunkSqlStore,
+    content_chunk_store: ContentChunkSqlStore,
     delay: BlobDelay,
 }
 
@@ -198,6 +224,12 @@ impl Sqlblob {
                             read_master_connections.clone(),
                         ),
                         chunk_store: ChunkSqlStore::new(
+                            shard_num,
+                            write_connections.clone(),
+                            read_connections.clone(),
+                            read_master_connections.clone(),
+                        ),
+                        content_chunk_store: ContentChunkSqlStore::new(
                             shard_num,
                             write_connections,
                             read_connections,
@@ -229,8 +261,8 @@ impl Sqlblob {
                 &pathbuf.join(format!("shard_{}.sqlite", shard_id)),
                 readonly_storage,
             )?;
-            // When opening an sqlite database we might already have the proper tables in it, so ignore
-            // errors from table creation
+            // Tables are only created if missing, so databases from before a table was added get
+            // it on open. Ignore errors, e.g. from a read only database.
             let _ = con.execute_batch(Self::CREATION_QUERY);
             Ok(con)
         })
@@ -256,7 +288,18 @@ impl Sqlblob {
                     cons.clone(),
                     cons.clone(),
                 ),
-                chunk_store: ChunkSqlStore::new(SQLITE_SHARD_NUM, cons.clone(), cons.clone(), cons),
+                chunk_store: ChunkSqlStore::new(
+                    SQLITE_SHARD_NUM,
+                    cons.clone(),
+                    cons.clone(),
+                    cons.clone(),
+                ),
+                content_chunk_store: ContentChunkSqlStore::new(
+                    SQLITE_SHARD_NUM,
+                    cons.clone(),
+                    cons.clone(),
+                    cons,
+                ),
                 delay: BlobDelay::dummy(),
             },
             "sqlite".into(),
@@ -268,6 +311,97 @@ impl Sqlblob {
     fn counted(self, label: String) -> CountedBlobstore<Self> {
         CountedBlobstore::new(format!("{}.{}", COUNTED_ID, label), self)
     }
+
+    /// Move the chunks of a value stored in the legacy per-key layout to the shared content chunk
+    /// table. Resolves to true if `key` was migrated, false if there was nothing to do.
+    pub fn migrate_legacy_chunks(&self, key: String) -> BoxFuture<bool, Error> {
+        cloned!(
+            self.data_store,
+            self.chunk_store,
+            self.content_chunk_store,
+            self.delay
+        );
+
+        data_store
+            .get(&key)
+            .and_then(move |maybe_entry| match maybe_entry {
+                Some(DataEntry::InChunk(num_of_chunks)) => {
+                    let num_of_chunks = num_of_chunks.get() as u32;
+                    let chunk_futs: Vec<_> = (0..num_of_chunks)
+                        .map(|chunk_id| chunk_store.get(&key, chunk_id))
+                        .collect();
+
+                    join_all(chunk_futs)
+                        .and_then(move |chunks| {
+                            let chunk_ids: Vec<_> = chunks
+                                .iter()
+                                .map(|chunk| content_chunk_id(chunk.as_raw_bytes()))
+                                .collect();
+                            let writes = chunks
+                                .iter()
+                                .zip(chunk_ids.iter())
+                                .map(|(chunk, chunk_id)| {
+                                    content_chunk_store
+                                        .add_ref(chunk_id, chunk.as_raw_bytes())
+                                        .boxify()
+                                })
+                                .collect();
+
+                            delayed_sequence(delay.clone(), writes)
+                                .and_then(move |_| delay.delay())
+                                .and_then({
+                                    cloned!(key, chunk_ids);
+                                    move |_| {
+                                        data_store.update_chunked(
+                                            &key,
+                                            &DataEntry::InContentChunks(chunk_ids),
+                                        )
+                                    }
+                                })
+                                .and_then(move |updated| {
+                                    if updated {
+                                        let deletes: Vec<_> = (0..num_of_chunks)
+                                            .map(|chunk_id| chunk_store.delete(&key, chunk_id))
+                                            .collect();
+                                        join_all(deletes).map(|_| true).left_future()
+                                    } else {
+                                        // Deleted while we were copying, drop our references
+                                        release_content_chunks(&content_chunk_store, &chunk_ids)
+                                            .map(|()| false)
+                                            .right_future()
+                                    }
+                                })
+                        })
+                        .left_future()
+                }
+                Some(DataEntry::Data(_)) | Some(DataEntry::InContentChunks(_)) | None => {
+                    Ok(false).into_future().right_future()
+                }
+            })
+            .boxify()
+    }
+}
+
+fn check_key_size(key: &str) -> Result<()> {
+    if key.as_bytes().len() > MAX_KEY_SIZE {
+        return Err(format_err!(
+            "Key {} exceeded max key size {}",
+            key,
+            MAX_KEY_SIZE
+        ));
+    }
+    Ok(())
+}
+
+fn release_content_chunks(
+    content_chunk_store: &ContentChunkSqlStore,
+    chunk_ids: &[String],
+) -> impl Future<Item = (), Error = Error> {
+    let release_futs: Vec<_> = chunk_ids
+        .iter()
+        .map(|chunk_id| content_chunk_store.remove_ref(chunk_id))
+        .collect();
+    join_all(release_futs).map(|_| ())
 }
 
 impl fmt::Debug for Sqlblob {
@@ -278,85 +412,84 @@ impl fmt::Debug for Sqlblob {
 
 impl Blobstore for Sqlblob {
     fn get(&self, _ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
-        cloned!(self.data_store, self.chunk_store);
+        cloned!(self.data_store, self.chunk_store, self.content_chunk_store);
 
         data_store
             .get(&key)
-            .and_then(move |maybe_entry| match maybe_entry {
-                None => Ok(None).into_future().left_future(),
-                Some(DataEntry::Data(value)) => Ok(Some(value)).into_future().left_future(),
-                Some(DataEntry::InChunk(num_of_chunks)) => {
-                    let chunk_fut: Vec<_> = (0..num_of_chunks.get() as u32)
-                        .map(move |chunk_id| chunk_store.get(&key, chunk_id))
-                        .collect();
-
-                    join_all(chunk_fut)
-                        .map(|chunks| {
-                            Some(BlobstoreGetData::from_bytes(
-                                chunks
-                                    .into_iter()
-                                    .map(BlobstoreGetData::into_raw_bytes)
-                                    .flatten()
-                                    .collect::<Vec<u8>>(),
-                            ))
-                        })
-                        .right_future()
-                }
+            .and_then(move |maybe_entry| {
+                let chunk_fut: Vec<_> = match maybe_entry {
+                    None => return Ok(None).into_future().left_future(),
+                    Some(DataEntry::Data(value)) => {
+                        return Ok(Some(value)).into_future().left_future();
+                    }
+                    Some(DataEntry::InChunk(num_of_chunks)) => (0..num_of_chunks.get() as u32)
+                        .map(move |chunk_id| chunk_store.get(&key, chunk_id).boxify())
+                        .collect(),
+                    Some(DataEntry::InContentChunks(chunk_ids)) => chunk_ids
+                        .iter()
+                        .map(|chunk_id| content_chunk_store.get(chunk_id).boxify())
+                        .collect(),
+                };
+
+                join_all(chunk_fut)
+                    .map(|chunks| {
+                        Some(BlobstoreGetData::from_bytes(
+                            chunks
+                                .into_iter()
+                                .map(BlobstoreGetData::into_raw_bytes)
+                                .flatten()
+                                .collect::<Vec<u8>>(),
+                        ))
+                    })
+                    .right_future()
             })
             .boxify()
     }
 
     fn put(&self, _ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
-        if key.as_bytes().len() > MAX_KEY_SIZE {
-            return Err(format_err!(
-                "Key {} exceeded max key size {}",
-                key,
-                MAX_KEY_SIZE
-            ))
-            .into_future()
-            .boxify();
-        }
+        try_boxfuture!(check_key_size(&key));
 
         let delay = self.delay.clone();
         if value.len() < CHUNK_SIZE {
             let init_delay = delay.delay();
             let put = self.data_store.put(&key, &DataEntry::Data(value.into()));
-            init_delay.and_then(move |_| put).boxify()
+            init_delay.and_then(move |_| put).map(|_| ()).boxify()
         } else {
-            cloned!(self.data_store, self.chunk_store, self.delay);
+            cloned!(self.data_store, self.content_chunk_store, self.delay);
             data_store
                 .is_present(&key)
                 .and_then(move |is_present| {
                     if is_present {
                         Ok(()).into_future().left_future()
                     } else {
-                        let chunk_futs: Vec<_> = value
-                            .as_bytes()
-                            .chunks(CHUNK_SIZE)
-                            .enumerate()
-                            .map({
-                                |(chunk_id, chunk)| chunk_store.put(&key, chunk_id as u32, chunk)
+                        let chunks: Vec<_> = value.as_bytes().chunks(CHUNK_SIZE).collect();
+                        let chunk_ids: Vec<_> =
+                            chunks.iter().map(|chunk| content_chunk_id(chunk)).collect();
+                        let writes = chunks
+                            .iter()
+                            .zip(chunk_ids.iter())
+                            .map(|(chunk, chunk_id)| {
+                                content_chunk_store.add_ref(chunk_id, chunk).boxify()
                             })
                             .collect();
-                        let len = chunk_futs.len();
-
-                        let fut = chunk_futs.into_iter().fold(future::ok(()).boxify(), {
-                            let delay = delay.clone();
-                            move |chain, next| {
-                                let delay = delay.clone();
-                                chain
-                                    .and_then(move |_| delay.delay().and_then(|()| next))
-                                    .boxify()
-                            }
-                        });
-                        fut.and_then(move |_| delay.delay())
-                            .and_then(move |_| {
-                                data_store.put(
-                                    &key,
-                                    &DataEntry::InChunk(
-                                        NonZeroUsize::new(len).expect("No way this is zero"),
-                                    ),
-                                )
+
+                        delayed_sequence(delay.clone(), writes)
+                            .and_then(move |_| delay.delay())
+                            .and_then({
+                                cloned!(chunk_ids);
+                                move |_| {
+                                    data_store.put(&key, &DataEntry::InContentChunks(chunk_ids))
+                                }
+                            })
+                            .and_then(move |inserted| {
+                                if inserted {
+                                    Ok(()).into_future().left_future()
+                                } else {
+                                    // A concurrent put of the same key won, and its row holds its
+                                    // own references to the chunks.
+                                    release_content_chunks(&content_chunk_store, &chunk_ids)
+                                        .right_future()
+                                }
                             })
                             .right_future()
                     }
@@ -368,6 +501,92 @@ impl Blobstore for Sqlblob {
     fn is_present(&self, _ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
         self.data_store.is_present(&key).boxify()
     }
+
+    fn enumerate(&self, _ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.data_store.enumerate(range).boxify()
+    }
+
+    // Legacy chunks belong to the key alone, and are removed before the data row, so that a failed
+    // delete can be retried: the key stays visible (and unreadable) until everything it owns is
+    // gone. Content chunks are shared, so the data row goes first: a failure after that leaks
+    // references, but never drops one twice.
+    fn delete(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        cloned!(self.data_store, self.chunk_store, self.content_chunk_store);
+
+        data_store
+            .get(&key)
+            .and_then(move |maybe_entry| match maybe_entry {
+                Some(DataEntry::InContentChunks(chunk_ids)) => data_store
+                    .delete(&key)
+                    .and_then(move |()| release_content_chunks(&content_chunk_store, &chunk_ids))
+                    .boxify(),
+                Some(DataEntry::InChunk(num_of_chunks)) => {
+                    let chunk_futs: Vec<_> = (0..num_of_chunks.get() as u32)
+                        .map(|chunk_id| chunk_store.delete(&key, chunk_id))
+                        .collect();
+                    join_all(chunk_futs)
+                        .and_then(move |_| data_store.delete(&key))
+                        .boxify()
+                }
+                Some(DataEntry::Data(_)) | None => data_store.delete(&key).boxify(),
+            })
+            .boxify()
+    }
+
+    // The new content chunks are referenced before the data row is replaced, and whatever the old
+    // row owned is only released afterwards, so readers always find a complete value. Concurrent
+    // overwrites of the same key are not supported, as each releases what the row it read owned.
+    fn overwrite(
+        &self,
+        _ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        try_boxfuture!(check_key_size(&key));
+        cloned!(
+            self.data_store,
+            self.chunk_store,
+            self.content_chunk_store,
+            self.delay
+        );
+
+        data_store
+            .get(&key)
+            .and_then(move |old_entry| {
+                let (entry, writes) = if value.len() < CHUNK_SIZE {
+                    (DataEntry::Data(value.into()), vec![])
+                } else {
+                    let chunks: Vec<_> = value.as_bytes().chunks(CHUNK_SIZE).collect();
+                    let chunk_ids: Vec<_> =
+                        chunks.iter().map(|chunk| content_chunk_id(chunk)).collect();
+                    let writes = chunks
+                        .iter()
+                        .zip(chunk_ids.iter())
+                        .map(|(chunk, chunk_id)| {
+                            content_chunk_store.add_ref(chunk_id, chunk).boxify()
+                        })
+                        .collect();
+                    (DataEntry::InContentChunks(chunk_ids), writes)
+                };
+
+                delayed_sequence(delay.clone(), writes)
+                    .and_then(move |_| delay.delay())
+                    .and_then(move |_| data_store.replace(&key, &entry).map(move |()| key))
+                    .and_then(move |key| match old_entry {
+                        Some(DataEntry::InContentChunks(chunk_ids)) => {
+                            release_content_chunks(&content_chunk_store, &chunk_ids).boxify()
+                        }
+                        Some(DataEntry::InChunk(num_of_chunks)) => {
+                            let chunk_futs: Vec<_> = (0..num_of_chunks.get() as u32)
+                                .map(|chunk_id| chunk_store.delete(&key, chunk_id))
+                                .collect();
+                            join_all(chunk_futs).map(|_| ()).boxify()
+                        }
+                        Some(DataEntry::Data(_)) | None => Ok(()).into_future().boxify(),
+                    })
+            })
+            .boxify()
+    }
 }
 
 #[cfg(test)]
@@ -407,4 +626,199 @@ mod tests {
 
         fut.compat().await.unwrap()
     }
+
+    #[fbinit::compat_test]
+    async fn enumerate(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let bs = Sqlblob::with_sqlite_in_memory()?;
+
+        let keys = vec!["repo0000.a", "repo0000.b", "repo0000.c", "repo0001.a"];
+        for key in &keys {
+            bs.put(
+                ctx.clone(),
+                key.to_string(),
+                BlobstoreBytes::from_bytes(Bytes::from_static(b"value")),
+            )
+            .compat()
+            .await?;
+        }
+        // Chunked values are only enumerated once.
+        bs.put(
+            ctx.clone(),
+            "repo0000.large".to_string(),
+            BlobstoreBytes::from_bytes(vec![0u8; CHUNK_SIZE * 2 + 1]),
+        )
+        .compat()
+        .await?;
+
+        let mut all = bs
+            .enumerate(ctx.clone(), BlobstoreKeyRange::all())
+            .collect()
+            .compat()
+            .await?;
+        all.sort();
+        assert_eq!(
+            all,
+            vec![
+                "repo0000.a",
+                "repo0000.b",
+                "repo0000.c",
+                "repo0000.large",
+                "repo0001.a"
+            ]
+        );
+
+        let (first, _) = BlobstoreKeyRange::with_prefix("repo0000.").split_at("repo0000.c");
+        let mut first = bs.enumerate(ctx, first).collect().compat().await?;
+        first.sort();
+        assert_eq!(first, vec!["repo0000.a", "repo0000.b"]);
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn delete(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let bs = Sqlblob::with_sqlite_in_memory()?;
+
+        let small = "repo0000.small".to_string();
+        let large = "repo0000.large".to_string();
+        bs.put(
+            ctx.clone(),
+            small.clone(),
+            BlobstoreBytes::from_bytes(Bytes::from_static(b"value")),
+        )
+        .compat()
+        .await?;
+        bs.put(
+            ctx.clone(),
+            large.clone(),
+            BlobstoreBytes::from_bytes(vec![0u8; CHUNK_SIZE * 2 + 1]),
+        )
+        .compat()
+        .await?;
+
+        for key in &[&small, &large] {
+            bs.delete(ctx.clone(), key.to_string()).compat().await?;
+            assert!(!bs.is_present(ctx.clone(), key.to_string()).compat().await?);
+            assert!(bs.get(ctx.clone(), key.to_string()).compat().await?.is_none());
+        }
+        // The chunks went with the data row.
+        let chunk_id = content_chunk_id(&[0u8; CHUNK_SIZE]);
+        assert!(bs.content_chunk_store.get(&chunk_id).compat().await.is_err());
+
+        // Deleting a missing key is not an error.
+        bs.delete(ctx, small).compat().await?;
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn shared_chunks(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let bs = Sqlblob::with_sqlite_in_memory()?;
+
+        // Both values are made of the same chunk, repeated.
+        let value = BlobstoreBytes::from_bytes(vec![0u8; CHUNK_SIZE * 2]);
+        let chunk_id = content_chunk_id(&[0u8; CHUNK_SIZE]);
+        let keys = vec!["repo0000.a".to_string(), "repo0000.b".to_string()];
+        for key in &keys {
+            bs.put(ctx.clone(), key.clone(), value.clone())
+                .compat()
+                .await?;
+        }
+
+        bs.delete(ctx.clone(), keys[0].clone()).compat().await?;
+        let remaining = bs.get(ctx.clone(), keys[1].clone()).compat().await?;
+        assert_eq!(remaining.map(|v| v.into_bytes()), Some(value));
+        assert!(bs.content_chunk_store.get(&chunk_id).compat().await.is_ok());
+
+        bs.delete(ctx, keys[1].clone()).compat().await?;
+        assert!(bs.content_chunk_store.get(&chunk_id).compat().await.is_err());
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn overwrite(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let bs = Sqlblob::with_sqlite_in_memory()?;
+
+        let small = BlobstoreBytes::from_bytes(Bytes::from_static(b"value"));
+        let large = BlobstoreBytes::from_bytes(vec![1u8; CHUNK_SIZE * 2]);
+        let key = "repo0000.key".to_string();
+
+        // Put keeps the existing value, overwrite replaces it.
+        bs.put(ctx.clone(), key.clone(), small.clone())
+            .compat()
+            .await?;
+        bs.put(ctx.clone(), key.clone(), large.clone())
+            .compat()
+            .await?;
+        let stored = bs.get(ctx.clone(), key.clone()).compat().await?;
+        assert_eq!(stored.map(|v| v.into_bytes()), Some(small.clone()));
+
+        bs.overwrite(ctx.clone(), key.clone(), large.clone())
+            .compat()
+            .await?;
+        let stored = bs.get(ctx.clone(), key.clone()).compat().await?;
+        assert_eq!(stored.map(|v| v.into_bytes()), Some(large.clone()));
+
+        // Overwriting a chunked value releases its chunks.
+        let chunk_id = content_chunk_id(&[1u8; CHUNK_SIZE]);
+        assert!(bs.content_chunk_store.get(&chunk_id).compat().await.is_ok());
+        bs.overwrite(ctx.clone(), key.clone(), small.clone())
+            .compat()
+            .await?;
+        let stored = bs.get(ctx.clone(), key.clone()).compat().await?;
+        assert_eq!(stored.map(|v| v.into_bytes()), Some(small));
+        assert!(bs.content_chunk_store.get(&chunk_id).compat().await.is_err());
+
+        // Overwriting a missing key stores the value.
+        let missing = "repo0000.missing".to_string();
+        bs.overwrite(ctx.clone(), missing.clone(), large.clone())
+            .compat()
+            .await?;
+        let stored = bs.get(ctx, missing).compat().await?;
+        assert_eq!(stored.map(|v| v.into_bytes()), Some(large));
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn migrate_legacy_chunks(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let bs = Sqlblob::with_sqlite_in_memory()?;
+
+        let key = "repo0000.legacy".to_string();
+        let chunks: Vec<Vec<u8>> = vec![vec![1u8; CHUNK_SIZE], vec![2u8; 10]];
+        for (chunk_id, chunk) in chunks.iter().enumerate() {
+            bs.chunk_store
+                .put(&key, chunk_id as u32, chunk)
+                .compat()
+                .await?;
+        }
+        bs.data_store
+            .put(&key, &DataEntry::InChunk(NonZeroUsize::new(2).unwrap()))
+            .compat()
+            .await?;
+        let value = BlobstoreBytes::from_bytes(chunks.concat());
+
+        let legacy = bs.get(ctx.clone(), key.clone()).compat().await?;
+        assert_eq!(legacy.map(|v| v.into_bytes()), Some(value.clone()));
+
+        assert!(bs.migrate_legacy_chunks(key.clone()).compat().await?);
+        assert!(bs.chunk_store.get(&key, 0).compat().await.is_err());
+        let migrated = bs.get(ctx.clone(), key.clone()).compat().await?;
+        assert_eq!(migrated.map(|v| v.into_bytes()), Some(value));
+
+        // Already migrated
+        assert!(!bs.migrate_legacy_chunks(key.clone()).compat().await?);
+
+        bs.delete(ctx, key).compat().await?;
+        let chunk_id = content_chunk_id(&chunks[0]);
+        assert!(bs.content_chunk_store.get(&chunk_id).compat().await.is_err());
+
+        Ok(())
+    }
 }
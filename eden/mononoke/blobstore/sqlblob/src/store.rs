@@ -10,17 +10,18 @@ use std::num::NonZeroUsize;
 use std::sync::Arc;
 
 use anyhow::{bail, format_err, Error};
+use cloned::cloned;
 use fbthrift::compact_protocol;
 use futures_ext::FutureExt;
-use futures_old::prelude::*;
+use futures_old::{prelude::*, stream};
 use sql::{queries, Connection};
 use twox_hash::XxHash32;
 
-use blobstore::BlobstoreGetData;
+use blobstore::{BlobstoreGetData, BlobstoreKeyRange};
 use mononoke_types::BlobstoreBytes;
 use sqlblob_thrift::InChunk;
 
-use crate::{i32_to_non_zero_usize, DataEntry};
+use crate::{i32_to_non_zero_usize, DataEntry, ENUMERATE_PAGE_SIZE};
 
 mod types {
     use sql::mysql_async::{
@@ -82,15 +83,62 @@ queries! {
         ) VALUES {values}"
     }
 
-    write InsertChunk(values: (id: &str, chunk_id: u32, value: &[u8])) {
-        insert_or_ignore,
-        "{insert_or_ignore} INTO chunk (
+    write ReplaceData(values: (id: &str, dtype: DataType, value: &[u8])) {
+        none,
+        "REPLACE INTO data (
             id
-            , chunk_id
+            , type
             , value
         ) VALUES {values}"
     }
 
+    write UpdateData(id: String, dtype: DataType, value: &[u8]) {
+        none,
+        "UPDATE data
+         SET value = {value}
+         WHERE id = {id}
+           AND type = {dtype}"
+    }
+
+    write DeleteData(id: String) {
+        none,
+        "DELETE FROM data
+         WHERE id = {id}"
+    }
+
+    write DeleteChunk(id: String, chunk_id: u32) {
+        none,
+        "DELETE FROM chunk
+         WHERE id = {id}
+           AND chunk_id = {chunk_id}"
+    }
+
+    write AddContentChunkRef(id: &str, value: &[u8]) {
+        none,
+        mysql(
+            "INSERT INTO content_chunk (id, refcount, value) VALUES ({id}, 1, {value})
+             ON DUPLICATE KEY UPDATE refcount = refcount + 1"
+        )
+        sqlite(
+            "INSERT INTO content_chunk (id, refcount, value) VALUES ({id}, 1, {value})
+             ON CONFLICT(id) DO UPDATE SET refcount = refcount + 1"
+        )
+    }
+
+    write RemoveContentChunkRef(id: String) {
+        none,
+        "UPDATE content_chunk
+         SET refcount = refcount - 1
+         WHERE id = {id}"
+    }
+
+    write DeleteUnreferencedContentChunk(id: String) {
+        none,
+        "DELETE FROM content_chunk
+         WHERE id = {id}
+           AND refcount <= 0"
+    }
+
     read SelectData(id: String) -> (DataType, Vec<u8>) {
         "SELECT type, value
          FROM data
@@ -103,12 +151,47 @@ queries! {
          WHERE id = {id}"
     }
 
+    read SelectKeysFrom(begin: String, limit: u64) -> (String) {
+        "SELECT id
+         FROM data
+         WHERE id >= {begin}
+         ORDER BY id ASC
+         LIMIT {limit}"
+    }
+
+    read SelectKeysAfter(after: String, limit: u64) -> (String) {
+        "SELECT id
+         FROM data
+         WHERE id > {after}
+         ORDER BY id ASC
+         LIMIT {limit}"
+    }
+
     read SelectChunk(id: String, chunk_id: u32) -> (Vec<u8>) {
         "SELECT value
          FROM chunk
          WHERE id = {id}
            AND chunk_id = {chunk_id}"
     }
+
+    read SelectContentChunk(id: String) -> (Vec<u8>) {
+        "SELECT value
+         FROM content_chunk
+         WHERE id = {id}"
+    }
+}
+
+// New values are no longer written to the chunk table, tests still need legacy values
+#[cfg(test)]
+queries! {
+    write InsertChunk(values: (id: &str, chunk_id: u32, value: &[u8])) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO chunk (
+            id
+            , chunk_id
+            , value
+        ) VALUES {values}"
+    }
 }
 
 #[derive(Clone)]
@@ -158,6 +241,12 @@ impl DataSqlStore {
                             Some(num_of_chunks) => Ok(Some(DataEntry::InChunk(num_of_chunks))),
                         }
                     }
+                    Ok(InChunk::content_chunk_ids(chunk_ids)) => {
+                        if chunk_ids.is_empty() {
+                            bail!("Encoded content chunk ids were empty")
+                        }
+                        Ok(Some(DataEntry::InContentChunks(chunk_ids)))
+                    }
                     Err(_) | Ok(InChunk::UnknownField(_)) => {
                         bail!("Failed to deserialize InChunk data")
                     }
@@ -165,29 +254,63 @@ impl DataSqlStore {
             })
     }
 
+    /// Resolves to false if there already was a row for `key`, in which case it is left as is.
     pub(crate) fn put(
         &self,
         key: &str,
         entry: &DataEntry,
-    ) -> impl Future<Item = (), Error = Error> {
+    ) -> impl Future<Item = bool, Error = Error> {
         let shard_id = self.shard(key);
-
-        let (dtype, value) = match entry {
-            DataEntry::Data(ref value) => (DataType::Data, BlobstoreBytes::from(value.clone())),
-            DataEntry::InChunk(num_of_chunks) => {
-                let in_chunk_meta = InChunk::num_of_chunks(num_of_chunks.get() as i32);
-                let in_chunk_meta = compact_protocol::serialize(&in_chunk_meta);
-                (DataType::InChunk, BlobstoreBytes::from_bytes(in_chunk_meta))
-            }
-        };
+        let (dtype, value) = encode_entry(entry);
 
         InsertData::query(
             &self.write_connection[shard_id - 1],
             &[(&key, &dtype, &value.into_bytes().as_ref())],
         )
+        .map(|res| res.affected_rows() > 0)
+    }
+
+    /// Store `entry` for `key`, replacing the existing row if there is one.
+    pub(crate) fn replace(
+        &self,
+        key: &str,
+        entry: &DataEntry,
+    ) -> impl Future<Item = (), Error = Error> {
+        let shard_id = self.shard(key);
+        let (dtype, value) = encode_entry(entry);
+
+        ReplaceData::query(
+            &self.write_connection[shard_id - 1],
+            &[(&key, &dtype, &value.into_bytes().as_ref())],
+        )
         .map(|_| ())
     }
 
+    /// Replace the chunk layout of the chunked value at `key`. Resolves to false if there is no
+    /// chunked value at `key`.
+    pub(crate) fn update_chunked(
+        &self,
+        key: &str,
+        entry: &DataEntry,
+    ) -> impl Future<Item = bool, Error = Error> {
+        let shard_id = self.shard(key);
+        let (dtype, value) = encode_entry(entry);
+
+        UpdateData::query(
+            &self.write_connection[shard_id - 1],
+            &key.to_owned(),
+            &dtype,
+            &value.into_bytes().as_ref(),
+        )
+        .map(|res| res.affected_rows() > 0)
+    }
+
+    pub(crate) fn delete(&self, key: &str) -> impl Future<Item = (), Error = Error> {
+        let shard_id = self.shard(key);
+
+        DeleteData::query(&self.write_connection[shard_id - 1], &key.to_owned()).map(|_| ())
+    }
+
     pub(crate) fn is_present(&self, key: &str) -> impl Future<Item = bool, Error = Error> {
         let key = key.to_owned();
         let shard_id = self.shard(&key);
@@ -206,6 +329,21 @@ impl DataSqlStore {
         )
     }
 
+    /// Stream all keys in `range`, one shard after another. Within a shard, keys are read from
+    /// the replica in key order, a page at a time.
+    pub(crate) fn enumerate(
+        &self,
+        range: BlobstoreKeyRange,
+    ) -> impl Stream<Item = String, Error = Error> {
+        let read_connection = self.read_connection.clone();
+
+        stream::iter_ok(0..self.shard_num.get())
+            .map(move |shard_idx| {
+                enumerate_shard(read_connection[shard_idx].clone(), range.clone())
+            })
+            .flatten()
+    }
+
     fn shard(&self, key: &str) -> usize {
         let mut hasher = XxHash32::with_seed(0);
         hasher.write(key.as_bytes());
@@ -213,6 +351,62 @@ impl DataSqlStore {
     }
 }
 
+fn encode_entry(entry: &DataEntry) -> (DataType, BlobstoreBytes) {
+    match entry {
+        DataEntry::Data(ref value) => (DataType::Data, BlobstoreBytes::from(value.clone())),
+        DataEntry::InChunk(num_of_chunks) => {
+            let in_chunk_meta = InChunk::num_of_chunks(num_of_chunks.get() as i32);
+            let in_chunk_meta = compact_protocol::serialize(&in_chunk_meta);
+            (DataType::InChunk, BlobstoreBytes::from_bytes(in_chunk_meta))
+        }
+        DataEntry::InContentChunks(chunk_ids) => {
+            let in_chunk_meta = InChunk::content_chunk_ids(chunk_ids.clone());
+            let in_chunk_meta = compact_protocol::serialize(&in_chunk_meta);
+            (DataType::InChunk, BlobstoreBytes::from_bytes(in_chunk_meta))
+        }
+    }
+}
+
+fn enumerate_shard(
+    connection: Connection,
+    range: BlobstoreKeyRange,
+) -> impl Stream<Item = String, Error = Error> {
+    // The state is None once the shard is exhausted, otherwise the last key seen (if any).
+    stream::unfold(Some(None), move |state: Option<Option<String>>| {
+        let page = match state? {
+            None => SelectKeysFrom::query(
+                &connection,
+                &range.lower_bound().to_string(),
+                &ENUMERATE_PAGE_SIZE,
+            )
+            .left_future(),
+            Some(after) => {
+                SelectKeysAfter::query(&connection, &after, &ENUMERATE_PAGE_SIZE).right_future()
+            }
+        };
+
+        cloned!(range);
+        Some(page.map(move |rows| {
+            let next = match rows.last() {
+                Some((last,))
+                    if rows.len() as u64 == ENUMERATE_PAGE_SIZE && !range.is_past(last) =>
+                {
+                    Some(Some(last.clone()))
+                }
+                _ => None,
+            };
+            let keys: Vec<_> = rows
+                .into_iter()
+                .map(|(key,)| key)
+                .take_while(|key| !range.is_past(key))
+                .filter(|key| range.contains(key))
+                .collect();
+            (stream::iter_ok(keys), next)
+        }))
+    })
+    .flatten()
+}
+
 #[derive(Clone)]
 pub(crate) struct ChunkSqlStore {
     shard_num: NonZeroUsize,
@@ -264,6 +458,7 @@ impl ChunkSqlStore {
         )
     }
 
+    #[cfg(test)]
     pub(crate) fn put(
         &self,
         key: &str,
@@ -279,6 +474,17 @@ impl ChunkSqlStore {
         .map(|_| ())
     }
 
+    pub(crate) fn delete(&self, key: &str, chunk_id: u32) -> impl Future<Item = (), Error = Error> {
+        let shard_id = self.shard(key, chunk_id);
+
+        DeleteChunk::query(
+            &self.write_connection[shard_id - 1],
+            &key.to_owned(),
+            &chunk_id,
+        )
+        .map(|_| ())
+    }
+
     fn shard(&self, key: &str, chunk_id: u32) -> usize {
         let mut hasher = XxHash32::with_seed(0);
         hasher.write(key.as_bytes());
@@ -286,3 +492,78 @@ impl ChunkSqlStore {
         ((hasher.finish() % self.shard_num.get() as u64) + 1) as usize
     }
 }
+
+/// Chunks of large values, stored once per distinct content and shared between keys by
+/// reference counting.
+#[derive(Clone)]
+pub(crate) struct ContentChunkSqlStore {
+    shard_num: NonZeroUsize,
+    write_connection: Arc<Vec<Connection>>,
+    read_connection: Arc<Vec<Connection>>,
+    read_master_connection: Arc<Vec<Connection>>,
+}
+
+impl ContentChunkSqlStore {
+    pub(crate) fn new(
+        shard_num: NonZeroUsize,
+        write_connection: Arc<Vec<Connection>>,
+        read_connection: Arc<Vec<Connection>>,
+        read_master_connection: Arc<Vec<Connection>>,
+    ) -> Self {
+        Self {
+            shard_num,
+            write_connection,
+            read_connection,
+            read_master_connection,
+        }
+    }
+
+    pub(crate) fn get(&self, id: &str) -> impl Future<Item = BlobstoreGetData, Error = Error> {
+        let id = id.to_owned();
+        let shard_id = self.shard(&id);
+        let read_master_connection = self.read_master_connection[shard_id - 1].clone();
+
+        SelectContentChunk::query(&self.read_connection[shard_id - 1], &id).and_then(move |rows| {
+            match rows.into_iter().next() {
+                Some((value,)) => Ok(BlobstoreGetData::from_bytes(value))
+                    .into_future()
+                    .left_future(),
+                None => SelectContentChunk::query(&read_master_connection, &id)
+                    .and_then(move |rows| match rows.into_iter().next() {
+                        Some((value,)) => Ok(BlobstoreGetData::from_bytes(value)),
+                        None => Err(format_err!(
+                            "Missing content chunk {} shard {}",
+                            id,
+                            shard_id
+                        )),
+                    })
+                    .right_future(),
+            }
+        })
+    }
+
+    /// Take a reference to the chunk `id`, storing `value` if this is the first one.
+    pub(crate) fn add_ref(&self, id: &str, value: &[u8]) -> impl Future<Item = (), Error = Error> {
+        let shard_id = self.shard(id);
+
+        AddContentChunkRef::query(&self.write_connection[shard_id - 1], &id, &value).map(|_| ())
+    }
+
+    /// Drop a reference to the chunk `id`, deleting it if that was the last one. The delete is
+    /// conditional so that a reference taken concurrently keeps the chunk.
+    pub(crate) fn remove_ref(&self, id: &str) -> impl Future<Item = (), Error = Error> {
+        let id = id.to_owned();
+        let shard_id = self.shard(&id);
+        let write_connection = self.write_connection[shard_id - 1].clone();
+
+        RemoveContentChunkRef::query(&write_connection, &id)
+            .and_then(move |_| DeleteUnreferencedContentChunk::query(&write_connection, &id))
+            .map(|_| ())
+    }
+
+    fn shard(&self, id: &str) -> usize {
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(id.as_bytes());
+        ((hasher.finish() % self.shard_num.get() as u64) + 1) as usize
+    }
+}
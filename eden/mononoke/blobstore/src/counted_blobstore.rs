@@ -9,13 +9,13 @@ use std::ops::Deref;
 use std::sync::Arc;
 
 use anyhow::Error;
-use futures::Future;
-use futures_ext::{BoxFuture, FutureExt};
+use futures::{Future, Stream};
+use futures_ext::{BoxFuture, BoxStream, FutureExt, StreamExt};
 use stats::prelude::*;
 
 use context::CoreContext;
 
-use crate::{Blobstore, BlobstoreBytes, BlobstoreGetData};
+use crate::{Blobstore, BlobstoreBytes, BlobstoreGetData, BlobstoreKeyRange};
 
 define_stats_struct! {
     CountedBlobstoreStats("mononoke.blobstore.{}", prefix: String),
@@ -31,6 +31,15 @@ define_stats_struct! {
     assert_present: timeseries(Rate, Sum),
     assert_present_ok: timeseries(Rate, Sum),
     assert_present_err: timeseries(Rate, Sum),
+    enumerate: timeseries(Rate, Sum),
+    enumerate_keys: timeseries(Rate, Sum),
+    enumerate_err: timeseries(Rate, Sum),
+    delete: timeseries(Rate, Sum),
+    delete_ok: timeseries(Rate, Sum),
+    delete_err: timeseries(Rate, Sum),
+    overwrite: timeseries(Rate, Sum),
+    overwrite_ok: timeseries(Rate, Sum),
+    overwrite_err: timeseries(Rate, Sum),
 }
 
 #[derive(Clone, Debug)]
@@ -116,6 +125,56 @@ impl<T: Blobstore> Blobstore for CountedBlobstore<T> {
             })
             .boxify()
     }
+
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        let stats = self.stats.clone();
+        stats.enumerate.add_value(1);
+        self.blobstore
+            .enumerate(ctx, range)
+            .then(move |res| {
+                match res {
+                    Ok(_) => stats.enumerate_keys.add_value(1),
+                    Err(_) => stats.enumerate_err.add_value(1),
+                }
+                res
+            })
+            .boxify()
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        let stats = self.stats.clone();
+        stats.delete.add_value(1);
+        self.blobstore
+            .delete(ctx, key)
+            .then(move |res| {
+                match res {
+                    Ok(()) => stats.delete_ok.add_value(1),
+                    Err(_) => stats.delete_err.add_value(1),
+                }
+                res
+            })
+            .boxify()
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        let stats = self.stats.clone();
+        stats.overwrite.add_value(1);
+        self.blobstore
+            .overwrite(ctx, key, value)
+            .then(move |res| {
+                match res {
+                    Ok(()) => stats.overwrite_ok.add_value(1),
+                    Err(_) => stats.overwrite_err.add_value(1),
+                }
+                res
+            })
+            .boxify()
+    }
 }
 
 impl<T: Blobstore> Deref for CountedBlobstore<T> {
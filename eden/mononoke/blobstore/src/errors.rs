@@ -7,10 +7,18 @@
 
 use thiserror::Error;
 
+use crate::BlobstoreKeyRange;
+
 #[derive(Debug, Error)]
 pub enum ErrorKind {
     #[error("Blob {0} not found in blobstore")]
     NotFound(String),
     #[error("Error while opening state for blob store")]
     StateOpen,
+    #[error("Blobstore does not support enumerating keys in {0}")]
+    EnumerationUnsupported(BlobstoreKeyRange),
+    #[error("Blobstore does not support deleting blob {0}")]
+    DeletionUnsupported(String),
+    #[error("Blobstore does not support overwriting blob {0}")]
+    OverwriteUnsupported(String),
 }
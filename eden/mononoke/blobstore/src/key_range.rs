@@ -0,0 +1,195 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt;
+
+/// A range of blobstore keys to enumerate. Only keys that start with `prefix` are part of the
+/// range; `begin` (inclusive) and `end` (exclusive) further bound the range, and are compared
+/// against the whole key including the prefix.
+///
+/// Ranges can be partitioned with `split_at` so that several enumerations can run in parallel,
+/// e.g. one per hash prefix.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlobstoreKeyRange {
+    prefix: String,
+    begin: Option<String>,
+    end: Option<String>,
+}
+
+impl BlobstoreKeyRange {
+    /// The range containing every key in the blobstore.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// The range containing every key that starts with `prefix`.
+    pub fn with_prefix(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            begin: None,
+            end: None,
+        }
+    }
+
+    /// The range containing every key in `[begin, end)`. Either bound can be omitted.
+    pub fn between(begin: Option<String>, end: Option<String>) -> Self {
+        Self {
+            prefix: String::new(),
+            begin,
+            end,
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn begin(&self) -> Option<&str> {
+        self.begin.as_ref().map(String::as_str)
+    }
+
+    pub fn end(&self) -> Option<&str> {
+        self.end.as_ref().map(String::as_str)
+    }
+
+    /// The smallest key that can be part of this range. Useful for backends that can seek
+    /// to a key and scan forwards in key order.
+    pub fn lower_bound(&self) -> &str {
+        match self.begin {
+            Some(ref begin) if begin.as_str() > self.prefix.as_str() => begin.as_str(),
+            _ => self.prefix.as_str(),
+        }
+    }
+
+    /// Returns true if `key` is part of this range.
+    pub fn contains(&self, key: &str) -> bool {
+        key.starts_with(self.prefix.as_str())
+            && self
+                .begin
+                .as_ref()
+                .map_or(true, |begin| key >= begin.as_str())
+            && self.end.as_ref().map_or(true, |end| key < end.as_str())
+    }
+
+    /// Returns true if neither `key` nor any key that sorts after it can be part of this range.
+    /// Backends that scan in key order use this to stop early.
+    pub fn is_past(&self, key: &str) -> bool {
+        if let Some(ref end) = self.end {
+            if key >= end.as_str() {
+                return true;
+            }
+        }
+        !key.starts_with(self.prefix.as_str()) && key > self.prefix.as_str()
+    }
+
+    /// Split this range in two at `key`: the first range contains the keys that sort before
+    /// `key`, the second range contains `key` and everything after it.
+    pub fn split_at(self, key: impl Into<String>) -> (Self, Self) {
+        let key = key.into();
+        let first = Self {
+            prefix: self.prefix.clone(),
+            begin: self.begin,
+            end: Some(match self.end {
+                Some(ref end) if end < &key => end.clone(),
+                _ => key.clone(),
+            }),
+        };
+        let second = Self {
+            prefix: self.prefix,
+            begin: Some(match first.begin {
+                Some(ref begin) if begin > &key => begin.clone(),
+                _ => key,
+            }),
+            end: self.end,
+        };
+        (first, second)
+    }
+
+    /// Return a copy of this range with `outer` prepended to the prefix and both bounds.
+    /// Used by blobstores that store keys under a prefix of their own.
+    pub fn prepend(&self, outer: &str) -> Self {
+        Self {
+            prefix: [outer, self.prefix.as_str()].concat(),
+            begin: self
+                .begin
+                .as_ref()
+                .map(|begin| [outer, begin.as_str()].concat()),
+            end: self.end.as_ref().map(|end| [outer, end.as_str()].concat()),
+        }
+    }
+}
+
+impl fmt::Display for BlobstoreKeyRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}* in [{}, {})",
+            self.prefix,
+            self.begin().unwrap_or(""),
+            self.end().unwrap_or("")
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        let range = BlobstoreKeyRange::with_prefix("repo0000.");
+        assert!(range.contains("repo0000.content.blake2.00"));
+        assert!(!range.contains("repo0001.content.blake2.00"));
+        assert!(!range.contains("repo"));
+
+        let range = BlobstoreKeyRange::between(Some("b".to_string()), Some("d".to_string()));
+        assert!(!range.contains("a"));
+        assert!(range.contains("b"));
+        assert!(range.contains("cat"));
+        assert!(!range.contains("d"));
+    }
+
+    #[test]
+    fn test_is_past() {
+        let range = BlobstoreKeyRange::with_prefix("repo0001.");
+        assert!(!range.is_past("repo0000.foo"));
+        assert!(!range.is_past("repo0001.foo"));
+        assert!(range.is_past("repo0002.foo"));
+
+        let range = BlobstoreKeyRange::between(None, Some("m".to_string()));
+        assert!(!range.is_past("a"));
+        assert!(range.is_past("m"));
+        assert!(range.is_past("z"));
+    }
+
+    #[test]
+    fn test_split_at() {
+        let range = BlobstoreKeyRange::with_prefix("p.");
+        let (first, second) = range.split_at("p.m");
+        assert!(first.contains("p.a"));
+        assert!(!first.contains("p.m"));
+        assert!(second.contains("p.m"));
+        assert!(second.contains("p.z"));
+        assert!(!second.contains("q.a"));
+
+        let range = BlobstoreKeyRange::between(Some("c".to_string()), Some("f".to_string()));
+        let (first, second) = range.split_at("a");
+        assert_eq!(first.end(), Some("a"));
+        assert!(!first.contains("b"));
+        assert_eq!(second.begin(), Some("c"));
+        assert_eq!(second.end(), Some("f"));
+    }
+
+    #[test]
+    fn test_prepend() {
+        let range = BlobstoreKeyRange::between(Some("b".to_string()), None).prepend("repo0000.");
+        assert_eq!(range.prefix(), "repo0000.");
+        assert_eq!(range.begin(), Some("repo0000.b"));
+        assert_eq!(range.end(), None);
+        assert_eq!(range.lower_bound(), "repo0000.b");
+    }
+}
@@ -14,7 +14,8 @@ use std::fmt;
 use abomonation_derive::Abomonation;
 use anyhow::Error;
 use futures::future::{self, Future};
-use futures_ext::{BoxFuture, FutureExt};
+use futures::stream;
+use futures_ext::{BoxFuture, BoxStream, FutureExt, StreamExt};
 use thiserror::Error;
 
 use context::CoreContext;
@@ -28,6 +29,9 @@ pub use crate::errors::ErrorKind;
 mod disabled;
 pub use crate::disabled::DisabledBlob;
 
+mod key_range;
+pub use crate::key_range::BlobstoreKeyRange;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BlobstoreGetData {
     meta: BlobstoreMetadata,
@@ -275,6 +279,45 @@ pub trait Blobstore: fmt::Debug + Send + Sync + 'static {
             })
             .boxify()
     }
+
+    /// Stream every key in `range` that `get` would return a value for. Keys are produced in no
+    /// particular order, and each key is produced at most once. Keys written concurrently with
+    /// the enumeration may or may not be included.
+    ///
+    /// Enumeration is optional: the provided implementation fails with
+    /// `ErrorKind::EnumerationUnsupported`, and only backends that can list their contents
+    /// cheaply (and the wrappers around them) override it.
+    fn enumerate(&self, _ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        stream::once(Err(ErrorKind::EnumerationUnsupported(range).into())).boxify()
+    }
+
+    /// Remove the value associated with `key`, so that later `get`s return `None`. Deleting a
+    /// key that is not present succeeds. Callers are responsible for making sure nothing still
+    /// refers to the key - in practice that means only garbage collection should call this.
+    ///
+    /// Deletion is optional: the provided implementation fails with
+    /// `ErrorKind::DeletionUnsupported`, and only backends that can remove data (and the
+    /// wrappers around them) override it.
+    fn delete(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        future::err(ErrorKind::DeletionUnsupported(key).into()).boxify()
+    }
+
+    /// Associate `value` with `key`, replacing any value already there. Unlike `put`, which may
+    /// keep an existing value, this is for tools that rewrite blobs in place (re-encryption,
+    /// repacking). The new value must be an equivalent encoding of the same data, as readers
+    /// may observe either one while the overwrite is in progress.
+    ///
+    /// Overwriting is optional: the provided implementation fails with
+    /// `ErrorKind::OverwriteUnsupported`, and only backends that can replace data (and the
+    /// wrappers around them) override it.
+    fn overwrite(
+        &self,
+        _ctx: CoreContext,
+        key: String,
+        _value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        future::err(ErrorKind::OverwriteUnsupported(key).into()).boxify()
+    }
 }
 
 #[derive(Debug, Error)]
@@ -19,7 +19,7 @@ use futures::Future;
 use tempdir::TempDir;
 use tokio::{prelude::*, runtime::Runtime};
 
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use context::CoreContext;
 use fileblob::Fileblob;
 use memblob::EagerMemblob;
@@ -112,6 +112,104 @@ where
     assert_eq!(out.into_raw_bytes(), Bytes::from_static(b"bar"));
 }
 
+fn enumerate<B>(fb: FacebookInit, blobstore: B)
+where
+    B: IntoFuture,
+    B::Item: Blobstore,
+    B::Future: Send + 'static,
+    Error: From<B::Error>,
+{
+    let ctx = CoreContext::test_mock(fb);
+    let blobstore = blobstore.into_future().map_err(|err| err.into());
+
+    let fut = future::lazy(move || {
+        blobstore.and_then(move |blobstore| {
+            let puts: Vec<_> = vec!["repo0000.a", "repo0000.b.c", "repo0000.c", "repo0001.a"]
+                .into_iter()
+                .map(|key| {
+                    blobstore.put(
+                        ctx.clone(),
+                        key.to_string(),
+                        BlobstoreBytes::from_bytes(&b"bar"[..]),
+                    )
+                })
+                .collect();
+            future::join_all(puts).and_then(move |_| {
+                let all = blobstore.enumerate(ctx.clone(), BlobstoreKeyRange::all());
+                let prefixed =
+                    blobstore.enumerate(ctx.clone(), BlobstoreKeyRange::with_prefix("repo0000."));
+                let (_, bounded) =
+                    BlobstoreKeyRange::with_prefix("repo0000.").split_at("repo0000.b");
+                let bounded = blobstore.enumerate(ctx, bounded);
+                all.collect().join3(prefixed.collect(), bounded.collect())
+            })
+        })
+    });
+
+    let mut runtime = Runtime::new().expect("runtime creation failed");
+    let (mut all, mut prefixed, mut bounded) = runtime.block_on(fut).expect("enumerate failed");
+    all.sort();
+    prefixed.sort();
+    bounded.sort();
+
+    assert_eq!(
+        all,
+        vec!["repo0000.a", "repo0000.b.c", "repo0000.c", "repo0001.a"]
+    );
+    assert_eq!(prefixed, vec!["repo0000.a", "repo0000.b.c", "repo0000.c"]);
+    assert_eq!(bounded, vec!["repo0000.b.c", "repo0000.c"]);
+}
+
+fn delete<B>(fb: FacebookInit, blobstore: B)
+where
+    B: IntoFuture,
+    B::Item: Blobstore,
+    B::Future: Send + 'static,
+    Error: From<B::Error>,
+{
+    let ctx = CoreContext::test_mock(fb);
+    let blobstore = blobstore.into_future().map_err(|err| err.into());
+
+    let fut = future::lazy(move || {
+        blobstore.and_then(move |blobstore| {
+            let blobstore = Arc::new(blobstore);
+            let puts: Vec<_> = vec!["foo", "other"]
+                .into_iter()
+                .map(|key| {
+                    blobstore.put(
+                        ctx.clone(),
+                        key.to_string(),
+                        BlobstoreBytes::from_bytes(&b"bar"[..]),
+                    )
+                })
+                .collect();
+            let deletes = {
+                let blobstore = blobstore.clone();
+                let ctx = ctx.clone();
+                move |_| {
+                    // Deleting a key that isn't there is not an error
+                    blobstore
+                        .delete(ctx.clone(), "foo".to_string())
+                        .and_then(move |()| blobstore.delete(ctx, "missing".to_string()))
+                }
+            };
+            future::join_all(puts)
+                .and_then(deletes)
+                .and_then(move |()| {
+                    blobstore
+                        .get(ctx.clone(), "foo".to_string())
+                        .join(blobstore.is_present(ctx, "other".to_string()))
+                })
+        })
+    });
+
+    let mut runtime = Runtime::new().expect("runtime creation failed");
+    let (deleted, other_present) = runtime.block_on(fut).expect("delete failed");
+
+    assert!(deleted.is_none());
+    assert!(other_present);
+}
+
 macro_rules! blobstore_test_impl {
     ($mod_name: ident => {
         state: $state: expr,
@@ -140,6 +238,18 @@ macro_rules! blobstore_test_impl {
                 let state = $state;
                 boxable(fb, $new_cb(state.clone()));
             }
+
+            #[fbinit::test]
+            fn test_enumerate(fb: FacebookInit) {
+                let state = $state;
+                enumerate(fb, $new_cb(state.clone()));
+            }
+
+            #[fbinit::test]
+            fn test_delete(fb: FacebookInit) {
+                let state = $state;
+                delete(fb, $new_cb(state.clone()));
+            }
         }
     };
 }
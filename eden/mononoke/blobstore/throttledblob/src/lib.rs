@@ -13,11 +13,13 @@ use std::num::NonZeroU32;
 
 use async_limiter::{AsyncLimiter, TokioFlavor};
 use futures::future::Future;
-use futures_ext::{try_boxfuture, BoxFuture, FutureExt as Futures01FutureExt};
+use futures_ext::{
+    try_boxfuture, BoxFuture, BoxStream, FutureExt as Futures01FutureExt, StreamExt,
+};
 use futures_util::future::{FutureExt, TryFutureExt};
 use ratelimit_meter::{algorithms::LeakyBucket, example_algorithms::Allower, DirectRateLimiter};
 
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange};
 use context::CoreContext;
 use mononoke_types::BlobstoreBytes;
 
@@ -116,6 +118,46 @@ impl<T: Blobstore + Clone> Blobstore for ThrottledBlob<T> {
             .and_then(move |_| blobstore.assert_present(ctx, key))
             .boxify()
     }
+
+    // Enumeration is throttled as a single read, as it is only used by offline tooling.
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        let access = match self.read_limiter.access() {
+            Ok(access) => access,
+            Err(err) => return futures::stream::once(Err(err)).boxify(),
+        };
+        let blobstore = self.blobstore.clone();
+        access
+            .boxed()
+            .compat()
+            .map(move |_| blobstore.enumerate(ctx, range))
+            .flatten_stream()
+            .boxify()
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        let access = try_boxfuture!(self.write_limiter.access());
+        let blobstore = self.blobstore.clone();
+        access
+            .boxed()
+            .compat()
+            .and_then(move |_| blobstore.delete(ctx, key))
+            .boxify()
+    }
+
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        let access = try_boxfuture!(self.write_limiter.access());
+        let blobstore = self.blobstore.clone();
+        access
+            .boxed()
+            .compat()
+            .and_then(move |_| blobstore.overwrite(ctx, key, value))
+            .boxify()
+    }
 }
 
 impl<T: Blobstore + Clone> fmt::Debug for ThrottledBlob<T> {
@@ -0,0 +1,391 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod errors;
+
+pub use crate::errors::ErrorKind;
+
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Error, Result};
+use bytes::Bytes;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    future::{self, FutureExt, TryFutureExt},
+    stream::TryStreamExt,
+};
+use futures_ext::{BoxFuture, BoxStream, FutureExt as _};
+use futures_old::Future;
+use slog::warn;
+
+use blobstore::{Blobstore, BlobstoreGetData, BlobstoreKeyRange, BlobstoreMetadata};
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+
+/// Every blob in the hot tier starts with this, followed by the time it was written there as
+/// big-endian seconds since the epoch. Not every blobstore reports a ctime, and promotion needs
+/// the time of the copy rather than of the original put.
+const MAGIC: &[u8] = b"\x89MNTIER\n";
+const HEADER_LEN: usize = 16;
+const DEMOTION_CONCURRENCY: usize = 10;
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|since_epoch| since_epoch.as_secs().try_into().ok())
+        .unwrap_or(0)
+}
+
+fn encode_hot(value: &BlobstoreBytes, ctime: i64) -> BlobstoreBytes {
+    let raw = value.as_bytes();
+    let mut envelope = Vec::with_capacity(HEADER_LEN + raw.len());
+    envelope.extend_from_slice(MAGIC);
+    envelope.extend_from_slice(&ctime.to_be_bytes());
+    envelope.extend_from_slice(raw);
+    BlobstoreBytes::from_bytes(envelope)
+}
+
+fn decode_hot(key: &str, bytes: Bytes) -> Result<BlobstoreGetData> {
+    if bytes.len() < HEADER_LEN || !bytes.starts_with(MAGIC) {
+        return Err(ErrorKind::MissingHeader(key.to_string()).into());
+    }
+    let mut ctime = [0u8; 8];
+    ctime.copy_from_slice(&bytes[MAGIC.len()..HEADER_LEN]);
+    Ok(BlobstoreGetData::new(
+        BlobstoreMetadata::new(Some(i64::from_be_bytes(ctime))),
+        BlobstoreBytes::from_bytes(bytes.slice(HEADER_LEN..)),
+    ))
+}
+
+struct Tiers<H, C> {
+    hot: H,
+    cold: C,
+    promote_on_read: bool,
+}
+
+/// A blobstore with a fast tier for recent data in front of a durable cold tier holding
+/// everything. Writes go to both tiers, reads are served by the hot tier and fall back to the
+/// cold one. Blobs leave the hot tier by demotion once they are old enough.
+#[derive(Clone)]
+pub struct TieredBlobstore<H, C> {
+    tiers: Arc<Tiers<H, C>>,
+}
+
+impl<H: Blobstore + Clone, C: Blobstore + Clone> TieredBlobstore<H, C> {
+    /// If `promote_on_read` is set, blobs read from the cold tier are copied back to the hot one.
+    pub fn new(hot: H, cold: C, promote_on_read: bool) -> Self {
+        Self {
+            tiers: Arc::new(Tiers {
+                hot,
+                cold,
+                promote_on_read,
+            }),
+        }
+    }
+
+    pub fn as_hot(&self) -> &H {
+        &self.tiers.hot
+    }
+
+    pub fn as_cold(&self) -> &C {
+        &self.tiers.cold
+    }
+
+    /// Remove blobs written to the hot tier at least `age` ago from it, leaving them in the cold
+    /// tier only. Resolves to the number of blobs demoted.
+    ///
+    /// This isn't run by the blobstore itself: a single job (`mononoke_admin blobstore-demote`)
+    /// should run it for each tiered blobstore.
+    pub async fn demote(&self, ctx: CoreContext, age: Duration) -> Result<usize> {
+        let cutoff = now().saturating_sub(age.as_secs().try_into().unwrap_or(i64::MAX));
+        let tiers = &self.tiers;
+
+        tiers
+            .hot
+            .enumerate(ctx.clone(), BlobstoreKeyRange::all())
+            .compat()
+            .map_ok(|key| demote_blob(tiers, ctx.clone(), key, cutoff))
+            .try_buffer_unordered(DEMOTION_CONCURRENCY)
+            .try_fold(0, |demoted, moved| future::ok(demoted + moved as usize))
+            .await
+    }
+}
+
+async fn demote_blob<H: Blobstore, C: Blobstore>(
+    tiers: &Tiers<H, C>,
+    ctx: CoreContext,
+    key: String,
+    cutoff: i64,
+) -> Result<bool> {
+    let data = match tiers.hot.get(ctx.clone(), key.clone()).compat().await? {
+        Some(data) => decode_hot(&key, data.into_raw_bytes())?,
+        None => return Ok(false),
+    };
+    match data.as_meta().as_ctime() {
+        Some(ctime) if *ctime <= cutoff => {}
+        _ => return Ok(false),
+    }
+
+    // A put whose cold write failed can have left the blob in the hot tier only
+    if !tiers
+        .cold
+        .is_present(ctx.clone(), key.clone())
+        .compat()
+        .await?
+    {
+        tiers
+            .cold
+            .put(ctx.clone(), key.clone(), data.into_bytes())
+            .compat()
+            .await?;
+    }
+    tiers.hot.delete(ctx, key).compat().await?;
+    Ok(true)
+}
+
+impl<H, C> fmt::Debug for TieredBlobstore<H, C>
+where
+    H: Blobstore + Clone,
+    C: Blobstore + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TieredBlobstore")
+            .field("hot", &self.tiers.hot)
+            .field("cold", &self.tiers.cold)
+            .field("promote_on_read", &self.tiers.promote_on_read)
+            .finish()
+    }
+}
+
+impl<H: Blobstore + Clone, C: Blobstore + Clone> Blobstore for TieredBlobstore<H, C> {
+    fn get(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+        let tiers = self.tiers.clone();
+        async move {
+            // The cold tier has everything, so a failing hot tier only costs latency
+            match tiers.hot.get(ctx.clone(), key.clone()).compat().await {
+                Ok(Some(data)) => return decode_hot(&key, data.into_raw_bytes()).map(Some),
+                Ok(None) => {}
+                Err(e) => warn!(ctx.logger(), "Hot tier get of {} failed: {:#}", key, e),
+            }
+
+            let data = tiers.cold.get(ctx.clone(), key.clone()).compat().await?;
+            if let (Some(data), true) = (&data, tiers.promote_on_read) {
+                let value = encode_hot(data.as_bytes(), now());
+                if let Err(e) = tiers
+                    .hot
+                    .put(ctx.clone(), key.clone(), value)
+                    .compat()
+                    .await
+                {
+                    warn!(ctx.logger(), "Promotion of {} failed: {:#}", key, e);
+                }
+            }
+            Ok(data)
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+
+    fn put(&self, ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
+        let hot_value = encode_hot(&value, now());
+        self.tiers
+            .cold
+            .put(ctx.clone(), key.clone(), value)
+            .join(self.tiers.hot.put(ctx, key, hot_value))
+            .map(|_| ())
+            .boxify()
+    }
+
+    fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
+        let tiers = self.tiers.clone();
+        async move {
+            if let Ok(true) = tiers
+                .hot
+                .is_present(ctx.clone(), key.clone())
+                .compat()
+                .await
+            {
+                return Ok(true);
+            }
+            tiers.cold.is_present(ctx, key).compat().await
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+
+    // Demotion only ever removes from the hot tier, so the cold tier has every key
+    fn enumerate(&self, ctx: CoreContext, range: BlobstoreKeyRange) -> BoxStream<String, Error> {
+        self.tiers.cold.enumerate(ctx, range)
+    }
+
+    fn delete(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        let tiers = self.tiers.clone();
+        async move {
+            future::try_join(
+                tiers.hot.delete(ctx.clone(), key.clone()).compat(),
+                tiers.cold.delete(ctx, key).compat(),
+            )
+            .map_ok(|_| ())
+            .await
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+
+    // The hot copy is dropped rather than replaced, so that overwriting doesn't promote the key;
+    // it is promoted again on the next read if `promote_on_read` is set.
+    fn overwrite(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> BoxFuture<(), Error> {
+        let tiers = self.tiers.clone();
+        async move {
+            tiers
+                .cold
+                .overwrite(ctx.clone(), key.clone(), value)
+                .compat()
+                .await?;
+            tiers.hot.delete(ctx, key).compat().await
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use fbinit::FacebookInit;
+
+    use memblob::EagerMemblob;
+
+    fn bytes(value: &'static [u8]) -> BlobstoreBytes {
+        BlobstoreBytes::from_bytes(Bytes::from_static(value))
+    }
+
+    #[fbinit::compat_test]
+    async fn test_put_writes_both_tiers(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let store = TieredBlobstore::new(EagerMemblob::new(), EagerMemblob::new(), false);
+
+        store
+            .put(ctx.clone(), "key".to_string(), bytes(b"value"))
+            .compat()
+            .await?;
+
+        let cold = store
+            .as_cold()
+            .get(ctx.clone(), "key".to_string())
+            .compat()
+            .await?;
+        assert_eq!(
+            cold.map(BlobstoreGetData::into_bytes),
+            Some(bytes(b"value"))
+        );
+
+        let hot = store
+            .as_hot()
+            .get(ctx.clone(), "key".to_string())
+            .compat()
+            .await?;
+        let hot = decode_hot("key", hot.expect("blob should be hot").into_raw_bytes())?;
+        assert!(hot.as_meta().as_ctime().is_some());
+
+        let out = store.get(ctx, "key".to_string()).compat().await?;
+        assert_eq!(out.map(BlobstoreGetData::into_bytes), Some(bytes(b"value")));
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_cold_fallback(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+
+        for promote_on_read in &[false, true] {
+            let store =
+                TieredBlobstore::new(EagerMemblob::new(), EagerMemblob::new(), *promote_on_read);
+            store
+                .as_cold()
+                .put(ctx.clone(), "key".to_string(), bytes(b"value"))
+                .compat()
+                .await?;
+
+            assert!(
+                store
+                    .is_present(ctx.clone(), "key".to_string())
+                    .compat()
+                    .await?
+            );
+            let out = store.get(ctx.clone(), "key".to_string()).compat().await?;
+            assert_eq!(out.map(BlobstoreGetData::into_bytes), Some(bytes(b"value")));
+
+            let promoted = store
+                .as_hot()
+                .is_present(ctx.clone(), "key".to_string())
+                .compat()
+                .await?;
+            assert_eq!(promoted, *promote_on_read);
+        }
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_demote(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let store = TieredBlobstore::new(EagerMemblob::new(), EagerMemblob::new(), false);
+
+        for key in &["a", "b"] {
+            store
+                .put(ctx.clone(), key.to_string(), bytes(b"value"))
+                .compat()
+                .await?;
+        }
+        // Only in the hot tier, e.g. after a failed put
+        store
+            .as_hot()
+            .put(
+                ctx.clone(),
+                "c".to_string(),
+                encode_hot(&bytes(b"value"), 0),
+            )
+            .compat()
+            .await?;
+
+        // Nothing is a day old yet, except c
+        assert_eq!(
+            store
+                .demote(ctx.clone(), Duration::from_secs(86400))
+                .await?,
+            1
+        );
+        assert_eq!(store.demote(ctx.clone(), Duration::from_secs(0)).await?, 2);
+
+        for key in &["a", "b", "c"] {
+            let hot = store
+                .as_hot()
+                .get(ctx.clone(), key.to_string())
+                .compat()
+                .await?;
+            assert!(hot.is_none());
+            let out = store.get(ctx.clone(), key.to_string()).compat().await?;
+            assert_eq!(out.map(BlobstoreGetData::into_bytes), Some(bytes(b"value")));
+        }
+        Ok(())
+    }
+}
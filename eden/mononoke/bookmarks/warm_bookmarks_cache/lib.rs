@@ -12,12 +12,13 @@ use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use anyhow::Error;
-use blame::BlameRoot;
+use blame::{BlameDetectedCopiesRoot, BlameRoot};
 use blobrepo::BlobRepo;
 use bookmarks::{BookmarkName, Freshness};
 use changeset_info::ChangesetInfo;
 use cloned::cloned;
 use context::CoreContext;
+use copy_detection::DetectedCopies;
 use deleted_files_manifest::RootDeletedManifestId;
 use derived_data::BonsaiDerived;
 use fsnodes::RootFsnodeId;
@@ -102,6 +103,12 @@ impl WarmBookmarksCache {
         if derived_data_types.contains(RootDeletedManifestId::NAME) {
             warmers.push(create_warmer::<RootDeletedManifestId>(&ctx));
         }
+        if derived_data_types.contains(DetectedCopies::NAME) {
+            warmers.push(create_warmer::<DetectedCopies>(&ctx));
+        }
+        if derived_data_types.contains(BlameDetectedCopiesRoot::NAME) {
+            warmers.push(create_warmer::<BlameDetectedCopiesRoot>(&ctx));
+        }
 
         let warmers = Arc::new(warmers);
         let (sender, receiver) = oneshot::channel();
@@ -6,6 +6,7 @@
  */
 
 use blobrepo_factory::Caching;
+use blobstore_factory::LocalCacheOptions;
 use clap::{App, Arg, ArgMatches};
 use fbinit::FacebookInit;
 use lazy_static::lazy_static;
@@ -18,6 +19,12 @@ pub const WITH_CONTENT_SHA1_CACHE: &str = "with-content-sha1-cache";
 const SKIP_CACHING: &str = "skip-caching";
 const CACHELIB_ONLY_BLOBSTORE: &str = "cachelib-only-blobstore";
 const READONLY_STORAGE: &str = "readonly-storage";
+const LOCAL_CACHE: &str = "local-blobstore-cache";
+const LOCAL_CACHE_PATH: &str = "local-blobstore-cache-path";
+const LOCAL_CACHE_SIZE_MB: &str = "local-blobstore-cache-size-mb";
+
+const DEFAULT_MEMORY_LOCAL_CACHE_SIZE_MB: u64 = 1024;
+const DEFAULT_DISK_LOCAL_CACHE_SIZE_MB: u64 = 10 * 1024;
 
 const PHASES_CACHE_SIZE: &str = "phases-cache-size";
 const BUCKETS_POWER: &str = "buckets-power";
@@ -123,10 +130,33 @@ pub fn add_cachelib_args<'a, 'b>(app: App<'a, 'b>, hide_advanced_args: bool) ->
             .long(READONLY_STORAGE)
             .help("Error on any attempts to write to storage"),
     )
+    .arg(
+        Arg::with_name(LOCAL_CACHE)
+            .long(LOCAL_CACHE)
+            .takes_value(true)
+            .possible_values(&["memory", "disk"])
+            .help("cache blobs on this host, for deployments without cachelib and memcache"),
+    )
+    .arg(
+        Arg::with_name(LOCAL_CACHE_PATH)
+            .long(LOCAL_CACHE_PATH)
+            .takes_value(true)
+            .value_name("PATH")
+            .required_if(LOCAL_CACHE, "disk")
+            .help("directory for the disk cache, which can be shared by processes on this host"),
+    )
+    .arg(
+        Arg::with_name(LOCAL_CACHE_SIZE_MB)
+            .long(LOCAL_CACHE_SIZE_MB)
+            .takes_value(true)
+            .value_name("SIZE")
+            .requires(LOCAL_CACHE)
+            .help("size of the local blobstore cache, in MiB"),
+    )
     .args(&cache_args)
 }
 
-pub(crate) fn parse_caching<'a>(matches: &ArgMatches<'a>) -> Caching {
+pub fn parse_caching<'a>(matches: &ArgMatches<'a>) -> Caching {
     if matches.is_present(SKIP_CACHING) {
         Caching::Disabled
     } else if matches.is_present(CACHELIB_ONLY_BLOBSTORE) {
@@ -136,6 +166,28 @@ pub(crate) fn parse_caching<'a>(matches: &ArgMatches<'a>) -> Caching {
     }
 }
 
+pub fn parse_local_cache_options<'a>(matches: &ArgMatches<'a>) -> LocalCacheOptions {
+    let size_mb = |default| {
+        matches
+            .value_of(LOCAL_CACHE_SIZE_MB)
+            .map(|size| size.parse::<u64>().expect("Provided cache size is not u64"))
+            .unwrap_or(default)
+    };
+    match matches.value_of(LOCAL_CACHE) {
+        Some("memory") => LocalCacheOptions::Memory {
+            max_bytes: (size_mb(DEFAULT_MEMORY_LOCAL_CACHE_SIZE_MB) * 1024 * 1024) as usize,
+        },
+        Some("disk") => LocalCacheOptions::Disk {
+            path: matches
+                .value_of(LOCAL_CACHE_PATH)
+                .expect("disk cache requires a path")
+                .into(),
+            max_bytes: size_mb(DEFAULT_DISK_LOCAL_CACHE_SIZE_MB) * 1024 * 1024,
+        },
+        _ => LocalCacheOptions::Disabled,
+    }
+}
+
 pub fn init_cachelib<'a>(
     fb: FacebookInit,
     matches: &ArgMatches<'a>,
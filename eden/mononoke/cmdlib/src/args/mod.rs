@@ -9,7 +9,10 @@ mod cache;
 #[cfg(fbcode_build)]
 mod facebook;
 
-pub use self::cache::{add_cachelib_args, init_cachelib, WITH_CONTENT_SHA1_CACHE};
+pub use self::cache::{
+    add_cachelib_args, init_cachelib, parse_caching, parse_local_cache_options,
+    WITH_CONTENT_SHA1_CACHE,
+};
 
 use std::collections::{HashMap, HashSet};
 use std::io;
@@ -50,8 +53,6 @@ use crate::helpers::{
 };
 use crate::log;
 
-use self::cache::parse_caching;
-
 pub const CONFIG_PATH: &str = "mononoke-config-path";
 
 const REPO_ID: &str = "repo-id";
@@ -975,6 +976,7 @@ pub fn parse_blobstore_options<'a>(matches: &ArgMatches<'a>) -> BlobstoreOptions
         ChaosOptions::new(read_chaos, write_chaos),
         ThrottleOptions::new(read_qps, write_qps),
         manifold_api_key,
+        parse_local_cache_options(matches),
     )
 }
 
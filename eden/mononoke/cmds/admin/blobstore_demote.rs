@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use anyhow::{format_err, Error};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fbinit::FacebookInit;
+
+use blobstore_factory::make_blobstore;
+use cmdlib::args;
+use context::CoreContext;
+use metaconfig_types::BlobConfig;
+use slog::{info, Logger};
+use tieredblob::TieredBlobstore;
+
+use crate::error::SubcommandError;
+
+pub const BLOBSTORE_DEMOTE: &str = "blobstore-demote";
+const ARG_AGE: &str = "age";
+
+pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(BLOBSTORE_DEMOTE)
+        .about(
+            "removes old blobs from the hot tier of the tiered blobstore, leaving them in the cold \
+             tier only. Run it from a single place, e.g. a periodic job",
+        )
+        .arg(
+            Arg::with_name(ARG_AGE)
+                .long(ARG_AGE)
+                .takes_value(true)
+                .required(false)
+                .help("Demote blobs written to the hot tier this many seconds ago, instead of the configured demote_after_secs"),
+        )
+}
+
+pub async fn subcommand_blobstore_demote<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), SubcommandError> {
+    let (_, config) = args::get_config(fb, &matches)?;
+    let (hot, cold, promote_on_read, demote_after) = match config.storage_config.blobstore {
+        BlobConfig::Tiered {
+            hot,
+            cold,
+            promote_on_read,
+            demote_after,
+        } => (*hot, *cold, promote_on_read, demote_after),
+        other => {
+            return Err(format_err!("blobstore is not tiered: {:?}", other).into());
+        }
+    };
+    let age = match sub_m.value_of(ARG_AGE) {
+        Some(age) => Duration::from_secs(age.parse().map_err(Error::from)?),
+        None => demote_after.ok_or_else(|| {
+            format_err!(
+                "demote_after_secs is not configured, and --{} is not given",
+                ARG_AGE
+            )
+        })?,
+    };
+
+    let mysql_options = args::parse_mysql_options(&matches);
+    let blobstore_options = args::parse_blobstore_options(&matches);
+    let readonly_storage = args::parse_readonly_storage(&matches);
+    let hot = make_blobstore(
+        fb,
+        hot,
+        mysql_options,
+        readonly_storage,
+        &blobstore_options,
+        &logger,
+    )
+    .await?;
+    let cold = make_blobstore(
+        fb,
+        cold,
+        mysql_options,
+        readonly_storage,
+        &blobstore_options,
+        &logger,
+    )
+    .await?;
+    let blobstore = TieredBlobstore::new(hot, cold, promote_on_read);
+
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    let demoted = blobstore.demote(ctx, age).await?;
+    info!(
+        logger,
+        "Demoted {} blobs older than {}s to the cold tier",
+        demoted,
+        age.as_secs()
+    );
+    Ok(())
+}
@@ -0,0 +1,153 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::{format_err, Error};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fbinit::FacebookInit;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    future::{self, TryFutureExt},
+    stream::TryStreamExt,
+};
+use futures_ext::StreamExt;
+use futures_old::stream;
+
+use blobstore::{Blobstore, BlobstoreKeyRange};
+use blobstore_factory::make_blobstore;
+use cmdlib::args;
+use context::CoreContext;
+use encryptedblob::{EncryptedBlobstore, EncryptionKeys, ErrorKind};
+use metaconfig_types::BlobConfig;
+use slog::{info, warn, Logger};
+
+use crate::error::SubcommandError;
+
+pub const BLOBSTORE_REENCRYPT: &str = "blobstore-reencrypt";
+const DEFAULT_NUM_JOBS: usize = 10;
+
+pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(BLOBSTORE_REENCRYPT)
+        .about("re-encrypts blobs with the key currently configured for the encrypted blobstore")
+        .args_from_usage(
+            "[KEY]...    'keys of the blobs to re-encrypt, all blobs of the repo if none are given'",
+        )
+        .arg(
+            Arg::with_name("no-prefix")
+                .long("no-prefix")
+                .short("P")
+                .takes_value(false)
+                .required(false)
+                .help("Don't prepend a prefix based on the repo id to the keys"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .short("j")
+                .takes_value(true)
+                .required(false)
+                .help("The number of blobs to re-encrypt in parallel"),
+        )
+}
+
+pub async fn subcommand_blobstore_reencrypt<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), SubcommandError> {
+    let repo_id = args::get_repo_id(fb, &matches)?;
+    let (_, config) = args::get_config(fb, &matches)?;
+    let (blobconfig, keyfile, key_id) = match config.storage_config.blobstore {
+        BlobConfig::Encrypted {
+            blobconfig,
+            keyfile,
+            key_id,
+        } => (*blobconfig, keyfile, key_id),
+        other => {
+            return Err(format_err!("blobstore is not encrypted: {:?}", other).into());
+        }
+    };
+    let keys = EncryptionKeys::from_keyfile(&keyfile, key_id)?;
+    let jobs: usize = sub_m
+        .value_of("jobs")
+        .map_or(Ok(DEFAULT_NUM_JOBS), |j| j.parse())
+        .map_err(Error::from)?;
+
+    let mysql_options = args::parse_mysql_options(&matches);
+    let blobstore_options = args::parse_blobstore_options(&matches);
+    let readonly_storage = args::parse_readonly_storage(&matches);
+    let inner = make_blobstore(
+        fb,
+        blobconfig,
+        mysql_options,
+        readonly_storage,
+        &blobstore_options,
+        &logger,
+    )
+    .await?;
+    let blobstore = EncryptedBlobstore::new(inner, Arc::new(keys));
+
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    let prefix = if sub_m.is_present("no-prefix") {
+        String::new()
+    } else {
+        repo_id.prefix()
+    };
+    let blob_keys = match sub_m.values_of("KEY") {
+        Some(keys) => {
+            let keys: Vec<_> = keys.map(|key| format!("{}{}", prefix, key)).collect();
+            stream::iter_ok(keys).boxify()
+        }
+        None => blobstore.enumerate(ctx.clone(), BlobstoreKeyRange::with_prefix(prefix)),
+    };
+
+    // Blobs the blobstore kept the old value for are reported rather than counted as done
+    let (reencrypted, not_reencrypted, total) = blob_keys
+        .compat()
+        .map_ok(|key| {
+            blobstore
+                .reencrypt(ctx.clone(), key)
+                .compat()
+                .map_ok(Some)
+                .or_else(|error| match error.downcast_ref::<ErrorKind>() {
+                    Some(ErrorKind::NotReencrypted(key, old_id)) => {
+                        warn!(
+                            logger,
+                            "Blob {} is still encrypted with key {}", key, old_id
+                        );
+                        future::ok(None)
+                    }
+                    _ => future::err(error),
+                })
+        })
+        .try_buffer_unordered(jobs)
+        .try_fold(
+            (0usize, 0usize, 0usize),
+            |(reencrypted, not_reencrypted, total), outcome| {
+                future::ok(match outcome {
+                    Some(changed) => (reencrypted + changed as usize, not_reencrypted, total + 1),
+                    None => (reencrypted, not_reencrypted + 1, total + 1),
+                })
+            },
+        )
+        .await?;
+
+    info!(
+        logger,
+        "Re-encrypted {} of {} blobs with key {}", reencrypted, total, key_id
+    );
+    if not_reencrypted > 0 {
+        return Err(format_err!(
+            "{} blobs are still encrypted with an old key, as the blobstore kept the existing values",
+            not_reencrypted
+        )
+        .into());
+    }
+    Ok(())
+}
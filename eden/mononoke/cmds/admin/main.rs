@@ -17,7 +17,9 @@ use cmdlib::args;
 use context::CoreContext;
 use slog::error;
 
+use crate::blobstore_demote::subcommand_blobstore_demote;
 use crate::blobstore_fetch::subcommand_blobstore_fetch;
+use crate::blobstore_reencrypt::subcommand_blobstore_reencrypt;
 use crate::bonsai_fetch::subcommand_bonsai_fetch;
 use crate::content_fetch::subcommand_content_fetch;
 use crate::crossrepo::subcommand_crossrepo;
@@ -29,8 +31,12 @@ use crate::hg_sync::subcommand_process_hg_sync;
 use crate::mutable_counters::subcommand_mutable_counters;
 use crate::redaction::subcommand_redaction;
 use crate::skiplist_subcommand::subcommand_skiplist;
+use crate::sqlblob_migrate_chunks::subcommand_sqlblob_migrate_chunks;
+use crate::storage_usage::subcommand_storage_usage;
 
+mod blobstore_demote;
 mod blobstore_fetch;
+mod blobstore_reencrypt;
 mod bonsai_fetch;
 mod bookmarks_manager;
 mod common;
@@ -47,6 +53,8 @@ mod mutable_counters;
 mod phases;
 mod redaction;
 mod skiplist_subcommand;
+mod sqlblob_migrate_chunks;
+mod storage_usage;
 mod subcommand_blame;
 mod subcommand_deleted_manifest;
 mod subcommand_unodes;
@@ -58,7 +66,9 @@ fn setup_app<'a, 'b>() -> App<'a, 'b> {
         .build()
         .version("0.0.0")
         .about("Poke at mononoke internals for debugging and investigating data structures.")
+        .subcommand(blobstore_demote::build_subcommand())
         .subcommand(blobstore_fetch::build_subcommand())
+        .subcommand(blobstore_reencrypt::build_subcommand())
         .subcommand(bonsai_fetch::build_subcommand())
         .subcommand(content_fetch::build_subcommand())
         .subcommand(bookmarks_manager::build_subcommand())
@@ -76,6 +86,8 @@ fn setup_app<'a, 'b>() -> App<'a, 'b> {
         .subcommand(subcommand_blame::build_subcommand())
         .subcommand(subcommand_deleted_manifest::build_subcommand())
         .subcommand(derived_data::build_subcommand())
+        .subcommand(sqlblob_migrate_chunks::build_subcommand())
+        .subcommand(storage_usage::build_subcommand())
 }
 
 #[fbinit::main]
@@ -92,9 +104,15 @@ fn main(fb: FacebookInit) -> ExitCode {
     let mut runtime = args::init_runtime(&matches).expect("failed to initialize Tokio runtime");
     let res = runtime.block_on_std(async {
         match matches.subcommand() {
+            (blobstore_demote::BLOBSTORE_DEMOTE, Some(sub_m)) => {
+                subcommand_blobstore_demote(fb, logger, &matches, sub_m).await
+            }
             (blobstore_fetch::BLOBSTORE_FETCH, Some(sub_m)) => {
                 subcommand_blobstore_fetch(fb, logger, &matches, sub_m).await
             }
+            (blobstore_reencrypt::BLOBSTORE_REENCRYPT, Some(sub_m)) => {
+                subcommand_blobstore_reencrypt(fb, logger, &matches, sub_m).await
+            }
             (bonsai_fetch::BONSAI_FETCH, Some(sub_m)) => {
                 subcommand_bonsai_fetch(fb, logger, &matches, sub_m).await
             }
@@ -116,6 +134,9 @@ fn main(fb: FacebookInit) -> ExitCode {
             (skiplist_subcommand::SKIPLIST, Some(sub_m)) => {
                 subcommand_skiplist(fb, logger, &matches, sub_m).await
             }
+            (sqlblob_migrate_chunks::SQLBLOB_MIGRATE_CHUNKS, Some(sub_m)) => {
+                subcommand_sqlblob_migrate_chunks(fb, logger, &matches, sub_m).await
+            }
             (hash_convert::HASH_CONVERT, Some(sub_m)) => {
                 subcommand_hash_convert(fb, logger, &matches, sub_m).await
             }
@@ -152,6 +173,9 @@ fn main(fb: FacebookInit) -> ExitCode {
             (derived_data::DERIVED_DATA, Some(sub_m)) => {
                 derived_data::subcommand_derived_data(fb, logger, &matches, sub_m).await
             }
+            (storage_usage::STORAGE_USAGE, Some(sub_m)) => {
+                subcommand_storage_usage(fb, logger, &matches, sub_m).await
+            }
             _ => Err(SubcommandError::InvalidArgs),
         }
     });
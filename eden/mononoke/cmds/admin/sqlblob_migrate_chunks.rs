@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fbinit::FacebookInit;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    future,
+    stream::TryStreamExt,
+};
+
+use blobstore::{Blobstore, BlobstoreKeyRange};
+use blobstore_factory::make_sqlblob;
+use cmdlib::args;
+use context::CoreContext;
+use slog::{info, Logger};
+
+use crate::error::SubcommandError;
+
+pub const SQLBLOB_MIGRATE_CHUNKS: &str = "sqlblob-migrate-chunks";
+const DEFAULT_NUM_JOBS: usize = 10;
+
+pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(SQLBLOB_MIGRATE_CHUNKS)
+        .about(
+            "moves large blobs stored in the per-key chunk layout of a SQL blobstore to \
+             the shared content chunk table",
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .short("j")
+                .takes_value(true)
+                .required(false)
+                .help("The number of blobs to migrate in parallel"),
+        )
+}
+
+pub async fn subcommand_sqlblob_migrate_chunks<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), SubcommandError> {
+    let (_, config) = args::get_config(fb, &matches)?;
+    let jobs: usize = sub_m
+        .value_of("jobs")
+        .map_or(Ok(DEFAULT_NUM_JOBS), |j| j.parse())
+        .map_err(Error::from)?;
+
+    let mysql_options = args::parse_mysql_options(&matches);
+    let readonly_storage = args::parse_readonly_storage(&matches);
+    let sqlblob = make_sqlblob(
+        fb,
+        config.storage_config.blobstore,
+        mysql_options,
+        readonly_storage,
+    )
+    .await?;
+
+    // Chunks are shared between all keys in the store, so migrate all of them and not just
+    // those of this repo.
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    let (migrated, total) = sqlblob
+        .enumerate(ctx, BlobstoreKeyRange::all())
+        .compat()
+        .map_ok(|key| sqlblob.migrate_legacy_chunks(key).compat())
+        .try_buffer_unordered(jobs)
+        .try_fold((0usize, 0usize), |(migrated, total), changed| {
+            future::ok((migrated + changed as usize, total + 1))
+        })
+        .await?;
+
+    info!(logger, "Migrated {} of {} blobs", migrated, total);
+    Ok(())
+}
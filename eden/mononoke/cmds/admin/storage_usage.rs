@@ -0,0 +1,371 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use cmdlib::{args, helpers::csid_resolve};
+use context::CoreContext;
+use derived_data::BonsaiDerived;
+use fbinit::FacebookInit;
+use fsnodes::RootFsnodeId;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    future::{self, TryFutureExt},
+    stream::{self, StreamExt, TryStreamExt},
+};
+use manifest::ManifestOps;
+use mononoke_types::{BonsaiChangeset, ChangesetId, ContentId, MPath};
+use revset::RangeNodeStream;
+use serde_derive::Serialize;
+use slog::{info, Logger};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::error::SubcommandError;
+
+pub const STORAGE_USAGE: &str = "storage-usage";
+const ARG_START: &str = "start";
+const ARG_END: &str = "end";
+const ARG_FORMAT: &str = "format";
+const ARG_LIMIT: &str = "limit";
+
+const FORMAT_CSV: &str = "csv";
+const FORMAT_JSON: &str = "json";
+const LOAD_CONCURRENCY: usize = 100;
+
+pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(STORAGE_USAGE)
+        .about(
+            "attribute the bytes of file contents first added in a range of commits to top-level \
+             directories, file extensions and authors. Contents already in the parents of the \
+             first commit are not attributed",
+        )
+        .arg(
+            Arg::with_name(ARG_START)
+                .help("(hg|bonsai) commit hash or bookmark of the first commit in the range")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(ARG_END)
+                .help("(hg|bonsai) commit hash or bookmark of the last commit in the range")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name(ARG_FORMAT)
+                .long(ARG_FORMAT)
+                .takes_value(true)
+                .possible_values(&[FORMAT_CSV, FORMAT_JSON])
+                .default_value(FORMAT_CSV)
+                .help("output format"),
+        )
+        .arg(
+            Arg::with_name(ARG_LIMIT)
+                .long(ARG_LIMIT)
+                .takes_value(true)
+                .help("only output the N largest entries of each kind"),
+        )
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Usage {
+    files: u64,
+    bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, size: u64) {
+        self.files += 1;
+        self.bytes += size;
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct UsageRow {
+    kind: &'static str,
+    key: String,
+    files: u64,
+    bytes: u64,
+}
+
+// Each content is counted once, against the first file change in the range that added it.
+// Contents that were already in the repo before the range are counted as duplicates
+#[derive(Default)]
+struct StorageUsage {
+    seen: HashSet<ContentId>,
+    unique: Usage,
+    duplicate: Usage,
+    by_directory: HashMap<String, Usage>,
+    by_extension: HashMap<String, Usage>,
+    by_author: HashMap<String, Usage>,
+}
+
+fn top_level_directory(path: &MPath) -> String {
+    if path.num_components() > 1 {
+        match path.into_iter().next() {
+            Some(element) => String::from_utf8_lossy(element.as_ref()).into_owned(),
+            None => "/".to_string(),
+        }
+    } else {
+        "/".to_string()
+    }
+}
+
+fn extension(path: &MPath) -> String {
+    let basename = path.basename().as_ref();
+    // A leading dot is a hidden file rather than an extension
+    match basename.iter().rposition(|b| *b == b'.') {
+        Some(pos) if pos > 0 => String::from_utf8_lossy(&basename[pos + 1..]).to_lowercase(),
+        _ => String::new(),
+    }
+}
+
+impl StorageUsage {
+    fn new(existing: HashSet<ContentId>) -> Self {
+        Self {
+            seen: existing,
+            ..Default::default()
+        }
+    }
+
+    fn add_changeset(&mut self, bcs: &BonsaiChangeset) {
+        for (path, change) in bcs.file_changes() {
+            let change = match change {
+                Some(change) => change,
+                None => continue,
+            };
+            let size = change.size();
+            if !self.seen.insert(change.content_id()) {
+                self.duplicate.add(size);
+                continue;
+            }
+            self.unique.add(size);
+            self.by_directory
+                .entry(top_level_directory(path))
+                .or_default()
+                .add(size);
+            self.by_extension
+                .entry(extension(path))
+                .or_default()
+                .add(size);
+            self.by_author
+                .entry(bcs.author().to_string())
+                .or_default()
+                .add(size);
+        }
+    }
+
+    fn into_rows(self, limit: Option<usize>) -> Vec<UsageRow> {
+        let mut rows = vec![
+            UsageRow {
+                kind: "total",
+                key: "unique".to_string(),
+                files: self.unique.files,
+                bytes: self.unique.bytes,
+            },
+            UsageRow {
+                kind: "total",
+                key: "duplicate".to_string(),
+                files: self.duplicate.files,
+                bytes: self.duplicate.bytes,
+            },
+        ];
+        for (kind, usage) in vec![
+            ("directory", self.by_directory),
+            ("extension", self.by_extension),
+            ("author", self.by_author),
+        ] {
+            let mut usage: Vec<_> = usage.into_iter().collect();
+            // Largest first, then by key so the output is stable
+            usage.sort_by(|(k1, u1), (k2, u2)| u2.bytes.cmp(&u1.bytes).then_with(|| k1.cmp(k2)));
+            usage.truncate(limit.unwrap_or(usage.len()));
+            rows.extend(usage.into_iter().map(|(key, usage)| UsageRow {
+                kind,
+                key,
+                files: usage.files,
+                bytes: usage.bytes,
+            }));
+        }
+        rows
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv<W: Write>(mut w: W, rows: &[UsageRow]) -> Result<(), Error> {
+    writeln!(w, "kind,key,files,bytes")?;
+    for row in rows {
+        writeln!(
+            w,
+            "{},{},{},{}",
+            row.kind,
+            csv_field(&row.key),
+            row.files,
+            row.bytes
+        )?;
+    }
+    Ok(())
+}
+
+// Contents of the files in the parents of `start`, which the range did not add
+async fn existing_contents(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    start: ChangesetId,
+) -> Result<HashSet<ContentId>, Error> {
+    let parents = repo
+        .get_changeset_parents_by_bonsai(ctx.clone(), start)
+        .compat()
+        .await?;
+    let mut contents = HashSet::new();
+    for parent in parents {
+        let root = RootFsnodeId::derive(ctx.clone(), repo.clone(), parent)
+            .compat()
+            .await?;
+        root.fsnode_id()
+            .list_leaf_entries(ctx.clone(), repo.get_blobstore())
+            .compat()
+            .try_for_each(|(_, file)| {
+                contents.insert(*file.content_id());
+                future::ready(Ok(()))
+            })
+            .await?;
+    }
+    Ok(contents)
+}
+
+async fn compute_storage_usage(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    start: ChangesetId,
+    end: ChangesetId,
+) -> Result<StorageUsage, Error> {
+    let cs_ids: Vec<ChangesetId> =
+        RangeNodeStream::new(ctx.clone(), repo.get_changeset_fetcher(), start, end)
+            .compat()
+            .try_collect()
+            .await?;
+    info!(ctx.logger(), "Found {} commits in range", cs_ids.len());
+
+    let existing = existing_contents(ctx, repo, start).await?;
+    info!(
+        ctx.logger(),
+        "Found {} contents in the parents of the range",
+        existing.len()
+    );
+
+    // Oldest first, so each content is attributed to the commit that added it
+    let mut cs_ids: Vec<_> = stream::iter(cs_ids.into_iter().map(|cs_id| async move {
+        let generation = repo
+            .get_generation_number(ctx.clone(), cs_id)
+            .compat()
+            .await?
+            .ok_or_else(|| format_err!("Changeset {} not found", cs_id))?;
+        Ok::<_, Error>((generation, cs_id))
+    }))
+    .buffer_unordered(LOAD_CONCURRENCY)
+    .try_collect()
+    .await?;
+    cs_ids.sort_by_key(|(generation, _)| *generation);
+
+    let blobstore = repo.get_blobstore();
+    stream::iter(cs_ids.into_iter().map(|(_, cs_id)| {
+        cs_id
+            .load(ctx.clone(), &blobstore)
+            .compat()
+            .map_err(Error::from)
+    }))
+    .buffered(LOAD_CONCURRENCY)
+    .try_fold(StorageUsage::new(existing), |mut usage, bcs| async move {
+        usage.add_changeset(&bcs);
+        Ok(usage)
+    })
+    .await
+}
+
+pub async fn subcommand_storage_usage<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), SubcommandError> {
+    args::init_cachelib(fb, &matches, None);
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+
+    let start = sub_m
+        .value_of(ARG_START)
+        .ok_or(SubcommandError::InvalidArgs)?
+        .to_string();
+    let end = sub_m
+        .value_of(ARG_END)
+        .ok_or(SubcommandError::InvalidArgs)?
+        .to_string();
+    let limit = sub_m
+        .value_of(ARG_LIMIT)
+        .map(|limit| limit.parse::<usize>())
+        .transpose()
+        .map_err(Error::from)?;
+
+    let repo = args::open_repo(fb, &logger, &matches).compat().await?;
+    let start = csid_resolve(ctx.clone(), repo.clone(), start)
+        .compat()
+        .await?;
+    let end = csid_resolve(ctx.clone(), repo.clone(), end)
+        .compat()
+        .await?;
+
+    let rows = compute_storage_usage(&ctx, &repo, start, end)
+        .await?
+        .into_rows(limit);
+
+    let stdout = io::stdout();
+    match sub_m.value_of(ARG_FORMAT) {
+        Some(FORMAT_JSON) => serde_json::to_writer(stdout.lock(), &rows).map_err(Error::from)?,
+        _ => write_csv(stdout.lock(), &rows)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(p: &str) -> MPath {
+        MPath::new(p).unwrap()
+    }
+
+    #[test]
+    fn test_top_level_directory() {
+        assert_eq!(top_level_directory(&path("README")), "/");
+        assert_eq!(top_level_directory(&path("foo/bar/baz.txt")), "foo");
+    }
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(extension(&path("foo/bar.TAR.GZ")), "gz");
+        assert_eq!(extension(&path("foo/.hgignore")), "");
+        assert_eq!(extension(&path("Makefile")), "");
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("foo"), "foo");
+        assert_eq!(
+            csv_field("Foo \"Bar\", <foo@example.com>"),
+            "\"Foo \"\"Bar\"\", <foo@example.com>\""
+        );
+    }
+}
@@ -8,7 +8,7 @@
 use crate::error::SubcommandError;
 
 use anyhow::{format_err, Error};
-use blame::{fetch_blame, fetch_file_full_content};
+use blame::{blame_filesize_limit, fetch_blame, fetch_file_full_content};
 use blobrepo::BlobRepo;
 use blobstore::{Blobstore, Loadable};
 use bytes::Bytes;
@@ -192,6 +192,7 @@ fn subcommand_show_diffs(
     path: MPath,
 ) -> impl Future<Item = (), Error = Error> {
     let blobstore = repo.get_blobstore();
+    let filesize_limit = blame_filesize_limit(&repo);
     find_leaf(ctx.clone(), repo, csid, path)
         .and_then({
             cloned!(ctx, blobstore);
@@ -211,7 +212,15 @@ fn subcommand_show_diffs(
                 .into_iter()
                 .map({
                     cloned!(ctx, blobstore);
-                    move |parent| diff(ctx.clone(), blobstore.boxed(), file_unode_id, parent)
+                    move |parent| {
+                        diff(
+                            ctx.clone(),
+                            blobstore.boxed(),
+                            file_unode_id,
+                            parent,
+                            filesize_limit,
+                        )
+                    }
                 });
             future::join_all(diffs).map(|diffs| {
                 for diff in diffs {
@@ -226,11 +235,13 @@ fn diff(
     blobstore: Arc<dyn Blobstore>,
     new: FileUnodeId,
     old: FileUnodeId,
+    filesize_limit: u64,
 ) -> impl Future<Item = String, Error = Error> {
     (
-        fetch_file_full_content(ctx.clone(), blobstore.clone(), new)
+        fetch_file_full_content(ctx.clone(), blobstore.clone(), new, filesize_limit)
+            .and_then(|result| result.map_err(Error::from)),
+        fetch_file_full_content(ctx, blobstore, old, filesize_limit)
             .and_then(|result| result.map_err(Error::from)),
-        fetch_file_full_content(ctx, blobstore, old).and_then(|result| result.map_err(Error::from)),
     )
         .into_future()
         .map(|(new, old)| {
@@ -265,6 +276,7 @@ fn subcommand_compute_blame(
     line_number: bool,
 ) -> impl Future<Item = (), Error = Error> {
     let blobstore = repo.get_blobstore().boxed();
+    let filesize_limit = blame_filesize_limit(&repo);
     find_leaf(ctx.clone(), repo.clone(), csid, path.clone())
         .and_then({
             cloned!(ctx, repo);
@@ -324,7 +336,7 @@ fn subcommand_compute_blame(
                     {
                         move |(csid, path, file_unode_id), parents: Iter<Result<(Bytes, Blame), BlameRejected>>| {
                             cloned!(path);
-                            fetch_file_full_content(ctx.clone(), blobstore.clone(), file_unode_id)
+                            fetch_file_full_content(ctx.clone(), blobstore.clone(), file_unode_id, filesize_limit)
                                 .and_then(move |content| match content {
                                     Err(rejected) => Ok(Err(rejected)),
                                     Ok(content) => {
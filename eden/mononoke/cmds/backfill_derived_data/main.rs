@@ -9,7 +9,7 @@
 #![deny(warnings)]
 
 use anyhow::{anyhow, format_err, Error};
-use blame::BlameRoot;
+use blame::{BlameDetectedCopiesRoot, BlameRoot};
 use blobrepo::{BlobRepo, DangerousOverride};
 use bookmarks::{BookmarkPrefix, Freshness};
 use bulkops::fetch_all_public_changesets;
@@ -91,6 +91,7 @@ const UNREDACTED_TYPES: &[&str] = &[
     RootFsnodeId::NAME,
     // Blame does not contain any content of the file itself
     BlameRoot::NAME,
+    BlameDetectedCopiesRoot::NAME,
 ];
 
 fn open_repo_maybe_unredacted<'a>(
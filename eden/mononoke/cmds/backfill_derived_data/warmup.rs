@@ -6,7 +6,7 @@
  */
 
 use anyhow::Error;
-use blame::{fetch_file_full_content, BlameRoot};
+use blame::{blame_filesize_limit, fetch_file_full_content, BlameDetectedCopiesRoot, BlameRoot};
 use blobrepo::BlobRepo;
 use blobstore::{Blobstore, Loadable};
 use cloned::cloned;
@@ -28,7 +28,7 @@ use unodes::{find_unode_renames, RootUnodeManifestId};
 
 /// Types of derived data for which prefetching content for changed files
 /// migth speed up derivation.
-const PREFETCH_CONTENT_TYPES: &[&str] = &[BlameRoot::NAME];
+const PREFETCH_CONTENT_TYPES: &[&str] = &[BlameRoot::NAME, BlameDetectedCopiesRoot::NAME];
 const PREFETCH_CONTENT_METADATA_TYPES: &[&str] = &[RootFsnodeId::NAME];
 const PREFETCH_UNODE_TYPES: &[&str] = &[RootFastlog::NAME, RootDeletedManifestId::NAME];
 
@@ -167,6 +167,7 @@ async fn prefetch_content(
         blobstore: Arc<dyn Blobstore>,
         rename: Option<FileUnodeId>,
         file_unode_id: FileUnodeId,
+        filesize_limit: u64,
     ) -> Result<(), Error> {
         let ctx = &ctx;
         let file_unode = file_unode_id.load(ctx.clone(), &blobstore).compat().await?;
@@ -178,14 +179,26 @@ async fn prefetch_content(
             .map({
                 cloned!(blobstore);
                 move |file_unode_id| {
-                    fetch_file_full_content(ctx.clone(), blobstore.clone(), file_unode_id).compat()
+                    fetch_file_full_content(
+                        ctx.clone(),
+                        blobstore.clone(),
+                        file_unode_id,
+                        filesize_limit,
+                    )
+                    .compat()
                 }
             })
             .collect();
 
         // the assignment is needed to avoid unused_must_use warnings
         let _ = future::try_join(
-            fetch_file_full_content(ctx.clone(), blobstore.clone(), file_unode_id).compat(),
+            fetch_file_full_content(
+                ctx.clone(),
+                blobstore.clone(),
+                file_unode_id,
+                filesize_limit,
+            )
+            .compat(),
             future::try_join_all(parents_content),
         )
         .await?;
@@ -214,6 +227,7 @@ async fn prefetch_content(
     .await?;
 
     let blobstore = repo.get_blobstore().boxed();
+    let filesize_limit = blame_filesize_limit(repo);
 
     find_intersection_of_diffs(
         ctx.clone(),
@@ -228,7 +242,13 @@ async fn prefetch_content(
         match result {
             Ok((path, file)) => {
                 let rename = renames.get(&path).copied();
-                let fut = prefetch_content_unode(ctx.clone(), blobstore.clone(), rename, file);
+                let fut = prefetch_content_unode(
+                    ctx.clone(),
+                    blobstore.clone(),
+                    rename,
+                    file,
+                    filesize_limit,
+                );
                 let join_handle = tokio::task::spawn(fut);
                 join_handle.await?
             }
@@ -144,28 +144,29 @@ async fn maybe_schedule_healer_for_storage(
         Arc::new(sync_queue)
     };
 
-    let blobstores = blobstore_configs
-        .into_iter()
-        .map(|(id, blobconfig)| async move {
-            let blobstore = make_blobstore(
-                fb,
-                blobconfig,
-                mysql_options,
-                readonly_storage,
-                blobstore_options,
-                ctx.logger(),
-            )
-            .await?;
-
-            let blobstore: Arc<dyn Blobstore> = if dry_run {
-                let logger = ctx.logger().new(o!("blobstore" => format!("{:?}", id)));
-                Arc::new(DummyBlobstore::new(blobstore, logger))
-            } else {
-                blobstore
-            };
-
-            Result::<_, Error>::Ok((id, blobstore))
-        });
+    let blobstores =
+        blobstore_configs
+            .into_iter()
+            .map(|(id, _store_type, blobconfig)| async move {
+                let blobstore = make_blobstore(
+                    fb,
+                    blobconfig,
+                    mysql_options,
+                    readonly_storage,
+                    blobstore_options,
+                    ctx.logger(),
+                )
+                .await?;
+
+                let blobstore: Arc<dyn Blobstore> = if dry_run {
+                    let logger = ctx.logger().new(o!("blobstore" => format!("{:?}", id)));
+                    Arc::new(DummyBlobstore::new(blobstore, logger))
+                } else {
+                    blobstore
+                };
+
+                Result::<_, Error>::Ok((id, blobstore))
+            });
 
     let blobstores = future::try_join_all(blobstores)
         .await?
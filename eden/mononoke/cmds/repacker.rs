@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use anyhow::{bail, format_err, Context, Error};
+use blobstore::Blobstore;
+use blobstore_factory::make_blobstore;
+use clap::Arg;
+use cloned::cloned;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::{
+    compat::Future01CompatExt,
+    future,
+    stream::{self, TryStreamExt},
+};
+use metaconfig_types::BlobConfig;
+use packblob::PackBlob;
+use slog::info;
+use std::fs;
+
+use cmdlib::{args, helpers::block_execute};
+
+const NAME: &str = "repacker";
+const DEFAULT_NUM_JOBS: usize = 10;
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<(), Error> {
+    let matches = args::MononokeApp::new(NAME)
+        .with_advanced_args_hidden()
+        .build()
+        .version("0.0.0")
+        .about("Repack related blobs into delta-encoded packs")
+        .arg(
+            Arg::with_name("keys-file")
+                .long("keys-file")
+                .value_name("FILE")
+                .takes_value(true)
+                .required(true)
+                .help(
+                    "File with one pack per line, each a whitespace separated list of keys. \
+                     Keys should be ordered so that similar blobs are next to each other. \
+                     Keys that are already packed are skipped",
+                ),
+        )
+        .arg(
+            Arg::with_name("delete-superseded")
+                .long("delete-superseded")
+                .help("Delete packs that no key refers to any more, instead of only logging them"),
+        )
+        .arg(
+            Arg::with_name("no-prefix")
+                .long("no-prefix")
+                .help("Don't prepend a prefix based on the repo id to the keys"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .value_name("JOBS")
+                .takes_value(true)
+                .help("The number of packs to write in parallel"),
+        )
+        .get_matches();
+
+    args::init_cachelib(fb, &matches, None);
+
+    let logger = args::init_logging(fb, &matches);
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+
+    let jobs: usize = matches
+        .value_of("jobs")
+        .map_or(Ok(DEFAULT_NUM_JOBS), |j| j.parse())
+        .map_err(Error::from)?;
+
+    let repo_id = args::get_repo_id(fb, &matches)?;
+    let (_, config) = args::get_config(fb, &matches)?;
+    let blobconfig = match config.storage_config.blobstore {
+        BlobConfig::Pack { blobconfig } => *blobconfig,
+        other => bail!("Repacking requires a pack blobstore, got {:?}", other),
+    };
+    let delete_superseded = matches.is_present("delete-superseded");
+    let mysql_options = args::parse_mysql_options(&matches);
+    let blobstore_options = args::parse_blobstore_options(&matches);
+    let readonly_storage = args::parse_readonly_storage(&matches);
+
+    let prefix = if matches.is_present("no-prefix") {
+        String::new()
+    } else {
+        repo_id.prefix()
+    };
+
+    let keys_file = matches.value_of("keys-file").unwrap();
+    let packs: Vec<Vec<String>> = fs::read_to_string(keys_file)
+        .with_context(|| format!("While reading {}", keys_file))?
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .map(|key| format!("{}{}", prefix, key))
+                .collect::<Vec<_>>()
+        })
+        .filter(|keys| !keys.is_empty())
+        .collect();
+
+    let repack = {
+        cloned!(logger);
+        async move {
+            let inner = make_blobstore(
+                fb,
+                blobconfig,
+                mysql_options,
+                readonly_storage,
+                &blobstore_options,
+                &logger,
+            )
+            .await?;
+            let blobstore = PackBlob::new(inner);
+
+            stream::iter(packs.into_iter().map(Ok::<_, Error>))
+                .try_for_each_concurrent(jobs, |keys| {
+                    cloned!(blobstore, ctx, logger, prefix);
+                    async move {
+                        let entries = future::try_join_all(keys.into_iter().map(|key| {
+                            cloned!(blobstore, ctx);
+                            async move {
+                                if let Some(pack_key) = blobstore
+                                    .pack_key(ctx.clone(), key.clone())
+                                    .compat()
+                                    .await?
+                                {
+                                    info!(
+                                        ctx.logger(),
+                                        "{} is already packed in {}", key, pack_key
+                                    );
+                                    return Ok(None);
+                                }
+                                let value = blobstore
+                                    .get(ctx, key.clone())
+                                    .compat()
+                                    .await?
+                                    .ok_or_else(|| format_err!("Key {} is missing", key))?;
+                                Ok::<_, Error>(Some((key, value.into_bytes())))
+                            }
+                        }))
+                        .await?;
+                        let entries: Vec<_> = entries.into_iter().flatten().collect();
+                        if entries.is_empty() {
+                            return Ok(());
+                        }
+
+                        let num_entries = entries.len();
+                        let packed = blobstore
+                            .put_packed(ctx.clone(), &prefix, entries)
+                            .compat()
+                            .await?;
+                        info!(
+                            logger,
+                            "Packed {} blobs into {}", num_entries, packed.pack_key
+                        );
+
+                        // A key on several lines can be packed by another job after it was
+                        // checked above, leaving that job's pack with nothing referring to it.
+                        for pack_key in packed.superseded {
+                            if delete_superseded {
+                                blobstore
+                                    .delete(ctx.clone(), pack_key.clone())
+                                    .compat()
+                                    .await?;
+                                info!(logger, "Deleted superseded pack {}", pack_key);
+                            } else {
+                                info!(logger, "Pack {} is superseded", pack_key);
+                            }
+                        }
+                        Ok(())
+                    }
+                })
+                .await
+        }
+    };
+
+    block_execute(
+        repack,
+        fb,
+        NAME,
+        &logger,
+        &matches,
+        cmdlib::monitoring::AliveService,
+    )
+}
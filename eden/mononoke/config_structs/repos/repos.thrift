@@ -125,6 +125,8 @@ struct RawDerivedDataConfig {
   2: optional set<string> derived_data_types,
   // Defaults to v1
   3: optional RawUnodeVersion raw_unode_version,
+  // Maximum size in bytes of a file to derive blame for. Defaults to 10 MiB
+  4: optional i64 blame_filesize_limit,
 }
 
 union RawUnodeVersion {
@@ -155,17 +157,61 @@ struct RawBlobstoreMultiplexed {
     3: optional i64 scuba_sample_rate,
     4: optional i32 multiplex_id,
     5: optional RawDbConfig queue_db,
+    // Number of components a put must succeed in, 1 if not set
+    6: optional i64 minimum_successful_writes,
+    // Write blobs found by a get to the components that are missing them
+    7: optional bool read_repair,
 }
 struct RawBlobstoreManifoldWithTtl {
     1: string manifold_bucket,
     2: string manifold_prefix,
     3: i64 ttl_secs,
 }
+struct RawBlobstoreS3 {
+    1: string endpoint,
+    2: string bucket,
+    3: optional string prefix,
+    // defaults to us-east-1
+    4: optional string region,
+    // file with "<access key id> <secret access key>"; if not set,
+    // AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY are used
+    5: optional string credentials_path,
+}
 struct RawBlobstoreLogging {
     1: optional string scuba_table,
     2: optional i64 scuba_sample_rate,
     3: RawBlobstoreConfig blobstore (rust.box),
 }
+struct RawBlobstoreCompressed {
+    1: RawBlobstoreConfig blobstore (rust.box),
+    // zstd compression level, defaults to 3
+    2: optional i32 level,
+    3: optional string dictionary_path,
+}
+
+struct RawBlobstorePack {
+    1: RawBlobstoreConfig blobstore (rust.box),
+}
+
+struct RawBlobstoreEncrypted {
+    1: RawBlobstoreConfig blobstore (rust.box),
+    // file with one "<key id> <hex encoded AES-256 key>" per line
+    2: string keyfile,
+    // key new values are encrypted with
+    3: i64 key_id,
+}
+
+struct RawBlobstoreTiered {
+    // fast store new and recently read blobs are kept in
+    1: RawBlobstoreConfig hot (rust.box),
+    // durable store holding every blob
+    2: RawBlobstoreConfig cold (rust.box),
+    // copy blobs read from the cold store to the hot one, defaults to false
+    3: optional bool promote_on_read,
+    // remove blobs from the hot store this long after they were written there
+    // when `mononoke_admin blobstore-demote` runs, they are never removed if unset
+    4: optional i64 demote_after_secs,
+}
 
 // Configuration for a single blobstore. These are intended to be defined in a
 // separate blobstore.toml config file, and then referenced by name from a
@@ -183,11 +229,18 @@ union RawBlobstoreConfig {
     7: RawBlobstoreMultiplexed multiplexed,
     8: RawBlobstoreManifoldWithTtl manifold_with_ttl,
     9: RawBlobstoreLogging logging,
+    10: RawBlobstoreCompressed compressed,
+    11: RawBlobstorePack pack,
+    12: RawBlobstoreEncrypted encrypted,
+    13: RawBlobstoreS3 s3,
+    14: RawBlobstoreTiered tiered,
 }
 
 struct RawBlobstoreIdConfig {
     1: i64 blobstore_id,
     2: RawBlobstoreConfig blobstore,
+    // Only write to this component, e.g. while it is being populated
+    3: optional bool write_only,
 }
 
 struct RawDbLocal {
@@ -11,21 +11,30 @@ use blobstore::{Blobstore, BlobstoreBytes, Loadable};
 use bytes::Bytes;
 use cloned::cloned;
 use context::CoreContext;
+use copy_detection::DetectedCopies;
 use derived_data::{BonsaiDerived, BonsaiDerivedMapping};
 use filestore::{self, FetchKey};
 use futures_ext::{spawn_future, BoxFuture, FutureExt, StreamExt};
 use futures_old::{future, stream, Future, IntoFuture, Stream};
 use manifest::find_intersection_of_diffs;
 use mononoke_types::{
-    blame::{store_blame, Blame, BlameId, BlameRejected},
+    blame::{store_blame, Blame, BlameCopies, BlameId, BlameRejected},
     BonsaiChangeset, ChangesetId, FileUnodeId, MPath,
 };
 use std::{collections::HashMap, iter::FromIterator, sync::Arc};
 use thiserror::Error;
-use unodes::{find_unode_renames, RootUnodeManifestId};
+use unodes::{find_unode_copy_sources, find_unode_renames, RootUnodeManifestId};
 
 pub const BLAME_FILESIZE_LIMIT: u64 = 10 * 1024 * 1024;
 
+/// Files larger than this get no blame in this repo
+pub fn blame_filesize_limit(repo: &BlobRepo) -> u64 {
+    repo.get_derived_data_config()
+        .blame_filesize_limit
+        .unwrap_or(BLAME_FILESIZE_LIMIT)
+}
+
+/// Blame of the files changed in a changeset, following the copies recorded in bonsai.
 #[derive(Debug, Clone, Copy)]
 pub struct BlameRoot(ChangesetId);
 
@@ -43,52 +52,140 @@ impl BonsaiDerived for BlameRoot {
         bonsai: BonsaiChangeset,
         _parents: Vec<Self>,
     ) -> BoxFuture<Self, Error> {
-        let csid = bonsai.get_changeset_id();
-        let root_manifest = RootUnodeManifestId::derive(ctx.clone(), repo.clone(), csid)
-            .from_err()
-            .map(|mf| mf.manifest_unode_id().clone());
-        let parents_manifest = bonsai
-            .parents()
-            .collect::<Vec<_>>() // iterator should be owned
-            .into_iter()
-            .map({
-                cloned!(ctx, repo);
-                move |csid| {
-                    RootUnodeManifestId::derive(ctx.clone(), repo.clone(), csid)
-                        .from_err()
-                        .map(|mf| mf.manifest_unode_id().clone())
-                }
-            });
-
-        (
-            root_manifest,
-            future::join_all(parents_manifest),
-            find_unode_renames(ctx.clone(), repo.clone(), &bonsai),
-        )
-            .into_future()
-            .and_then(move |(root_mf, parents_mf, renames)| {
-                let renames = Arc::new(renames);
-                let blobstore = repo.get_blobstore().boxed();
-                find_intersection_of_diffs(ctx.clone(), blobstore.clone(), root_mf, parents_mf)
-                    .filter_map(|(path, entry)| Some((path?, entry.into_leaf()?)))
-                    .map(move |(path, file)| {
-                        spawn_future(create_blame(
-                            ctx.clone(),
-                            blobstore.clone(),
-                            renames.clone(),
-                            csid,
-                            path,
-                            file,
-                        ))
-                    })
-                    .buffered(256)
-                    .for_each(|_| Ok(()))
-                    .map(move |_| BlameRoot(csid))
-            })
+        derive_blame(ctx, repo, bonsai, BlameCopies::Recorded)
+            .map(BlameRoot)
+            .boxify()
+    }
+}
+
+/// Blame of the files changed in a changeset, also following the copies detected in
+/// changesets that didn't record them.
+#[derive(Debug, Clone, Copy)]
+pub struct BlameDetectedCopiesRoot(ChangesetId);
+
+impl BonsaiDerived for BlameDetectedCopiesRoot {
+    const NAME: &'static str = "blame_detected_copies";
+    type Mapping = BlameDetectedCopiesRootMapping;
+
+    fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
+        BlameDetectedCopiesRootMapping::new(repo.blobstore().boxed())
+    }
+
+    fn derive_from_parents(
+        ctx: CoreContext,
+        repo: BlobRepo,
+        bonsai: BonsaiChangeset,
+        _parents: Vec<Self>,
+    ) -> BoxFuture<Self, Error> {
+        derive_blame(ctx, repo, bonsai, BlameCopies::Detected)
+            .map(BlameDetectedCopiesRoot)
             .boxify()
     }
 }
 
+/// The copies followed by the blame this repo serves: the detected ones if their blame
+/// is derived, otherwise only the recorded ones.
+pub fn blame_copies(repo: &BlobRepo) -> BlameCopies {
+    if repo
+        .get_derived_data_config()
+        .derived_data_types
+        .contains(BlameDetectedCopiesRoot::NAME)
+    {
+        BlameCopies::Detected
+    } else {
+        BlameCopies::Recorded
+    }
+}
+
+fn derive_blame(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    bonsai: BonsaiChangeset,
+    copies: BlameCopies,
+) -> impl Future<Item = ChangesetId, Error = Error> {
+    let csid = bonsai.get_changeset_id();
+    let filesize_limit = blame_filesize_limit(&repo);
+    let root_manifest = RootUnodeManifestId::derive(ctx.clone(), repo.clone(), csid)
+        .from_err()
+        .map(|mf| mf.manifest_unode_id().clone());
+    let parents_manifest = bonsai
+        .parents()
+        .collect::<Vec<_>>() // iterator should be owned
+        .into_iter()
+        .map({
+            cloned!(ctx, repo);
+            move |csid| {
+                RootUnodeManifestId::derive(ctx.clone(), repo.clone(), csid)
+                    .from_err()
+                    .map(|mf| mf.manifest_unode_id().clone())
+            }
+        });
+
+    (
+        root_manifest,
+        future::join_all(parents_manifest),
+        find_blame_renames(ctx.clone(), repo.clone(), &bonsai, copies),
+    )
+        .into_future()
+        .and_then(move |(root_mf, parents_mf, renames)| {
+            let renames = Arc::new(renames);
+            let blobstore = repo.get_blobstore().boxed();
+            find_intersection_of_diffs(ctx.clone(), blobstore.clone(), root_mf, parents_mf)
+                .filter_map(|(path, entry)| Some((path?, entry.into_leaf()?)))
+                .map(move |(path, file)| {
+                    spawn_future(create_blame(
+                        ctx.clone(),
+                        blobstore.clone(),
+                        renames.clone(),
+                        csid,
+                        path,
+                        BlameId::new(file, copies),
+                        filesize_limit,
+                    ))
+                })
+                .buffered(256)
+                .for_each(|_| Ok(()))
+                .map(move |_| csid)
+        })
+}
+
+/// Finds unodes of the files copied in the changeset, including the copies that
+/// were detected if blame follows them.
+fn find_blame_renames(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    bonsai: &BonsaiChangeset,
+    copies: BlameCopies,
+) -> BoxFuture<HashMap<MPath, FileUnodeId>, Error> {
+    let renames = find_unode_renames(ctx.clone(), repo.clone(), bonsai);
+    if copies == BlameCopies::Recorded {
+        return renames.boxify();
+    }
+
+    let detected = DetectedCopies::derive(ctx.clone(), repo.clone(), bonsai.get_changeset_id())
+        .from_err()
+        .and_then(move |detected| {
+            let mut references: HashMap<ChangesetId, HashMap<MPath, Vec<MPath>>> = HashMap::new();
+            for (to_path, copy) in detected.copies() {
+                references
+                    .entry(*copy.from_changeset_id())
+                    .or_default()
+                    .entry(copy.from_path().clone())
+                    .or_default()
+                    .push(to_path.clone());
+            }
+            find_unode_copy_sources(ctx, repo, references)
+        });
+
+    (renames, detected)
+        .into_future()
+        .map(|(mut renames, detected)| {
+            renames.extend(detected);
+            renames
+        })
+        .boxify()
+}
+
 #[derive(Clone)]
 pub struct BlameRootMapping {
     blobstore: Arc<dyn Blobstore>,
@@ -132,14 +229,59 @@ impl BonsaiDerivedMapping for BlameRootMapping {
     }
 }
 
+#[derive(Clone)]
+pub struct BlameDetectedCopiesRootMapping {
+    blobstore: Arc<dyn Blobstore>,
+}
+
+impl BlameDetectedCopiesRootMapping {
+    pub fn new(blobstore: Arc<dyn Blobstore>) -> Self {
+        Self { blobstore }
+    }
+
+    fn format_key(&self, csid: &ChangesetId) -> String {
+        format!("derived_rootblame_detected_copies.v1.{}", csid)
+    }
+}
+
+impl BonsaiDerivedMapping for BlameDetectedCopiesRootMapping {
+    type Value = BlameDetectedCopiesRoot;
+
+    fn get(
+        &self,
+        ctx: CoreContext,
+        csids: Vec<ChangesetId>,
+    ) -> BoxFuture<HashMap<ChangesetId, Self::Value>, Error> {
+        let futs = csids.into_iter().map(|csid| {
+            self.blobstore
+                .get(ctx.clone(), self.format_key(&csid))
+                .map(move |val| val.map(|_| (csid.clone(), BlameDetectedCopiesRoot(csid))))
+        });
+        stream::FuturesUnordered::from_iter(futs)
+            .filter_map(|v| v)
+            .collect_to()
+            .boxify()
+    }
+
+    fn put(&self, ctx: CoreContext, csid: ChangesetId, _id: Self::Value) -> BoxFuture<(), Error> {
+        self.blobstore.put(
+            ctx,
+            self.format_key(&csid),
+            BlobstoreBytes::from_bytes(Bytes::new()),
+        )
+    }
+}
+
 fn create_blame(
     ctx: CoreContext,
     blobstore: Arc<dyn Blobstore>,
     renames: Arc<HashMap<MPath, FileUnodeId>>,
     csid: ChangesetId,
     path: MPath,
-    file_unode_id: FileUnodeId,
+    blame_id: BlameId,
+    filesize_limit: u64,
 ) -> impl Future<Item = BlameId, Error = Error> {
+    let file_unode_id = FileUnodeId::from(blame_id);
     file_unode_id
         .load(ctx.clone(), &blobstore)
         .from_err()
@@ -153,8 +295,13 @@ fn create_blame(
                     cloned!(ctx, blobstore);
                     move |file_unode_id| {
                         (
-                            fetch_file_full_content(ctx.clone(), blobstore.clone(), file_unode_id),
-                            BlameId::from(file_unode_id)
+                            fetch_file_full_content(
+                                ctx.clone(),
+                                blobstore.clone(),
+                                file_unode_id,
+                                filesize_limit,
+                            ),
+                            BlameId::new(file_unode_id, blame_id.copies())
                                 .load(ctx.clone(), &blobstore)
                                 .from_err(),
                         )
@@ -164,7 +311,12 @@ fn create_blame(
                 .collect();
 
             (
-                fetch_file_full_content(ctx.clone(), blobstore.clone(), file_unode_id),
+                fetch_file_full_content(
+                    ctx.clone(),
+                    blobstore.clone(),
+                    file_unode_id,
+                    filesize_limit,
+                ),
                 future::join_all(parents_content_and_blame),
             )
                 .into_future()
@@ -184,7 +336,7 @@ fn create_blame(
                     Ok(blame_maybe_rejected)
                 })
                 .and_then(move |blame_maybe_rejected| {
-                    store_blame(ctx, &blobstore, file_unode_id, blame_maybe_rejected)
+                    store_blame(ctx, &blobstore, blame_id, blame_maybe_rejected)
                 })
         })
 }
@@ -193,6 +345,7 @@ pub fn fetch_file_full_content(
     ctx: CoreContext,
     blobstore: Arc<dyn Blobstore>,
     file_unode_id: FileUnodeId,
+    filesize_limit: u64,
 ) -> impl Future<Item = Result<Bytes, BlameRejected>, Error = Error> {
     #[derive(Error, Debug)]
     enum FetchError {
@@ -224,7 +377,7 @@ pub fn fetch_file_full_content(
                         future::err(error).left_future()
                     }
                     Some((stream, size)) => {
-                        if size > BLAME_FILESIZE_LIMIT {
+                        if size > filesize_limit {
                             return future::err(FetchError::Rejected(BlameRejected::TooBig))
                                 .left_future();
                         }
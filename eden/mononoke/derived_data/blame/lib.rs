@@ -9,7 +9,10 @@
 #![type_length_limit = "1441792"]
 
 mod derived;
-pub use derived::{fetch_file_full_content, BlameRoot, BlameRootMapping};
+pub use derived::{
+    blame_copies, blame_filesize_limit, fetch_file_full_content, BlameDetectedCopiesRoot,
+    BlameDetectedCopiesRootMapping, BlameRoot, BlameRootMapping, BLAME_FILESIZE_LIMIT,
+};
 
 #[cfg(test)]
 mod tests;
@@ -25,7 +28,7 @@ use futures_ext::FutureExt;
 use futures_old::{future, Future};
 use manifest::ManifestOps;
 use mononoke_types::{
-    blame::{Blame, BlameId, BlameMaybeRejected, BlameRejected},
+    blame::{Blame, BlameCopies, BlameId, BlameMaybeRejected, BlameRejected},
     ChangesetId, MPath,
 };
 use thiserror::Error;
@@ -47,21 +50,23 @@ pub enum BlameError {
 
 /// Fetch content and blame for a file with specified file path
 ///
-/// Blame will be derived if it is not available yet.
+/// Blame will be derived if it is not available yet. It follows the detected copies
+/// if the repo derives blame for them (see `blame_copies`).
 pub fn fetch_blame(
     ctx: CoreContext,
     repo: BlobRepo,
     csid: ChangesetId,
     path: MPath,
 ) -> impl Future<Item = (Bytes, Blame), Error = BlameError> {
-    fetch_blame_if_derived(ctx.clone(), repo.clone(), csid, path)
+    let copies = blame_copies(&repo);
+    fetch_blame_if_derived(ctx.clone(), repo.clone(), csid, path, copies)
         .and_then({
             cloned!(ctx, repo);
             move |result| match result {
                 Ok((blame_id, blame)) => future::ok((blame_id, blame)).left_future(),
-                Err(blame_id) => BlameRoot::derive(ctx.clone(), repo.clone(), csid)
+                Err(blame_id) => derive_blame_root(ctx.clone(), repo.clone(), csid, copies)
                     .from_err()
-                    .and_then(move |_| {
+                    .and_then(move |()| {
                         blame_id
                             .load(ctx.clone(), repo.blobstore())
                             .from_err()
@@ -73,18 +78,38 @@ pub fn fetch_blame(
             }
         })
         .and_then(move |(blame_id, blame)| {
-            derived::fetch_file_full_content(ctx, repo.get_blobstore().boxed(), blame_id.into())
-                .and_then(|result| result.map_err(Error::from))
-                .map(|content| (content, blame))
-                .from_err()
+            derived::fetch_file_full_content(
+                ctx,
+                repo.get_blobstore().boxed(),
+                blame_id.into(),
+                blame_filesize_limit(&repo),
+            )
+            .and_then(|result| result.map_err(Error::from))
+            .map(|content| (content, blame))
+            .from_err()
         })
 }
 
+fn derive_blame_root(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    csid: ChangesetId,
+    copies: BlameCopies,
+) -> impl Future<Item = (), Error = DeriveError> {
+    match copies {
+        BlameCopies::Recorded => BlameRoot::derive(ctx, repo, csid).map(|_| ()).left_future(),
+        BlameCopies::Detected => BlameDetectedCopiesRoot::derive(ctx, repo, csid)
+            .map(|_| ())
+            .right_future(),
+    }
+}
+
 fn fetch_blame_if_derived(
     ctx: CoreContext,
     repo: BlobRepo,
     csid: ChangesetId,
     path: MPath,
+    copies: BlameCopies,
 ) -> impl Future<Item = Result<(BlameId, Blame), BlameId>, Error = BlameError> {
     let blobstore = repo.get_blobstore();
     RootUnodeManifestId::derive(ctx.clone(), repo, csid)
@@ -105,7 +130,7 @@ fn fetch_blame_if_derived(
                 let entry = entry_opt.ok_or_else(|| BlameError::NoSuchPath(path.clone()))?;
                 match entry.into_leaf() {
                     None => Err(BlameError::IsDirectory(path)),
-                    Some(file_unode_id) => Ok(BlameId::from(file_unode_id)),
+                    Some(file_unode_id) => Ok(BlameId::new(file_unode_id, copies)),
                 }
             }
         })
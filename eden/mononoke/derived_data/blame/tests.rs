@@ -5,14 +5,18 @@
  * GNU General Public License version 2.
  */
 
-use crate::fetch_blame;
+use crate::{fetch_blame, BlameDetectedCopiesRoot, BlameError};
 use anyhow::Error;
+use blobrepo::DangerousOverride;
 use bytes::Bytes;
 use context::CoreContext;
+use copy_detection::DetectedCopies;
+use derived_data::BonsaiDerived;
 use fbinit::FacebookInit;
 use futures::compat::Future01CompatExt;
 use maplit::{btreemap, hashmap};
-use mononoke_types::{Blame, ChangesetId, MPath};
+use metaconfig_types::DerivedDataConfig;
+use mononoke_types::{blame::BlameRejected, Blame, ChangesetId, MPath};
 use std::collections::HashMap;
 use tests_utils::{create_commit, store_files, store_rename};
 
@@ -240,6 +244,123 @@ fn test_blame(fb: FacebookInit) -> Result<(), Error> {
     })
 }
 
+#[fbinit::test]
+fn test_blame_filesize_limit(fb: FacebookInit) -> Result<(), Error> {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = blobrepo_factory::new_memblob_empty(None)?;
+        let repo = repo.dangerous_override(|mut derived_data_config: DerivedDataConfig| {
+            derived_data_config.blame_filesize_limit = Some(10);
+            derived_data_config
+        });
+
+        let c0 = create_commit(
+            ctx.clone(),
+            repo.clone(),
+            vec![],
+            store_files(
+                ctx.clone(),
+                btreemap! {
+                    "small" => Some("one\n"),
+                    "large" => Some("one\ntwo\nthree\n"),
+                },
+                repo.clone(),
+            )
+            .await,
+        )
+        .await;
+
+        let (content, _blame) = fetch_blame(ctx.clone(), repo.clone(), c0, MPath::new("small")?)
+            .compat()
+            .await?;
+        assert_eq!(content, Bytes::from("one\n"));
+
+        match fetch_blame(ctx.clone(), repo.clone(), c0, MPath::new("large")?)
+            .compat()
+            .await
+        {
+            Err(BlameError::Rejected(BlameRejected::TooBig)) => {}
+            result => panic!("unexpected blame result: {:?}", result.map(|_| ())),
+        }
+
+        Ok(())
+    })
+}
+
+#[fbinit::test]
+fn test_blame_detected_copies(fb: FacebookInit) -> Result<(), Error> {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = blobrepo_factory::new_memblob_empty(None)?;
+        let repo = repo.dangerous_override(|mut derived_data_config: DerivedDataConfig| {
+            derived_data_config
+                .derived_data_types
+                .insert(DetectedCopies::NAME.to_string());
+            derived_data_config
+        });
+        let detected_repo =
+            repo.dangerous_override(|mut derived_data_config: DerivedDataConfig| {
+                derived_data_config
+                    .derived_data_types
+                    .insert(BlameDetectedCopiesRoot::NAME.to_string());
+                derived_data_config
+            });
+
+        let c0 = create_commit(
+            ctx.clone(),
+            repo.clone(),
+            vec![],
+            store_files(
+                ctx.clone(),
+                btreemap! {"a" => Some("one\ntwo\n")},
+                repo.clone(),
+            )
+            .await,
+        )
+        .await;
+        // "a" is renamed to "b" without copy information
+        let c1 = create_commit(
+            ctx.clone(),
+            repo.clone(),
+            vec![c0],
+            store_files(
+                ctx.clone(),
+                btreemap! {
+                    "a" => None,
+                    "b" => Some("one\ntwo\nthree\n"),
+                },
+                repo.clone(),
+            )
+            .await,
+        )
+        .await;
+
+        let names = hashmap! {
+            c0 => "c0",
+            c1 => "c1",
+        };
+        // Blame follows the detected rename only if the repo derives blame for detected copies,
+        // so that the blame of a changeset doesn't depend on whether copies are detected.
+        let (content, blame) = fetch_blame(ctx.clone(), repo.clone(), c1, MPath::new("b")?)
+            .compat()
+            .await?;
+        assert_eq!(
+            annotate(content, blame, &names)?,
+            "c1: one\nc1: two\nc1: three\n"
+        );
+        let (content, blame) =
+            fetch_blame(ctx.clone(), detected_repo.clone(), c1, MPath::new("b")?)
+                .compat()
+                .await?;
+        assert_eq!(
+            annotate(content, blame, &names)?,
+            "c0: one\nc0: two\nc1: three\n"
+        );
+
+        Ok(())
+    })
+}
+
 fn annotate(
     content: Bytes,
     blame: Blame,
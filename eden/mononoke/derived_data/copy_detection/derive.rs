@@ -0,0 +1,204 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use std::{collections::HashMap, iter::FromIterator, sync::Arc};
+
+use blobrepo::BlobRepo;
+use blobstore::Blobstore;
+use context::CoreContext;
+use derived_data::{BonsaiDerived, BonsaiDerivedMapping};
+use fbthrift::compact_protocol;
+use futures::{FutureExt as NewFutureExt, TryFutureExt};
+use futures_ext::{BoxFuture, FutureExt};
+use futures_old::{stream::FuturesUnordered, Future, Stream};
+use mononoke_types::{BlobstoreBytes, BonsaiChangeset, ChangesetId};
+
+use crate::detect::detect_copies;
+use crate::DetectedCopies;
+
+/// Whether detected copies should be used in addition to the copies recorded
+/// in Bonsai changesets.
+pub fn detected_copies_enabled(repo: &BlobRepo) -> bool {
+    repo.get_derived_data_config()
+        .derived_data_types
+        .contains(DetectedCopies::NAME)
+}
+
+impl BonsaiDerived for DetectedCopies {
+    const NAME: &'static str = "detected_copies";
+    type Mapping = DetectedCopiesMapping;
+
+    fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
+        DetectedCopiesMapping::new(repo.blobstore().boxed())
+    }
+
+    fn derive_from_parents(
+        ctx: CoreContext,
+        repo: BlobRepo,
+        bonsai: BonsaiChangeset,
+        _parents: Vec<Self>,
+    ) -> BoxFuture<Self, Error> {
+        async move {
+            let copies = detect_copies(&ctx, &repo, &bonsai).await?;
+            Ok(DetectedCopies::new(bonsai.get_changeset_id(), copies))
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+}
+
+#[derive(Clone)]
+pub struct DetectedCopiesMapping {
+    blobstore: Arc<dyn Blobstore>,
+}
+
+impl DetectedCopiesMapping {
+    pub fn new(blobstore: Arc<dyn Blobstore>) -> Self {
+        Self { blobstore }
+    }
+
+    fn format_key(&self, csid: &ChangesetId) -> String {
+        format!("detected_copies.blake2.{}", csid)
+    }
+}
+
+impl BonsaiDerivedMapping for DetectedCopiesMapping {
+    type Value = DetectedCopies;
+
+    fn get(
+        &self,
+        ctx: CoreContext,
+        csids: Vec<ChangesetId>,
+    ) -> BoxFuture<HashMap<ChangesetId, Self::Value>, Error> {
+        let futs = csids.into_iter().map(|csid| {
+            self.blobstore
+                .get(ctx.clone(), self.format_key(&csid))
+                .map(move |value| {
+                    value.map(|bytes| {
+                        let copies = DetectedCopies::from_bytes(bytes.as_raw_bytes())?;
+                        Ok((csid, copies))
+                    })
+                })
+        });
+        FuturesUnordered::from_iter(futs)
+            .filter_map(|maybe_copies| maybe_copies)
+            .collect()
+            .and_then(move |copies| copies.into_iter().collect::<Result<HashMap<_, _>, Error>>())
+            .boxify()
+    }
+
+    fn put(
+        &self,
+        ctx: CoreContext,
+        csid: ChangesetId,
+        copies: Self::Value,
+    ) -> BoxFuture<(), Error> {
+        let data = {
+            let data = compact_protocol::serialize(&copies.into_thrift());
+            BlobstoreBytes::from_bytes(data)
+        };
+        self.blobstore.put(ctx, self.format_key(&csid), data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use blobrepo::DangerousOverride;
+    use fbinit::FacebookInit;
+    use futures::compat::Future01CompatExt;
+    use maplit::btreeset;
+    use metaconfig_types::DerivedDataConfig;
+    use mononoke_types::MPath;
+    use tests_utils::CreateCommitContext;
+    use tokio_compat::runtime::Runtime;
+
+    fn copies(copies: &DetectedCopies) -> Vec<(String, String, ChangesetId, u8)> {
+        copies
+            .copies()
+            .map(|(to_path, copy)| {
+                (
+                    to_path.to_string(),
+                    copy.from_path().to_string(),
+                    *copy.from_changeset_id(),
+                    copy.similarity(),
+                )
+            })
+            .collect()
+    }
+
+    #[fbinit::test]
+    fn derive_detected_copies_test(fb: FacebookInit) -> Result<(), Error> {
+        let mut runtime = Runtime::new()?;
+        runtime.block_on_std(async move {
+            let ctx = CoreContext::test_mock(fb);
+            let repo = blobrepo_factory::new_memblob_empty(None)?.dangerous_override(
+                |mut derived_data_config: DerivedDataConfig| {
+                    derived_data_config.derived_data_types = btreeset! {
+                        DetectedCopies::NAME.to_string(),
+                        unodes::RootUnodeManifestId::NAME.to_string(),
+                    };
+                    derived_data_config
+                },
+            );
+            assert!(detected_copies_enabled(&repo));
+
+            let root = CreateCommitContext::new_root(&ctx, &repo)
+                .add_file("renamed", "renamed\n")
+                .add_file("copied", "copied\n")
+                .add_file("similar", "one\ntwo\nthree\nfour\n")
+                .add_file("different", "a\nb\nc\nfour\n")
+                .add_file("recorded", "recorded\n")
+                .commit()
+                .await?;
+
+            let cs_id = CreateCommitContext::new(&ctx, &repo, vec![root])
+                .delete_file("renamed")
+                .add_file("dir/renamed", "renamed\n")
+                .add_file("copied", "modified\n")
+                .add_file("dir/copied", "copied\n")
+                .delete_file("similar")
+                .add_file("dir/similar", "one\ntwo\nthree\nfive\n")
+                .delete_file("different")
+                .add_file("dir/different", "five\nsix\nseven\nfour\n")
+                .delete_file("recorded")
+                .add_file_with_copy_info("dir/recorded", "recorded\n", (root, "recorded"))?
+                .add_file("new", "new\n")
+                .commit()
+                .await?;
+
+            let root_copies = DetectedCopies::derive(ctx.clone(), repo.clone(), root)
+                .compat()
+                .await?;
+            assert!(root_copies.is_empty());
+
+            let detected = DetectedCopies::derive(ctx.clone(), repo.clone(), cs_id)
+                .compat()
+                .await?;
+            assert_eq!(detected.changeset_id(), &cs_id);
+            assert_eq!(
+                copies(&detected),
+                vec![
+                    ("dir/copied".to_string(), "copied".to_string(), root, 100),
+                    ("dir/renamed".to_string(), "renamed".to_string(), root, 100),
+                    ("dir/similar".to_string(), "similar".to_string(), root, 75),
+                ]
+            );
+            assert!(detected.get(&MPath::new("new")?).is_none());
+
+            // Make sure that the detected copies were saved in the blobstore
+            let mapping = DetectedCopies::mapping(&ctx, &repo);
+            let stored = mapping.get(ctx.clone(), vec![cs_id]).compat().await?;
+            assert_eq!(stored.get(&cs_id), Some(&detected));
+
+            Ok(())
+        })
+    }
+}
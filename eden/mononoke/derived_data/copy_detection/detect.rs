@@ -0,0 +1,282 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use blobstore::{Blobstore, Loadable};
+use bytes::Bytes;
+use context::CoreContext;
+use derived_data::BonsaiDerived;
+use filestore::{self, FetchKey};
+use futures::{compat::Future01CompatExt, future, TryStreamExt};
+use manifest::ManifestOps;
+use mononoke_types::{BonsaiChangeset, ChangesetId, ContentId, FileChange, MPath};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use unodes::RootUnodeManifestId;
+
+use crate::DetectedCopy;
+
+/// Files larger than this are only considered copies if their content is identical.
+const SIMILARITY_MAX_FILESIZE: u64 = 256 * 1024;
+
+/// Every added file is compared with every removed or modified one, so similarity
+/// is only computed if there are at most this many files of each kind.
+const SIMILARITY_MAX_CANDIDATES: usize = 100;
+
+/// Minimal percentage of common lines for an added file to be considered a copy.
+const SIMILARITY_THRESHOLD: u8 = 50;
+
+/// A file removed or modified by the changeset, as it was in a parent.
+struct CopySource {
+    path: MPath,
+    csid: ChangesetId,
+    content_id: ContentId,
+    removed: bool,
+}
+
+/// Finds the files added by the changeset without copy information that are
+/// likely copies or renames of the files it removed or modified.
+pub(crate) async fn detect_copies(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bonsai: &BonsaiChangeset,
+) -> Result<BTreeMap<MPath, DetectedCopy>, Error> {
+    let blobstore = repo.get_blobstore();
+    let file_changes: BTreeMap<&MPath, Option<&FileChange>> = bonsai.file_changes().collect();
+    let paths: Vec<MPath> = file_changes.keys().map(|path| (*path).clone()).collect();
+
+    // Find the changed paths that were files in one of the parents. These are
+    // the possible sources of the copies.
+    let mut sources = Vec::new();
+    let mut seen = HashSet::new();
+    for parent in bonsai.parents() {
+        let root_unode_id = RootUnodeManifestId::derive(ctx.clone(), repo.clone(), parent)
+            .compat()
+            .await?;
+        let entries = root_unode_id
+            .manifest_unode_id()
+            .find_entries(ctx.clone(), blobstore.clone(), paths.clone())
+            .compat()
+            .try_collect::<Vec<_>>()
+            .await?;
+        let files = entries
+            .into_iter()
+            .filter_map(|(path, entry)| match (path, entry.into_leaf()) {
+                (Some(path), Some(file_unode_id)) if !seen.contains(&path) => {
+                    Some((path, file_unode_id))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let file_unodes = future::try_join_all(
+            files
+                .iter()
+                .map(|(_, file_unode_id)| file_unode_id.load(ctx.clone(), &blobstore).compat()),
+        )
+        .await?;
+        for ((path, _), file_unode) in files.into_iter().zip(file_unodes) {
+            seen.insert(path.clone());
+            let removed = file_changes.get(&path).map_or(false, Option::is_none);
+            sources.push(CopySource {
+                path,
+                csid: parent,
+                content_id: *file_unode.content_id(),
+                removed,
+            });
+        }
+    }
+    if sources.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    // Renames are preferred over copies, otherwise the first path wins.
+    sources.sort_by(|a, b| (!a.removed, &a.path).cmp(&(!b.removed, &b.path)));
+
+    // Files added without copy information are the possible destinations.
+    let added = file_changes
+        .iter()
+        .filter_map(|(path, file_change)| {
+            let file_change = (*file_change)?;
+            if file_change.copy_from().is_none() && !seen.contains(*path) {
+                Some((*path, file_change))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut copies = BTreeMap::new();
+
+    // Look for exact matches first.
+    let mut sources_by_content = HashMap::new();
+    for source in sources.iter() {
+        sources_by_content
+            .entry(source.content_id)
+            .or_insert(source);
+    }
+    let mut unmatched = Vec::new();
+    for (path, file_change) in added {
+        match sources_by_content.get(&file_change.content_id()) {
+            Some(source) => {
+                copies.insert(
+                    path.clone(),
+                    DetectedCopy::new(source.path.clone(), source.csid, 100),
+                );
+            }
+            None => unmatched.push((path, file_change)),
+        }
+    }
+
+    // Then compare the contents of small text files.
+    let unmatched = unmatched
+        .into_iter()
+        .filter(|(_, file_change)| {
+            file_change.size() > 0 && file_change.size() <= SIMILARITY_MAX_FILESIZE
+        })
+        .collect::<Vec<_>>();
+    if unmatched.is_empty()
+        || unmatched.len() > SIMILARITY_MAX_CANDIDATES
+        || sources.len() > SIMILARITY_MAX_CANDIDATES
+    {
+        return Ok(copies);
+    }
+
+    let source_sizes = future::try_join_all(sources.iter().map(|source| {
+        filestore::get_metadata(
+            &blobstore,
+            ctx.clone(),
+            &FetchKey::Canonical(source.content_id),
+        )
+        .compat()
+    }))
+    .await?;
+    let sources = sources
+        .iter()
+        .zip(source_sizes)
+        .filter(|(_, metadata)| {
+            metadata.as_ref().map_or(false, |metadata| {
+                metadata.total_size > 0 && metadata.total_size <= SIMILARITY_MAX_FILESIZE
+            })
+        })
+        .map(|(source, _)| source)
+        .collect::<Vec<_>>();
+    if sources.is_empty() {
+        return Ok(copies);
+    }
+
+    let (added_contents, source_contents) = future::try_join(
+        future::try_join_all(
+            unmatched
+                .iter()
+                .map(|(_, file_change)| fetch_text(ctx, &blobstore, file_change.content_id())),
+        ),
+        future::try_join_all(
+            sources
+                .iter()
+                .map(|source| fetch_text(ctx, &blobstore, source.content_id)),
+        ),
+    )
+    .await?;
+    let source_lines = source_contents
+        .iter()
+        .map(|content| content.as_ref().map(|content| LineCounts::new(content)))
+        .collect::<Vec<_>>();
+
+    for ((path, _), content) in unmatched.into_iter().zip(added_contents) {
+        let lines = match content.as_ref() {
+            Some(content) => LineCounts::new(content),
+            None => continue,
+        };
+        let mut best: Option<(u8, &CopySource)> = None;
+        for (source, source_lines) in sources.iter().zip(source_lines.iter()) {
+            if let Some(source_lines) = source_lines {
+                let similarity = lines.similarity(source_lines);
+                if similarity >= SIMILARITY_THRESHOLD
+                    && best.map_or(true, |(best_similarity, _)| similarity > best_similarity)
+                {
+                    best = Some((similarity, *source));
+                }
+            }
+        }
+        if let Some((similarity, source)) = best {
+            copies.insert(
+                path.clone(),
+                DetectedCopy::new(source.path.clone(), source.csid, similarity),
+            );
+        }
+    }
+
+    Ok(copies)
+}
+
+/// Fetches file content, unless it's binary.
+async fn fetch_text(
+    ctx: &CoreContext,
+    blobstore: &(impl Blobstore + Clone),
+    content_id: ContentId,
+) -> Result<Option<Bytes>, Error> {
+    let content =
+        filestore::fetch_concat_opt(blobstore, ctx.clone(), &FetchKey::Canonical(content_id))
+            .compat()
+            .await?
+            .ok_or_else(|| format_err!("missing content {}", content_id))?;
+    if content.contains(&0u8) {
+        Ok(None)
+    } else {
+        Ok(Some(content))
+    }
+}
+
+/// Number of occurrences of each line in a file.
+struct LineCounts<'a> {
+    counts: HashMap<&'a [u8], usize>,
+    total: usize,
+}
+
+impl<'a> LineCounts<'a> {
+    fn new(content: &'a [u8]) -> Self {
+        let mut counts = HashMap::new();
+        let mut total = 0;
+        let content = match content.last() {
+            Some(b'\n') => &content[..content.len() - 1],
+            _ => content,
+        };
+        for line in content.split(|c| *c == b'\n') {
+            *counts.entry(line).or_insert(0) += 1;
+            total += 1;
+        }
+        Self { counts, total }
+    }
+
+    /// Percentage of lines the files have in common.
+    fn similarity(&self, other: &LineCounts) -> u8 {
+        let common: usize = self
+            .counts
+            .iter()
+            .map(|(line, count)| std::cmp::min(*count, *other.counts.get(line).unwrap_or(&0)))
+            .sum();
+        (common * 200 / (self.total + other.total)) as u8
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_line_similarity() {
+        let a = LineCounts::new(b"a\nb\nc\nd\n");
+        assert_eq!(a.similarity(&LineCounts::new(b"a\nb\nc\nd\n")), 100);
+        assert_eq!(a.similarity(&LineCounts::new(b"a\nb\nc\nd")), 100);
+        assert_eq!(a.similarity(&LineCounts::new(b"a\nb\nx\ny\n")), 50);
+        assert_eq!(a.similarity(&LineCounts::new(b"d\nc\nb\na\n")), 100);
+        assert_eq!(a.similarity(&LineCounts::new(b"x\ny\n")), 0);
+        assert_eq!(
+            LineCounts::new(b"a\na\na\n").similarity(&LineCounts::new(b"a\n")),
+            50
+        );
+    }
+}
@@ -0,0 +1,149 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Context, Result};
+use copy_detection_thrift as thrift;
+use fbthrift::compact_protocol;
+use mononoke_types::{errors::ErrorKind, ChangesetId, MPath};
+use std::collections::BTreeMap;
+
+/// Detected Copies is a derived data structure that holds the copies and
+/// renames of a changeset that were not recorded in its Bonsai.
+///
+/// Commits imported from git or created by tools that don't track copies
+/// have no `copy_from` information in their file changes. Detected Copies
+/// recovers it heuristically: a file added by the changeset is considered
+/// to be copied from a file the changeset removed or modified if the files
+/// have the same content, or, for small text files, if they share enough
+/// lines.
+///
+/// Whether a detected copy is a copy or a rename depends on whether the
+/// source file still exists in the changeset, which consumers can check the
+/// same way they do for recorded copies.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DetectedCopies {
+    /// changeset id of the source Bonsai changeset
+    changeset_id: ChangesetId,
+    /// detected copies keyed by the path they were copied to
+    copies: BTreeMap<MPath, DetectedCopy>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DetectedCopy {
+    from_path: MPath,
+    from_changeset_id: ChangesetId,
+    similarity: u8,
+}
+
+impl DetectedCopy {
+    pub fn new(from_path: MPath, from_changeset_id: ChangesetId, similarity: u8) -> Self {
+        Self {
+            from_path,
+            from_changeset_id,
+            similarity,
+        }
+    }
+
+    /// Get the path the file was copied from.
+    pub fn from_path(&self) -> &MPath {
+        &self.from_path
+    }
+
+    /// Get the parent changeset the file was copied from.
+    pub fn from_changeset_id(&self) -> &ChangesetId {
+        &self.from_changeset_id
+    }
+
+    /// Get the percentage of lines the files have in common. This is 100 for
+    /// files with identical content.
+    pub fn similarity(&self) -> u8 {
+        self.similarity
+    }
+
+    /// Get the copy in the form used by Bonsai file changes.
+    pub fn copy_from(&self) -> (MPath, ChangesetId) {
+        (self.from_path.clone(), self.from_changeset_id)
+    }
+}
+
+impl DetectedCopies {
+    pub fn new(changeset_id: ChangesetId, copies: BTreeMap<MPath, DetectedCopy>) -> Self {
+        Self {
+            changeset_id,
+            copies,
+        }
+    }
+
+    /// Get id of the source Bonsai changeset.
+    pub fn changeset_id(&self) -> &ChangesetId {
+        &self.changeset_id
+    }
+
+    /// Get the copy detected for a path, if any.
+    pub fn get(&self, to_path: &MPath) -> Option<&DetectedCopy> {
+        self.copies.get(to_path)
+    }
+
+    /// Get all the detected copies, ordered by the path they were copied to.
+    pub fn copies(&self) -> impl Iterator<Item = (&MPath, &DetectedCopy)> {
+        self.copies.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.copies.is_empty()
+    }
+
+    pub(crate) fn from_thrift(tc: thrift::DetectedCopies) -> Result<Self> {
+        let catch_block = || -> Result<_> {
+            Ok(DetectedCopies {
+                changeset_id: ChangesetId::from_thrift(tc.changeset_id)?,
+                copies: tc
+                    .copies
+                    .into_iter()
+                    .map(|copy| {
+                        Ok((
+                            MPath::from_thrift(copy.to_path)?,
+                            DetectedCopy {
+                                from_path: MPath::from_thrift(copy.from_path)?,
+                                from_changeset_id: ChangesetId::from_thrift(
+                                    copy.from_changeset_id,
+                                )?,
+                                similarity: copy.similarity as u8,
+                            },
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+            })
+        };
+
+        Ok(catch_block().with_context(|| {
+            ErrorKind::InvalidThrift("DetectedCopies".into(), "Invalid detected copies".into())
+        })?)
+    }
+
+    pub(crate) fn into_thrift(self) -> thrift::DetectedCopies {
+        thrift::DetectedCopies {
+            changeset_id: self.changeset_id.into_thrift(),
+            copies: self
+                .copies
+                .into_iter()
+                .map(|(to_path, copy)| thrift::DetectedCopy {
+                    to_path: to_path.into_thrift(),
+                    from_path: copy.from_path.into_thrift(),
+                    from_changeset_id: copy.from_changeset_id.into_thrift(),
+                    similarity: copy.similarity as i8,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let thrift_tc = compact_protocol::deserialize(bytes)
+            .with_context(|| ErrorKind::BlobDeserializeError("DetectedCopies".into()))?;
+        Self::from_thrift(thrift_tc)
+    }
+}
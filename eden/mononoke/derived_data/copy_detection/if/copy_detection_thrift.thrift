@@ -0,0 +1,27 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+include "eden/mononoke/mononoke_types/if/mononoke_types_thrift.thrift"
+
+// A copy or a rename that wasn't recorded in a Bonsai changeset, but was
+// detected by comparing the files the changeset added with the files it
+// removed or modified.
+struct DetectedCopy {
+  1: mononoke_types_thrift.MPath to_path,
+  2: mononoke_types_thrift.MPath from_path,
+  // Parent changeset the file was copied from
+  3: mononoke_types_thrift.ChangesetId from_changeset_id,
+  // Percentage of lines the files have in common, 100 for identical content
+  4: byte similarity,
+}
+
+// Derived data structure with all the copies detected in a changeset.
+struct DetectedCopies {
+  // Changeset id of the source Bonsai changeset
+  1: mononoke_types_thrift.ChangesetId changeset_id,
+  2: list<DetectedCopy> copies,
+}
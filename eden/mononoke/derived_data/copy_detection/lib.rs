@@ -0,0 +1,15 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod derive;
+mod detect;
+mod detected_copies;
+
+pub use crate::derive::{detected_copies_enabled, DetectedCopiesMapping};
+pub use crate::detected_copies::{DetectedCopies, DetectedCopy};
@@ -162,7 +162,7 @@ fn convert_to_raw_list(
     res
 }
 
-pub(crate) async fn fetch_fastlog_batch_by_unode_id(
+pub async fn fetch_fastlog_batch_by_unode_id(
     ctx: &CoreContext,
     blobstore: &Arc<dyn Blobstore>,
     unode_entry: Entry<ManifestUnodeId, FileUnodeId>,
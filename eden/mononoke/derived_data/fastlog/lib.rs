@@ -29,7 +29,11 @@ mod thrift {
     pub use mononoke_types_thrift::*;
 }
 
+pub use fastlog_impl::fetch_fastlog_batch_by_unode_id;
 pub use mapping::{
     fetch_parent_root_unodes, ErrorKind, FastlogParent, RootFastlog, RootFastlogMapping,
 };
-pub use ops::{list_file_history, prefetch_history, FastlogError, HistoryAcrossDeletions};
+pub use ops::{
+    list_file_history, list_file_history_across_copies, prefetch_history, FastlogError,
+    FileHistoryEntry, HistoryAcrossDeletions,
+};
@@ -11,12 +11,13 @@ use blobstore::{Blobstore, Loadable, LoadableError};
 use bounded_traversal::bounded_traversal_stream;
 use cloned::cloned;
 use context::CoreContext;
+use copy_detection::{detected_copies_enabled, DetectedCopies};
 use deleted_files_manifest::{self as deleted_manifest, RootDeletedManifestId};
 use derived_data::{BonsaiDerived, DeriveError};
 use futures::{
     compat::Future01CompatExt,
     future::{self, FutureExt as NewFutureExt, TryFutureExt},
-    stream::{self, Stream as NewStream},
+    stream::{self, BoxStream, Stream as NewStream},
 };
 use futures_old::Future;
 use futures_stats::futures03::TimedFutureExt;
@@ -62,8 +63,25 @@ pub enum HistoryAcrossDeletions {
     DontTrack,
 }
 
+/// A commit in the history of a file that is followed across copies and moves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileHistoryEntry {
+    pub cs_id: ChangesetId,
+    /// The path of the file in this commit
+    pub path: Option<MPath>,
+    /// The path and commit the file was copied or moved from, if that happened in this commit
+    pub copied_from: Option<(MPath, ChangesetId)>,
+}
+
+// Number of bonsai changesets loaded concurrently to look for copy sources
+const COPY_SOURCE_CONCURRENCY: usize = 100;
+
 /// Returns a full history of the given path starting from the given unode in BFS order.
 ///
+/// The path can be a file or a directory, as fastlog batches are derived for both file
+/// and manifest unodes. The history of a directory consists of the commits that changed
+/// any file under it.
+///
 /// Can accept a terminator function: a function on changeset id, that returns true if
 /// the history fetching on the current branch has to be terminated.
 /// The terminator will be called on changeset id when a fatslog batch is going to be
@@ -199,6 +217,179 @@ where
         .boxed())
 }
 
+/// Like `list_file_history`, but when the file turns out to have been copied or moved from
+/// another path, carries on with the history of that path, following at most `max_copy_hops`
+/// copies. The history of each copy source comes after the history of the copy.
+///
+/// Every commit in the history is loaded to find its copy sources, so this is slower than
+/// `list_file_history`.
+pub async fn list_file_history_across_copies<Terminator, TFut>(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    path: Option<MPath>,
+    changeset_id: ChangesetId,
+    terminator: Option<Terminator>,
+    history_across_deletions: HistoryAcrossDeletions,
+    max_copy_hops: usize,
+) -> Result<impl NewStream<Item = Result<FileHistoryEntry, Error>>, FastlogError>
+where
+    Terminator: Fn(ChangesetId) -> TFut + 'static + Clone + Send + Sync,
+    TFut: NewFuture<Output = Result<bool, Error>> + 'static + Send,
+{
+    let history = list_file_history(
+        ctx.clone(),
+        repo.clone(),
+        path.clone(),
+        changeset_id,
+        terminator.clone(),
+        history_across_deletions,
+    )
+    .await?;
+    let state = CopyTraversalState {
+        segment: Some(CopyTraversalSegment {
+            history: find_copy_sources(&ctx, &repo, &path, history),
+            path,
+            hops: 0,
+        }),
+        pending: VecDeque::new(),
+        visited: HashSet::new(),
+    };
+
+    Ok(stream::try_unfold(state, move |state| {
+        cloned!(ctx, repo, terminator);
+        async move {
+            do_copy_history_unfold(
+                ctx,
+                repo,
+                state,
+                terminator,
+                history_across_deletions,
+                max_copy_hops,
+            )
+            .await
+        }
+    })
+    .boxed())
+}
+
+// History of one path, with the copy source of each commit
+struct CopyTraversalSegment {
+    history: BoxStream<'static, Result<(ChangesetId, Option<(MPath, ChangesetId)>), Error>>,
+    path: Option<MPath>,
+    hops: usize,
+}
+
+struct CopyTraversalState {
+    segment: Option<CopyTraversalSegment>,
+    // copy sources whose history is still to be listed, with the number of hops to reach them
+    pending: VecDeque<(MPath, ChangesetId, usize)>,
+    visited: HashSet<(MPath, ChangesetId)>,
+}
+
+fn find_copy_sources(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    path: &Option<MPath>,
+    history: impl NewStream<Item = Result<ChangesetId, Error>> + Send + 'static,
+) -> BoxStream<'static, Result<(ChangesetId, Option<(MPath, ChangesetId)>), Error>> {
+    history
+        .map_ok({
+            cloned!(ctx, repo, path);
+            move |cs_id| {
+                cloned!(ctx, repo, path);
+                async move {
+                    // The root directory can't be copied, and bonsai doesn't record
+                    // directory copies
+                    let copied_from = match path {
+                        Some(path) => find_copy_source(&ctx, &repo, cs_id, &path).await?,
+                        None => None,
+                    };
+                    Ok::<_, Error>((cs_id, copied_from))
+                }
+            }
+        })
+        .try_buffered(COPY_SOURCE_CONCURRENCY)
+        .boxed()
+}
+
+async fn find_copy_source(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    cs_id: ChangesetId,
+    path: &MPath,
+) -> Result<Option<(MPath, ChangesetId)>, Error> {
+    let bonsai = cs_id.load(ctx.clone(), repo.blobstore()).compat().await?;
+    let copied_from = match bonsai.file_changes_map().get(path) {
+        Some(Some(file_change)) => file_change.copy_from().cloned(),
+        _ => return Ok(None),
+    };
+    // Fall back to the detected copies if the changeset didn't record any
+    if copied_from.is_none() && detected_copies_enabled(repo) {
+        let detected = DetectedCopies::derive(ctx.clone(), repo.clone(), cs_id)
+            .compat()
+            .await?;
+        return Ok(detected.get(path).map(|copy| copy.copy_from()));
+    }
+    Ok(copied_from)
+}
+
+async fn do_copy_history_unfold<Terminator, TFut>(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    mut state: CopyTraversalState,
+    terminator: Option<Terminator>,
+    history_across_deletions: HistoryAcrossDeletions,
+    max_copy_hops: usize,
+) -> Result<Option<(FileHistoryEntry, CopyTraversalState)>, Error>
+where
+    Terminator: Fn(ChangesetId) -> TFut + 'static + Clone + Send + Sync,
+    TFut: NewFuture<Output = Result<bool, Error>> + 'static + Send,
+{
+    loop {
+        if let Some(segment) = state.segment.as_mut() {
+            if let Some((cs_id, copied_from)) = segment.history.try_next().await? {
+                if let Some((from_path, from_cs_id)) = &copied_from {
+                    if segment.hops < max_copy_hops
+                        && state.visited.insert((from_path.clone(), *from_cs_id))
+                    {
+                        state
+                            .pending
+                            .push_back((from_path.clone(), *from_cs_id, segment.hops + 1));
+                    }
+                }
+                let entry = FileHistoryEntry {
+                    cs_id,
+                    path: segment.path.clone(),
+                    copied_from,
+                };
+                return Ok(Some((entry, state)));
+            }
+            state.segment = None;
+        }
+
+        let (path, cs_id, hops) = match state.pending.pop_front() {
+            Some(pending) => pending,
+            None => return Ok(None),
+        };
+        let path = Some(path);
+        let history = list_file_history(
+            ctx.clone(),
+            repo.clone(),
+            path.clone(),
+            cs_id,
+            terminator.clone(),
+            history_across_deletions,
+        )
+        .await
+        .map_err(Error::from)?;
+        state.segment = Some(CopyTraversalSegment {
+            history: find_copy_sources(&ctx, &repo, &path, history),
+            path,
+            hops,
+        });
+    }
+}
+
 async fn fetch_linknodes_and_update_graph(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -1325,6 +1516,77 @@ mod test {
 
     type TestCommitGraph = HashMap<ChangesetId, Vec<ChangesetId>>;
 
+    #[fbinit::compat_test]
+    async fn test_list_history_across_copies(fb: FacebookInit) -> Result<(), Error> {
+        // a: "a" is created and changed
+        // b: "a" is moved to "dir/b"
+        // c: "dir/b" is copied to "dir/c", which is changed again in d
+        let repo = new_memblob_empty(None).unwrap();
+        let ctx = CoreContext::test_mock(fb);
+
+        let a1 = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("a", "1")
+            .commit()
+            .await?;
+        let a2 = CreateCommitContext::new(&ctx, &repo, vec![a1])
+            .add_file("a", "2")
+            .commit()
+            .await?;
+        let b = CreateCommitContext::new(&ctx, &repo, vec![a2])
+            .delete_file("a")
+            .add_file_with_copy_info("dir/b", "2", (a2, "a"))?
+            .commit()
+            .await?;
+        let c = CreateCommitContext::new(&ctx, &repo, vec![b])
+            .add_file_with_copy_info("dir/c", "3", (b, "dir/b"))?
+            .commit()
+            .await?;
+        let d = CreateCommitContext::new(&ctx, &repo, vec![c])
+            .add_file("dir/c", "4")
+            .commit()
+            .await?;
+
+        let history = |max_copy_hops| {
+            cloned!(ctx, repo);
+            async move {
+                let terminator = Some(|_cs_id| future::ready(Ok(false)));
+                let history_stream = list_file_history_across_copies(
+                    ctx,
+                    repo,
+                    path("dir/c"),
+                    d,
+                    terminator,
+                    HistoryAcrossDeletions::Track,
+                    max_copy_hops,
+                )
+                .await?;
+                history_stream
+                    .map_ok(|entry| (entry.cs_id, entry.path, entry.copied_from))
+                    .try_collect::<Vec<_>>()
+                    .await
+            }
+        };
+
+        let c_history = vec![
+            (d, path("dir/c"), None),
+            (c, path("dir/c"), Some((MPath::new("dir/b")?, b))),
+        ];
+        let b_history = vec![(b, path("dir/b"), Some((MPath::new("a")?, a2)))];
+        let a_history = vec![(a2, path("a"), None), (a1, path("a"), None)];
+
+        assert_eq!(history(0).await?, c_history);
+
+        let mut expected = c_history.clone();
+        expected.extend(b_history.clone());
+        assert_eq!(history(1).await?, expected);
+
+        expected.extend(a_history);
+        assert_eq!(history(2).await?, expected);
+        assert_eq!(history(10).await?, expected);
+
+        Ok(())
+    }
+
     async fn create_branch(
         ctx: &CoreContext,
         repo: &BlobRepo,
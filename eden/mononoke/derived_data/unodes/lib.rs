@@ -38,16 +38,30 @@ pub fn find_unode_renames(
     repo: BlobRepo,
     bonsai: &BonsaiChangeset,
 ) -> impl Future<Item = HashMap<MPath, FileUnodeId>, Error = Error> {
-    let mut references: HashMap<ChangesetId, HashMap<MPath, MPath>> = HashMap::new();
+    let mut references: HashMap<ChangesetId, HashMap<MPath, Vec<MPath>>> = HashMap::new();
     for (to_path, file_change) in bonsai.file_changes() {
         if let Some((from_path, csid)) = file_change.and_then(|fc| fc.copy_from()) {
             references
                 .entry(*csid)
                 .or_default()
-                .insert(from_path.clone(), to_path.clone());
+                .entry(from_path.clone())
+                .or_default()
+                .push(to_path.clone());
         }
     }
 
+    find_unode_copy_sources(ctx, repo, references)
+}
+
+/// Given copies, as mapping from source changesets to the paths copied from them and
+/// the paths they were copied to, find unodes of the copied files.
+///
+/// Returns mapping from paths the files were copied to to file unodes in source changesets.
+pub fn find_unode_copy_sources(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    references: HashMap<ChangesetId, HashMap<MPath, Vec<MPath>>>,
+) -> impl Future<Item = HashMap<MPath, FileUnodeId>, Error = Error> {
     let blobstore = repo.get_blobstore();
     let unodes = references.into_iter().map(move |(csid, mut paths)| {
         RootUnodeManifestId::derive(ctx.clone(), repo.clone(), csid)
@@ -68,6 +82,9 @@ pub fn find_unode_renames(
                                 .filter_map(|(from_path, unode_id)| {
                                     Some((paths.remove(&from_path)?, unode_id))
                                 })
+                                .flat_map(|(to_paths, unode_id)| {
+                                    to_paths.into_iter().map(move |to_path| (to_path, unode_id))
+                                })
                                 .collect::<HashMap<_, _>>()
                         })
                 }
@@ -7,13 +7,14 @@
 
 use anyhow::{format_err, Error};
 use async_trait::async_trait;
-use blame::{BlameRoot, BlameRootMapping};
+use blame::{BlameDetectedCopiesRoot, BlameDetectedCopiesRootMapping, BlameRoot, BlameRootMapping};
 use blobrepo::{BlobRepo, DangerousOverride};
 use blobstore::{Blobstore, Loadable};
 use cacheblob::{dummy::DummyLease, LeaseOps, MemWritesBlobstore};
 use changeset_info::{ChangesetInfo, ChangesetInfoMapping};
 use cloned::cloned;
 use context::CoreContext;
+use copy_detection::{DetectedCopies, DetectedCopiesMapping};
 use deleted_files_manifest::{RootDeletedManifestId, RootDeletedManifestMapping};
 use derived_data::{
     derive_impl::derive_impl, BonsaiDerived, BonsaiDerivedMapping, DeriveError, Mode as DeriveMode,
@@ -25,6 +26,7 @@ use fsnodes::{RootFsnodeId, RootFsnodeMapping};
 use futures::{compat::Future01CompatExt, stream, StreamExt, TryStreamExt};
 use futures_ext::{BoxFuture, FutureExt as OldFutureExt};
 use futures_old::{future, stream as stream_old, Future, Stream};
+use git_types::{GitCommitMapping, MappedGitCommitId, TreeHandle, TreeMapping};
 use mercurial_derived_data::{HgChangesetIdMapping, MappedHgChangesetId};
 use mononoke_types::{BonsaiChangeset, ChangesetId};
 use std::{
@@ -42,6 +44,10 @@ pub const POSSIBLE_DERIVED_TYPES: &[&str] = &[
     ChangesetInfo::NAME,
     RootDeletedManifestId::NAME,
     FilenodesOnlyPublic::NAME,
+    DetectedCopies::NAME,
+    BlameDetectedCopiesRoot::NAME,
+    TreeHandle::NAME,
+    MappedGitCommitId::NAME,
 ];
 
 #[async_trait]
@@ -354,6 +360,22 @@ fn derived_data_utils_impl(
             let mapping = FilenodesOnlyPublicMapping::new(repo);
             Ok(Arc::new(DerivedUtilsFromMapping::new(mapping, mode)))
         }
+        DetectedCopies::NAME => {
+            let mapping = DetectedCopiesMapping::new(repo.get_blobstore().boxed());
+            Ok(Arc::new(DerivedUtilsFromMapping::new(mapping, mode)))
+        }
+        BlameDetectedCopiesRoot::NAME => {
+            let mapping = BlameDetectedCopiesRootMapping::new(repo.get_blobstore().boxed());
+            Ok(Arc::new(DerivedUtilsFromMapping::new(mapping, mode)))
+        }
+        TreeHandle::NAME => {
+            let mapping = TreeMapping::new(repo.get_blobstore().boxed());
+            Ok(Arc::new(DerivedUtilsFromMapping::new(mapping, mode)))
+        }
+        MappedGitCommitId::NAME => {
+            let mapping = GitCommitMapping::new(&repo);
+            Ok(Arc::new(DerivedUtilsFromMapping::new(mapping, mode)))
+        }
         name => Err(format_err!("Unsupported derived data type: {}", name)),
     }
 }
@@ -195,6 +195,35 @@ pub fn get_metadata_readonly<B: Blobstore + Clone>(
         })
 }
 
+/// Recompute the metadata for the underlying content from its bytes, ignoring (and not updating)
+/// any stored metadata. This will return None if the content does not exist. Useful to check the
+/// stored metadata is correct.
+pub fn compute_metadata<B: Blobstore + Clone>(
+    blobstore: &B,
+    ctx: CoreContext,
+    key: &FetchKey,
+) -> impl Future<Item = Option<ContentMetadata>, Error = Error> {
+    key.load(ctx.clone(), blobstore)
+        .map(Some)
+        .or_else(|err| match err {
+            LoadableError::Error(err) => Err(err),
+            LoadableError::Missing(_) => Ok(None),
+        })
+        .and_then({
+            cloned!(blobstore, ctx);
+            move |maybe_id| match maybe_id {
+                Some(id) => metadata::compute_metadata(blobstore, ctx, id)
+                    .map(Some)
+                    .or_else(|e| match e {
+                        metadata::RebuildBackmappingError::NotFound(_) => Ok(None),
+                        e => Err(e.into()),
+                    })
+                    .left_future(),
+                None => Ok(None).into_future().right_future(),
+            }
+        })
+}
+
 /// Return true if the given key exists. A successful return means the key definitely
 /// either exists or doesn't; an error means the existence could not be determined.
 pub fn exists<B: Blobstore + Clone>(
@@ -79,9 +79,8 @@ pub fn get_metadata_readonly<B: Blobstore + Clone>(
 
 /// If the metadata is missing, we can rebuild it on the fly, since all that's needed to do so
 /// is the file contents. This can happen if we successfully stored a file, but failed to store
-/// its metadata. To rebuild the metadata, we peek at the content in the blobstore to get
-/// its size, then produce a stream of its contents and compute aliases over it. Finally, store
-/// the metadata, and return it.
+/// its metadata. To rebuild the metadata, we compute it from the contents (see
+/// `compute_metadata`), then store it, and return it.
 fn rebuild_metadata<B: Blobstore + Clone>(
     blobstore: B,
     ctx: CoreContext,
@@ -89,45 +88,52 @@ fn rebuild_metadata<B: Blobstore + Clone>(
 ) -> impl Future<Item = ContentMetadata, Error = RebuildBackmappingError> {
     use RebuildBackmappingError::*;
 
+    compute_metadata(blobstore.clone(), ctx.clone(), content_id).and_then(move |metadata| {
+        let blob = metadata.clone().into_blob();
+
+        blob.store(ctx, &blobstore)
+            .map_err(move |e| InternalError(content_id, e))
+            .map(|_| metadata)
+    })
+}
+
+/// Computes the metadata for a ContentId from the file contents, without storing it. To do so,
+/// we peek at the content in the blobstore to get its size, then produce a stream of its contents
+/// and compute aliases over it.
+pub fn compute_metadata<B: Blobstore + Clone>(
+    blobstore: B,
+    ctx: CoreContext,
+    content_id: ContentId,
+) -> impl Future<Item = ContentMetadata, Error = RebuildBackmappingError> {
+    use RebuildBackmappingError::*;
+
     content_id
         .load(ctx.clone(), &blobstore)
         .or_else(move |err| match err {
             LoadableError::Error(err) => Err(InternalError(content_id, err)),
             LoadableError::Missing(_) => Err(NotFound(content_id)),
         })
-        .and_then({
-            cloned!(blobstore, ctx);
-            move |file_contents| {
-                // NOTE: We implicitly trust data from the Filestore here. We do not validate
-                // the size, nor the ContentId.
-                let total_size = file_contents.size();
-                let content_stream =
-                    fetch::stream_file_bytes(blobstore, ctx, file_contents, fetch::Range::All);
+        .and_then(move |file_contents| {
+            // NOTE: We implicitly trust data from the Filestore here. We do not validate
+            // the size, nor the ContentId.
+            let total_size = file_contents.size();
+            let content_stream =
+                fetch::stream_file_bytes(blobstore, ctx, file_contents, fetch::Range::All);
 
-                alias_stream(ExpectedSize::new(total_size), content_stream)
-                    .from_err()
-                    .and_then(move |redeemable| Ok((redeemable.redeem(total_size)?, total_size)))
-                    .map_err(move |e| InternalError(content_id, e))
-            }
+            alias_stream(ExpectedSize::new(total_size), content_stream)
+                .from_err()
+                .and_then(move |redeemable| Ok((redeemable.redeem(total_size)?, total_size)))
+                .map_err(move |e| InternalError(content_id, e))
         })
-        .and_then({
-            cloned!(blobstore, ctx);
-            move |(aliases, total_size)| {
-                let (sha1, sha256, git_sha1) = aliases;
-
-                let metadata = ContentMetadata {
-                    total_size,
-                    content_id,
-                    sha1,
-                    sha256,
-                    git_sha1,
-                };
-
-                let blob = metadata.clone().into_blob();
+        .map(move |(aliases, total_size)| {
+            let (sha1, sha256, git_sha1) = aliases;
 
-                blob.store(ctx, &blobstore)
-                    .map_err(move |e| InternalError(content_id, e))
-                    .map(|_| metadata)
+            ContentMetadata {
+                total_size,
+                content_id,
+                sha1,
+                sha256,
+                git_sha1,
             }
         })
 }
@@ -729,6 +729,49 @@ async fn filestore_rebuild_metadata(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn filestore_compute_metadata(fb: FacebookInit) -> Result<()> {
+    let req = request(HELLO_WORLD);
+    let content_id = canonical(HELLO_WORLD);
+    let metadata: ContentMetadataId = content_id.clone().into();
+
+    let expected = Some(ContentMetadata {
+        total_size: HELLO_WORLD_LENGTH,
+        content_id,
+        sha1: *HELLO_WORLD_SHA1,
+        git_sha1: *HELLO_WORLD_GIT_SHA1,
+        sha256: *HELLO_WORLD_SHA256,
+    });
+
+    let blob = memblob::LazyMemblob::new();
+    let ctx = CoreContext::test_mock(fb);
+
+    // Computing needs the content to exist
+    let fut: OldBoxFuture<_, _> =
+        filestore::compute_metadata(&blob, ctx.clone(), &FetchKey::Canonical(content_id)).boxify();
+    assert_eq!(fut.compat().await?, None);
+
+    filestore::store(
+        blob.clone(),
+        DEFAULT_CONFIG,
+        ctx.clone(),
+        &req,
+        stream::once(Ok(Bytes::from(HELLO_WORLD))),
+    )
+    .boxify()
+    .compat()
+    .await?;
+
+    // Computing doesn't rely on the stored metadata, nor store it
+    assert!(blob.remove(&metadata.blobstore_key()).is_some());
+    let fut: OldBoxFuture<_, _> =
+        filestore::compute_metadata(&blob, ctx.clone(), &FetchKey::Canonical(content_id)).boxify();
+    assert_eq!(fut.compat().await?, expected);
+    assert!(blob.remove(&metadata.blobstore_key()).is_none());
+
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn filestore_test_missing_metadata(fb: FacebookInit) -> Result<()> {
     let content_id = canonical(HELLO_WORLD);
@@ -27,3 +27,28 @@ struct Tree {
   1: TreeHandle handle,
   2: map<mononoke_types_thrift.MPathElement, TreeMember> members,
 }
+
+struct CommitHandle {
+  1: mononoke_types_thrift.GitSha1 oid,
+  2: i64 size,
+}
+
+struct Signature {
+  1: string identity,
+  2: mononoke_types_thrift.DateTime date,
+}
+
+struct ExtraHeader {
+  1: string name,
+  2: binary value,
+}
+
+struct Commit {
+  1: CommitHandle handle,
+  2: TreeHandle tree,
+  3: list<mononoke_types_thrift.GitSha1> parents,
+  4: Signature author,
+  5: Signature committer,
+  6: list<ExtraHeader> extra_headers,
+  7: string message,
+}
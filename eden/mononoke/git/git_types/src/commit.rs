@@ -0,0 +1,415 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+
+use mononoke_types::{
+    hash::{GitSha1, RichGitSha1},
+    DateTime,
+};
+
+use crate::errors::ErrorKind;
+use crate::thrift;
+use crate::{ObjectKind, TreeHandle};
+
+/// Header used to preserve Bonsai extras in Git commits. This is the same
+/// header hg-git uses, so the extras are interpreted the same way.
+pub const HG_EXTRA_HEADER: &str = "HG:extra";
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct CommitHandle {
+    oid: RichGitSha1,
+}
+
+impl CommitHandle {
+    pub fn oid(&self) -> &RichGitSha1 {
+        &self.oid
+    }
+
+    pub fn blobstore_key(&self) -> String {
+        commit_blobstore_key(&self.oid.sha1())
+    }
+}
+
+/// Commits are stored by oid only, so that they can be fetched from their
+/// Git commit id without knowing their size.
+pub(crate) fn commit_blobstore_key(oid: &GitSha1) -> String {
+    format!("git.commit.{}", oid)
+}
+
+/// Objects of commits imported from Git are stored as is, as a `Commit` can't
+/// reproduce every Git commit (e.g. signed ones) byte for byte.
+pub(crate) fn imported_commit_blobstore_key(oid: &GitSha1) -> String {
+    format!("git.imported_commit.{}", oid)
+}
+
+impl TryFrom<thrift::CommitHandle> for CommitHandle {
+    type Error = Error;
+
+    fn try_from(t: thrift::CommitHandle) -> Result<Self, Error> {
+        let size = t.size.try_into()?;
+        let oid = RichGitSha1::from_bytes(&t.oid.0, ObjectKind::Commit.as_str(), size)?;
+        Ok(Self { oid })
+    }
+}
+
+impl Into<thrift::CommitHandle> for CommitHandle {
+    fn into(self) -> thrift::CommitHandle {
+        let size = self.oid.size();
+
+        thrift::CommitHandle {
+            oid: self.oid.into_thrift(),
+            size: size.try_into().expect("Commit size must fit in a i64"),
+        }
+    }
+}
+
+/// Author, committer or tagger of a Git object.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Signature {
+    identity: String,
+    date: DateTime,
+}
+
+impl Signature {
+    /// Creates a signature from a Mercurial-style `Name <email>` user. Users
+    /// without an email address are converted the way hg-git converts them.
+    pub fn from_user(user: &str, date: DateTime) -> Self {
+        let user = user.trim();
+        let identity = match (user.find('<'), user.ends_with('>')) {
+            (Some(start), true) => format!(
+                "{} <{}>",
+                sanitize(&user[..start]),
+                sanitize(&user[start + 1..user.len() - 1])
+            ),
+            _ if user.contains('@') => {
+                let user = sanitize(user);
+                format!("{} <{}>", user, user)
+            }
+            _ => format!("{} <none@none>", sanitize(user)),
+        };
+        Self { identity, date }
+    }
+
+    /// The `Name <email>` part of the signature.
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    pub fn date(&self) -> &DateTime {
+        &self.date
+    }
+
+    pub(crate) fn write_serialized(&self, writer: &mut impl Write) -> Result<(), io::Error> {
+        // Git stores the offset east of UTC, Mononoke stores it west of UTC.
+        let offset = -self.date.tz_offset_secs();
+        let sign = if offset < 0 { '-' } else { '+' };
+        let minutes = offset.abs() / 60;
+        write!(
+            writer,
+            "{} {} {}{:02}{:02}",
+            self.identity,
+            self.date.timestamp_secs(),
+            sign,
+            minutes / 60,
+            minutes % 60
+        )
+    }
+}
+
+impl TryFrom<thrift::Signature> for Signature {
+    type Error = Error;
+
+    fn try_from(t: thrift::Signature) -> Result<Self, Error> {
+        Ok(Self {
+            identity: t.identity,
+            date: DateTime::from_thrift(t.date)?,
+        })
+    }
+}
+
+impl Into<thrift::Signature> for Signature {
+    fn into(self) -> thrift::Signature {
+        thrift::Signature {
+            identity: self.identity,
+            date: self.date.into_thrift(),
+        }
+    }
+}
+
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .filter(|c| *c != '<' && *c != '>' && *c != '\n')
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[derive(Debug, Clone)]
+pub struct Commit {
+    handle: CommitHandle,
+    builder: CommitBuilder,
+}
+
+impl Commit {
+    pub fn handle(&self) -> &CommitHandle {
+        &self.handle
+    }
+
+    pub fn tree(&self) -> &TreeHandle {
+        &self.builder.tree
+    }
+
+    pub fn parents(&self) -> &[GitSha1] {
+        &self.builder.parents
+    }
+
+    pub fn author(&self) -> &Signature {
+        &self.builder.author
+    }
+
+    pub fn committer(&self) -> &Signature {
+        &self.builder.committer
+    }
+
+    pub fn extra_headers(&self) -> &[(String, Vec<u8>)] {
+        &self.builder.extra_headers
+    }
+
+    pub fn message(&self) -> &str {
+        &self.builder.message
+    }
+
+    pub fn write_serialized_object(&self, writer: &mut impl Write) -> Result<(), io::Error> {
+        self.builder.write_serialized_object(writer)
+    }
+}
+
+impl TryFrom<thrift::Commit> for Commit {
+    type Error = Error;
+
+    fn try_from(t: thrift::Commit) -> Result<Self, Error> {
+        let handle = t.handle.try_into()?;
+        let parents = t
+            .parents
+            .into_iter()
+            .map(|oid| GitSha1::from_bytes(&oid.0))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let extra_headers = t
+            .extra_headers
+            .into_iter()
+            .map(|header| (header.name, header.value))
+            .collect();
+
+        Ok(Self {
+            handle,
+            builder: CommitBuilder {
+                tree: t.tree.try_into()?,
+                parents,
+                author: t.author.try_into()?,
+                committer: t.committer.try_into()?,
+                extra_headers,
+                message: t.message,
+            },
+        })
+    }
+}
+
+impl Into<thrift::Commit> for Commit {
+    fn into(self) -> thrift::Commit {
+        let Commit { handle, builder } = self;
+
+        let extra_headers = builder
+            .extra_headers
+            .into_iter()
+            .map(|(name, value)| thrift::ExtraHeader { name, value })
+            .collect();
+
+        thrift::Commit {
+            handle: handle.into(),
+            tree: builder.tree.into(),
+            parents: builder
+                .parents
+                .into_iter()
+                .map(GitSha1::into_thrift)
+                .collect(),
+            author: builder.author.into(),
+            committer: builder.committer.into(),
+            extra_headers,
+            message: builder.message,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommitBuilder {
+    tree: TreeHandle,
+    parents: Vec<GitSha1>,
+    author: Signature,
+    committer: Signature,
+    extra_headers: Vec<(String, Vec<u8>)>,
+    message: String,
+}
+
+impl CommitBuilder {
+    pub fn new(
+        tree: TreeHandle,
+        parents: Vec<GitSha1>,
+        author: Signature,
+        committer: Signature,
+        message: String,
+    ) -> Self {
+        Self {
+            tree,
+            parents,
+            author,
+            committer,
+            extra_headers: Vec::new(),
+            message,
+        }
+    }
+
+    /// Adds a header after the standard ones. The value may contain newlines,
+    /// they are escaped the way Git escapes multi-line headers.
+    pub fn add_extra_header(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<Vec<u8>>,
+    ) -> Result<Self, Error> {
+        let name = name.into();
+        if name.is_empty()
+            || name.contains(|c: char| c.is_whitespace())
+            || RESERVED_HEADERS.contains(&name.as_str())
+        {
+            return Err(ErrorKind::InvalidHeaderName(name).into());
+        }
+        self.extra_headers.push((name, value.into()));
+        Ok(self)
+    }
+
+    /// Preserves a Bonsai extra as an `HG:extra` header, with the key and
+    /// the value percent-encoded like hg-git does.
+    pub fn add_extra(self, key: &str, value: &[u8]) -> Result<Self, Error> {
+        let mut header = percent_encode(key.as_bytes());
+        header.push(':');
+        header.push_str(&percent_encode(value));
+        self.add_extra_header(HG_EXTRA_HEADER, header)
+    }
+
+    pub fn write_serialized_object(&self, writer: &mut impl Write) -> Result<(), io::Error> {
+        writeln!(writer, "tree {}", self.tree.oid())?;
+        for parent in self.parents.iter() {
+            writeln!(writer, "parent {}", parent)?;
+        }
+        writer.write_all(b"author ")?;
+        self.author.write_serialized(writer)?;
+        writer.write_all(b"\ncommitter ")?;
+        self.committer.write_serialized(writer)?;
+        writer.write_all(b"\n")?;
+        for (name, value) in self.extra_headers.iter() {
+            write!(writer, "{} ", name)?;
+            for (i, line) in value.split(|c| *c == b'\n').enumerate() {
+                if i > 0 {
+                    writer.write_all(b"\n ")?;
+                }
+                writer.write_all(line)?;
+            }
+            writer.write_all(b"\n")?;
+        }
+        writer.write_all(b"\n")?;
+        writer.write_all(self.message.as_bytes())
+    }
+}
+
+impl Into<Commit> for CommitBuilder {
+    fn into(self) -> Commit {
+        let mut object_buff = Vec::new();
+        self.write_serialized_object(&mut object_buff)
+            .expect("Writes to Vec cannot fail");
+
+        let oid = ObjectKind::Commit.create_oid(&object_buff);
+
+        Commit {
+            handle: CommitHandle { oid },
+            builder: self,
+        }
+    }
+}
+
+impl Display for Commit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut object_buff = Vec::new();
+        self.write_serialized_object(&mut object_buff)
+            .map_err(|_| fmt::Error)?;
+        write!(f, "{}", String::from_utf8_lossy(&object_buff))
+    }
+}
+
+/// Headers Git gives a meaning to, which can't be used for extras.
+const RESERVED_HEADERS: &[&str] = &[
+    "tree",
+    "parent",
+    "author",
+    "committer",
+    "encoding",
+    "gpgsig",
+    "mergetag",
+];
+
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len());
+    for b in bytes {
+        if b.is_ascii_alphanumeric() || b"-_./~".contains(b) {
+            encoded.push(*b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_signature_from_user() -> Result<(), Error> {
+        let date = DateTime::from_timestamp(1_500_000_000, -3600)?;
+        let identity = |user| Signature::from_user(user, date).identity().to_string();
+
+        assert_eq!(
+            identity("Jane Doe <jane@example.com>"),
+            "Jane Doe <jane@example.com>"
+        );
+        assert_eq!(
+            identity("jane@example.com"),
+            "jane@example.com <jane@example.com>"
+        );
+        assert_eq!(identity("jane"), "jane <none@none>");
+        assert_eq!(identity(" Jane  <jane>>"), "Jane <jane>");
+
+        let mut buff = Vec::new();
+        Signature::from_user("jane", date).write_serialized(&mut buff)?;
+        assert_eq!(buff, b"jane <none@none> 1500000000 +0100".to_vec());
+
+        let mut buff = Vec::new();
+        Signature::from_user("jane", DateTime::from_timestamp(0, 16200)?)
+            .write_serialized(&mut buff)?;
+        assert_eq!(buff, b"jane <none@none> 0 -0430".to_vec());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode(b"rebase_source"), "rebase_source");
+        assert_eq!(percent_encode(b"a:b c\n%"), "a%3Ab%20c%0A%25");
+    }
+}
@@ -0,0 +1,351 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use bytes::Bytes;
+use futures::{compat::Future01CompatExt, FutureExt as NewFutureExt, TryFutureExt};
+use futures_ext::{BoxFuture, FutureExt};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+use blobrepo::BlobRepo;
+use blobstore::{Blobstore, Storable};
+use bonsai_git_mapping::{
+    extract_git_sha1_from_bonsai_extra, BonsaiGitMapping, BonsaiGitMappingEntry,
+};
+use context::CoreContext;
+use derived_data::{BonsaiDerived, BonsaiDerivedMapping};
+use mononoke_types::{hash::GitSha1, BlobstoreBytes, BonsaiChangeset, ChangesetId};
+
+use crate::commit::{commit_blobstore_key, imported_commit_blobstore_key};
+use crate::{Commit, CommitBuilder, ObjectKind, Signature, TreeHandle};
+
+/// Git commit id of a Bonsai changeset, as recorded in the Bonsai Git mapping.
+///
+/// Changesets imported from Git keep the commit id they had in Git, and their
+/// original commit object is stored when they are imported (see
+/// `store_imported_commit`). For all the others, a Git commit object is
+/// generated and stored in the blobstore.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct MappedGitCommitId(GitSha1);
+
+impl MappedGitCommitId {
+    pub fn oid(&self) -> &GitSha1 {
+        &self.0
+    }
+
+    /// Fetches the commit object generated for this commit id. Returns `None`
+    /// for commits imported from Git, see `fetch_object`.
+    pub async fn fetch_commit(
+        &self,
+        ctx: CoreContext,
+        blobstore: &impl Blobstore,
+    ) -> Result<Option<Commit>, Error> {
+        let bytes = blobstore
+            .get(ctx, commit_blobstore_key(&self.0))
+            .compat()
+            .await?;
+        bytes.map(|bytes| bytes.try_into()).transpose()
+    }
+
+    /// Fetches the serialized Git object of this commit, whether it was
+    /// generated or imported from Git. Returns `None` for commits imported
+    /// before their objects were stored.
+    pub async fn fetch_object(
+        &self,
+        ctx: CoreContext,
+        blobstore: &impl Blobstore,
+    ) -> Result<Option<Bytes>, Error> {
+        if let Some(commit) = self.fetch_commit(ctx.clone(), blobstore).await? {
+            let mut object_buff = Vec::new();
+            commit.write_serialized_object(&mut object_buff)?;
+            return Ok(Some(Bytes::from(object_buff)));
+        }
+        let bytes = blobstore
+            .get(ctx, imported_commit_blobstore_key(&self.0))
+            .compat()
+            .await?;
+        Ok(bytes.map(|bytes| bytes.into_raw_bytes()))
+    }
+}
+
+/// Stores the object of a commit imported from Git, as read from the Git
+/// object database (without the object header). Returns its commit id.
+pub async fn store_imported_commit(
+    ctx: CoreContext,
+    blobstore: &impl Blobstore,
+    object: Bytes,
+) -> Result<GitSha1, Error> {
+    let oid = ObjectKind::Commit.create_oid(&object).sha1();
+    blobstore
+        .put(
+            ctx,
+            imported_commit_blobstore_key(&oid),
+            BlobstoreBytes::from_bytes(object),
+        )
+        .compat()
+        .await?;
+    Ok(oid)
+}
+
+impl BonsaiDerived for MappedGitCommitId {
+    const NAME: &'static str = "git_commits";
+    type Mapping = GitCommitMapping;
+
+    fn mapping(_ctx: &CoreContext, repo: &BlobRepo) -> Self::Mapping {
+        GitCommitMapping::new(repo)
+    }
+
+    fn derive_from_parents(
+        ctx: CoreContext,
+        repo: BlobRepo,
+        bonsai: BonsaiChangeset,
+        parents: Vec<Self>,
+    ) -> BoxFuture<Self, Error> {
+        async move {
+            let bcs_id = bonsai.get_changeset_id();
+            let git_sha1 = match extract_git_sha1_from_bonsai_extra(bonsai.extra())? {
+                Some(git_sha1) => git_sha1,
+                None => {
+                    let tree = TreeHandle::derive(ctx.clone(), repo.clone(), bcs_id)
+                        .compat()
+                        .await?;
+                    let commit = commit_from_bonsai(&bonsai, tree, parents)?;
+                    let handle = commit.store(ctx.clone(), repo.blobstore()).compat().await?;
+                    handle.oid().sha1()
+                }
+            };
+
+            repo.bonsai_git_mapping()
+                .bulk_add(&[BonsaiGitMappingEntry::new(git_sha1, bcs_id)])
+                .await?;
+
+            Ok(MappedGitCommitId(git_sha1))
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+}
+
+/// Builds the Git commit for a Bonsai changeset. Parents are the Git commit
+/// ids of the Bonsai parents, in the same order.
+fn commit_from_bonsai(
+    bonsai: &BonsaiChangeset,
+    tree: TreeHandle,
+    parents: Vec<MappedGitCommitId>,
+) -> Result<Commit, Error> {
+    let author = Signature::from_user(bonsai.author(), *bonsai.author_date());
+    let committer = Signature::from_user(
+        bonsai.committer().unwrap_or(bonsai.author()),
+        *bonsai.committer_date().unwrap_or(bonsai.author_date()),
+    );
+
+    let mut builder = CommitBuilder::new(
+        tree,
+        parents.into_iter().map(|parent| parent.0).collect(),
+        author,
+        committer,
+        bonsai.message().to_string(),
+    );
+    for (key, value) in bonsai.extra() {
+        builder = builder.add_extra(key, value)?;
+    }
+
+    Ok(builder.into())
+}
+
+#[derive(Clone)]
+pub struct GitCommitMapping {
+    mapping: Arc<dyn BonsaiGitMapping>,
+}
+
+impl GitCommitMapping {
+    pub fn new(repo: &BlobRepo) -> Self {
+        Self {
+            mapping: repo.bonsai_git_mapping().clone(),
+        }
+    }
+}
+
+impl BonsaiDerivedMapping for GitCommitMapping {
+    type Value = MappedGitCommitId;
+
+    fn get(
+        &self,
+        _ctx: CoreContext,
+        csids: Vec<ChangesetId>,
+    ) -> BoxFuture<HashMap<ChangesetId, Self::Value>, Error> {
+        let mapping = self.mapping.clone();
+        async move {
+            let entries = mapping.get(csids.into()).await?;
+            Ok(entries
+                .into_iter()
+                .map(|entry| (entry.bcs_id, MappedGitCommitId(entry.git_sha1)))
+                .collect())
+        }
+        .boxed()
+        .compat()
+        .boxify()
+    }
+
+    // This just succeeds, because generation of the derived data also saves the mapping
+    fn put(&self, _ctx: CoreContext, _csid: ChangesetId, _id: Self::Value) -> BoxFuture<(), Error> {
+        futures_old::future::ok(()).boxify()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blobrepo::DangerousOverride;
+    use fbinit::FacebookInit;
+    use git2::{ObjectType, Oid, Repository};
+    use maplit::btreeset;
+    use metaconfig_types::DerivedDataConfig;
+    use mononoke_types::DateTime;
+    use tempdir::TempDir;
+    use tests_utils::CreateCommitContext;
+    use tokio_compat::runtime::Runtime;
+
+    #[fbinit::test]
+    fn derive_git_commits_test(fb: FacebookInit) -> Result<(), Error> {
+        let mut runtime = Runtime::new()?;
+        runtime.block_on_std(async move {
+            let ctx = CoreContext::test_mock(fb);
+            let repo = blobrepo_factory::new_memblob_empty(None)?.dangerous_override(
+                |mut derived_data_config: DerivedDataConfig| {
+                    derived_data_config.derived_data_types = btreeset! {
+                        MappedGitCommitId::NAME.to_string(),
+                        TreeHandle::NAME.to_string(),
+                    };
+                    derived_data_config
+                },
+            );
+
+            // A commit imported from Git keeps its commit id.
+            let imported_sha1 = "0123456789abcdef0123456789abcdef01234567";
+            let imported = CreateCommitContext::new_root(&ctx, &repo)
+                .add_file("file", "content\n")
+                .add_extra("hg-git-rename-source".to_string(), b"git".to_vec())
+                .add_extra("convert_revision".to_string(), imported_sha1.into())
+                .commit()
+                .await?;
+
+            let cs_id = CreateCommitContext::new(&ctx, &repo, vec![imported])
+                .add_file("file", "modified\n")
+                .set_author("Jane Doe <jane@example.com>")
+                .set_author_date(DateTime::from_timestamp(1_500_000_000, -7200)?)
+                .set_message("modify file\n")
+                .add_extra("rebase_source".to_string(), b"some value".to_vec())
+                .commit()
+                .await?;
+
+            let git_commit_id = MappedGitCommitId::derive(ctx.clone(), repo.clone(), cs_id)
+                .compat()
+                .await?;
+
+            let imported_commit_id = repo
+                .bonsai_git_mapping()
+                .get_git_sha1_from_bonsai(imported)
+                .await?;
+            assert_eq!(
+                imported_commit_id.map(|oid| oid.to_hex().to_string()),
+                Some(imported_sha1.to_string())
+            );
+            assert_eq!(
+                repo.bonsai_git_mapping()
+                    .get_bonsai_from_git_sha1(*git_commit_id.oid())
+                    .await?,
+                Some(cs_id)
+            );
+
+            let commit = git_commit_id
+                .fetch_commit(ctx.clone(), repo.blobstore())
+                .await?
+                .expect("commit object should be stored");
+            let tree = TreeHandle::derive(ctx.clone(), repo.clone(), cs_id)
+                .compat()
+                .await?;
+            assert_eq!(commit.tree(), &tree);
+
+            // Make sure that libgit parses the commit and computes the same id.
+            let tmp_dir = TempDir::new("git_types_test")?;
+            let git = Repository::init(tmp_dir.path())?;
+            let mut object_buff = Vec::new();
+            commit.write_serialized_object(&mut object_buff)?;
+            let oid = git.odb()?.write(ObjectType::Commit, &object_buff)?;
+            assert_eq!(oid, Oid::from_bytes(git_commit_id.oid().as_ref())?);
+
+            let git_commit = git.find_commit(oid)?;
+            assert_eq!(git_commit.tree_id(), Oid::from_bytes(tree.oid().as_ref())?);
+            assert_eq!(
+                git_commit.parent_ids().collect::<Vec<_>>(),
+                vec![Oid::from_str(imported_sha1)?]
+            );
+            assert_eq!(git_commit.author().name(), Some("Jane Doe"));
+            assert_eq!(git_commit.author().email(), Some("jane@example.com"));
+            assert_eq!(git_commit.author().when().seconds(), 1_500_000_000);
+            assert_eq!(git_commit.author().when().offset_minutes(), 120);
+            assert_eq!(git_commit.committer().name(), Some("Jane Doe"));
+            assert_eq!(git_commit.message(), Some("modify file\n"));
+            assert!(git_commit
+                .raw_header()
+                .unwrap_or("")
+                .contains("\nHG:extra rebase_source:some%20value\n"));
+
+            tmp_dir.close()?;
+
+            Ok(())
+        })
+    }
+
+    #[fbinit::test]
+    fn fetch_imported_object_test(fb: FacebookInit) -> Result<(), Error> {
+        let mut runtime = Runtime::new()?;
+        runtime.block_on_std(async move {
+            let ctx = CoreContext::test_mock(fb);
+            let repo = blobrepo_factory::new_memblob_empty(None)?;
+
+            // Signed commits can't be generated from a Bonsai changeset, their
+            // object is kept as it was in Git.
+            let object = Bytes::from_static(
+                b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+                  author Jane Doe <jane@example.com> 1500000000 +0200\n\
+                  committer Jane Doe <jane@example.com> 1500000000 +0200\n\
+                  gpgsig -----BEGIN PGP SIGNATURE-----\n \n not really a signature\n \
+                  -----END PGP SIGNATURE-----\n\
+                  \n\
+                  signed commit\n",
+            );
+            let tmp_dir = TempDir::new("git_types_test")?;
+            let git = Repository::init(tmp_dir.path())?;
+            let oid = git.odb()?.write(ObjectType::Commit, &object)?;
+
+            let git_sha1 =
+                store_imported_commit(ctx.clone(), repo.blobstore(), object.clone()).await?;
+            assert_eq!(Oid::from_bytes(git_sha1.as_ref())?, oid);
+
+            let commit_id = MappedGitCommitId(git_sha1);
+            assert!(commit_id
+                .fetch_commit(ctx.clone(), repo.blobstore())
+                .await?
+                .is_none());
+            assert_eq!(
+                commit_id
+                    .fetch_object(ctx.clone(), repo.blobstore())
+                    .await?,
+                Some(object)
+            );
+
+            tmp_dir.close()?;
+
+            Ok(())
+        })
+    }
+}
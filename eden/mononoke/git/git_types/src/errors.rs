@@ -16,4 +16,6 @@ pub enum ErrorKind {
     TreeDerivationFailed,
     #[error("Invalid Thrift")]
     InvalidThrift,
+    #[error("Invalid header name: {0:?}")]
+    InvalidHeaderName(String),
 }
@@ -15,6 +15,7 @@ pub enum ObjectKind {
     Blob,
     Tree,
     Commit,
+    Tag,
 }
 
 impl ObjectKind {
@@ -23,6 +24,7 @@ impl ObjectKind {
             Self::Blob => "blob",
             Self::Tree => "tree",
             Self::Commit => "commit",
+            Self::Tag => "tag",
         }
     }
 
@@ -31,6 +33,7 @@ impl ObjectKind {
             Self::Blob => false,
             Self::Tree => true,
             Self::Commit => false,
+            Self::Tag => false,
         }
     }
 
@@ -14,7 +14,7 @@ use futures_old::Future;
 use std::convert::TryFrom;
 use std::convert::TryInto;
 
-use crate::{thrift, Tree, TreeHandle};
+use crate::{thrift, Commit, CommitHandle, Tree, TreeHandle};
 
 macro_rules! impl_blobstore_conversions {
     ($ty:ident) => {
@@ -97,3 +97,4 @@ macro_rules! impl_loadable_storable {
 }
 
 impl_loadable_storable!(TreeHandle, Tree);
+impl_loadable_storable!(CommitHandle, Commit);
@@ -0,0 +1,252 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use clap::{Arg, ArgMatches, SubCommand};
+use cmdlib::args;
+use cmdlib::helpers::{block_execute, csid_resolve};
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::compat::Future01CompatExt;
+use mononoke_types::{hash::GitSha1, ChangesetId};
+use packfile::{export_pack, PackOptions};
+use slog::info;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const SUBCOMMAND_BOOKMARK: &str = "bookmark";
+const SUBCOMMAND_RANGE: &str = "range";
+
+const ARG_OUTPUT: &str = "output";
+const ARG_THIN: &str = "thin";
+const ARG_BOOKMARK: &str = "bookmark";
+const ARG_FROM: &str = "from";
+const ARG_TO: &str = "to";
+const ARG_REF_NAME: &str = "ref-name";
+
+const DEFAULT_REF_NAME: &str = "master";
+
+/// Commits to export, and the branch to point at the last one.
+struct ExportTarget {
+    head: ChangesetId,
+    excludes: Vec<ChangesetId>,
+    ref_name: String,
+}
+
+impl ExportTarget {
+    async fn from_matches(
+        ctx: &CoreContext,
+        repo: &BlobRepo,
+        matches: &ArgMatches<'_>,
+    ) -> Result<Self, Error> {
+        let resolve = |rev: &str| csid_resolve(ctx.clone(), repo.clone(), rev).compat();
+
+        match matches.subcommand() {
+            (SUBCOMMAND_BOOKMARK, Some(sub_matches)) => {
+                let bookmark = sub_matches.value_of(ARG_BOOKMARK).unwrap();
+                Ok(Self {
+                    head: resolve(bookmark).await?,
+                    excludes: vec![],
+                    ref_name: bookmark.to_string(),
+                })
+            }
+            (SUBCOMMAND_RANGE, Some(sub_matches)) => Ok(Self {
+                head: resolve(sub_matches.value_of(ARG_TO).unwrap()).await?,
+                excludes: vec![resolve(sub_matches.value_of(ARG_FROM).unwrap()).await?],
+                ref_name: sub_matches
+                    .value_of(ARG_REF_NAME)
+                    .unwrap_or(DEFAULT_REF_NAME)
+                    .to_string(),
+            }),
+            _ => Err(Error::msg("A valid subcommand is required")),
+        }
+    }
+}
+
+/// Writes a thin pack to `path`. It has to be fixed with
+/// `git index-pack --fix-thin` in a repository that has the excluded commits.
+async fn write_thin_pack(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    target: ExportTarget,
+    path: &Path,
+) -> Result<(), Error> {
+    let options = PackOptions {
+        thin: true,
+        ..Default::default()
+    };
+    let writer = BufWriter::new(File::create(path)?);
+    let (_, exported) = export_pack(
+        ctx,
+        repo,
+        vec![target.head],
+        target.excludes,
+        &options,
+        writer,
+    )
+    .await?;
+
+    let head = exported.heads[&target.head];
+    println!("{} refs/heads/{}", head, target.ref_name);
+    info!(
+        ctx.logger(),
+        "Wrote thin pack with {} objects to {}",
+        exported.summary.entries.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Writes the pack and its index to the bare Git repository at `path`,
+/// creating it if needed, and points the branch at the exported head.
+async fn write_to_bare_repo(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    target: ExportTarget,
+    path: &Path,
+) -> Result<(), Error> {
+    init_bare_repo(path, &target.ref_name)?;
+
+    let pack_dir = path.join("objects").join("pack");
+    let tmp_pack_path = pack_dir.join("tmp_pack_gitexport");
+    let writer = BufWriter::new(File::create(&tmp_pack_path)?);
+    let (_, exported) = export_pack(
+        ctx,
+        repo,
+        vec![target.head],
+        target.excludes,
+        &PackOptions::default(),
+        writer,
+    )
+    .await?;
+
+    let pack_name = format!("pack-{}", exported.summary.checksum);
+    let mut index = BufWriter::new(File::create(pack_dir.join(format!("{}.idx", pack_name)))?);
+    exported.summary.write_index(&mut index)?;
+    index.flush()?;
+    fs::rename(&tmp_pack_path, pack_dir.join(format!("{}.pack", pack_name)))?;
+
+    let head = exported.heads[&target.head];
+    write_ref(path, &target.ref_name, &head)?;
+    info!(
+        ctx.logger(),
+        "Wrote {} with {} objects, {} is at {}",
+        pack_name,
+        exported.summary.entries.len(),
+        target.ref_name,
+        head
+    );
+    Ok(())
+}
+
+fn init_bare_repo(path: &Path, ref_name: &str) -> Result<(), Error> {
+    fs::create_dir_all(path.join("objects").join("pack"))?;
+    fs::create_dir_all(path.join("objects").join("info"))?;
+    fs::create_dir_all(path.join("refs").join("heads"))?;
+    fs::create_dir_all(path.join("refs").join("tags"))?;
+
+    let head = path.join("HEAD");
+    if !head.exists() {
+        fs::write(head, format!("ref: refs/heads/{}\n", ref_name))?;
+    }
+    let config = path.join("config");
+    if !config.exists() {
+        fs::write(
+            config,
+            "[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = true\n",
+        )?;
+    }
+    Ok(())
+}
+
+fn write_ref(path: &Path, ref_name: &str, oid: &GitSha1) -> Result<(), Error> {
+    if ref_name
+        .split('/')
+        .any(|part| part.is_empty() || part == "..")
+    {
+        return Err(format_err!("Invalid ref name: {}", ref_name));
+    }
+    let ref_path = path.join("refs").join("heads").join(ref_name);
+    if let Some(parent) = ref_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(ref_path, format!("{}\n", oid))?;
+    Ok(())
+}
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<(), Error> {
+    let app = args::MononokeApp::new("Mononoke Git Exporter")
+        .with_advanced_args_hidden()
+        .build()
+        .arg(
+            Arg::with_name(ARG_OUTPUT)
+                .long(ARG_OUTPUT)
+                .help("Bare Git repository to write to, or pack file to write for thin packs")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_THIN)
+                .long(ARG_THIN)
+                .help("Write a thin pack, using objects of the excluded commits as delta bases")
+                .required(false)
+                .takes_value(false),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_BOOKMARK)
+                .about("Export all the ancestors of a bookmark")
+                .arg(
+                    Arg::with_name(ARG_BOOKMARK)
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_RANGE)
+                .about("Export the ancestors of a commit that are not ancestors of another one")
+                .arg(Arg::with_name(ARG_FROM).required(true).takes_value(true))
+                .arg(Arg::with_name(ARG_TO).required(true).takes_value(true))
+                .arg(
+                    Arg::with_name(ARG_REF_NAME)
+                        .long(ARG_REF_NAME)
+                        .help("Branch to point at the last exported commit")
+                        .takes_value(true),
+                ),
+        );
+
+    let matches = app.get_matches();
+
+    args::init_cachelib(fb, &matches, None);
+    let logger = args::init_logging(fb, &matches);
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+
+    let repo = args::open_repo(fb, &logger, &matches);
+    let output = Path::new(matches.value_of(ARG_OUTPUT).unwrap());
+    let thin = matches.is_present(ARG_THIN);
+
+    block_execute(
+        async {
+            let repo = repo.compat().await?;
+            let target = ExportTarget::from_matches(&ctx, &repo, &matches).await?;
+            if thin {
+                write_thin_pack(&ctx, &repo, target, output).await
+            } else {
+                write_to_bare_repo(&ctx, &repo, target, output).await
+            }
+        },
+        fb,
+        "gitexport",
+        &logger,
+        &matches,
+        cmdlib::monitoring::AliveService,
+    )
+}
@@ -10,10 +10,12 @@
 mod git_pool;
 mod mem_writes_bonsai_hg_mapping;
 mod mem_writes_changesets;
+mod mirror;
 
 use anyhow::{format_err, Context, Error};
 use blobrepo::{BlobRepo, DangerousOverride};
 use blobstore::{Blobstore, LoadableError};
+use bonsai_git_mapping::BonsaiGitMappingEntry;
 use bonsai_hg_mapping::BonsaiHgMapping;
 use bytes::Bytes;
 use cacheblob::{dummy::DummyLease, LeaseOps, MemWritesBlobstore};
@@ -37,7 +39,7 @@ use futures_old::{
     stream::{self as stream_old, Stream},
 };
 use git2::{ObjectType, Oid, Repository, Revwalk, Sort};
-use git_types::{mode, TreeHandle};
+use git_types::{mode, store_imported_commit, TreeHandle};
 use linked_hash_map::LinkedHashMap;
 use manifest::{bonsai_diff, BonsaiDiffFileChange, Entry, Manifest, StoreLoadable};
 use mercurial_types::HgManifestId;
@@ -58,12 +60,14 @@ use tokio::task;
 use crate::git_pool::GitPool;
 use crate::mem_writes_bonsai_hg_mapping::MemWritesBonsaiHgMapping;
 use crate::mem_writes_changesets::MemWritesChangesets;
+use crate::mirror::{mirrored_refs, update_bookmarks, BookmarkNaming, MirroredRef};
 
 // Refactor this a bit. Use a thread pool for git operations. Pass that wherever we use store repo.
 // Transform the walk into a stream of commit + file changes.
 
 const SUBCOMMAND_FULL_REPO: &str = "full-repo";
 const SUBCOMMAND_GIT_RANGE: &str = "git-range";
+const SUBCOMMAND_MIRROR: &str = "mirror";
 
 const ARG_GIT_REPOSITORY_PATH: &str = "git-repository-path";
 const ARG_DERIVE_TREES: &str = "derive-trees";
@@ -73,6 +77,15 @@ const ARG_HGGIT_COMPATIBILITY: &str = "hggit-compatibility";
 const ARG_GIT_FROM: &str = "git-from";
 const ARG_GIT_TO: &str = "git-to";
 
+const ARG_BRANCH_BOOKMARK_TEMPLATE: &str = "branch-bookmark-template";
+const ARG_TAG_BOOKMARK_TEMPLATE: &str = "tag-bookmark-template";
+
+const DEFAULT_BRANCH_BOOKMARK_TEMPLATE: &str = "{}";
+const DEFAULT_TAG_BOOKMARK_TEMPLATE: &str = "tags/{}";
+
+/// Number of commits looked up at once in the Bonsai Git mapping.
+const MAPPING_LOOKUP_CHUNK_SIZE: usize = 1000;
+
 const HGGIT_COMMIT_ID_EXTRA: &str = "convert_revision";
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
@@ -101,14 +114,34 @@ impl GitimportPreferences {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 enum GitimportTarget {
     FullRepo,
     GitRange(Oid, Oid),
+    /// Import the commits of all the branches and tags that are not imported
+    /// yet, then mirror these refs as bookmarks.
+    Mirror(BookmarkNaming),
 }
 
 impl GitimportTarget {
-    fn populate_walk(&self, repo: &Repository, walk: &mut Revwalk) -> Result<(), Error> {
+    fn mirrored_refs(
+        &self,
+        ctx: &CoreContext,
+        repo: &Repository,
+    ) -> Result<Vec<MirroredRef>, Error> {
+        match self {
+            Self::FullRepo | Self::GitRange(..) => Ok(vec![]),
+            Self::Mirror(naming) => mirrored_refs(ctx, repo, naming),
+        }
+    }
+
+    fn populate_walk(
+        &self,
+        repo: &Repository,
+        refs: &[MirroredRef],
+        roots: &HashMap<Oid, ChangesetId>,
+        walk: &mut Revwalk,
+    ) -> Result<(), Error> {
         match self {
             Self::FullRepo => {
                 for reference in repo.references()? {
@@ -122,6 +155,14 @@ impl GitimportTarget {
                 walk.hide(*from)?;
                 walk.push(*to)?;
             }
+            Self::Mirror(..) => {
+                for mirrored_ref in refs {
+                    walk.push(mirrored_ref.commit)?;
+                }
+                for root in roots.keys() {
+                    walk.hide(*root)?;
+                }
+            }
         };
 
         Ok(())
@@ -131,6 +172,7 @@ impl GitimportTarget {
         &self,
         _ctx: &CoreContext,
         repo: &BlobRepo,
+        refs: &[MirroredRef],
         roots: &mut HashMap<Oid, ChangesetId>,
     ) -> Result<(), Error> {
         match self {
@@ -151,10 +193,60 @@ impl GitimportTarget {
 
                 roots.insert(*from, root);
             }
+            Self::Mirror(..) => {
+                let oids = refs
+                    .iter()
+                    .map(|mirrored_ref| mirrored_ref.commit)
+                    .collect();
+                roots.extend(find_imported(repo, oids).await?);
+            }
         };
 
         Ok(())
     }
+
+    /// Removes the commits that were already imported from the commits to
+    /// import, and adds them to the roots. Only needed when mirroring, as
+    /// the other targets always import all the commits they walk.
+    async fn filter_imported(
+        &self,
+        repo: &BlobRepo,
+        oids: Vec<Oid>,
+        roots: &mut HashMap<Oid, ChangesetId>,
+    ) -> Result<Vec<Oid>, Error> {
+        match self {
+            Self::FullRepo | Self::GitRange(..) => Ok(oids),
+            Self::Mirror(..) => {
+                let imported = find_imported(repo, oids.clone()).await?;
+                let oids = oids
+                    .into_iter()
+                    .filter(|oid| !imported.contains_key(oid))
+                    .collect();
+                roots.extend(imported);
+                Ok(oids)
+            }
+        }
+    }
+}
+
+/// Looks up the commits in the Bonsai Git mapping, returning the ones that
+/// were already imported.
+async fn find_imported(
+    repo: &BlobRepo,
+    oids: Vec<Oid>,
+) -> Result<HashMap<Oid, ChangesetId>, Error> {
+    let mut imported = HashMap::new();
+    for chunk in oids.chunks(MAPPING_LOOKUP_CHUNK_SIZE) {
+        let git_sha1s = chunk
+            .iter()
+            .map(|oid| GitSha1::from_bytes(oid))
+            .collect::<Result<Vec<_>, _>>()?;
+        let entries = repo.bonsai_git_mapping().get(git_sha1s.into()).await?;
+        for entry in entries {
+            imported.insert(Oid::from_bytes(entry.git_sha1.as_ref())?, entry.bcs_id);
+        }
+    }
+    Ok(imported)
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
@@ -303,6 +395,8 @@ struct CommitMetadata {
     author: String,
     message: String,
     author_date: DateTime,
+    /// The commit object, as stored in the Git object database.
+    object: Bytes,
 }
 
 struct ExtractedCommit {
@@ -340,6 +434,8 @@ impl ExtractedCommit {
             let time = commit.time();
             let author_date = DateTime::from_timestamp(time.seconds(), time.offset_minutes() * 60)?;
 
+            let object = Bytes::copy_from_slice(repo.odb()?.read(oid)?.data());
+
             Result::<_, Error>::Ok(ExtractedCommit {
                 metadata: CommitMetadata {
                     oid: commit.id(),
@@ -347,6 +443,7 @@ impl ExtractedCommit {
                     message,
                     author,
                     author_date,
+                    object,
                 },
                 tree,
                 parent_trees,
@@ -366,27 +463,28 @@ async fn gitimport(
     let walk_repo = Repository::open(&path)?;
     let pool = &GitPool::new(path.to_path_buf())?;
 
-    let mut walk = walk_repo.revwalk()?;
-    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
-    target.populate_walk(&walk_repo, &mut walk)?;
+    let refs = target.mirrored_refs(ctx, &walk_repo)?;
 
-    // TODO: Don't import everything in one go. Instead, hide things we already imported from the
-    // traversal.
+    let mut roots = HashMap::new();
+    target
+        .populate_roots(&ctx, &repo, &refs, &mut roots)
+        .await?;
 
-    let roots = &{
-        let mut roots = HashMap::new();
-        target.populate_roots(&ctx, &repo, &mut roots).await?;
-        roots
-    };
+    let mut walk = walk_repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    target.populate_walk(&walk_repo, &refs, &roots, &mut walk)?;
+    let oids = walk
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| "While walking commits")?;
+    let oids = target.filter_imported(&repo, oids, &mut roots).await?;
+    let roots = &roots;
 
     // Kick off a stream that consumes the walk and prepared commits. Then, produce the Bonsais.
 
     // TODO: Make concurrency configurable below.
 
-    let import_map: LinkedHashMap<Oid, (ChangesetId, BonsaiChangeset)> = stream::iter(walk)
+    let import_map: LinkedHashMap<Oid, (ChangesetId, BonsaiChangeset)> = stream::iter(oids)
         .map(|oid| async move {
-            let oid = oid.with_context(|| "While walking commits")?;
-
             let ExtractedCommit {
                 metadata,
                 tree,
@@ -419,6 +517,7 @@ async fn gitimport(
                         author,
                         message,
                         author_date,
+                        object,
                     } = metadata;
 
                     let mut extra = BTreeMap::new();
@@ -465,6 +564,10 @@ async fn gitimport(
                         .compat()
                         .await?;
 
+                    // Exporting the commit back to Git needs its original object, which can't be
+                    // generated from the Bonsai changeset.
+                    store_imported_commit(ctx.clone(), repo.blobstore(), object).await?;
+
                     repo.get_changesets_object()
                         .add(
                             ctx.clone(),
@@ -477,6 +580,17 @@ async fn gitimport(
                         .compat()
                         .await?;
 
+                    // Record the mapping once the changeset is stored, so that an interrupted
+                    // mirror resumes from the first commit that was not imported.
+                    if !prefs.dry_run {
+                        repo.bonsai_git_mapping()
+                            .bulk_add(&[BonsaiGitMappingEntry::new(
+                                GitSha1::from_bytes(oid)?,
+                                bcs_id,
+                            )])
+                            .await?;
+                    }
+
                     info!(ctx.logger(), "Created {:?} => {:?}", oid, bcs_id);
 
                     import_map.insert(oid, (bcs_id, bcs));
@@ -559,6 +673,12 @@ async fn gitimport(
         }
     }
 
+    if let GitimportTarget::Mirror(naming) = &target {
+        let mut changesets = roots.clone();
+        changesets.extend(import_map.iter().map(|(oid, (bcs_id, _))| (*oid, *bcs_id)));
+        update_bookmarks(ctx, repo, naming, &refs, &changesets, prefs.dry_run).await?;
+    }
+
     Ok(())
 }
 
@@ -596,6 +716,31 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
                         .takes_value(true),
                 )
                 .arg(Arg::with_name(ARG_GIT_TO).required(true).takes_value(true)),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_MIRROR)
+                .about(
+                    "Import the branches and tags that changed since the last mirror, \
+                     and set bookmarks to them",
+                )
+                .arg(
+                    Arg::with_name(ARG_BRANCH_BOOKMARK_TEMPLATE)
+                        .long(ARG_BRANCH_BOOKMARK_TEMPLATE)
+                        .help(
+                            "Name of the bookmark for a branch, {} is replaced by the branch name. \
+                             Bookmarks of deleted branches are only deleted if the template has \
+                             more than {}",
+                        )
+                        .takes_value(true)
+                        .default_value(DEFAULT_BRANCH_BOOKMARK_TEMPLATE),
+                )
+                .arg(
+                    Arg::with_name(ARG_TAG_BOOKMARK_TEMPLATE)
+                        .long(ARG_TAG_BOOKMARK_TEMPLATE)
+                        .help("Name of the bookmark for a tag, {} is replaced by the tag name")
+                        .takes_value(true)
+                        .default_value(DEFAULT_TAG_BOOKMARK_TEMPLATE),
+                ),
         );
 
     let mut prefs = GitimportPreferences::default();
@@ -627,6 +772,12 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
             let to = range_matches.value_of(ARG_GIT_TO).unwrap().parse()?;
             GitimportTarget::GitRange(from, to)
         }
+        (SUBCOMMAND_MIRROR, Some(mirror_matches)) => GitimportTarget::Mirror(BookmarkNaming::new(
+            mirror_matches
+                .value_of(ARG_BRANCH_BOOKMARK_TEMPLATE)
+                .unwrap(),
+            mirror_matches.value_of(ARG_TAG_BOOKMARK_TEMPLATE).unwrap(),
+        )?),
         _ => {
             return Err(Error::msg("A valid subcommand is required"));
         }
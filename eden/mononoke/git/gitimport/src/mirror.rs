@@ -0,0 +1,359 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use bookmarks::{BookmarkName, BookmarkUpdateReason};
+use context::CoreContext;
+use futures::compat::Future01CompatExt;
+use futures_old::Stream;
+use git2::{Oid, Repository};
+use mononoke_types::ChangesetId;
+use slog::{info, warn};
+use std::collections::{HashMap, HashSet};
+
+const BRANCH_REF_PREFIX: &str = "refs/heads/";
+const TAG_REF_PREFIX: &str = "refs/tags/";
+
+/// Placeholder for the branch or tag name in bookmark name templates.
+const TEMPLATE_PLACEHOLDER: &str = "{}";
+
+/// How the branches and tags of a Git repository are named as bookmarks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BookmarkNaming {
+    branch_template: String,
+    tag_template: String,
+}
+
+impl BookmarkNaming {
+    /// Templates must contain `{}`, which is replaced by the name of the
+    /// branch or tag.
+    pub fn new(branch_template: &str, tag_template: &str) -> Result<Self, Error> {
+        for template in &[branch_template, tag_template] {
+            if !template.contains(TEMPLATE_PLACEHOLDER) {
+                return Err(format_err!(
+                    "Bookmark template {:?} does not contain {}",
+                    template,
+                    TEMPLATE_PLACEHOLDER
+                ));
+            }
+        }
+        Ok(Self {
+            branch_template: branch_template.to_string(),
+            tag_template: tag_template.to_string(),
+        })
+    }
+
+    /// Name of the bookmark for a ref, if it's a branch or a tag.
+    pub fn bookmark_name(&self, ref_name: &str) -> Option<String> {
+        if let Some(branch) = ref_name.strip_prefix(BRANCH_REF_PREFIX) {
+            Some(self.branch_template.replace(TEMPLATE_PLACEHOLDER, branch))
+        } else if let Some(tag) = ref_name.strip_prefix(TAG_REF_PREFIX) {
+            Some(self.tag_template.replace(TEMPLATE_PLACEHOLDER, tag))
+        } else {
+            None
+        }
+    }
+
+    /// Whether a bookmark is named like a mirrored branch or tag, i.e. it
+    /// matches one of the templates with a non-empty name in place of `{}`.
+    /// A template that is only `{}` matches any bookmark, including the ones
+    /// created in Mononoke, so no bookmark is considered mirrored by it.
+    pub fn is_mirrored(&self, bookmark: &str) -> bool {
+        [&self.branch_template, &self.tag_template]
+            .iter()
+            .any(|template| {
+                let (prefix, suffix) =
+                    template.split_at(template.find(TEMPLATE_PLACEHOLDER).unwrap_or(0));
+                let suffix = &suffix[TEMPLATE_PLACEHOLDER.len()..];
+                (!prefix.is_empty() || !suffix.is_empty())
+                    && bookmark.len() > prefix.len() + suffix.len()
+                    && bookmark.starts_with(prefix)
+                    && bookmark.ends_with(suffix)
+            })
+    }
+}
+
+/// A branch or tag to mirror, and the commit it points to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirroredRef {
+    pub ref_name: String,
+    pub bookmark: BookmarkName,
+    pub commit: Oid,
+}
+
+/// Lists the branches and tags of a Git repository that are mirrored as
+/// bookmarks. Annotated tags are peeled to the commit they tag, tags of other
+/// objects are skipped.
+pub fn mirrored_refs(
+    ctx: &CoreContext,
+    repo: &Repository,
+    naming: &BookmarkNaming,
+) -> Result<Vec<MirroredRef>, Error> {
+    let mut refs: Vec<MirroredRef> = Vec::new();
+    let mut ref_by_bookmark: HashMap<BookmarkName, String> = HashMap::new();
+
+    for reference in repo.references()? {
+        let reference = reference?;
+        let ref_name = match reference.name() {
+            Some(ref_name) => ref_name.to_string(),
+            None => {
+                warn!(ctx.logger(), "Skipping ref with a non UTF-8 name");
+                continue;
+            }
+        };
+        let bookmark = match naming.bookmark_name(&ref_name) {
+            Some(bookmark) => BookmarkName::new(bookmark)?,
+            None => continue,
+        };
+        let commit = match reference.peel_to_commit() {
+            Ok(commit) => commit.id(),
+            Err(e) => {
+                warn!(ctx.logger(), "Skipping {}: {}", ref_name, e);
+                continue;
+            }
+        };
+
+        if let Some(other) = ref_by_bookmark.insert(bookmark.clone(), ref_name.clone()) {
+            return Err(format_err!(
+                "Both {} and {} are mirrored as bookmark {}",
+                other,
+                ref_name,
+                bookmark
+            ));
+        }
+        refs.push(MirroredRef {
+            ref_name,
+            bookmark,
+            commit,
+        });
+    }
+
+    Ok(refs)
+}
+
+/// Points the bookmarks at the changesets the mirrored refs were imported
+/// as, and deletes the bookmarks named like mirrored refs whose ref is gone
+/// upstream. All the bookmarks are updated in one transaction, which fails if
+/// any of them was moved concurrently.
+pub async fn update_bookmarks(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    naming: &BookmarkNaming,
+    refs: &[MirroredRef],
+    changesets: &HashMap<Oid, ChangesetId>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let mut txn = repo.update_bookmark_transaction(ctx.clone());
+    let mut updated = 0;
+
+    for MirroredRef {
+        ref_name,
+        bookmark,
+        commit,
+    } in refs
+    {
+        let cs_id = *changesets
+            .get(commit)
+            .ok_or_else(|| format_err!("Commit of {} was not imported: {}", ref_name, commit))?;
+        let old_cs_id = repo
+            .get_bonsai_bookmark(ctx.clone(), bookmark)
+            .compat()
+            .await?;
+
+        match old_cs_id {
+            Some(old_cs_id) if old_cs_id == cs_id => continue,
+            Some(old_cs_id) => {
+                txn.update(bookmark, cs_id, old_cs_id, BookmarkUpdateReason::Blobimport)?
+            }
+            None => txn.create(bookmark, cs_id, BookmarkUpdateReason::Blobimport)?,
+        }
+        info!(
+            ctx.logger(),
+            "Bookmark {} ({}): {:?} => {:?}", bookmark, ref_name, old_cs_id, cs_id
+        );
+        updated += 1;
+    }
+
+    let mirrored: HashSet<_> = refs.iter().map(|r| &r.bookmark).collect();
+    let existing = repo
+        .get_bonsai_publishing_bookmarks_maybe_stale(ctx.clone())
+        .collect()
+        .compat()
+        .await?;
+    for (bookmark, old_cs_id) in existing {
+        let bookmark = bookmark.into_name();
+        if !naming.is_mirrored(bookmark.as_str()) || mirrored.contains(&bookmark) {
+            continue;
+        }
+        txn.delete(&bookmark, old_cs_id, BookmarkUpdateReason::Blobimport)?;
+        info!(
+            ctx.logger(),
+            "Bookmark {} (ref gone): {:?} => None", bookmark, old_cs_id
+        );
+        updated += 1;
+    }
+
+    if updated > 0 && !dry_run {
+        if !txn.commit().compat().await? {
+            return Err(format_err!("Bookmarks were moved while importing"));
+        }
+    }
+    info!(ctx.logger(), "{} bookmark(s) updated", updated);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blobrepo_factory::new_memblob_empty;
+    use fbinit::FacebookInit;
+    use maplit::hashmap;
+    use tests_utils::{bookmark, CreateCommitContext};
+
+    #[test]
+    fn test_bookmark_naming() -> Result<(), Error> {
+        let naming = BookmarkNaming::new("{}", "tags/{}")?;
+        assert_eq!(
+            naming.bookmark_name("refs/heads/master"),
+            Some("master".to_string())
+        );
+        assert_eq!(
+            naming.bookmark_name("refs/heads/feature/x"),
+            Some("feature/x".to_string())
+        );
+        assert_eq!(
+            naming.bookmark_name("refs/tags/v1.0"),
+            Some("tags/v1.0".to_string())
+        );
+        assert_eq!(naming.bookmark_name("refs/remotes/origin/master"), None);
+        assert_eq!(naming.bookmark_name("refs/notes/commits"), None);
+
+        let naming = BookmarkNaming::new("upstream/{}", "upstream/tags/{}")?;
+        assert_eq!(
+            naming.bookmark_name("refs/heads/master"),
+            Some("upstream/master".to_string())
+        );
+        assert_eq!(
+            naming.bookmark_name("refs/tags/v1.0"),
+            Some("upstream/tags/v1.0".to_string())
+        );
+
+        assert!(BookmarkNaming::new("master", "tags/{}").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bookmark_naming_is_mirrored() -> Result<(), Error> {
+        let naming = BookmarkNaming::new("upstream/{}", "upstream/tags/{}")?;
+        assert!(naming.is_mirrored("upstream/master"));
+        assert!(naming.is_mirrored("upstream/tags/v1.0"));
+        assert!(!naming.is_mirrored("upstream/"));
+        assert!(!naming.is_mirrored("master"));
+
+        let naming = BookmarkNaming::new("{}-upstream", "tags/{}")?;
+        assert!(naming.is_mirrored("master-upstream"));
+        assert!(naming.is_mirrored("tags/v1.0"));
+        assert!(!naming.is_mirrored("-upstream"));
+        assert!(!naming.is_mirrored("master"));
+
+        // Bookmarks named like the branches of the default template can't be
+        // told apart from other bookmarks.
+        let naming = BookmarkNaming::new("{}", "tags/{}")?;
+        assert!(!naming.is_mirrored("master"));
+        assert!(!naming.is_mirrored("upstream/master"));
+        assert!(naming.is_mirrored("tags/v1.0"));
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_update_bookmarks_deletes_gone_refs(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = new_memblob_empty(None)?;
+        let naming = BookmarkNaming::new("upstream/{}", "upstream/tags/{}")?;
+
+        let first = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("a", "a")
+            .commit()
+            .await?;
+        let second = CreateCommitContext::new(&ctx, &repo, vec![first])
+            .add_file("b", "b")
+            .commit()
+            .await?;
+        let master = bookmark(&ctx, &repo, "upstream/master")
+            .set_to(first)
+            .await?;
+        let feature = bookmark(&ctx, &repo, "upstream/feature")
+            .set_to(first)
+            .await?;
+        let local = bookmark(&ctx, &repo, "local").set_to(first).await?;
+
+        // The feature branch was deleted upstream, and master moved.
+        let oid = Oid::from_bytes(&[1; 20])?;
+        let refs = vec![MirroredRef {
+            ref_name: "refs/heads/master".to_string(),
+            bookmark: master.clone(),
+            commit: oid,
+        }];
+        let changesets = hashmap! { oid => second };
+        update_bookmarks(&ctx, &repo, &naming, &refs, &changesets, false).await?;
+
+        for (bookmark, expected) in &[
+            (master, Some(second)),
+            (feature, None),
+            (local, Some(first)),
+        ] {
+            let cs_id = repo
+                .get_bonsai_bookmark(ctx.clone(), bookmark)
+                .compat()
+                .await?;
+            assert_eq!(&cs_id, expected, "bookmark {}", bookmark);
+        }
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_update_bookmarks_default_naming(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = new_memblob_empty(None)?;
+        let naming = BookmarkNaming::new("{}", "tags/{}")?;
+
+        let cs_id = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("a", "a")
+            .commit()
+            .await?;
+        let master = bookmark(&ctx, &repo, "master").set_to(cs_id).await?;
+        let local = bookmark(&ctx, &repo, "local").set_to(cs_id).await?;
+        let tag = bookmark(&ctx, &repo, "tags/v1.0").set_to(cs_id).await?;
+
+        // Only master is left upstream. The other branch bookmarks can't be
+        // told apart from the bookmarks created in Mononoke, so they are
+        // kept, but the tag is gone.
+        let oid = Oid::from_bytes(&[1; 20])?;
+        let refs = vec![MirroredRef {
+            ref_name: "refs/heads/master".to_string(),
+            bookmark: master.clone(),
+            commit: oid,
+        }];
+        let changesets = hashmap! { oid => cs_id };
+        update_bookmarks(&ctx, &repo, &naming, &refs, &changesets, false).await?;
+
+        for (bookmark, expected) in &[(master, Some(cs_id)), (local, Some(cs_id)), (tag, None)] {
+            let cs_id = repo
+                .get_bonsai_bookmark(ctx.clone(), bookmark)
+                .compat()
+                .await?;
+            assert_eq!(&cs_id, expected, "bookmark {}", bookmark);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+
+/// Size of the blocks of the source that are indexed to find matches.
+const BLOCK_SIZE: usize = 16;
+
+/// Git supports larger copies, but older versions only handle copies of up
+/// to 64KiB.
+const MAX_COPY_SIZE: usize = 0x10000;
+
+/// Maximum number of literal bytes in a single insert instruction.
+const MAX_INSERT_SIZE: usize = 0x7f;
+
+/// Maximum number of positions indexed for identical blocks of the source.
+const MAX_BLOCK_POSITIONS: usize = 64;
+
+/// Creates a delta in the format used by Git, which transforms `source` into
+/// `target` by copying ranges of `source` and inserting literal bytes.
+pub fn create_delta(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_size(&mut delta, source.len());
+    write_size(&mut delta, target.len());
+
+    let mut index: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for offset in (0..source.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE) {
+        let positions = index
+            .entry(&source[offset..offset + BLOCK_SIZE])
+            .or_insert_with(Vec::new);
+        if positions.len() < MAX_BLOCK_POSITIONS {
+            positions.push(offset);
+        }
+    }
+
+    let mut insert_start = 0;
+    let mut pos = 0;
+    while pos + BLOCK_SIZE <= target.len() {
+        let best = index
+            .get(&target[pos..pos + BLOCK_SIZE])
+            .into_iter()
+            .flatten()
+            .map(|offset| {
+                let len = source[*offset..]
+                    .iter()
+                    .zip(target[pos..].iter())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                (*offset, len)
+            })
+            .max_by_key(|(_, len)| *len);
+
+        match best {
+            Some((offset, len)) => {
+                write_inserts(&mut delta, &target[insert_start..pos]);
+                write_copies(&mut delta, offset, len);
+                pos += len;
+                insert_start = pos;
+            }
+            None => pos += 1,
+        }
+    }
+    write_inserts(&mut delta, &target[insert_start..]);
+
+    delta
+}
+
+fn write_size(delta: &mut Vec<u8>, mut size: usize) {
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            delta.push(byte);
+            return;
+        }
+        delta.push(byte | 0x80);
+    }
+}
+
+fn write_inserts(delta: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT_SIZE) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+fn write_copies(delta: &mut Vec<u8>, mut offset: usize, mut len: usize) {
+    while len > 0 {
+        let size = std::cmp::min(len, MAX_COPY_SIZE);
+        let mut instruction = vec![0x80];
+        for i in 0..4 {
+            let byte = ((offset >> (8 * i)) & 0xff) as u8;
+            if byte != 0 {
+                instruction[0] |= 1 << i;
+                instruction.push(byte);
+            }
+        }
+        // A size of 0 stands for 0x10000.
+        if size != MAX_COPY_SIZE {
+            for i in 0..3 {
+                let byte = ((size >> (8 * i)) & 0xff) as u8;
+                if byte != 0 {
+                    instruction[0] |= 1 << (4 + i);
+                    instruction.push(byte);
+                }
+            }
+        }
+        delta.extend_from_slice(&instruction);
+        offset += size;
+        len -= size;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read_size(delta: &[u8], pos: &mut usize) -> usize {
+        let mut size = 0;
+        let mut shift = 0;
+        loop {
+            let byte = delta[*pos];
+            *pos += 1;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return size;
+            }
+        }
+    }
+
+    fn apply_delta(source: &[u8], delta: &[u8]) -> Vec<u8> {
+        let mut pos = 0;
+        assert_eq!(read_size(delta, &mut pos), source.len());
+        let target_size = read_size(delta, &mut pos);
+        let mut target = Vec::new();
+        while pos < delta.len() {
+            let instruction = delta[pos];
+            pos += 1;
+            if instruction & 0x80 != 0 {
+                let mut offset = 0;
+                let mut size = 0;
+                for i in 0..4 {
+                    if instruction & (1 << i) != 0 {
+                        offset |= (delta[pos] as usize) << (8 * i);
+                        pos += 1;
+                    }
+                }
+                for i in 0..3 {
+                    if instruction & (1 << (4 + i)) != 0 {
+                        size |= (delta[pos] as usize) << (8 * i);
+                        pos += 1;
+                    }
+                }
+                if size == 0 {
+                    size = 0x10000;
+                }
+                target.extend_from_slice(&source[offset..offset + size]);
+            } else {
+                let size = instruction as usize;
+                assert!(size > 0);
+                target.extend_from_slice(&delta[pos..pos + size]);
+                pos += size;
+            }
+        }
+        assert_eq!(target.len(), target_size);
+        target
+    }
+
+    fn check(source: &[u8], target: &[u8]) -> Vec<u8> {
+        let delta = create_delta(source, target);
+        assert_eq!(apply_delta(source, &delta), target);
+        delta
+    }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        check(b"", b"");
+        check(b"", b"new content");
+        check(b"old content", b"");
+        check(b"short", b"short");
+
+        let source: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let mut target = source.clone();
+        target[100] = 0xff;
+        target.splice(50_000..50_000, b"inserted".iter().cloned());
+        target.truncate(150_000);
+        let delta = check(&source, &target);
+        assert!(delta.len() < 200);
+    }
+
+    #[test]
+    fn test_delta_text() {
+        let source = b"line 1\nline 2\nline 3\nline 4\nline 5\nline 6\nline 7\n".repeat(10);
+        let mut target = source.clone();
+        target.extend_from_slice(b"appended line\n");
+        target.drain(0..14);
+        let delta = check(&source, &target);
+        assert!(delta.len() < target.len() / 4);
+    }
+}
@@ -0,0 +1,17 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use mononoke_types::{hash::GitSha1, ChangesetId};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("No Git commit object is stored for {0} (Git commit {1})")]
+    MissingCommitObject(ChangesetId, GitSha1),
+    #[error("Could not locate content of Git blob {0}")]
+    ContentMissing(GitSha1),
+}
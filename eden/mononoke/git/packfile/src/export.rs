@@ -0,0 +1,445 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use bytes::Bytes;
+use context::CoreContext;
+use derived_data::BonsaiDerived;
+use filestore::{self, Alias, FetchKey};
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    future, TryStreamExt,
+};
+use futures_old::Stream;
+use git_types::{BlobHandle, MappedGitCommitId, ObjectKind, TreeHandle, Treeish};
+use manifest::{find_intersection_of_diffs_and_parents, Entry};
+use mononoke_types::{hash::GitSha1, ChangesetId};
+use revset::DifferenceOfUnionsOfAncestorsNodeStream;
+use skiplist::SkiplistIndex;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::delta::create_delta;
+use crate::errors::ErrorKind;
+use crate::writer::{PackSummary, PackWriter};
+
+/// Blobs larger than this are never stored as deltas, as computing a delta
+/// requires having both the blob and its base in memory.
+const DEFAULT_DELTA_MAX_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// Whether deltas can use objects of the excluded commits as bases. The
+    /// resulting thin pack can only be used by a receiver that has them.
+    pub thin: bool,
+    /// Blobs larger than this are streamed as-is rather than stored as deltas.
+    pub delta_max_size: u64,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            thin: false,
+            delta_max_size: DEFAULT_DELTA_MAX_SIZE,
+        }
+    }
+}
+
+pub struct ExportedPack {
+    pub summary: PackSummary,
+    /// Git commit ids of the exported heads.
+    pub heads: HashMap<ChangesetId, GitSha1>,
+}
+
+enum PackObject {
+    /// A commit, and its serialized object.
+    Commit(GitSha1, Bytes),
+    Tree(TreeHandle),
+    /// A blob, and the blob it can be stored as a delta of.
+    Blob(BlobHandle, Option<BlobHandle>),
+}
+
+/// Writes a packfile with the Git objects of the ancestors of `heads` that
+/// are not ancestors of `excludes`.
+///
+/// Git commits are derived for all these commits. Commits imported from Git
+/// need the commit object stored by the import.
+pub async fn export_pack<W: Write>(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    heads: Vec<ChangesetId>,
+    excludes: Vec<ChangesetId>,
+    options: &PackOptions,
+    writer: W,
+) -> Result<(W, ExportedPack), Error> {
+    let objects = find_objects(ctx, repo, heads.clone(), excludes, options).await?;
+
+    let blobstore = repo.get_blobstore();
+    let mut pack = PackWriter::new(writer, objects.len().try_into()?)?;
+    for object in objects {
+        match object {
+            PackObject::Commit(oid, object) => {
+                pack.write_object(ObjectKind::Commit, oid, &object)?;
+            }
+            PackObject::Tree(handle) => {
+                let tree = handle.load(ctx.clone(), &blobstore).compat().await?;
+                let mut object_buff = Vec::new();
+                tree.write_serialized_object(&mut object_buff)?;
+                pack.write_object(ObjectKind::Tree, handle.oid().sha1(), &object_buff)?;
+            }
+            PackObject::Blob(blob, Some(base)) => {
+                let (content, base_content) =
+                    future::try_join(fetch_blob(ctx, repo, &blob), fetch_blob(ctx, repo, &base))
+                        .await?;
+                let delta = create_delta(&base_content, &content);
+                if delta.len() < content.len() {
+                    pack.write_ref_delta(blob.oid().sha1(), base.oid().sha1(), &delta)?;
+                } else {
+                    pack.write_object(ObjectKind::Blob, blob.oid().sha1(), &content)?;
+                }
+            }
+            PackObject::Blob(blob, None) => {
+                let oid = blob.oid().sha1();
+                let mut content = filestore::fetch(
+                    &blobstore,
+                    ctx.clone(),
+                    &FetchKey::Aliased(Alias::GitSha1(oid)),
+                )
+                .compat()
+                .await?
+                .ok_or(ErrorKind::ContentMissing(oid))?
+                .compat();
+
+                let mut object = pack.start_object(ObjectKind::Blob, oid, blob.oid().size())?;
+                while let Some(chunk) = content.try_next().await? {
+                    object.write_all(&chunk)?;
+                }
+                object.finish()?;
+            }
+        }
+    }
+    let (writer, summary) = pack.finish()?;
+
+    let heads = future::try_join_all(heads.into_iter().map(|cs_id| async move {
+        let git_commit_id = MappedGitCommitId::derive(ctx.clone(), repo.clone(), cs_id)
+            .compat()
+            .await?;
+        Result::<_, Error>::Ok((cs_id, *git_commit_id.oid()))
+    }))
+    .await?
+    .into_iter()
+    .collect();
+
+    Ok((writer, ExportedPack { summary, heads }))
+}
+
+/// Finds the objects to write in the pack, parents before their children.
+/// Only the trees and blobs that differ from all the parents of a commit
+/// are included, as the others are either already in the pack or part of an
+/// excluded commit.
+async fn find_objects(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    heads: Vec<ChangesetId>,
+    excludes: Vec<ChangesetId>,
+    options: &PackOptions,
+) -> Result<Vec<PackObject>, Error> {
+    let blobstore = repo.get_blobstore();
+    let mut commits = DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
+        ctx.clone(),
+        &repo.get_changeset_fetcher(),
+        Arc::new(SkiplistIndex::new()),
+        heads,
+        excludes,
+    )
+    .collect()
+    .compat()
+    .await?;
+    // Commits are returned by decreasing generation number.
+    commits.reverse();
+
+    let mut objects = Vec::new();
+    let mut seen = HashSet::new();
+    for cs_id in commits {
+        let git_commit_id = MappedGitCommitId::derive(ctx.clone(), repo.clone(), cs_id)
+            .compat()
+            .await?;
+        let object = git_commit_id
+            .fetch_object(ctx.clone(), &blobstore)
+            .await?
+            .ok_or(ErrorKind::MissingCommitObject(cs_id, *git_commit_id.oid()))?;
+
+        let parents = repo
+            .get_changeset_parents_by_bonsai(ctx.clone(), cs_id)
+            .compat()
+            .await?;
+        let parent_trees = future::try_join_all(
+            parents
+                .into_iter()
+                .map(|parent| TreeHandle::derive(ctx.clone(), repo.clone(), parent).compat()),
+        )
+        .await?;
+
+        let root = TreeHandle::derive(ctx.clone(), repo.clone(), cs_id)
+            .compat()
+            .await?;
+        if seen.insert(root.oid().sha1()) {
+            objects.push(PackObject::Tree(root));
+        }
+
+        let entries = find_intersection_of_diffs_and_parents(
+            ctx.clone(),
+            blobstore.clone(),
+            root,
+            parent_trees,
+        )
+        .compat()
+        .try_collect::<Vec<_>>()
+        .await?;
+        for (_path, entry, parent_entries) in entries {
+            match entry {
+                Entry::Tree(tree) => {
+                    if seen.insert(tree.oid().sha1()) {
+                        objects.push(PackObject::Tree(tree));
+                    }
+                }
+                Entry::Leaf(blob) => {
+                    if !seen.insert(blob.oid().sha1()) {
+                        continue;
+                    }
+                    let base = parent_entries.into_iter().find_map(|entry| match entry {
+                        Entry::Leaf(base)
+                            if blob.oid().size() <= options.delta_max_size
+                                && base.oid().size() <= options.delta_max_size
+                                && (options.thin || seen.contains(&base.oid().sha1())) =>
+                        {
+                            Some(base)
+                        }
+                        _ => None,
+                    });
+                    objects.push(PackObject::Blob(blob, base));
+                }
+            }
+        }
+
+        seen.insert(*git_commit_id.oid());
+        objects.push(PackObject::Commit(*git_commit_id.oid(), object));
+    }
+
+    Ok(objects)
+}
+
+async fn fetch_blob(ctx: &CoreContext, repo: &BlobRepo, blob: &BlobHandle) -> Result<Bytes, Error> {
+    let oid = blob.oid().sha1();
+    let content = filestore::fetch_concat_opt(
+        repo.blobstore(),
+        ctx.clone(),
+        &FetchKey::Aliased(Alias::GitSha1(oid)),
+    )
+    .compat()
+    .await?
+    .ok_or(ErrorKind::ContentMissing(oid))?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blobrepo::DangerousOverride;
+    use bonsai_git_mapping::BonsaiGitMappingEntry;
+    use fbinit::FacebookInit;
+    use git2::{ObjectType, Oid, Repository};
+    use git_types::store_imported_commit;
+    use maplit::btreeset;
+    use metaconfig_types::DerivedDataConfig;
+    use tempdir::TempDir;
+    use tests_utils::CreateCommitContext;
+    use tokio_compat::runtime::Runtime;
+
+    fn import_pack(git: &Repository, pack: &[u8]) -> Result<(), Error> {
+        let odb = git.odb()?;
+        let mut packwriter = odb.packwriter()?;
+        packwriter.write_all(pack)?;
+        packwriter.commit()?;
+        Ok(())
+    }
+
+    #[fbinit::test]
+    fn export_pack_test(fb: FacebookInit) -> Result<(), Error> {
+        let mut runtime = Runtime::new()?;
+        runtime.block_on_std(async move {
+            let ctx = CoreContext::test_mock(fb);
+            let repo = blobrepo_factory::new_memblob_empty(None)?.dangerous_override(
+                |mut derived_data_config: DerivedDataConfig| {
+                    derived_data_config.derived_data_types = btreeset! {
+                        MappedGitCommitId::NAME.to_string(),
+                        TreeHandle::NAME.to_string(),
+                    };
+                    derived_data_config
+                },
+            );
+
+            let content: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+            let root = CreateCommitContext::new_root(&ctx, &repo)
+                .add_file("file", content.clone())
+                .add_file("dir/a", "a\n")
+                .commit()
+                .await?;
+            let head = CreateCommitContext::new(&ctx, &repo, vec![root])
+                .add_file("file", content.replace("line 500\n", "modified\n"))
+                .add_file("dir/b", "b\n")
+                .commit()
+                .await?;
+
+            // Export the root commit, and check that the pack and its index
+            // are the same as the ones written by libgit.
+            let (pack, exported) = export_pack(
+                &ctx,
+                &repo,
+                vec![root],
+                vec![],
+                &PackOptions::default(),
+                Vec::new(),
+            )
+            .await?;
+            let tmp_dir = TempDir::new("packfile_test")?;
+            let git = Repository::init_bare(tmp_dir.path())?;
+            import_pack(&git, &pack)?;
+
+            let mut index = Vec::new();
+            exported.summary.write_index(&mut index)?;
+            let git_index = std::fs::read(tmp_dir.path().join(format!(
+                "objects/pack/pack-{}.idx",
+                exported.summary.checksum
+            )))?;
+            assert_eq!(index, git_index);
+
+            let root_oid = Oid::from_bytes(exported.heads[&root].as_ref())?;
+            let root_commit = git.find_commit(root_oid)?;
+            let root_tree = TreeHandle::derive(ctx.clone(), repo.clone(), root)
+                .compat()
+                .await?;
+            assert_eq!(
+                root_commit.tree_id(),
+                Oid::from_bytes(root_tree.oid().as_ref())?
+            );
+
+            // Export the head commit as a thin pack, which uses the previous
+            // version of the file as a delta base.
+            let (full_pack, _) = export_pack(
+                &ctx,
+                &repo,
+                vec![head],
+                vec![root],
+                &PackOptions::default(),
+                Vec::new(),
+            )
+            .await?;
+            let (thin_pack, exported) = export_pack(
+                &ctx,
+                &repo,
+                vec![head],
+                vec![root],
+                &PackOptions {
+                    thin: true,
+                    ..Default::default()
+                },
+                Vec::new(),
+            )
+            .await?;
+            assert!(thin_pack.len() < full_pack.len());
+            import_pack(&git, &thin_pack)?;
+
+            let head_oid = Oid::from_bytes(exported.heads[&head].as_ref())?;
+            let head_commit = git.find_commit(head_oid)?;
+            assert_eq!(head_commit.parent_ids().collect::<Vec<_>>(), vec![root_oid]);
+            let file = head_commit
+                .tree()?
+                .get_path(std::path::Path::new("file"))?
+                .to_object(&git)?
+                .peel_to_blob()?;
+            assert_eq!(
+                file.content(),
+                content.replace("line 500\n", "modified\n").as_bytes()
+            );
+
+            tmp_dir.close()?;
+
+            Ok(())
+        })
+    }
+
+    #[fbinit::test]
+    fn export_imported_commit_test(fb: FacebookInit) -> Result<(), Error> {
+        let mut runtime = Runtime::new()?;
+        runtime.block_on_std(async move {
+            let ctx = CoreContext::test_mock(fb);
+            let repo = blobrepo_factory::new_memblob_empty(None)?.dangerous_override(
+                |mut derived_data_config: DerivedDataConfig| {
+                    derived_data_config.derived_data_types = btreeset! {
+                        MappedGitCommitId::NAME.to_string(),
+                        TreeHandle::NAME.to_string(),
+                    };
+                    derived_data_config
+                },
+            );
+
+            // A signed commit imported from Git, whose object can't be
+            // generated from the Bonsai changeset.
+            let cs_id = CreateCommitContext::new_root(&ctx, &repo)
+                .add_file("file", "content\n")
+                .commit()
+                .await?;
+            let tree = TreeHandle::derive(ctx.clone(), repo.clone(), cs_id)
+                .compat()
+                .await?;
+            let object = format!(
+                "tree {}\n\
+                 author Jane Doe <jane@example.com> 1500000000 +0200\n\
+                 committer Jane Doe <jane@example.com> 1500000000 +0200\n\
+                 gpgsig -----BEGIN PGP SIGNATURE-----\n \n not really a signature\n \
+                 -----END PGP SIGNATURE-----\n\
+                 \n\
+                 signed commit\n",
+                tree.oid()
+            );
+            let git_sha1 =
+                store_imported_commit(ctx.clone(), repo.blobstore(), Bytes::from(object.clone()))
+                    .await?;
+            repo.bonsai_git_mapping()
+                .bulk_add(&[BonsaiGitMappingEntry::new(git_sha1, cs_id)])
+                .await?;
+
+            let (pack, exported) = export_pack(
+                &ctx,
+                &repo,
+                vec![cs_id],
+                vec![],
+                &PackOptions::default(),
+                Vec::new(),
+            )
+            .await?;
+            assert_eq!(exported.heads[&cs_id], git_sha1);
+
+            let tmp_dir = TempDir::new("packfile_test")?;
+            let git = Repository::init_bare(tmp_dir.path())?;
+            import_pack(&git, &pack)?;
+            let oid = Oid::from_bytes(git_sha1.as_ref())?;
+            let exported_object = git.odb()?.read(oid)?;
+            assert_eq!(exported_object.kind(), ObjectType::Commit);
+            assert_eq!(exported_object.data(), object.as_bytes());
+
+            tmp_dir.close()?;
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,67 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use mononoke_types::hash::GitSha1;
+use std::io::{self, Write};
+
+use crate::writer::{HashingWriter, IndexEntry};
+
+const INDEX_SIGNATURE: &[u8] = b"\xfftOc";
+const INDEX_VERSION: u32 = 2;
+
+/// Offsets that don't fit in 31 bits are stored in a separate table.
+const LARGE_OFFSET_FLAG: u32 = 0x8000_0000;
+
+/// Writes a version 2 pack index for the given entries.
+pub(crate) fn write_index(
+    writer: impl Write,
+    entries: &[IndexEntry],
+    pack_checksum: &GitSha1,
+) -> io::Result<()> {
+    let mut entries: Vec<_> = entries.iter().collect();
+    entries.sort_by_key(|entry| entry.oid);
+
+    let mut writer = HashingWriter::new(writer);
+    writer.write_all(INDEX_SIGNATURE)?;
+    writer.write_all(&INDEX_VERSION.to_be_bytes())?;
+
+    // Number of objects whose first byte is less than or equal to the index.
+    let mut fanout = [0u32; 256];
+    for entry in entries.iter() {
+        fanout[entry.oid.as_ref()[0] as usize] += 1;
+    }
+    let mut count = 0;
+    for bucket in fanout.iter() {
+        count += bucket;
+        writer.write_all(&count.to_be_bytes())?;
+    }
+
+    for entry in entries.iter() {
+        writer.write_all(entry.oid.as_ref())?;
+    }
+    for entry in entries.iter() {
+        writer.write_all(&entry.crc32.to_be_bytes())?;
+    }
+
+    let mut large_offsets = Vec::new();
+    for entry in entries.iter() {
+        let offset = if entry.offset < LARGE_OFFSET_FLAG as u64 {
+            entry.offset as u32
+        } else {
+            large_offsets.push(entry.offset);
+            LARGE_OFFSET_FLAG | (large_offsets.len() - 1) as u32
+        };
+        writer.write_all(&offset.to_be_bytes())?;
+    }
+    for offset in large_offsets {
+        writer.write_all(&offset.to_be_bytes())?;
+    }
+
+    writer.write_all(pack_checksum.as_ref())?;
+    writer.write_checksum()?;
+    Ok(())
+}
@@ -0,0 +1,20 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Writes Git packfiles and their indexes, and exports Mononoke commits as
+//! Git packfiles.
+
+mod delta;
+mod errors;
+mod export;
+mod index;
+mod writer;
+
+pub use crate::delta::create_delta;
+pub use crate::errors::ErrorKind;
+pub use crate::export::{export_pack, ExportedPack, PackOptions};
+pub use crate::writer::{IndexEntry, ObjectWriter, PackSummary, PackWriter};
@@ -0,0 +1,241 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crc32fast::Hasher as Crc32;
+use digest::Digest;
+use flate2::{write::ZlibEncoder, Compression};
+use git_types::ObjectKind;
+use mononoke_types::hash::GitSha1;
+use sha1::Sha1;
+use std::io::{self, Write};
+
+use crate::index::write_index;
+
+const PACK_SIGNATURE: &[u8] = b"PACK";
+const PACK_VERSION: u32 = 2;
+
+/// Object types as they are encoded in packfiles.
+const OBJ_COMMIT: u8 = 1;
+const OBJ_TREE: u8 = 2;
+const OBJ_BLOB: u8 = 3;
+const OBJ_TAG: u8 = 4;
+const OBJ_REF_DELTA: u8 = 7;
+
+fn pack_type(kind: ObjectKind) -> u8 {
+    match kind {
+        ObjectKind::Commit => OBJ_COMMIT,
+        ObjectKind::Tree => OBJ_TREE,
+        ObjectKind::Blob => OBJ_BLOB,
+        ObjectKind::Tag => OBJ_TAG,
+    }
+}
+
+/// Writer computing the SHA-1 of everything written, as well as the CRC32 of
+/// the current object.
+pub(crate) struct HashingWriter<W> {
+    inner: W,
+    sha1: Sha1,
+    crc32: Crc32,
+    offset: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self {
+            inner,
+            sha1: Sha1::new(),
+            crc32: Crc32::new(),
+            offset: 0,
+        }
+    }
+
+    /// Writes the SHA-1 of all the data written so far, and returns it.
+    pub(crate) fn write_checksum(mut self) -> io::Result<(W, GitSha1)> {
+        let checksum: [u8; 20] = self.sha1.result().into();
+        self.inner.write_all(&checksum)?;
+        self.inner.flush()?;
+        Ok((self.inner, GitSha1::from_byte_array(checksum)))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.sha1.input(&buf[..written]);
+        self.crc32.update(&buf[..written]);
+        self.offset += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Location of an object in a packfile, as recorded in the pack index.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IndexEntry {
+    pub oid: GitSha1,
+    pub offset: u64,
+    pub crc32: u32,
+}
+
+/// Summary of a written packfile, used to write its index.
+#[derive(Debug, Clone)]
+pub struct PackSummary {
+    pub checksum: GitSha1,
+    pub entries: Vec<IndexEntry>,
+}
+
+impl PackSummary {
+    /// Writes the version 2 index of the pack. Thin packs can't be indexed,
+    /// as some of their delta bases are missing from the pack.
+    pub fn write_index(&self, writer: impl Write) -> io::Result<()> {
+        write_index(writer, &self.entries, &self.checksum)
+    }
+}
+
+/// Writes a version 2 packfile. The number of objects has to be known upfront,
+/// as it is part of the pack header.
+pub struct PackWriter<W> {
+    writer: HashingWriter<W>,
+    entries: Vec<IndexEntry>,
+    object_count: u32,
+}
+
+impl<W: Write> PackWriter<W> {
+    pub fn new(writer: W, object_count: u32) -> io::Result<Self> {
+        let mut writer = HashingWriter::new(writer);
+        writer.write_all(PACK_SIGNATURE)?;
+        writer.write_all(&PACK_VERSION.to_be_bytes())?;
+        writer.write_all(&object_count.to_be_bytes())?;
+
+        Ok(Self {
+            writer,
+            entries: Vec::new(),
+            object_count,
+        })
+    }
+
+    pub fn write_object(&mut self, kind: ObjectKind, oid: GitSha1, data: &[u8]) -> io::Result<()> {
+        let mut object = self.start_object(kind, oid, data.len() as u64)?;
+        object.write_all(data)?;
+        object.finish()
+    }
+
+    /// Starts writing an object whose content will be streamed. Exactly `size`
+    /// bytes must be written before calling `ObjectWriter::finish`.
+    pub fn start_object(
+        &mut self,
+        kind: ObjectKind,
+        oid: GitSha1,
+        size: u64,
+    ) -> io::Result<ObjectWriter<'_, W>> {
+        self.start_entry(oid, pack_type(kind), size)?;
+        Ok(self.object_writer(size))
+    }
+
+    /// Writes an object as a delta against `base`, which must be either in
+    /// this pack or, for thin packs, already known to the receiver.
+    pub fn write_ref_delta(&mut self, oid: GitSha1, base: GitSha1, delta: &[u8]) -> io::Result<()> {
+        self.start_entry(oid, OBJ_REF_DELTA, delta.len() as u64)?;
+        self.writer.write_all(base.as_ref())?;
+        let mut object = self.object_writer(delta.len() as u64);
+        object.write_all(delta)?;
+        object.finish()
+    }
+
+    /// Writes the trailing checksum of the pack.
+    pub fn finish(self) -> io::Result<(W, PackSummary)> {
+        if self.entries.len() != self.object_count as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "pack header announced {} objects, but {} were written",
+                    self.object_count,
+                    self.entries.len()
+                ),
+            ));
+        }
+        let (writer, checksum) = self.writer.write_checksum()?;
+        Ok((
+            writer,
+            PackSummary {
+                checksum,
+                entries: self.entries,
+            },
+        ))
+    }
+
+    fn start_entry(&mut self, oid: GitSha1, pack_type: u8, size: u64) -> io::Result<()> {
+        self.writer.crc32 = Crc32::new();
+        self.entries.push(IndexEntry {
+            oid,
+            offset: self.writer.offset,
+            crc32: 0,
+        });
+
+        // The type and the size of the object are encoded together: the first
+        // byte has the type and the 4 lowest bits of the size, the following
+        // ones have 7 bits of the size each.
+        let mut header = vec![(pack_type << 4) | (size & 0x0f) as u8];
+        let mut size = size >> 4;
+        while size > 0 {
+            *header.last_mut().expect("header is not empty") |= 0x80;
+            header.push((size & 0x7f) as u8);
+            size >>= 7;
+        }
+        self.writer.write_all(&header)
+    }
+
+    fn object_writer(&mut self, size: u64) -> ObjectWriter<'_, W> {
+        ObjectWriter {
+            encoder: ZlibEncoder::new(&mut self.writer, Compression::default()),
+            entry: self.entries.last_mut().expect("entry was just started"),
+            remaining: size,
+        }
+    }
+}
+
+/// Compresses the content of an object into the pack.
+pub struct ObjectWriter<'a, W: Write> {
+    encoder: ZlibEncoder<&'a mut HashingWriter<W>>,
+    entry: &'a mut IndexEntry,
+    remaining: u64,
+}
+
+impl<'a, W: Write> ObjectWriter<'a, W> {
+    pub fn finish(self) -> io::Result<()> {
+        if self.remaining != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} bytes are missing from object", self.remaining),
+            ));
+        }
+        let writer = self.encoder.finish()?;
+        self.entry.crc32 = writer.crc32.clone().finalize();
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> Write for ObjectWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() as u64 > self.remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "object is larger than its announced size",
+            ));
+        }
+        let written = self.encoder.write(buf)?;
+        self.remaining -= written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
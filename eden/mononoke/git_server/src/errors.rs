@@ -0,0 +1,31 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use mononoke_types::hash::GitSha1;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Invalid pkt-line length: {0:?}")]
+    InvalidPktLineLength(String),
+    #[error("Truncated pkt-line: expected {0} bytes, got {1}")]
+    TruncatedPktLine(usize, usize),
+    #[error("Unsupported service: {0:?}, only git-upload-pack is supported")]
+    UnsupportedService(Option<String>),
+    #[error("Only Git protocol version 2 is supported, use `git -c protocol.version=2`")]
+    UnsupportedProtocolVersion,
+    #[error("Request does not specify a command")]
+    MissingCommand,
+    #[error("Unsupported command: {0}")]
+    UnsupportedCommand(String),
+    #[error("Unsupported object format: {0}")]
+    UnsupportedObjectFormat(String),
+    #[error("Invalid argument: {0:?}")]
+    InvalidArgument(String),
+    #[error("Not our ref: {0}")]
+    NotOurRef(GitSha1),
+}
@@ -0,0 +1,276 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::cmp;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use anyhow::Error;
+use bytes::Bytes;
+use futures::{
+    channel::mpsc,
+    executor, future,
+    sink::SinkExt,
+    stream::{self, BoxStream, StreamExt},
+};
+use tokio::task;
+
+use gotham_ext::error::HttpError;
+use mononoke_api::git::{GitRepoContext, PackOptions};
+use mononoke_types::hash::GitSha1;
+
+use crate::errors::ErrorKind;
+use crate::pkt_line::{Band, PktLineWriter, MAX_DATA_SIZE};
+
+/// Amount of the packfile sent in each sideband packet, which also holds the
+/// band number.
+const SIDEBAND_DATA_SIZE: usize = MAX_DATA_SIZE - 1;
+/// Number of packets queued for the client before the export waits for it to
+/// catch up.
+const SIDEBAND_BUFFER_PACKETS: usize = 16;
+
+/// Arguments of the `fetch` command.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct FetchArgs {
+    wants: Vec<GitSha1>,
+    haves: Vec<GitSha1>,
+    done: bool,
+    thin_pack: bool,
+}
+
+impl FetchArgs {
+    fn parse(args: &[&str]) -> Result<Self, ErrorKind> {
+        let parse_oid = |oid: &str| {
+            GitSha1::from_str(oid).map_err(|_| ErrorKind::InvalidArgument(oid.to_string()))
+        };
+
+        let mut fetch_args = Self::default();
+        for arg in args {
+            if let Some(oid) = arg.strip_prefix("want ") {
+                fetch_args.wants.push(parse_oid(oid)?);
+            } else if let Some(oid) = arg.strip_prefix("have ") {
+                fetch_args.haves.push(parse_oid(oid)?);
+            } else if *arg == "done" {
+                fetch_args.done = true;
+            } else if *arg == "thin-pack" {
+                fetch_args.thin_pack = true;
+            }
+            // Other arguments, like no-progress or ofs-delta, don't change
+            // the response: it never has progress messages nor offset deltas.
+        }
+        Ok(fetch_args)
+    }
+}
+
+/// Frames the data written to it as sideband data packets, and sends each
+/// packet on as soon as it is full, so that packfiles are streamed to the
+/// client rather than built in memory.
+///
+/// Sending waits for room in the channel, so a slow client slows the export
+/// down instead of letting packets pile up. As writes are synchronous, this
+/// blocks the thread, and must run in a task on the threaded runtime.
+struct SidebandWriter {
+    packet: Vec<u8>,
+    sender: mpsc::Sender<Bytes>,
+}
+
+impl SidebandWriter {
+    fn new(sender: mpsc::Sender<Bytes>) -> Self {
+        Self {
+            packet: Vec::with_capacity(SIDEBAND_DATA_SIZE),
+            sender,
+        }
+    }
+
+    fn send_packet(&mut self) -> io::Result<()> {
+        if self.packet.is_empty() {
+            return Ok(());
+        }
+        let mut writer = PktLineWriter::new();
+        writer.write_sideband(Band::Data, &self.packet);
+        self.packet.clear();
+        let sender = &mut self.sender;
+        task::block_in_place(|| executor::block_on(sender.send(writer.into_bytes())))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the client has gone away"))
+    }
+}
+
+impl Write for SidebandWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len(), SIDEBAND_DATA_SIZE - self.packet.len());
+        self.packet.extend_from_slice(&buf[..len]);
+        if self.packet.len() == SIDEBAND_DATA_SIZE {
+            self.send_packet()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_packet()
+    }
+}
+
+/// The `fetch` command negotiates the commits the client has with it, then
+/// sends a packfile with the commits it wants.
+///
+/// As requests are stateless, each one has all the haves the client found
+/// in common so far. The packfile is sent as soon as one of them is known,
+/// or when the client is done sending haves. It is streamed as it is
+/// exported, and errors past its start are reported on the error band.
+pub async fn fetch(
+    repo: &GitRepoContext,
+    args: &[&str],
+) -> Result<BoxStream<'static, Result<Bytes, Error>>, HttpError> {
+    let args = FetchArgs::parse(args).map_err(HttpError::e400)?;
+
+    let wanted = repo
+        .changeset_ids(args.wants.clone())
+        .await
+        .map_err(HttpError::e500)?;
+    let mut heads = Vec::new();
+    for oid in args.wants.iter() {
+        match wanted.get(oid) {
+            Some(cs_id) => heads.push(*cs_id),
+            None => return Err(HttpError::e400(ErrorKind::NotOurRef(*oid))),
+        }
+    }
+
+    let common = repo
+        .changeset_ids(args.haves.clone())
+        .await
+        .map_err(HttpError::e500)?;
+    let common_oids = args
+        .haves
+        .iter()
+        .filter(|oid| common.contains_key(oid))
+        .collect::<Vec<_>>();
+
+    let mut writer = PktLineWriter::new();
+    if !args.done {
+        writer.write_line("acknowledgments");
+        if common_oids.is_empty() {
+            writer.write_line("NAK");
+        }
+        for oid in common_oids.iter() {
+            writer.write_line(format!("ACK {}", oid));
+        }
+        if common_oids.is_empty() && !args.haves.is_empty() {
+            // Let the client send more haves.
+            writer.write_flush();
+            return Ok(stream::once(future::ok(writer.into_bytes())).boxed());
+        }
+        writer.write_line("ready");
+        writer.write_delim();
+    }
+    writer.write_line("packfile");
+
+    let options = PackOptions {
+        thin: args.thin_pack,
+        ..Default::default()
+    };
+    let excludes = common.values().cloned().collect();
+    let (mut sender, receiver) = mpsc::channel(SIDEBAND_BUFFER_PACKETS);
+    let repo = repo.clone();
+    tokio::spawn(async move {
+        let result = async {
+            let pack_writer = SidebandWriter::new(sender.clone());
+            let (mut pack_writer, _) = repo
+                .export_pack(heads, excludes, &options, pack_writer)
+                .await?;
+            pack_writer.flush()?;
+            Ok::<_, Error>(())
+        }
+        .await;
+
+        let mut writer = PktLineWriter::new();
+        if let Err(e) = result {
+            writer.write_sideband(Band::Error, format!("{:#}", e).as_bytes());
+        }
+        writer.write_flush();
+        // The client may have gone away, there is no one to tell then.
+        let _ = sender.send(writer.into_bytes()).await;
+    });
+
+    Ok(stream::once(future::ok(writer.into_bytes()))
+        .chain(receiver.map(Ok))
+        .boxed())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pkt_line::{parse_packets, Packet};
+
+    #[test]
+    fn test_parse_fetch_args() -> Result<(), Error> {
+        let want = "0123456789abcdef0123456789abcdef01234567";
+        let have = "89abcdef0123456789abcdef0123456789abcdef";
+        let args = FetchArgs::parse(&[
+            "thin-pack",
+            "no-progress",
+            "ofs-delta",
+            &format!("want {}", want),
+            &format!("have {}", have),
+            "done",
+        ])?;
+        assert_eq!(
+            args,
+            FetchArgs {
+                wants: vec![GitSha1::from_str(want)?],
+                haves: vec![GitSha1::from_str(have)?],
+                done: true,
+                thin_pack: true,
+            }
+        );
+
+        assert!(FetchArgs::parse(&["want 0123"]).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn test_sideband_writer() -> Result<(), Error> {
+        let data: Vec<u8> = (0..SIDEBAND_DATA_SIZE * 2 + 10)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        // No packets are queued: each write of a full packet waits for the
+        // previous one to be received.
+        let (sender, mut receiver) = mpsc::channel(0);
+        let write = tokio::spawn({
+            let data = data.clone();
+            async move {
+                let mut writer = SidebandWriter::new(sender);
+                for chunk in data.chunks(1000) {
+                    writer.write_all(chunk)?;
+                }
+                writer.flush()
+            }
+        });
+
+        let mut sent = Vec::new();
+        while let Some(bytes) = receiver.next().await {
+            sent.push(bytes);
+        }
+        write.await??;
+        // Full packets are sent as soon as they are written, the rest on flush.
+        assert_eq!(sent.len(), 3);
+
+        let mut received = Vec::new();
+        for bytes in sent {
+            match parse_packets(&bytes)?.as_slice() {
+                [Packet::Data(packet)] => {
+                    assert_eq!(packet[0], Band::Data as u8);
+                    received.extend_from_slice(&packet[1..]);
+                }
+                packets => panic!("unexpected packets {:?}", packets),
+            }
+        }
+        assert_eq!(received, data);
+
+        Ok(())
+    }
+}
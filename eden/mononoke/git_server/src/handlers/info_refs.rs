@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use bytes::Bytes;
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use http::HeaderMap;
+use serde::Deserialize;
+
+use gotham_ext::{error::HttpError, response::BytesBody};
+
+use crate::context::ServerContext;
+use crate::errors::ErrorKind;
+use crate::middleware::RequestContext;
+use crate::pkt_line::PktLineWriter;
+
+use super::util::{advertisement_mime, get_repo};
+
+const UPLOAD_PACK_SERVICE: &str = "git-upload-pack";
+
+/// Header Git clients use to ask for a protocol version.
+const GIT_PROTOCOL_HEADER: &str = "git-protocol";
+
+/// Capabilities advertised to protocol version 2 clients.
+const CAPABILITIES: &[&str] = &["agent=mononoke-git-server", "ls-refs", "fetch"];
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct InfoRefsParams {
+    repo: String,
+}
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct InfoRefsQueryParams {
+    service: Option<String>,
+}
+
+/// Initial request of the smart HTTP protocol, to which servers respond with
+/// their capabilities. Only version 2 of the protocol is supported: refs are
+/// listed by the `ls-refs` command rather than advertised here.
+pub async fn info_refs(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let rctx = RequestContext::borrow_from(state);
+    let sctx = ServerContext::borrow_from(state);
+    let params = InfoRefsParams::borrow_from(state);
+    let query = InfoRefsQueryParams::borrow_from(state);
+
+    if query.service.as_deref() != Some(UPLOAD_PACK_SERVICE) {
+        return Err(HttpError::e403(ErrorKind::UnsupportedService(
+            query.service.clone(),
+        )));
+    }
+    if !is_protocol_v2(HeaderMap::try_borrow_from(state)) {
+        return Err(HttpError::e400(ErrorKind::UnsupportedProtocolVersion));
+    }
+    // Make sure that the repo exists and can be read.
+    get_repo(&sctx, &rctx, &params.repo).await?;
+
+    let mut writer = PktLineWriter::new();
+    writer.write_line(format!("# service={}", UPLOAD_PACK_SERVICE));
+    writer.write_flush();
+    writer.write_line("version 2");
+    for capability in CAPABILITIES {
+        writer.write_line(capability);
+    }
+    writer.write_flush();
+
+    Ok(BytesBody::new(writer.into_bytes(), advertisement_mime()))
+}
+
+/// The `Git-Protocol` header is a colon-separated list of parameters.
+fn is_protocol_v2(headers: Option<&HeaderMap>) -> bool {
+    headers
+        .and_then(|headers| headers.get(GIT_PROTOCOL_HEADER))
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| {
+            value.split(':').any(|param| param == "version=2")
+        })
+}
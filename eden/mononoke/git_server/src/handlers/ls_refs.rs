@@ -0,0 +1,78 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use bytes::Bytes;
+
+use gotham_ext::error::HttpError;
+use mononoke_api::git::GitRepoContext;
+use mononoke_types::hash::GitSha1;
+
+use crate::pkt_line::PktLineWriter;
+
+/// Bookmark HEAD points to, when it exists.
+const HEAD_BOOKMARK: &str = "master";
+
+const HEAD_REF: &str = "HEAD";
+const BRANCH_PREFIX: &str = "refs/heads/";
+
+/// A Git ref, and the ref it is an alias of for symbolic refs.
+struct Ref {
+    name: String,
+    oid: GitSha1,
+    symref_target: Option<String>,
+}
+
+/// The `ls-refs` command lists the refs of the repo. Bookmarks are listed as
+/// branches, and HEAD is a symbolic ref to the main bookmark.
+pub async fn ls_refs(repo: &GitRepoContext, args: &[&str]) -> Result<Bytes, HttpError> {
+    let mut symrefs = false;
+    let mut prefixes = Vec::new();
+    for arg in args {
+        if *arg == "symrefs" {
+            symrefs = true;
+        } else if let Some(prefix) = arg.strip_prefix("ref-prefix ") {
+            prefixes.push(prefix);
+        }
+        // Annotated tags are not supported, so there is nothing to peel.
+    }
+
+    let mut bookmarks = repo.bookmarks().await.map_err(HttpError::e500)?;
+    bookmarks.sort();
+
+    let mut refs = Vec::new();
+    if let Some((name, oid)) = bookmarks.iter().find(|(name, _)| name == HEAD_BOOKMARK) {
+        refs.push(Ref {
+            name: HEAD_REF.to_string(),
+            oid: *oid,
+            symref_target: Some(format!("{}{}", BRANCH_PREFIX, name)),
+        });
+    }
+    for (name, oid) in bookmarks {
+        refs.push(Ref {
+            name: format!("{}{}", BRANCH_PREFIX, name),
+            oid,
+            symref_target: None,
+        });
+    }
+
+    let mut writer = PktLineWriter::new();
+    for git_ref in refs {
+        if !prefixes.is_empty() && !prefixes.iter().any(|p| git_ref.name.starts_with(p)) {
+            continue;
+        }
+        match git_ref.symref_target {
+            Some(target) if symrefs => writer.write_line(format!(
+                "{} {} symref-target:{}",
+                git_ref.oid, git_ref.name, target
+            )),
+            _ => writer.write_line(format!("{} {}", git_ref.oid, git_ref.name)),
+        }
+    }
+    writer.write_flush();
+
+    Ok(writer.into_bytes())
+}
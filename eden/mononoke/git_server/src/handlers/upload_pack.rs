@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use bytes::Bytes;
+use futures::{
+    future,
+    stream::{self, BoxStream, StreamExt},
+};
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use serde::Deserialize;
+
+use gotham_ext::error::HttpError;
+
+use crate::context::ServerContext;
+use crate::errors::ErrorKind;
+use crate::middleware::RequestContext;
+use crate::pkt_line::{parse_packets, Packet};
+
+use super::fetch::fetch;
+use super::ls_refs::ls_refs;
+use super::util::{get_repo, get_request_body, result_mime, StreamBody};
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct UploadPackParams {
+    repo: String,
+}
+
+/// A protocol version 2 command: the command name and capabilities, then
+/// the arguments of the command after a delimiter.
+#[derive(Debug, Eq, PartialEq)]
+struct CommandRequest<'a> {
+    command: &'a str,
+    args: Vec<&'a str>,
+}
+
+impl<'a> CommandRequest<'a> {
+    fn parse(packets: &[Packet<'a>]) -> Result<Self, ErrorKind> {
+        let mut command = None;
+        let mut args = Vec::new();
+        let mut in_args = false;
+        for packet in packets {
+            match packet {
+                Packet::Flush | Packet::ResponseEnd => break,
+                Packet::Delim => in_args = true,
+                Packet::Data(_) => {
+                    let line = packet
+                        .as_line()
+                        .ok_or_else(|| ErrorKind::InvalidArgument(format!("{:?}", packet)))?;
+                    if in_args {
+                        args.push(line);
+                    } else if let Some(name) = line.strip_prefix("command=") {
+                        command = Some(name);
+                    } else if let Some(format) = line.strip_prefix("object-format=") {
+                        if format != "sha1" {
+                            return Err(ErrorKind::UnsupportedObjectFormat(format.to_string()));
+                        }
+                    }
+                    // Other capabilities, like agent, are informative.
+                }
+            }
+        }
+
+        Ok(Self {
+            command: command.ok_or(ErrorKind::MissingCommand)?,
+            args,
+        })
+    }
+}
+
+/// Requests of the smart HTTP protocol after the initial one. Each of them
+/// runs one command and returns its response, which is streamed as it may
+/// hold a packfile.
+pub async fn upload_pack(
+    state: &mut State,
+) -> Result<StreamBody<BoxStream<'static, Result<Bytes, Error>>>, HttpError> {
+    let rctx = RequestContext::borrow_from(state);
+    let sctx = ServerContext::borrow_from(state);
+    let params = UploadPackParams::borrow_from(state);
+
+    let repo = get_repo(&sctx, &rctx, &params.repo).await?;
+    let body = get_request_body(state).await?;
+
+    let packets = parse_packets(&body).map_err(HttpError::e400)?;
+    let request = CommandRequest::parse(&packets).map_err(HttpError::e400)?;
+    let response = match request.command {
+        "ls-refs" => {
+            let response = ls_refs(&repo, &request.args).await?;
+            stream::once(future::ok(response)).boxed()
+        }
+        "fetch" => fetch(&repo, &request.args).await?,
+        command => {
+            return Err(HttpError::e400(ErrorKind::UnsupportedCommand(
+                command.to_string(),
+            )));
+        }
+    };
+
+    Ok(StreamBody::new(response, result_mime()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_command_request() -> Result<(), ErrorKind> {
+        let packets = parse_packets(
+            b"0014command=ls-refs\n0015agent=git/2.28.0\n0017object-format=sha1\n\
+              00010009peel\n000csymrefs\n001bref-prefix refs/heads/\n0000",
+        )?;
+        assert_eq!(
+            CommandRequest::parse(&packets)?,
+            CommandRequest {
+                command: "ls-refs",
+                args: vec!["peel", "symrefs", "ref-prefix refs/heads/"],
+            }
+        );
+
+        let packets = parse_packets(b"0012command=fetch\n0000")?;
+        assert_eq!(
+            CommandRequest::parse(&packets)?,
+            CommandRequest {
+                command: "fetch",
+                args: vec![],
+            }
+        );
+
+        let packets = parse_packets(b"0015agent=git/2.28.0\n0000")?;
+        assert!(CommandRequest::parse(&packets).is_err());
+
+        let packets = parse_packets(b"0012command=fetch\n0019object-format=sha256\n0000")?;
+        assert!(CommandRequest::parse(&packets).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::io::Read;
+
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use futures::{
+    channel::mpsc,
+    stream::{Stream, StreamExt},
+};
+use gotham::state::{FromState, State};
+use http::{
+    header::{HeaderValue, CONTENT_ENCODING, CONTENT_TYPE},
+    HeaderMap,
+};
+use hyper::{Body, Response, StatusCode};
+use mime::Mime;
+use once_cell::sync::Lazy;
+
+use gotham_ext::{body_ext::BodyExt, error::HttpError, response::TryIntoResponse};
+use mononoke_api::git::GitRepoContext;
+
+use crate::context::ServerContext;
+use crate::middleware::RequestContext;
+
+static ADVERTISEMENT_MIME: Lazy<Mime> = Lazy::new(|| {
+    "application/x-git-upload-pack-advertisement"
+        .parse()
+        .unwrap()
+});
+
+static RESULT_MIME: Lazy<Mime> =
+    Lazy::new(|| "application/x-git-upload-pack-result".parse().unwrap());
+
+pub fn advertisement_mime() -> Mime {
+    ADVERTISEMENT_MIME.clone()
+}
+
+pub fn result_mime() -> Mime {
+    RESULT_MIME.clone()
+}
+
+pub async fn get_repo(
+    sctx: &ServerContext,
+    rctx: &RequestContext,
+    name: impl AsRef<str>,
+) -> Result<GitRepoContext, HttpError> {
+    let name = name.as_ref();
+    sctx.mononoke_api()
+        .repo(rctx.core_context().clone(), name)
+        .await
+        .map_err(HttpError::e403)?
+        .map(|repo| repo.git())
+        .ok_or_else(|| HttpError::e404(anyhow!("repo does not exist: {:?}", name)))
+}
+
+/// Get the body of the request. Git compresses large requests, so the body
+/// is decompressed if needed.
+pub async fn get_request_body(state: &mut State) -> Result<Bytes, HttpError> {
+    let body = Body::take_from(state);
+    let headers = HeaderMap::try_borrow_from(state);
+    let gzipped = headers
+        .and_then(|headers| headers.get(CONTENT_ENCODING))
+        .map_or(false, |encoding| encoding == "gzip");
+
+    let body = body
+        .try_concat_body_opt(headers)
+        .map_err(HttpError::e400)?
+        .await
+        .map_err(HttpError::e400)?;
+
+    if gzipped {
+        let mut decoded = Vec::new();
+        GzDecoder::new(body.as_ref())
+            .read_to_end(&mut decoded)
+            .map_err(HttpError::e400)?;
+        Ok(decoded.into())
+    } else {
+        Ok(body)
+    }
+}
+
+/// A response body sent as it is produced, for responses like packfiles that
+/// are too large to build in memory. Its length isn't known upfront, so it is
+/// sent with chunked encoding.
+pub struct StreamBody<S> {
+    stream: S,
+    mime: Mime,
+}
+
+impl<S> StreamBody<S> {
+    pub fn new(stream: S, mime: Mime) -> Self {
+        Self { stream, mime }
+    }
+}
+
+impl<S> TryIntoResponse for StreamBody<S>
+where
+    S: Stream<Item = Result<Bytes, Error>> + Send + 'static,
+{
+    fn try_into_response(self, _state: &mut State) -> Result<Response<Body>, Error> {
+        let mime_header: HeaderValue = self.mime.as_ref().parse()?;
+
+        // Hyper requires the stream of a Body to be Sync, so the stream is
+        // spawned on its own task and Hyper gets a channel receiving from it.
+        let (sender, receiver) = mpsc::channel(0);
+        tokio::spawn(self.stream.map(Ok).forward(sender));
+
+        Response::builder()
+            .header(CONTENT_TYPE, mime_header)
+            .status(StatusCode::OK)
+            .body(Body::wrap_stream(receiver))
+            .map_err(Error::from)
+    }
+}
@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! The pkt-line format Git uses to frame the messages of its protocols.
+//!
+//! Each packet starts with its length, including the length itself, as 4
+//! hex digits. Lengths 0 to 2 are special packets without data.
+
+use std::str;
+
+use bytes::Bytes;
+
+use crate::errors::ErrorKind;
+
+/// Size of the length prefix of a packet.
+const LENGTH_SIZE: usize = 4;
+
+/// Largest packet Git accepts, including the length prefix.
+const MAX_PACKET_SIZE: usize = 65520;
+
+/// Largest amount of data a packet can carry.
+pub const MAX_DATA_SIZE: usize = MAX_PACKET_SIZE - LENGTH_SIZE;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Packet<'a> {
+    /// Ends a message (`0000`).
+    Flush,
+    /// Separates the sections of a message (`0001`).
+    Delim,
+    /// Ends a response in stateless connections (`0002`).
+    ResponseEnd,
+    Data(&'a [u8]),
+}
+
+impl<'a> Packet<'a> {
+    /// The data of the packet as a line of text, without its trailing
+    /// newline. Returns `None` for special packets and binary data.
+    pub fn as_line(&self) -> Option<&'a str> {
+        match self {
+            Packet::Data(data) => {
+                let data = match data.last() {
+                    Some(b'\n') => &data[..data.len() - 1],
+                    _ => data,
+                };
+                str::from_utf8(data).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Splits a message into packets.
+pub fn parse_packets(mut input: &[u8]) -> Result<Vec<Packet<'_>>, ErrorKind> {
+    let mut packets = Vec::new();
+    while !input.is_empty() {
+        if input.len() < LENGTH_SIZE {
+            return Err(ErrorKind::TruncatedPktLine(LENGTH_SIZE, input.len()));
+        }
+        let length = str::from_utf8(&input[..LENGTH_SIZE])
+            .ok()
+            .and_then(|length| usize::from_str_radix(length, 16).ok())
+            .ok_or_else(|| {
+                ErrorKind::InvalidPktLineLength(
+                    String::from_utf8_lossy(&input[..LENGTH_SIZE]).into_owned(),
+                )
+            })?;
+
+        let packet = match length {
+            0 => Packet::Flush,
+            1 => Packet::Delim,
+            2 => Packet::ResponseEnd,
+            3 => return Err(ErrorKind::InvalidPktLineLength(format!("{:04x}", length))),
+            _ if length > input.len() => {
+                return Err(ErrorKind::TruncatedPktLine(length, input.len()));
+            }
+            _ => Packet::Data(&input[LENGTH_SIZE..length]),
+        };
+        packets.push(packet);
+        input = &input[std::cmp::max(length, LENGTH_SIZE)..];
+    }
+    Ok(packets)
+}
+
+/// Sideband channels, used to multiplex the pack with progress and error
+/// messages in fetch responses.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Band {
+    Data = 1,
+    Progress = 2,
+    Error = 3,
+}
+
+/// Builds a message out of packets.
+#[derive(Debug, Default)]
+pub struct PktLineWriter {
+    buff: Vec<u8>,
+}
+
+impl PktLineWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes a data packet. Panics if the data does not fit in a packet.
+    pub fn write_data(&mut self, data: &[u8]) {
+        assert!(
+            data.len() <= MAX_DATA_SIZE,
+            "pkt-line data is too large: {} bytes",
+            data.len()
+        );
+        self.buff
+            .extend_from_slice(format!("{:04x}", data.len() + LENGTH_SIZE).as_bytes());
+        self.buff.extend_from_slice(data);
+    }
+
+    /// Writes a line of text, adding the trailing newline.
+    pub fn write_line(&mut self, line: impl AsRef<str>) {
+        let mut data = Vec::with_capacity(line.as_ref().len() + 1);
+        data.extend_from_slice(line.as_ref().as_bytes());
+        data.push(b'\n');
+        self.write_data(&data);
+    }
+
+    /// Writes data on a sideband channel, split across as many packets as
+    /// needed.
+    pub fn write_sideband(&mut self, band: Band, data: &[u8]) {
+        let mut packet = Vec::with_capacity(MAX_DATA_SIZE);
+        for chunk in data.chunks(MAX_DATA_SIZE - 1) {
+            packet.clear();
+            packet.push(band as u8);
+            packet.extend_from_slice(chunk);
+            self.write_data(&packet);
+        }
+    }
+
+    pub fn write_flush(&mut self) {
+        self.buff.extend_from_slice(b"0000");
+    }
+
+    pub fn write_delim(&mut self) {
+        self.buff.extend_from_slice(b"0001");
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.buff.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_packets() -> Result<(), ErrorKind> {
+        let packets = parse_packets(b"0014command=ls-refs\n00010009peel\n0000")?;
+        assert_eq!(
+            packets,
+            vec![
+                Packet::Data(b"command=ls-refs\n"),
+                Packet::Delim,
+                Packet::Data(b"peel\n"),
+                Packet::Flush,
+            ]
+        );
+        assert_eq!(packets[0].as_line(), Some("command=ls-refs"));
+        assert_eq!(packets[1].as_line(), None);
+
+        assert_eq!(parse_packets(b"")?, vec![]);
+        assert_eq!(parse_packets(b"0004")?, vec![Packet::Data(b"")]);
+        assert_eq!(parse_packets(b"0002")?, vec![Packet::ResponseEnd]);
+        assert!(parse_packets(b"000").is_err());
+        assert!(parse_packets(b"0003").is_err());
+        assert!(parse_packets(b"zzzz").is_err());
+        assert!(parse_packets(b"0009peel").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_packets() -> Result<(), ErrorKind> {
+        let mut writer = PktLineWriter::new();
+        writer.write_line("version 2");
+        writer.write_delim();
+        writer.write_sideband(Band::Progress, b"done");
+        writer.write_flush();
+        assert_eq!(
+            writer.into_bytes(),
+            Bytes::from_static(b"000eversion 2\n00010009\x02done0000")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_sideband_splits_data() -> Result<(), ErrorKind> {
+        let data = vec![b'x'; MAX_DATA_SIZE * 2];
+        let mut writer = PktLineWriter::new();
+        writer.write_sideband(Band::Data, &data);
+        let bytes = writer.into_bytes();
+
+        let packets = parse_packets(&bytes)?;
+        assert_eq!(packets.len(), 3);
+        let mut received = Vec::new();
+        for packet in packets {
+            match packet {
+                Packet::Data(data) => {
+                    assert!(data.len() <= MAX_DATA_SIZE);
+                    assert_eq!(data[0], Band::Data as u8);
+                    received.extend_from_slice(&data[1..]);
+                }
+                _ => panic!("unexpected packet {:?}", packet),
+            }
+        }
+        assert_eq!(received, data);
+
+        Ok(())
+    }
+}
@@ -498,8 +498,8 @@ mod test {
         BlobConfig, BlobstoreId, BookmarkParams, Bundle2ReplayParams, CacheWarmupParams,
         DatabaseConfig, DerivedDataConfig, FilestoreParams, HookBypass, HookConfig,
         HookManagerParams, HookParams, InfinitepushNamespace, InfinitepushParams, LfsParams,
-        LocalDatabaseConfig, MetadataDatabaseConfig, MultiplexId, PushParams, PushrebaseFlags,
-        PushrebaseParams, RemoteDatabaseConfig, RemoteMetadataDatabaseConfig,
+        LocalDatabaseConfig, MetadataDatabaseConfig, MultiplexId, MultiplexedStoreType, PushParams,
+        PushrebaseFlags, PushrebaseParams, RemoteDatabaseConfig, RemoteMetadataDatabaseConfig,
         ShardableRemoteDatabaseConfig, ShardedRemoteDatabaseConfig, SourceControlServiceMonitoring,
         SourceControlServiceParams, UnodeVersion, WireprotoLoggingConfig,
     };
@@ -905,6 +905,7 @@ mod test {
 
             [derived_data_config]
             derived_data_types=["fsnodes"]
+            blame_filesize_limit=1048576
 
             [derived_data_config.raw_unode_version]
             unode_version_v2 = {}
@@ -917,9 +918,12 @@ mod test {
             [storage.main.blobstore.multiplexed]
             multiplex_id = 1
             scuba_table = "blobstore_scuba_table"
+            minimum_successful_writes = 2
+            read_repair = true
             components = [
                 { blobstore_id = 0, blobstore = { manifold = { manifold_bucket = "bucket" } } },
                 { blobstore_id = 1, blobstore = { blob_files = { path = "/tmp/foo" } } },
+                { blobstore_id = 2, write_only = true, blobstore = { blob_files = { path = "/tmp/bar" } } },
             ]
             queue_db = { remote = { db_address = "queue_db_address" } }
 
@@ -1020,6 +1024,7 @@ mod test {
             blobstores: vec![
                 (
                     BlobstoreId::new(0),
+                    MultiplexedStoreType::Normal,
                     BlobConfig::Manifold {
                         bucket: "bucket".into(),
                         prefix: "".into(),
@@ -1027,11 +1032,21 @@ mod test {
                 ),
                 (
                     BlobstoreId::new(1),
+                    MultiplexedStoreType::Normal,
                     BlobConfig::Files {
                         path: "/tmp/foo".into(),
                     },
                 ),
+                (
+                    BlobstoreId::new(2),
+                    MultiplexedStoreType::WriteOnly,
+                    BlobConfig::Files {
+                        path: "/tmp/bar".into(),
+                    },
+                ),
             ],
+            minimum_successful_writes: nonzero!(2usize),
+            read_repair: true,
             queue_db: DatabaseConfig::Remote(RemoteDatabaseConfig {
                 db_address: "queue_db_address".into(),
             }),
@@ -1175,6 +1190,7 @@ mod test {
                     derived_data_types: btreeset![String::from("fsnodes")],
                     scuba_table: None,
                     unode_version: UnodeVersion::V2,
+                    blame_filesize_limit: Some(1048576),
                 },
                 hgsql_name: HgsqlName("fbsource".to_string()),
                 hgsql_globalrevs_name: HgsqlGlobalrevsName("fbsource".to_string()),
@@ -1451,10 +1467,12 @@ mod test {
                         scuba_table: None,
                         scuba_sample_rate: nonzero!(100u64),
                         blobstores: vec![
-                            (BlobstoreId::new(1), BlobConfig::Files {
+                            (BlobstoreId::new(1), MultiplexedStoreType::Normal, BlobConfig::Files {
                                 path: "/tmp/foo".into()
                             })
                         ],
+                        minimum_successful_writes: nonzero!(1usize),
+                        read_repair: false,
                         queue_db: DatabaseConfig::Remote(
                             RemoteDatabaseConfig {
                                 db_address: "queue_db_address".into(),
@@ -1564,6 +1582,186 @@ mod test {
         )
     }
 
+    #[fbinit::test]
+    fn test_multiplexed_invalid_policies(fb: FacebookInit) {
+        fn check_fails(fb: FacebookInit, multiplexed: &str, expect: &str) {
+            let repo = format!(
+                r#"
+                repoid = 123
+                storage_config = "multiplex_store"
+
+                [storage.multiplex_store.metadata.local]
+                local_db_path = "/tmp/foo"
+
+                [storage.multiplex_store.blobstore.multiplexed]
+                multiplex_id = 1
+                queue_db = {{ local = {{ local_db_path = "/tmp/queue" }} }}
+                {}
+                "#,
+                multiplexed
+            );
+            let paths = btreemap! {
+                "common/commitsyncmap.toml" => "",
+                "repos/test/server.toml" => repo.as_str(),
+            };
+            let tmp_dir = write_files(&paths);
+            let res = load_repo_configs(fb, tmp_dir.path());
+            let msg = format!("{:#?}", res);
+            println!("res = {}", msg);
+            assert!(res.is_err());
+            assert!(msg.contains(expect));
+        }
+
+        check_fails(
+            fb,
+            r#"
+            minimum_successful_writes = 3
+            components = [
+                { blobstore_id = 1, blobstore = { blob_files = { path = "/tmp/foo" } } },
+                { blobstore_id = 2, blobstore = { blob_files = { path = "/tmp/bar" } } },
+            ]
+            "#,
+            "only 2 components",
+        );
+        check_fails(
+            fb,
+            r#"
+            minimum_successful_writes = 0
+            components = [
+                { blobstore_id = 1, blobstore = { blob_files = { path = "/tmp/foo" } } },
+            ]
+            "#,
+            "must be at least 1",
+        );
+        check_fails(
+            fb,
+            r#"
+            components = [
+                { blobstore_id = 1, write_only = true, blobstore = { blob_files = { path = "/tmp/foo" } } },
+            ]
+            "#,
+            "isn't write_only",
+        );
+        check_fails(
+            fb,
+            r#"
+            minimum_successful_writes = 2
+            components = [
+                { blobstore_id = 1, blobstore = { blob_files = { path = "/tmp/foo" } } },
+                { blobstore_id = 2, write_only = true, blobstore = { blob_files = { path = "/tmp/bar" } } },
+            ]
+            "#,
+            "only 1 components that aren't write_only",
+        );
+    }
+
+    #[fbinit::test]
+    fn test_compressed_blobstore(fb: FacebookInit) {
+        const REPO: &str = r#"
+        repoid = 123
+        storage_config = "compressed_store"
+
+        [storage.compressed_store.metadata.local]
+        local_db_path = "/tmp/foo"
+
+        [storage.compressed_store.blobstore.compressed]
+        dictionary_path = "/tmp/dict"
+        blobstore = { blob_sqlite = { path = "/tmp/foo" } }
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => "",
+            "repos/test/server.toml" => REPO,
+        };
+
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(fb, tmp_dir.path()).expect("read configs failed");
+
+        assert_eq!(
+            res.repos["test"].storage_config.blobstore,
+            BlobConfig::Compressed {
+                blobconfig: Box::new(BlobConfig::Sqlite {
+                    path: "/tmp/foo".into(),
+                }),
+                level: 3,
+                dictionary_path: Some("/tmp/dict".into()),
+            }
+        );
+    }
+
+    #[fbinit::test]
+    fn test_s3_blobstore(fb: FacebookInit) {
+        const REPO: &str = r#"
+        repoid = 123
+        storage_config = "s3_store"
+
+        [storage.s3_store.metadata.local]
+        local_db_path = "/tmp/foo"
+
+        [storage.s3_store.blobstore.s3]
+        endpoint = "http://localhost:9000"
+        bucket = "mononoke"
+        credentials_path = "/etc/mononoke/s3_credentials"
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => "",
+            "repos/test/server.toml" => REPO,
+        };
+
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(fb, tmp_dir.path()).expect("read configs failed");
+
+        assert_eq!(
+            res.repos["test"].storage_config.blobstore,
+            BlobConfig::S3 {
+                endpoint: "http://localhost:9000".to_string(),
+                bucket: "mononoke".to_string(),
+                prefix: "".to_string(),
+                region: "us-east-1".to_string(),
+                credentials_path: Some("/etc/mononoke/s3_credentials".into()),
+            }
+        );
+    }
+
+    #[fbinit::test]
+    fn test_tiered_blobstore(fb: FacebookInit) {
+        const REPO: &str = r#"
+        repoid = 123
+        storage_config = "tiered_store"
+
+        [storage.tiered_store.metadata.local]
+        local_db_path = "/tmp/foo"
+
+        [storage.tiered_store.blobstore.tiered]
+        hot = { blob_sqlite = { path = "/ssd/foo" } }
+        cold = { blob_files = { path = "/hdd/foo" } }
+        demote_after_secs = 604800
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => "",
+            "repos/test/server.toml" => REPO,
+        };
+
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(fb, tmp_dir.path()).expect("read configs failed");
+
+        assert_eq!(
+            res.repos["test"].storage_config.blobstore,
+            BlobConfig::Tiered {
+                hot: Box::new(BlobConfig::Sqlite {
+                    path: "/ssd/foo".into(),
+                }),
+                cold: Box::new(BlobConfig::Files {
+                    path: "/hdd/foo".into(),
+                }),
+                promote_on_read: false,
+                demote_after: Some(Duration::from_secs(604800)),
+            }
+        );
+    }
+
     #[fbinit::test]
     fn test_stray_fields(fb: FacebookInit) {
         const REPO: &str = r#"
@@ -291,10 +291,16 @@ impl Convert for RawDerivedDataConfig {
             UnodeVersion::default()
         };
 
+        let blame_filesize_limit = self
+            .blame_filesize_limit
+            .map(|limit| limit.try_into())
+            .transpose()?;
+
         Ok(DerivedDataConfig {
             scuba_table: self.scuba_table,
             derived_data_types: self.derived_data_types.unwrap_or_default(),
             unode_version,
+            blame_filesize_limit,
         })
     }
 }
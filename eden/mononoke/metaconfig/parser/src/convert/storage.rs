@@ -13,8 +13,9 @@ use std::time::Duration;
 use anyhow::{anyhow, Result};
 use metaconfig_types::{
     BlobConfig, BlobstoreId, DatabaseConfig, FilestoreParams, LocalDatabaseConfig,
-    MetadataDatabaseConfig, MultiplexId, RemoteDatabaseConfig, RemoteMetadataDatabaseConfig,
-    ShardableRemoteDatabaseConfig, ShardedRemoteDatabaseConfig, StorageConfig,
+    MetadataDatabaseConfig, MultiplexId, MultiplexedStoreType, RemoteDatabaseConfig,
+    RemoteMetadataDatabaseConfig, ShardableRemoteDatabaseConfig, ShardedRemoteDatabaseConfig,
+    StorageConfig,
 };
 use nonzero_ext::nonzero;
 use repos::{
@@ -24,6 +25,9 @@ use repos::{
 
 use crate::convert::Convert;
 
+const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+const DEFAULT_S3_REGION: &str = "us-east-1";
+
 impl Convert for RawStorageConfig {
     type Output = StorageConfig;
 
@@ -54,28 +58,61 @@ impl Convert for RawBlobstoreConfig {
             RawBlobstoreConfig::mysql(raw) => BlobConfig::Mysql {
                 remote: raw.remote.convert()?,
             },
-            RawBlobstoreConfig::multiplexed(raw) => BlobConfig::Multiplexed {
-                multiplex_id: raw
-                    .multiplex_id
-                    .map(MultiplexId::new)
-                    .ok_or_else(|| anyhow!("missing multiplex_id from configuration"))?,
-                scuba_table: raw.scuba_table,
-                scuba_sample_rate: parse_scuba_sample_rate(raw.scuba_sample_rate)?,
-                blobstores: raw
+            RawBlobstoreConfig::multiplexed(raw) => {
+                let blobstores = raw
                     .components
                     .into_iter()
                     .map(|comp| {
+                        let store_type = if comp.write_only.unwrap_or(false) {
+                            MultiplexedStoreType::WriteOnly
+                        } else {
+                            MultiplexedStoreType::Normal
+                        };
                         Ok((
                             BlobstoreId::new(comp.blobstore_id.try_into()?),
+                            store_type,
                             comp.blobstore.convert()?,
                         ))
                     })
-                    .collect::<Result<Vec<_>>>()?,
-                queue_db: raw
-                    .queue_db
-                    .ok_or_else(|| anyhow!("missing queue_db from configuration"))?
-                    .convert()?,
-            },
+                    .collect::<Result<Vec<_>>>()?;
+                let normal_count = blobstores
+                    .iter()
+                    .filter(|(_, store_type, _)| *store_type == MultiplexedStoreType::Normal)
+                    .count();
+                if normal_count == 0 {
+                    return Err(anyhow!(
+                        "multiplexed blobstore needs at least one component that isn't write_only"
+                    ));
+                }
+                let minimum_successful_writes = match raw.minimum_successful_writes {
+                    Some(writes) => NonZeroUsize::new(writes.try_into()?)
+                        .ok_or_else(|| anyhow!("minimum_successful_writes must be at least 1"))?,
+                    None => nonzero!(1usize),
+                };
+                // Write-only components don't count towards the write quorum
+                if minimum_successful_writes.get() > normal_count {
+                    return Err(anyhow!(
+                        "minimum_successful_writes is {}, but there are only {} components that aren't write_only",
+                        minimum_successful_writes,
+                        normal_count
+                    ));
+                }
+                BlobConfig::Multiplexed {
+                    multiplex_id: raw
+                        .multiplex_id
+                        .map(MultiplexId::new)
+                        .ok_or_else(|| anyhow!("missing multiplex_id from configuration"))?,
+                    scuba_table: raw.scuba_table,
+                    scuba_sample_rate: parse_scuba_sample_rate(raw.scuba_sample_rate)?,
+                    blobstores,
+                    minimum_successful_writes,
+                    read_repair: raw.read_repair.unwrap_or(false),
+                    queue_db: raw
+                        .queue_db
+                        .ok_or_else(|| anyhow!("missing queue_db from configuration"))?
+                        .convert()?,
+                }
+            }
             RawBlobstoreConfig::manifold_with_ttl(raw) => {
                 let ttl = Duration::from_secs(raw.ttl_secs.try_into()?);
                 BlobConfig::ManifoldWithTtl {
@@ -84,11 +121,40 @@ impl Convert for RawBlobstoreConfig {
                     ttl,
                 }
             }
+            RawBlobstoreConfig::s3(raw) => BlobConfig::S3 {
+                endpoint: raw.endpoint,
+                bucket: raw.bucket,
+                prefix: raw.prefix.unwrap_or_default(),
+                region: raw.region.unwrap_or_else(|| DEFAULT_S3_REGION.to_string()),
+                credentials_path: raw.credentials_path.map(PathBuf::from),
+            },
             RawBlobstoreConfig::logging(raw) => BlobConfig::Logging {
                 scuba_table: raw.scuba_table,
                 scuba_sample_rate: parse_scuba_sample_rate(raw.scuba_sample_rate)?,
                 blobconfig: Box::new(raw.blobstore.convert()?),
             },
+            RawBlobstoreConfig::compressed(raw) => BlobConfig::Compressed {
+                blobconfig: Box::new(raw.blobstore.convert()?),
+                level: raw.level.unwrap_or(DEFAULT_COMPRESSION_LEVEL),
+                dictionary_path: raw.dictionary_path.map(PathBuf::from),
+            },
+            RawBlobstoreConfig::pack(raw) => BlobConfig::Pack {
+                blobconfig: Box::new(raw.blobstore.convert()?),
+            },
+            RawBlobstoreConfig::encrypted(raw) => BlobConfig::Encrypted {
+                blobconfig: Box::new(raw.blobstore.convert()?),
+                keyfile: PathBuf::from(raw.keyfile),
+                key_id: raw.key_id.try_into()?,
+            },
+            RawBlobstoreConfig::tiered(raw) => BlobConfig::Tiered {
+                hot: Box::new(raw.hot.convert()?),
+                cold: Box::new(raw.cold.convert()?),
+                promote_on_read: raw.promote_on_read.unwrap_or(false),
+                demote_after: raw
+                    .demote_after_secs
+                    .map(|secs| -> Result<_> { Ok(Duration::from_secs(secs.try_into()?)) })
+                    .transpose()?,
+            },
             RawBlobstoreConfig::UnknownField(f) => {
                 return Err(anyhow!("unsupported blobstore configuration ({})", f));
             }
@@ -145,6 +145,9 @@ pub struct DerivedDataConfig {
     pub derived_data_types: BTreeSet<String>,
     /// What unode version should be used (defaults to V1)
     pub unode_version: UnodeVersion,
+    /// Files larger than this many bytes get no blame (defaults to 10 MiB). Raising it only
+    /// affects blame derived afterwards.
+    pub blame_filesize_limit: Option<u64>,
 }
 
 /// What type of unode derived data to generate
@@ -617,6 +620,15 @@ impl FromStr for ScrubAction {
     }
 }
 
+/// How a multiplexed blobstore uses one of its components
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MultiplexedStoreType {
+    /// Read from and written to
+    Normal,
+    /// Written to, but never read from, e.g. while it is being populated
+    WriteOnly,
+}
+
 /// Configuration for a blobstore
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum BlobConfig {
@@ -654,7 +666,11 @@ pub enum BlobConfig {
         /// A scuba table I guess
         scuba_table: Option<String>,
         /// Set of blobstores being multiplexed over
-        blobstores: Vec<(BlobstoreId, BlobConfig)>,
+        blobstores: Vec<(BlobstoreId, MultiplexedStoreType, BlobConfig)>,
+        /// Number of blobstores a put must succeed in before it is successful
+        minimum_successful_writes: NonZeroUsize,
+        /// Whether a get that finds a blob writes it to the blobstores missing it
+        read_repair: bool,
         /// 1 in scuba_sample_rate samples will be logged.
         scuba_sample_rate: NonZeroU64,
         /// DB config to use for the sync queue
@@ -667,7 +683,9 @@ pub enum BlobConfig {
         /// A scuba table I guess
         scuba_table: Option<String>,
         /// Set of blobstores being multiplexed over
-        blobstores: Vec<(BlobstoreId, BlobConfig)>,
+        blobstores: Vec<(BlobstoreId, MultiplexedStoreType, BlobConfig)>,
+        /// Number of blobstores a put must succeed in before it is successful
+        minimum_successful_writes: NonZeroUsize,
         /// Whether to attempt repair
         scrub_action: ScrubAction,
         /// 1 in scuba_sample_rate samples will be logged.
@@ -684,6 +702,20 @@ pub enum BlobConfig {
         /// TTL for each object we put in Manifold
         ttl: Duration,
     },
+    /// Store in a bucket of S3 or an S3-compatible object store
+    S3 {
+        /// Endpoint of the object store, e.g. "https://s3.us-east-1.amazonaws.com"
+        endpoint: String,
+        /// Bucket to store blobs in
+        bucket: String,
+        /// Prefix to be prepended to all the keys
+        prefix: String,
+        /// Region the bucket is in; used to sign requests
+        region: String,
+        /// File holding the access key id and secret. If not set, credentials are read from
+        /// the standard AWS environment variables
+        credentials_path: Option<PathBuf>,
+    },
     /// A logging blobstore that wraps another blobstore
     Logging {
         /// The config for the blobstore that is wrapped.
@@ -693,6 +725,41 @@ pub enum BlobConfig {
         /// 1 in scuba_sample_rate samples will be logged.
         scuba_sample_rate: NonZeroU64,
     },
+    /// A blobstore that compresses values before storing them in another blobstore
+    Compressed {
+        /// The config for the blobstore that is wrapped.
+        blobconfig: Box<BlobConfig>,
+        /// Zstd compression level
+        level: i32,
+        /// Path to a zstd dictionary to compress with, if any
+        dictionary_path: Option<PathBuf>,
+    },
+    /// A blobstore that can store related values delta-encoded in packs in another blobstore
+    Pack {
+        /// The config for the blobstore that is wrapped.
+        blobconfig: Box<BlobConfig>,
+    },
+    /// A blobstore that encrypts values before storing them in another blobstore
+    Encrypted {
+        /// The config for the blobstore that is wrapped.
+        blobconfig: Box<BlobConfig>,
+        /// Path to the file holding the encryption keys
+        keyfile: PathBuf,
+        /// Id of the key in the keyfile that new values are encrypted with
+        key_id: u32,
+    },
+    /// A fast blobstore for recent data in front of a durable one holding everything
+    Tiered {
+        /// The config for the fast blobstore
+        hot: Box<BlobConfig>,
+        /// The config for the durable blobstore
+        cold: Box<BlobConfig>,
+        /// Whether blobs read from the cold blobstore are copied to the hot one
+        promote_on_read: bool,
+        /// How long blobs stay in the hot blobstore after being written there, if they are
+        /// removed at all. Demotion is run by `mononoke_admin blobstore-demote`.
+        demote_after: Option<Duration>,
+    },
 }
 
 impl BlobConfig {
@@ -703,12 +770,16 @@ impl BlobConfig {
 
         match self {
             Disabled | Files { .. } | Sqlite { .. } => true,
-            Manifold { .. } | Mysql { .. } | ManifoldWithTtl { .. } => false,
+            Manifold { .. } | Mysql { .. } | ManifoldWithTtl { .. } | S3 { .. } => false,
             Multiplexed { blobstores, .. } | Scrub { blobstores, .. } => blobstores
                 .iter()
-                .map(|(_, config)| config)
+                .map(|(_, _, config)| config)
                 .all(BlobConfig::is_local),
-            Logging { blobconfig, .. } => blobconfig.is_local(),
+            Logging { blobconfig, .. }
+            | Compressed { blobconfig, .. }
+            | Pack { blobconfig }
+            | Encrypted { blobconfig, .. } => blobconfig.is_local(),
+            Tiered { hot, cold, .. } => hot.is_local() && cold.is_local(),
         }
     }
 
@@ -723,12 +794,14 @@ impl BlobConfig {
             scuba_table,
             scuba_sample_rate,
             blobstores,
+            minimum_successful_writes,
+            read_repair: _,
             queue_db,
         } = self
         {
             let scuba_table = mem::replace(scuba_table, None);
             let mut blobstores = mem::replace(blobstores, Vec::new());
-            for (_, store) in blobstores.iter_mut() {
+            for (_, _, store) in blobstores.iter_mut() {
                 store.set_scrubbed(scrub_action);
             }
             *self = Scrub {
@@ -736,6 +809,7 @@ impl BlobConfig {
                 scuba_table,
                 scuba_sample_rate: *scuba_sample_rate,
                 blobstores,
+                minimum_successful_writes: *minimum_successful_writes,
                 scrub_action,
                 queue_db: queue_db.clone(),
             };
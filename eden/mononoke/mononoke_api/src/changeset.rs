@@ -17,6 +17,7 @@ use changeset_info::ChangesetInfo;
 use chrono::{DateTime, FixedOffset};
 use cloned::cloned;
 use context::CoreContext;
+use copy_detection::{detected_copies_enabled, DetectedCopies};
 use derived_data::BonsaiDerived;
 use fsnodes::RootFsnodeId;
 use futures::compat::{Future01CompatExt, Stream01CompatExt};
@@ -352,6 +353,7 @@ impl ChangesetContext {
         // map from to_path to from_path
         let mut inv_copy_path_map = HashMap::new();
         let file_changes = self.file_changes().await?;
+        let detected_copies;
         // For now we only consider copies when comparing with parent.
         if include_copies_renames && self.parents().await?.contains(&other.id) {
             for (to_path, file_change) in file_changes.iter() {
@@ -363,6 +365,22 @@ impl ChangesetContext {
                 }
             }
 
+            // Copies that weren't recorded in the changeset may have been detected.
+            if detected_copies_enabled(self.repo().blob_repo()) {
+                detected_copies = DetectedCopies::derive(
+                    self.ctx().clone(),
+                    self.repo().blob_repo().clone(),
+                    self.id,
+                )
+                .compat()
+                .await?;
+                for (to_path, copy) in detected_copies.copies() {
+                    if *copy.from_changeset_id() == other.id {
+                        copy_path_map.insert(copy.from_path(), to_path);
+                    }
+                }
+            }
+
             // Prefetch fsnode entries for all "from paths" so that we don't need
             // to refetch them later
             let from_path_to_mf_entry = other
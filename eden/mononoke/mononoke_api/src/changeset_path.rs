@@ -18,11 +18,14 @@ use changeset_info::ChangesetInfo;
 use cloned::cloned;
 use context::CoreContext;
 use derived_data::BonsaiDerived;
-use fastlog::{list_file_history, FastlogError, HistoryAcrossDeletions};
+use fastlog::{
+    list_file_history, list_file_history_across_copies, FastlogError, FileHistoryEntry,
+    HistoryAcrossDeletions,
+};
 use filestore::FetchKey;
 use futures::compat::Future01CompatExt;
 use futures::future::{FutureExt, Shared};
-use futures::stream::Stream;
+use futures::stream::{Stream, StreamExt};
 use futures_old::Future as FutureLegacy;
 use futures_util::{try_join, TryStreamExt};
 use manifest::{Entry, ManifestOps};
@@ -45,6 +48,35 @@ pub struct HistoryEntry {
     pub changeset_id: ChangesetId,
 }
 
+/// A commit in the history of a path
+pub struct ChangesetPathHistoryEntry {
+    changeset: ChangesetContext,
+    path: MononokePath,
+    copied_from: Option<(MononokePath, ChangesetId)>,
+}
+
+impl ChangesetPathHistoryEntry {
+    /// The commit that changed the path.
+    pub fn changeset(&self) -> &ChangesetContext {
+        &self.changeset
+    }
+
+    pub fn into_changeset(self) -> ChangesetContext {
+        self.changeset
+    }
+
+    /// The path in this commit. This differs from the path history was requested for
+    /// when the history has been followed across copies.
+    pub fn path(&self) -> &MononokePath {
+        &self.path
+    }
+
+    /// The path and commit the file was copied or moved from in this commit, if any.
+    pub fn copied_from(&self) -> Option<&(MononokePath, ChangesetId)> {
+        self.copied_from.as_ref()
+    }
+}
+
 pub enum PathEntry {
     NotPresent,
     Tree(TreeContext),
@@ -272,14 +304,21 @@ impl ChangesetPathContext {
             .await
     }
 
-    /// Returns a list of `ChangesetContext` for the file at this path that represents
-    /// a history of the path.
+    /// Returns the commits that changed the file at this path, or any file under the
+    /// directory at this path, representing a history of the path.
+    ///
+    /// If `max_copy_hops` is not zero, when the file was copied or moved from another path,
+    /// the history carries on with that path, following at most that many copies.
+    /// Directories are never copied, so their history doesn't follow copies.
     pub async fn history(
         &self,
         until_timestamp: Option<i64>,
         follow_history_across_deletions: bool,
-    ) -> Result<impl Stream<Item = Result<ChangesetContext, MononokeError>> + '_, MononokeError>
-    {
+        max_copy_hops: usize,
+    ) -> Result<
+        impl Stream<Item = Result<ChangesetPathHistoryEntry, MononokeError>> + '_,
+        MononokeError,
+    > {
         let ctx = self.changeset.ctx().clone();
         let repo = self.repo().blob_repo().clone();
         let mpath = self.path.as_mpath();
@@ -314,15 +353,40 @@ impl ChangesetPathContext {
         } else {
             HistoryAcrossDeletions::DontTrack
         };
-        let history = list_file_history(
-            ctx,
-            repo,
-            mpath.cloned(),
-            self.changeset.id(),
-            terminator,
-            history_across_deletions,
-        )
-        .await
+        let history = if max_copy_hops == 0 || self.is_dir().await? {
+            // Nothing to follow, so avoid loading every commit to look for copies
+            let path = mpath.cloned();
+            list_file_history(
+                ctx,
+                repo,
+                path.clone(),
+                self.changeset.id(),
+                terminator,
+                history_across_deletions,
+            )
+            .await
+            .map(move |history| {
+                history
+                    .map_ok(move |cs_id| FileHistoryEntry {
+                        cs_id,
+                        path: path.clone(),
+                        copied_from: None,
+                    })
+                    .left_stream()
+            })
+        } else {
+            list_file_history_across_copies(
+                ctx,
+                repo,
+                mpath.cloned(),
+                self.changeset.id(),
+                terminator,
+                history_across_deletions,
+                max_copy_hops,
+            )
+            .await
+            .map(|history| history.right_stream())
+        }
         .map_err(|error| match error {
             FastlogError::NoSuchPath(_) => MononokeError::InvalidRequest(error.to_string()),
             FastlogError::InternalError(e) => MononokeError::from(format_err!(e)),
@@ -333,7 +397,13 @@ impl ChangesetPathContext {
 
         Ok(history
             .map_err(MononokeError::from)
-            .map_ok(move |changeset_id| ChangesetContext::new(self.repo().clone(), changeset_id)))
+            .map_ok(move |entry| ChangesetPathHistoryEntry {
+                changeset: ChangesetContext::new(self.repo().clone(), entry.cs_id),
+                path: MononokePath::new(entry.path),
+                copied_from: entry
+                    .copied_from
+                    .map(|(path, cs_id)| (MononokePath::from(path), cs_id)),
+            }))
     }
 }
 
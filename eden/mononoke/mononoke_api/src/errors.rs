@@ -7,6 +7,9 @@
 
 use blobstore::LoadableError;
 use derived_data::DeriveError;
+use hooks::HookRejectionInfo;
+use itertools::Itertools;
+use mononoke_types::ChangesetId;
 use std::backtrace::Backtrace;
 use std::convert::Infallible;
 use std::error::Error as StdError;
@@ -16,6 +19,8 @@ use std::sync::Arc;
 use anyhow::Error;
 use thiserror::Error;
 
+use crate::path::MononokePath;
+
 #[derive(Clone, Debug)]
 pub struct InternalError(Arc<Error>);
 
@@ -41,6 +46,49 @@ impl StdError for InternalError {
     }
 }
 
+/// A hook that rejected a changeset.
+#[derive(Clone, Debug)]
+pub struct HookRejection {
+    pub hook_name: String,
+    pub cs_id: ChangesetId,
+    pub reason: HookRejectionInfo,
+}
+
+impl fmt::Display for HookRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} for {}: {}",
+            self.hook_name, self.cs_id, self.reason.long_description
+        )
+    }
+}
+
+/// A pair of conflicting paths that prevented a pushrebase. `left` was
+/// changed by the commits that were rebased, `right` by the commits they
+/// would have been rebased over. One is a prefix of the other or they are
+/// the same path.
+#[derive(Clone, Debug)]
+pub struct PushrebaseConflict {
+    pub left: MononokePath,
+    pub right: MononokePath,
+}
+
+impl fmt::Display for PushrebaseConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' conflicts with '{}'", self.left, self.right)
+    }
+}
+
+impl From<pushrebase::PushrebaseConflict> for PushrebaseConflict {
+    fn from(conflict: pushrebase::PushrebaseConflict) -> Self {
+        Self {
+            left: MononokePath::new(Some(conflict.left)),
+            right: MononokePath::new(Some(conflict.right)),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Error)]
 pub enum MononokeError {
     #[error("invalid request: {0}")]
@@ -53,6 +101,10 @@ pub enum MononokeError {
     },
     #[error("not available: {0}")]
     NotAvailable(String),
+    #[error("hooks failed:\n{}", .0.iter().join("\n"))]
+    HookFailure(Vec<HookRejection>),
+    #[error("pushrebase conflicts:\n{}", .0.iter().join("\n"))]
+    PushrebaseConflicts(Vec<PushrebaseConflict>),
     #[error("internal error: {0}")]
     InternalError(#[source] InternalError),
 }
@@ -0,0 +1,12 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+pub use packfile::{ExportedPack, PackOptions};
+
+pub mod repo;
+
+pub use repo::GitRepoContext;
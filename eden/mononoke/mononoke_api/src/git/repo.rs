@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use blobrepo::BlobRepo;
+use context::CoreContext;
+use derived_data::BonsaiDerived;
+use futures::compat::{Future01CompatExt, Stream01CompatExt};
+use futures::future::try_join_all;
+use futures::TryStreamExt;
+use git_types::MappedGitCommitId;
+use mononoke_types::{hash::GitSha1, ChangesetId};
+use packfile::{export_pack, ExportedPack, PackOptions};
+
+use crate::errors::MononokeError;
+use crate::repo::RepoContext;
+
+#[derive(Clone)]
+pub struct GitRepoContext {
+    repo: RepoContext,
+}
+
+impl GitRepoContext {
+    pub(crate) fn new(repo: RepoContext) -> Self {
+        Self { repo }
+    }
+
+    /// The `CoreContext` for this query.
+    pub(crate) fn ctx(&self) -> &CoreContext {
+        &self.repo.ctx()
+    }
+
+    /// The `RepoContext` for this query.
+    pub(crate) fn repo(&self) -> &RepoContext {
+        &self.repo
+    }
+
+    /// The underlying Mononoke `BlobRepo` backing this repo.
+    pub(crate) fn blob_repo(&self) -> &BlobRepo {
+        &self.repo().blob_repo()
+    }
+
+    /// Get the Git commit id of a changeset, deriving it if needed.
+    pub async fn git_commit_id(&self, cs_id: ChangesetId) -> Result<GitSha1, MononokeError> {
+        let commit_id =
+            MappedGitCommitId::derive(self.ctx().clone(), self.blob_repo().clone(), cs_id)
+                .compat()
+                .await?;
+        Ok(*commit_id.oid())
+    }
+
+    /// Get the publishing bookmarks of the repo, along with the Git commit
+    /// ids of the changesets they point to.
+    pub async fn bookmarks(&self) -> Result<Vec<(String, GitSha1)>, MononokeError> {
+        let bookmarks = self
+            .repo()
+            .list_bookmarks(false, None, None)
+            .compat()
+            .try_collect::<Vec<_>>()
+            .await?;
+        try_join_all(bookmarks.into_iter().map(|(name, cs_id)| async move {
+            Ok::<_, MononokeError>((name, self.git_commit_id(cs_id).await?))
+        }))
+        .await
+    }
+
+    /// Look up the changesets for Git commit ids. Commit ids that are not
+    /// known to the repo are not included in the result.
+    pub async fn changeset_ids(
+        &self,
+        git_sha1s: Vec<GitSha1>,
+    ) -> Result<HashMap<GitSha1, ChangesetId>, MononokeError> {
+        let mapping = self
+            .blob_repo()
+            .bonsai_git_mapping()
+            .get(git_sha1s.into())
+            .await?
+            .into_iter()
+            .map(|entry| (entry.git_sha1, entry.bcs_id))
+            .collect();
+        Ok(mapping)
+    }
+
+    /// Write a Git packfile with the objects of the ancestors of `heads` that
+    /// are not ancestors of `excludes`.
+    ///
+    /// This is the data the Git `fetch` command sends, for a client that
+    /// wants `heads` and has `excludes`.
+    pub async fn export_pack<W: Write>(
+        &self,
+        heads: Vec<ChangesetId>,
+        excludes: Vec<ChangesetId>,
+        options: &PackOptions,
+        writer: W,
+    ) -> Result<(W, ExportedPack), MononokeError> {
+        let exported = export_pack(
+            self.ctx(),
+            self.blob_repo(),
+            heads,
+            excludes,
+            options,
+            writer,
+        )
+        .await?;
+        Ok(exported)
+    }
+}
@@ -18,6 +18,8 @@ use cloned::cloned;
 use fbinit::FacebookInit;
 use futures::future;
 use futures::future::try_join_all;
+#[cfg(test)]
+use hooks::HookManager;
 use skiplist::SkiplistIndex;
 use slog::{debug, info, o, Logger};
 use sql_ext::facebook::MysqlOptions;
@@ -25,6 +27,8 @@ use synced_commit_mapping::SyncedCommitMapping;
 use warm_bookmarks_cache::WarmBookmarksCache;
 
 use metaconfig_parser::RepoConfigs;
+#[cfg(test)]
+use metaconfig_types::BookmarkAttrs;
 use metaconfig_types::{CommitSyncConfig, SourceControlServiceMonitoring};
 
 use crate::repo::Repo;
@@ -34,6 +38,7 @@ pub mod changeset_path;
 pub mod changeset_path_diff;
 pub mod errors;
 pub mod file;
+pub mod git;
 pub mod hg;
 pub mod legacy;
 pub mod path;
@@ -49,14 +54,15 @@ pub use crate::legacy::get_content_by_path;
 
 pub use crate::changeset::{ChangesetContext, Generation};
 pub use crate::changeset_path::{
-    unified_diff, ChangesetPathContext, CopyInfo, PathEntry, UnifiedDiff, UnifiedDiffMode,
+    unified_diff, ChangesetPathContext, ChangesetPathHistoryEntry, CopyInfo, PathEntry,
+    UnifiedDiff, UnifiedDiffMode,
 };
 pub use crate::changeset_path_diff::ChangesetPathDiffContext;
-pub use crate::errors::MononokeError;
+pub use crate::errors::{HookRejection, MononokeError, PushrebaseConflict};
 pub use crate::file::{FileContext, FileId, FileMetadata, FileType};
 pub use crate::path::MononokePath;
 pub use crate::repo::RepoContext;
-pub use crate::repo_write::{CreateChange, CreateCopyInfo, RepoWriteContext};
+pub use crate::repo_write::{CreateChange, CreateCopyInfo, PushrebaseOutcome, RepoWriteContext};
 pub use crate::specifiers::{
     ChangesetId, ChangesetIdPrefix, ChangesetPrefixSpecifier, ChangesetSpecifier,
     ChangesetSpecifierPrefixResolution, Globalrev, HgChangesetId, HgChangesetIdPrefix,
@@ -140,6 +146,22 @@ impl Mononoke {
         Ok(Self { repos })
     }
 
+    /// Create a Mononoke instance for testing, with a single repo that has
+    /// bookmark attributes and hooks.
+    #[cfg(test)]
+    async fn new_test_bookmarks(
+        ctx: CoreContext,
+        name: String,
+        blob_repo: BlobRepo,
+        bookmark_attrs: BookmarkAttrs,
+        hook_manager: HookManager,
+    ) -> Result<Self, Error> {
+        let repo = Repo::new_test_bookmarks(ctx, blob_repo, bookmark_attrs, hook_manager).await?;
+        let mut repos = HashMap::new();
+        repos.insert(name, Arc::new(repo));
+        Ok(Self { repos })
+    }
+
     #[cfg(test)]
     async fn new_test_xrepo(
         ctx: CoreContext,
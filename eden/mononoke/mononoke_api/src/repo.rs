@@ -29,11 +29,17 @@ use futures::future::{try_join, try_join_all};
 use futures::StreamExt as NewStreamExt;
 use futures_ext::StreamExt;
 use futures_old::stream::{self, Stream};
+use hooks::{hook_loader::load_hooks, HookManager};
+use hooks_content_stores::blobrepo_text_only_fetcher;
+#[cfg(test)]
+use hooks_content_stores::InMemoryFileContentFetcher;
 use itertools::Itertools;
 use mercurial_types::Globalrev;
+#[cfg(test)]
+use metaconfig_types::HookManagerParams;
 use metaconfig_types::{
-    CommitSyncConfig, CommonConfig, RepoConfig, SourceControlServiceMonitoring,
-    SourceControlServiceParams,
+    BookmarkAttrs, CommitSyncConfig, CommonConfig, PushrebaseParams, RepoConfig,
+    SourceControlServiceMonitoring, SourceControlServiceParams,
 };
 use mononoke_types::{
     hash::{GitSha1, Sha1, Sha256},
@@ -41,6 +47,7 @@ use mononoke_types::{
 };
 use permission_checker::{ArcPermissionChecker, MononokeIdentitySet, PermissionCheckerBuilder};
 use revset::AncestorsNodeStream;
+use scuba_ext::ScubaSampleBuilder;
 use skiplist::{fetch_skiplist_index, SkiplistIndex};
 use slog::{debug, error, Logger};
 #[cfg(test)]
@@ -54,6 +61,7 @@ use warm_bookmarks_cache::WarmBookmarksCache;
 use crate::changeset::ChangesetContext;
 use crate::errors::MononokeError;
 use crate::file::{FileContext, FileId};
+use crate::git::GitRepoContext;
 use crate::hg::HgRepoContext;
 use crate::repo_write::RepoWriteContext;
 use crate::specifiers::{
@@ -79,6 +87,10 @@ pub(crate) struct Repo {
     pub(crate) monitoring_config: Option<SourceControlServiceMonitoring>,
     pub(crate) perm_checker: ArcPermissionChecker,
     pub(crate) commit_sync_config: Option<CommitSyncConfig>,
+    pub(crate) bookmark_attrs: BookmarkAttrs,
+    // Only built for repos that permit writes, as they are the only ones running hooks
+    pub(crate) hook_manager: Option<Arc<HookManager>>,
+    pub(crate) pushrebase_params: PushrebaseParams,
 }
 
 #[derive(Clone)]
@@ -142,6 +154,8 @@ impl Repo {
         .await?;
         let service_config = config.source_control_service.clone();
         let monitoring_config = config.source_control_service_monitoring.clone();
+        let bookmark_attrs = BookmarkAttrs::new(config.bookmarks.clone());
+        let pushrebase_params = config.pushrebase.clone();
 
         let builder = BlobrepoBuilder::new(
             fb,
@@ -180,6 +194,23 @@ impl Repo {
 
         let (perm_checker, skiplist_index) = try_join(perm_checker, skiplist_index).await?;
 
+        let hook_manager = if service_config.permit_writes {
+            let mut hooks_scuba =
+                ScubaSampleBuilder::with_opt_table(fb, config.scuba_table_hooks.clone());
+            hooks_scuba.add("repo", name.clone());
+            let mut hook_manager = HookManager::new(
+                fb,
+                blobrepo_text_only_fetcher(blob_repo.clone(), config.hook_max_file_size),
+                config.hook_manager_params.clone().unwrap_or_default(),
+                hooks_scuba,
+            )
+            .await?;
+            load_hooks(fb, &mut hook_manager, config.clone(), &HashSet::new())?;
+            Some(Arc::new(hook_manager))
+        } else {
+            None
+        };
+
         Ok(Self {
             name,
             blob_repo,
@@ -190,10 +221,16 @@ impl Repo {
             monitoring_config,
             perm_checker: ArcPermissionChecker::from(perm_checker),
             commit_sync_config: config.commit_sync_config,
+            bookmark_attrs,
+            hook_manager,
+            pushrebase_params,
         })
     }
 
     /// Temporary function to create directly from parts.
+    ///
+    /// Repos created this way do not permit writes, so they are not
+    /// configured with any bookmark attributes or hooks.
     pub(crate) fn new_from_parts(
         name: String,
         blob_repo: BlobRepo,
@@ -215,17 +252,42 @@ impl Repo {
             monitoring_config,
             perm_checker: ArcPermissionChecker::from(PermissionCheckerBuilder::always_allow()),
             commit_sync_config,
+            bookmark_attrs: BookmarkAttrs::new(Vec::new()),
+            hook_manager: None,
+            pushrebase_params: PushrebaseParams::default(),
         }
     }
 
     #[cfg(test)]
     /// Construct a Repo from a test BlobRepo
     pub(crate) async fn new_test(ctx: CoreContext, blob_repo: BlobRepo) -> Result<Self, Error> {
+        let hook_manager = empty_hook_manager(ctx.fb).await?;
         Self::new_test_common(
             ctx,
             blob_repo,
             None,
             Arc::new(SqlSyncedCommitMapping::with_sqlite_in_memory()?),
+            BookmarkAttrs::new(Vec::new()),
+            hook_manager,
+        )
+        .await
+    }
+
+    #[cfg(test)]
+    /// Construct a Repo from a test BlobRepo with bookmark attributes and hooks
+    pub(crate) async fn new_test_bookmarks(
+        ctx: CoreContext,
+        blob_repo: BlobRepo,
+        bookmark_attrs: BookmarkAttrs,
+        hook_manager: HookManager,
+    ) -> Result<Self, Error> {
+        Self::new_test_common(
+            ctx,
+            blob_repo,
+            None,
+            Arc::new(SqlSyncedCommitMapping::with_sqlite_in_memory()?),
+            bookmark_attrs,
+            hook_manager,
         )
         .await
     }
@@ -238,11 +300,14 @@ impl Repo {
         commit_sync_config: CommitSyncConfig,
         synced_commit_mapping: Arc<dyn SyncedCommitMapping>,
     ) -> Result<Self, Error> {
+        let hook_manager = empty_hook_manager(ctx.fb).await?;
         Self::new_test_common(
             ctx,
             blob_repo,
             Some(commit_sync_config),
             synced_commit_mapping,
+            BookmarkAttrs::new(Vec::new()),
+            hook_manager,
         )
         .await
     }
@@ -254,6 +319,8 @@ impl Repo {
         blob_repo: BlobRepo,
         commit_sync_config: Option<CommitSyncConfig>,
         synced_commit_mapping: Arc<dyn SyncedCommitMapping>,
+        bookmark_attrs: BookmarkAttrs,
+        hook_manager: HookManager,
     ) -> Result<Self, Error> {
         let warm_bookmarks_cache = Arc::new(
             WarmBookmarksCache::new(ctx.clone(), blob_repo.clone())
@@ -272,6 +339,9 @@ impl Repo {
             monitoring_config: None,
             perm_checker: ArcPermissionChecker::from(PermissionCheckerBuilder::always_allow()),
             commit_sync_config,
+            bookmark_attrs,
+            hook_manager: Some(Arc::new(hook_manager)),
+            pushrebase_params: PushrebaseParams::default(),
         })
     }
 
@@ -534,6 +604,20 @@ pub struct Stack {
     pub public: HashSet<ChangesetId>,
 }
 
+/// Create a hook manager without any hooks.
+#[cfg(test)]
+async fn empty_hook_manager(fb: FacebookInit) -> Result<HookManager, Error> {
+    HookManager::new(
+        fb,
+        Box::new(InMemoryFileContentFetcher::new()),
+        HookManagerParams {
+            disable_acl_checker: true,
+        },
+        ScubaSampleBuilder::with_discard(),
+    )
+    .await
+}
+
 /// A context object representing a query to a particular repo.
 impl RepoContext {
     pub(crate) async fn new(ctx: CoreContext, repo: Arc<Repo>) -> Result<Self, MononokeError> {
@@ -558,7 +642,7 @@ impl RepoContext {
     }
 
     /// The skiplist index for the referenced repository.
-    pub(crate) fn skiplist_index(&self) -> &SkiplistIndex {
+    pub(crate) fn skiplist_index(&self) -> &Arc<SkiplistIndex> {
         &self.repo.skiplist_index
     }
 
@@ -572,6 +656,21 @@ impl RepoContext {
         &self.repo.warm_bookmarks_cache
     }
 
+    /// The configured attributes of the bookmarks of the referenced repository.
+    pub(crate) fn bookmark_attrs(&self) -> &BookmarkAttrs {
+        &self.repo.bookmark_attrs
+    }
+
+    /// The hook manager for the referenced repository, if it permits writes.
+    pub(crate) fn hook_manager(&self) -> Option<&Arc<HookManager>> {
+        self.repo.hook_manager.as_ref()
+    }
+
+    /// The pushrebase configuration of the referenced repository.
+    pub(crate) fn pushrebase_params(&self) -> &PushrebaseParams {
+        &self.repo.pushrebase_params
+    }
+
     pub(crate) fn derive_changeset_info_enabled(&self) -> bool {
         self.blob_repo()
             .get_derived_data_config()
@@ -967,6 +1066,11 @@ impl RepoContext {
     pub fn hg(self) -> HgRepoContext {
         HgRepoContext::new(self)
     }
+
+    /// Get a GitRepoContext to access this repo's data in Git-specific formats.
+    pub fn git(self) -> GitRepoContext {
+        GitRepoContext::new(self)
+    }
 }
 
 #[cfg(test)]
@@ -5,27 +5,35 @@
  * GNU General Public License version 2.
  */
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::iter::FromIterator;
 use std::ops::Deref;
 
 use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use bookmarks::{BookmarkName, BookmarkUpdateReason, Transaction};
 use bytes::Bytes;
 use chrono::{DateTime, FixedOffset};
 use context::CoreContext;
 use filestore::{FetchKey, StoreRequest};
 use futures::Stream;
 use futures_old::stream as old_stream;
-use futures_util::compat::Future01CompatExt;
+use futures_util::compat::{Future01CompatExt, Stream01CompatExt};
 use futures_util::future;
 use futures_util::stream::{FuturesOrdered, FuturesUnordered, TryStreamExt};
-use manifest::PathTree;
+use hooks::HookOutcome;
+use manifest::{Diff, Entry, ManifestOps, PathTree};
 use mononoke_types::{
-    BonsaiChangesetMut, ChangesetId, DateTime as MononokeDateTime, FileChange, MPath,
+    BonsaiChangeset, BonsaiChangesetMut, ChangesetId, DateTime as MononokeDateTime, FileChange,
+    MPath,
 };
+use phases::Phases;
+use pushrebase::{OntoBookmarkParams, PushrebaseError};
+use revset::DifferenceOfUnionsOfAncestorsNodeStream;
+use unbundle::get_pushrebase_hooks;
 
 use crate::changeset::ChangesetContext;
-use crate::errors::MononokeError;
+use crate::errors::{HookRejection, MononokeError, PushrebaseConflict};
 use crate::file::{FileId, FileType};
 use crate::path::MononokePath;
 use crate::repo::RepoContext;
@@ -87,6 +95,16 @@ enum CreateChangeType {
     Delete,
 }
 
+/// The result of landing a stack.
+#[derive(Clone, Debug)]
+pub struct PushrebaseOutcome {
+    /// The changeset the bookmark was moved to.
+    pub head: ChangesetId,
+    /// The changesets of the stack, mapped to the changesets they were
+    /// rebased as.
+    pub rebased_changesets: HashMap<ChangesetId, ChangesetId>,
+}
+
 impl Default for CreateChangeType {
     fn default() -> Self {
         CreateChangeType::None
@@ -273,6 +291,95 @@ async fn verify_prefix_files_deleted(
         .await
 }
 
+/// Find the paths at which the parents conflict: paths that are files with
+/// different contents or types in two parents, or that are a file in one
+/// parent and a directory in another.
+async fn find_merge_conflicts(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    parent_ctxs: &Vec<ChangesetContext>,
+) -> Result<BTreeSet<MononokePath>, MononokeError> {
+    let root_fsnode_ids: Vec<_> = parent_ctxs
+        .iter()
+        .map(|parent_ctx| parent_ctx.root_fsnode_id())
+        .collect::<FuturesOrdered<_>>()
+        .try_collect()
+        .await?;
+
+    let mut conflicts = BTreeSet::new();
+    for (index, left) in root_fsnode_ids.iter().enumerate() {
+        for right in root_fsnode_ids[index + 1..].iter() {
+            // A path that is a file and a directory is reported as a file
+            // and a directory that are added or removed at the same path.
+            let (files, dirs, changed) = left
+                .fsnode_id()
+                .diff(ctx.clone(), repo.get_blobstore(), *right.fsnode_id())
+                .compat()
+                .try_fold(
+                    (HashSet::new(), HashSet::new(), Vec::new()),
+                    |(mut files, mut dirs, mut changed), diff| async move {
+                        match diff {
+                            Diff::Changed(Some(path), Entry::Leaf(_), Entry::Leaf(_)) => {
+                                changed.push(path)
+                            }
+                            Diff::Added(Some(path), Entry::Leaf(_))
+                            | Diff::Removed(Some(path), Entry::Leaf(_)) => {
+                                files.insert(path);
+                            }
+                            Diff::Added(Some(path), Entry::Tree(_))
+                            | Diff::Removed(Some(path), Entry::Tree(_)) => {
+                                dirs.insert(path);
+                            }
+                            _ => {}
+                        }
+                        Ok((files, dirs, changed))
+                    },
+                )
+                .await?;
+            conflicts.extend(changed);
+            conflicts.extend(files.intersection(&dirs).cloned());
+        }
+    }
+
+    Ok(conflicts
+        .into_iter()
+        .map(|path| MononokePath::new(Some(path)))
+        .collect())
+}
+
+/// Verify that all merge conflicts between the parents are resolved by a
+/// change, either at the conflicting path or at one of its prefixes.
+async fn verify_merge_conflicts_resolved(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    parent_ctxs: &Vec<ChangesetContext>,
+    path_changes: &PathTree<CreateChangeType>,
+) -> Result<(), MononokeError> {
+    let unresolved: Vec<_> = find_merge_conflicts(ctx, repo, parent_ctxs)
+        .await?
+        .into_iter()
+        .filter(|path| {
+            path_changes
+                .get(path.as_mpath())
+                .map_or(true, |change_type| *change_type == CreateChangeType::None)
+                && !is_prefix_changed(path, path_changes)
+        })
+        .collect();
+
+    match unresolved.as_slice() {
+        [] => Ok(()),
+        [path] => Err(MononokeError::InvalidRequest(format!(
+            "Merge conflict at '{}' must be resolved by a change",
+            path
+        ))),
+        [path, ..] => Err(MononokeError::InvalidRequest(format!(
+            "{} merge conflicts ('{}', ...) must be resolved by a change",
+            unresolved.len(),
+            path
+        ))),
+    }
+}
+
 impl RepoWriteContext {
     pub(crate) fn new(repo: RepoContext) -> Self {
         Self { repo }
@@ -296,9 +403,12 @@ impl RepoWriteContext {
     ///     otherwise be ignored.
     ///   - Any merge conflicts introduced by merging the parent changesets
     ///     must be resolved by a corresponding change in the set of changes.
+    ///     Parents conflict at a path if it is a file with different contents
+    ///     or types in two of them, or a file in one and a directory in
+    ///     another. Changing or deleting the path, or replacing one of its
+    ///     prefix directories with a file, resolves the conflict.
     ///
-    /// Currenly only a single parent is supported, and root changesets (changesets
-    /// with no parents) cannot be created.
+    /// Root changesets are created by passing no parents.
     pub async fn create_changeset(
         &self,
         parents: Vec<ChangesetId>,
@@ -310,13 +420,6 @@ impl RepoWriteContext {
         extra: BTreeMap<String, Vec<u8>>,
         changes: BTreeMap<MononokePath, CreateChange>,
     ) -> Result<ChangesetContext, MononokeError> {
-        // Merge rules are not validated yet, so only a single parent is supported.
-        if parents.len() != 1 {
-            return Err(MononokeError::InvalidRequest(String::from(
-                "Merge changesets and root changesets cannot be created",
-            )));
-        }
-
         // Obtain contexts for each of the parents (which should exist).
         let parent_ctxs: Vec<_> = parents
             .iter()
@@ -344,6 +447,7 @@ impl RepoWriteContext {
         //     file change for the prefix path.
         // (3) conversely, when a file has been replaced by a directory, there
         //     must be a delete for the file.
+        // (4) paths at which the parents conflict must be changed.
         //
         // First build a path tree recording each path that has been created or deleted.
         let path_changes = PathTree::from_iter(
@@ -364,6 +468,17 @@ impl RepoWriteContext {
             .try_for_each(|_| future::ok(()))
             .await?;
 
+        // Check merge conflicts are resolved. (4)
+        if parent_ctxs.len() > 1 {
+            verify_merge_conflicts_resolved(
+                self.ctx(),
+                self.blob_repo(),
+                &parent_ctxs,
+                &path_changes,
+            )
+            .await?;
+        }
+
         let changes: Vec<(MPath, CreateChange)> = changes
             .into_iter()
             // Filter deletions that have a change at a path prefix. The
@@ -434,4 +549,353 @@ impl RepoWriteContext {
         .await?;
         Ok(ChangesetContext::new(self.repo.clone(), new_changeset_id))
     }
+
+    /// Create a bookmark pointing at an existing changeset.
+    ///
+    /// Hooks configured for the bookmark are run on the changeset.
+    pub async fn create_bookmark(
+        &self,
+        bookmark: impl AsRef<str>,
+        target: ChangesetId,
+        pushvars: Option<&HashMap<String, Bytes>>,
+    ) -> Result<(), MononokeError> {
+        let bookmark = BookmarkName::new(bookmark.as_ref())?;
+        self.check_bookmark_modification_permitted(&bookmark)?;
+
+        if self.bookmark_target(&bookmark).await?.is_some() {
+            return Err(MononokeError::InvalidRequest(format!(
+                "Bookmark '{}' already exists",
+                bookmark
+            )));
+        }
+        self.verify_changeset_exists(target).await?;
+
+        if self.has_hooks(&bookmark) {
+            self.run_hooks(&bookmark, vec![target], pushvars).await?;
+        }
+
+        let mut txn = self
+            .blob_repo()
+            .update_bookmark_transaction(self.ctx().clone());
+        txn.create(&bookmark, target, BookmarkUpdateReason::ManualMove)?;
+        commit_bookmark_transaction(txn, &bookmark).await
+    }
+
+    /// Move a bookmark to another existing changeset.
+    ///
+    /// The move must be a fast-forward move unless `allow_non_fast_forward`
+    /// is set and the bookmark is not configured to be fast-forward only.
+    /// Hooks configured for the bookmark are run on the changesets that
+    /// become reachable from the bookmark.
+    pub async fn move_bookmark(
+        &self,
+        bookmark: impl AsRef<str>,
+        target: ChangesetId,
+        allow_non_fast_forward: bool,
+        pushvars: Option<&HashMap<String, Bytes>>,
+    ) -> Result<(), MononokeError> {
+        let bookmark = BookmarkName::new(bookmark.as_ref())?;
+        self.check_bookmark_modification_permitted(&bookmark)?;
+
+        let old_target = self.bookmark_target(&bookmark).await?.ok_or_else(|| {
+            MononokeError::InvalidRequest(format!("Bookmark '{}' does not exist", bookmark))
+        })?;
+        self.verify_changeset_exists(target).await?;
+        if old_target == target {
+            return Ok(());
+        }
+
+        if !allow_non_fast_forward || self.bookmark_attrs().is_fast_forward_only(&bookmark) {
+            let is_fast_forward = ChangesetContext::new(self.repo.clone(), old_target)
+                .is_ancestor_of(target)
+                .await?;
+            if !is_fast_forward {
+                return Err(MononokeError::InvalidRequest(format!(
+                    "Non fast-forward move of bookmark '{}' from {} to {} is not permitted",
+                    bookmark, old_target, target
+                )));
+            }
+        }
+
+        if self.has_hooks(&bookmark) {
+            let changesets = DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
+                self.ctx().clone(),
+                &self.blob_repo().get_changeset_fetcher(),
+                self.skiplist_index().clone(),
+                vec![target],
+                vec![old_target],
+            )
+            .compat()
+            .try_collect()
+            .await?;
+            self.run_hooks(&bookmark, changesets, pushvars).await?;
+        }
+
+        let mut txn = self
+            .blob_repo()
+            .update_bookmark_transaction(self.ctx().clone());
+        txn.update(
+            &bookmark,
+            target,
+            old_target,
+            BookmarkUpdateReason::ManualMove,
+        )?;
+        commit_bookmark_transaction(txn, &bookmark).await
+    }
+
+    /// Delete a bookmark.
+    ///
+    /// If `old_target` is provided, the bookmark is only deleted if it
+    /// still points at that changeset. Bookmarks that are configured to be
+    /// fast-forward only cannot be deleted.
+    pub async fn delete_bookmark(
+        &self,
+        bookmark: impl AsRef<str>,
+        old_target: Option<ChangesetId>,
+    ) -> Result<(), MononokeError> {
+        let bookmark = BookmarkName::new(bookmark.as_ref())?;
+        self.check_bookmark_modification_permitted(&bookmark)?;
+
+        if self.bookmark_attrs().is_fast_forward_only(&bookmark) {
+            return Err(MononokeError::InvalidRequest(format!(
+                "Deletion of bookmark '{}' is not permitted",
+                bookmark
+            )));
+        }
+
+        let current_target = self.bookmark_target(&bookmark).await?.ok_or_else(|| {
+            MononokeError::InvalidRequest(format!("Bookmark '{}' does not exist", bookmark))
+        })?;
+        if let Some(old_target) = old_target {
+            if old_target != current_target {
+                return Err(MononokeError::InvalidRequest(format!(
+                    "Bookmark '{}' points at {}, not {}",
+                    bookmark, current_target, old_target
+                )));
+            }
+        }
+
+        let mut txn = self
+            .blob_repo()
+            .update_bookmark_transaction(self.ctx().clone());
+        txn.delete(&bookmark, current_target, BookmarkUpdateReason::ManualMove)?;
+        commit_bookmark_transaction(txn, &bookmark).await
+    }
+
+    /// Land a stack of changesets by pushrebasing it onto a bookmark.
+    ///
+    /// The stack is made up of the ancestors of `head` that are not
+    /// ancestors of `base`. Hooks configured for the bookmark are run on the
+    /// stack before it is rebased, and the repository's pushrebase
+    /// configuration is honoured, including whether merges are blocked and
+    /// Globalrevs assigned. If the stack changes files that were also
+    /// changed on the bookmark since it diverged, the land fails with the
+    /// list of conflicts.
+    pub async fn land_stack(
+        &self,
+        bookmark: impl AsRef<str>,
+        head: ChangesetId,
+        base: ChangesetId,
+        pushvars: Option<&HashMap<String, Bytes>>,
+    ) -> Result<PushrebaseOutcome, MononokeError> {
+        let bookmark = BookmarkName::new(bookmark.as_ref())?;
+        self.check_bookmark_modification_permitted(&bookmark)?;
+
+        self.verify_changeset_exists(head).await?;
+        self.verify_changeset_exists(base).await?;
+        let is_ancestor = ChangesetContext::new(self.repo.clone(), base)
+            .is_ancestor_of(head)
+            .await?;
+        if !is_ancestor {
+            return Err(MononokeError::InvalidRequest(format!(
+                "Stack base {} is not an ancestor of stack head {}",
+                base, head
+            )));
+        }
+
+        let stack: Vec<ChangesetId> = DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
+            self.ctx().clone(),
+            &self.blob_repo().get_changeset_fetcher(),
+            self.skiplist_index().clone(),
+            vec![head],
+            vec![base],
+        )
+        .compat()
+        .try_collect()
+        .await?;
+        if stack.is_empty() {
+            return Err(MononokeError::InvalidRequest(String::from(
+                "Stack to land is empty",
+            )));
+        }
+
+        let changesets: HashSet<BonsaiChangeset> = stack
+            .iter()
+            .map(|cs_id| {
+                cs_id
+                    .load(self.ctx().clone(), self.blob_repo().blobstore())
+                    .compat()
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_collect()
+            .await?;
+
+        let pushrebase_params = self.pushrebase_params();
+        if pushrebase_params.block_merges && changesets.iter().any(BonsaiChangeset::is_merge) {
+            return Err(MononokeError::InvalidRequest(format!(
+                "Pushrebase onto bookmark '{}' is blocked because the stack contains a merge commit",
+                bookmark
+            )));
+        }
+
+        if self.has_hooks(&bookmark) {
+            self.run_hooks(&bookmark, stack, pushvars).await?;
+        }
+
+        let mut flags = pushrebase_params.flags.clone();
+        if let Some(rewritedates) = self.bookmark_attrs().should_rewrite_dates(&bookmark) {
+            // Bookmark config overrides repo flags.rewritedates config
+            flags.rewritedates = rewritedates;
+        }
+        let hooks = get_pushrebase_hooks(self.blob_repo(), pushrebase_params);
+
+        let result = pushrebase::do_pushrebase_bonsai(
+            self.ctx(),
+            self.blob_repo(),
+            &flags,
+            &OntoBookmarkParams::new(bookmark),
+            &changesets,
+            &None,
+            &hooks[..],
+        )
+        .await
+        .map_err(|err| match err {
+            PushrebaseError::Conflicts(conflicts) => MononokeError::PushrebaseConflicts(
+                conflicts
+                    .into_iter()
+                    .map(PushrebaseConflict::from)
+                    .collect(),
+            ),
+            PushrebaseError::PotentialCaseConflict(_)
+            | PushrebaseError::RebaseOverMerge
+            | PushrebaseError::RootTooFarBehind => MononokeError::InvalidRequest(err.to_string()),
+            _ => MononokeError::from(anyhow::Error::from(err)),
+        })?;
+
+        self.blob_repo()
+            .get_phases()
+            .add_reachable_as_public(self.ctx().clone(), vec![result.head])
+            .compat()
+            .await?;
+
+        Ok(PushrebaseOutcome {
+            head: result.head,
+            rebased_changesets: result
+                .rebased_changesets
+                .into_iter()
+                .map(|pair| (pair.id_old, pair.id_new))
+                .collect(),
+        })
+    }
+
+    /// Check that the user is permitted to modify the bookmark.
+    fn check_bookmark_modification_permitted(
+        &self,
+        bookmark: &BookmarkName,
+    ) -> Result<(), MononokeError> {
+        let user = self.ctx().user_unix_name();
+        if !self.bookmark_attrs().is_allowed_user(user, bookmark) {
+            return Err(MononokeError::InvalidRequest(format!(
+                "User '{}' is not permitted to modify bookmark '{}'",
+                user.as_deref().unwrap_or("unknown"),
+                bookmark
+            )));
+        }
+        Ok(())
+    }
+
+    /// The changeset the bookmark currently points at. This bypasses the
+    /// warm bookmarks cache, which may be out of date.
+    async fn bookmark_target(
+        &self,
+        bookmark: &BookmarkName,
+    ) -> Result<Option<ChangesetId>, MononokeError> {
+        Ok(self
+            .blob_repo()
+            .get_bonsai_bookmark(self.ctx().clone(), bookmark)
+            .compat()
+            .await?)
+    }
+
+    async fn verify_changeset_exists(&self, cs_id: ChangesetId) -> Result<(), MononokeError> {
+        match self.changeset(ChangesetSpecifier::Bonsai(cs_id)).await? {
+            Some(_) => Ok(()),
+            None => Err(MononokeError::InvalidRequest(format!(
+                "Changeset {} does not exist",
+                cs_id
+            ))),
+        }
+    }
+
+    /// Whether any hooks are configured for the bookmark.
+    fn has_hooks(&self, bookmark: &BookmarkName) -> bool {
+        self.bookmark_attrs()
+            .select(bookmark)
+            .any(|params| !params.hooks.is_empty())
+    }
+
+    /// Run the hooks configured for the bookmark on the changesets.
+    async fn run_hooks(
+        &self,
+        bookmark: &BookmarkName,
+        changesets: Vec<ChangesetId>,
+        pushvars: Option<&HashMap<String, Bytes>>,
+    ) -> Result<(), MononokeError> {
+        let changesets: Vec<BonsaiChangeset> = changesets
+            .into_iter()
+            .map(|cs_id| {
+                cs_id
+                    .load(self.ctx().clone(), self.blob_repo().blobstore())
+                    .compat()
+            })
+            .collect::<FuturesOrdered<_>>()
+            .try_collect()
+            .await?;
+
+        // Write contexts are only created for repos that permit writes, which have hooks loaded
+        let hook_manager = self.hook_manager().ok_or_else(|| {
+            MononokeError::from(anyhow::Error::msg("hooks are not loaded for this repo"))
+        })?;
+        let rejections: Vec<_> = hook_manager
+            .run_hooks_for_bookmark(self.ctx(), changesets.iter(), bookmark, pushvars)
+            .await?
+            .into_iter()
+            .filter_map(HookOutcome::into_rejection)
+            .map(|(hook_name, cs_id, reason)| HookRejection {
+                hook_name,
+                cs_id,
+                reason,
+            })
+            .collect();
+
+        if rejections.is_empty() {
+            Ok(())
+        } else {
+            Err(MononokeError::HookFailure(rejections))
+        }
+    }
+}
+
+/// Commit a transaction that modifies a bookmark.
+async fn commit_bookmark_transaction(
+    txn: Box<dyn Transaction>,
+    bookmark: &BookmarkName,
+) -> Result<(), MononokeError> {
+    if !txn.commit().compat().await? {
+        return Err(MononokeError::InvalidRequest(format!(
+            "Bookmark '{}' was modified concurrently",
+            bookmark
+        )));
+    }
+    Ok(())
 }
@@ -7,4 +7,62 @@
 
 mod test_history;
 mod test_repo;
+mod test_repo_bookmarks;
+mod test_repo_land_stack;
 mod test_repo_write;
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use anyhow::Error;
+use bookmarks::BookmarkName;
+use bytes::Bytes;
+use chrono::{FixedOffset, TimeZone};
+use futures::compat::Future01CompatExt;
+
+use crate::{
+    ChangesetContext, ChangesetId, CreateChange, FileType, MononokeError, MononokePath,
+    RepoWriteContext,
+};
+
+/// Look up the bookmark in the database, bypassing the warm bookmarks cache.
+async fn bookmark_target(
+    repo: &RepoWriteContext,
+    bookmark: &str,
+) -> Result<Option<ChangesetId>, Error> {
+    repo.blob_repo()
+        .get_bonsai_bookmark(repo.ctx().clone(), &BookmarkName::new(bookmark)?)
+        .compat()
+        .await
+}
+
+/// Create a draft changeset that sets the content of files, or deletes them
+/// if the content is `None`.
+async fn create_changeset(
+    repo: &RepoWriteContext,
+    parents: Vec<ChangesetId>,
+    changes: Vec<(&str, Option<&str>)>,
+    message: &str,
+) -> Result<ChangesetContext, MononokeError> {
+    let mut create_changes: BTreeMap<MononokePath, CreateChange> = BTreeMap::new();
+    for (path, content) in changes {
+        let change = match content {
+            Some(content) => {
+                CreateChange::NewContent(Bytes::from(content.to_string()), FileType::Regular, None)
+            }
+            None => CreateChange::Delete,
+        };
+        create_changes.insert(MononokePath::try_from(path)?, change);
+    }
+    repo.create_changeset(
+        parents,
+        String::from("Test Author <test@example.com>"),
+        FixedOffset::east(0).ymd(2000, 2, 1).and_hms(12, 0, 0),
+        None,
+        None,
+        message.to_string(),
+        BTreeMap::new(),
+        create_changes,
+    )
+    .await
+}
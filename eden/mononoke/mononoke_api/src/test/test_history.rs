@@ -203,9 +203,9 @@ async fn commit_path_history(fb: FacebookInit) -> Result<()> {
     let a_path = cs.path("a")?;
     let follow_history_across_deletions = true;
     let a_history: Vec<_> = a_path
-        .history(None, follow_history_across_deletions)
+        .history(None, follow_history_across_deletions, 0)
         .await?
-        .and_then(|cs| async move { Ok(cs.id()) })
+        .and_then(|entry| async move { Ok(entry.changeset().id()) })
         .try_collect()
         .await?;
     assert_eq!(
@@ -222,9 +222,9 @@ async fn commit_path_history(fb: FacebookInit) -> Result<()> {
     // History of directory "dir2" includes commits that modified "dir2/b".
     let dir2_path = cs.path("dir2")?;
     let dir2_history: Vec<_> = dir2_path
-        .history(None, follow_history_across_deletions)
+        .history(None, follow_history_across_deletions, 0)
         .await?
-        .and_then(|cs| async move { Ok(cs.id()) })
+        .and_then(|entry| async move { Ok(entry.changeset().id()) })
         .try_collect()
         .await?;
     assert_eq!(
@@ -237,12 +237,27 @@ async fn commit_path_history(fb: FacebookInit) -> Result<()> {
         ]
     );
 
+    // Directory history is the same when copies are followed.
+    let dir2_history_across_copies: Vec<_> = dir2_path
+        .history(None, follow_history_across_deletions, 1)
+        .await?
+        .and_then(|entry| async move { Ok((entry.changeset().id(), entry.copied_from().cloned())) })
+        .try_collect()
+        .await?;
+    assert_eq!(
+        dir2_history_across_copies,
+        dir2_history
+            .iter()
+            .map(|cs_id| (*cs_id, None))
+            .collect::<Vec<_>>()
+    );
+
     // History of directory "dir3" includes some commits on all branches.
     let dir3_path = cs.path("dir3")?;
     let dir3_history: Vec<_> = dir3_path
-        .history(None, follow_history_across_deletions)
+        .history(None, follow_history_across_deletions, 0)
         .await?
-        .and_then(|cs| async move { Ok(cs.id()) })
+        .and_then(|entry| async move { Ok(entry.changeset().id()) })
         .try_collect()
         .await?;
     assert_eq!(
@@ -262,9 +277,9 @@ async fn commit_path_history(fb: FacebookInit) -> Result<()> {
     // Root path history includes all commits except the empty ones.
     let root_path = cs.path("")?;
     let root_history: Vec<_> = root_path
-        .history(None, follow_history_across_deletions)
+        .history(None, follow_history_across_deletions, 0)
         .await?
-        .and_then(|cs| async move { Ok(cs.id()) })
+        .and_then(|entry| async move { Ok(entry.changeset().id()) })
         .try_collect()
         .await?;
     assert_eq!(
@@ -287,6 +302,71 @@ async fn commit_path_history(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn commit_path_history_across_copies(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx).await?;
+    let blob_repo = repo.blob_repo();
+
+    // Move "a" to "moved/a", then change it.
+    let moved = CreateCommitContext::new(&ctx, blob_repo, vec![changesets["c2"]])
+        .delete_file("a")
+        .add_file_with_copy_info("moved/a", "4", (changesets["c2"], "a"))?
+        .commit()
+        .await?;
+    let changed = CreateCommitContext::new(&ctx, blob_repo, vec![moved])
+        .add_file("moved/a", "5")
+        .commit()
+        .await?;
+
+    let cs = repo
+        .changeset(ChangesetSpecifier::Bonsai(changed))
+        .await?
+        .expect("changeset exists");
+    let path = cs.path("moved/a")?;
+
+    let history: Vec<_> = path
+        .history(None, true, 0)
+        .await?
+        .and_then(|entry| async move { Ok(entry.changeset().id()) })
+        .try_collect()
+        .await?;
+    assert_eq!(history, vec![changed, moved]);
+
+    let history: Vec<_> = path
+        .history(None, true, 1)
+        .await?
+        .and_then(|entry| async move {
+            Ok((
+                entry.changeset().id(),
+                entry.path().to_string(),
+                entry
+                    .copied_from()
+                    .map(|(path, cs_id)| (path.to_string(), *cs_id)),
+            ))
+        })
+        .try_collect()
+        .await?;
+    assert_eq!(
+        history,
+        vec![
+            (changed, "moved/a".to_string(), None),
+            (
+                moved,
+                "moved/a".to_string(),
+                Some(("a".to_string(), changesets["c2"]))
+            ),
+            (changesets["a4"], "a".to_string(), None),
+            (changesets["m1"], "a".to_string(), None),
+            (changesets["a3"], "a".to_string(), None),
+            (changesets["a2"], "a".to_string(), None),
+            (changesets["a1"], "a".to_string(), None),
+        ]
+    );
+
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn commit_history(fb: FacebookInit) -> Result<()> {
     let ctx = CoreContext::test_mock(fb);
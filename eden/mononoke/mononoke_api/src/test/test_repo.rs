@@ -11,15 +11,19 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Error};
+use blobrepo::DangerousOverride;
 use blobrepo_factory::new_memblob_empty;
 use blobstore::Loadable;
 use bytes::Bytes;
 use chrono::{FixedOffset, TimeZone};
+use copy_detection::DetectedCopies;
+use derived_data::BonsaiDerived;
 use fbinit::FacebookInit;
 use fixtures::{branch_uneven, linear, many_files_dirs};
 use futures::compat::Future01CompatExt;
 use futures_old::Future;
 use futures_util::stream::TryStreamExt;
+use metaconfig_types::DerivedDataConfig;
 
 use crate::{
     changeset_path_diff::ChangesetPathDiffContext, ChangesetId, ChangesetIdPrefix,
@@ -857,3 +861,57 @@ async fn test_diff_with_moves(fb: FacebookInit) -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[fbinit::compat_test]
+async fn test_diff_with_detected_moves(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo = new_memblob_empty(None)?.dangerous_override(
+        |mut derived_data_config: DerivedDataConfig| {
+            derived_data_config
+                .derived_data_types
+                .insert(DetectedCopies::NAME.to_string());
+            derived_data_config
+        },
+    );
+    let root = CreateCommitContext::new_root(&ctx, &blobrepo)
+        .add_file("file_to_move", "context1")
+        .commit()
+        .await?;
+
+    // The move isn't recorded, but the content is the same
+    let commit_with_move = CreateCommitContext::new(&ctx, &blobrepo, vec![root])
+        .add_file("file_moved", "context1")
+        .delete_file("file_to_move")
+        .commit()
+        .await?;
+
+    let mononoke =
+        Mononoke::new_test(ctx.clone(), vec![("test".to_string(), blobrepo.clone())]).await?;
+
+    let repo = mononoke
+        .repo(ctx.clone(), "test")
+        .await?
+        .expect("repo exists");
+    let commit_with_move_ctx = repo
+        .changeset(ChangesetSpecifier::Bonsai(commit_with_move))
+        .await?
+        .ok_or(anyhow!("commit not found"))?;
+    let diff = commit_with_move_ctx
+        .diff(
+            root, true, /* include_copies_renames */
+            None, /* path_restrictions */
+        )
+        .await?;
+
+    assert_eq!(diff.len(), 1);
+    match diff.get(0) {
+        Some(ChangesetPathDiffContext::Moved(to, from)) => {
+            assert_eq!(to.path(), &MononokePath::try_from("file_moved")?);
+            assert_eq!(from.path(), &MononokePath::try_from("file_to_move")?);
+        }
+        _ => {
+            panic!("unexpected diff");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,229 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use assert_matches::assert_matches;
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use fbinit::FacebookInit;
+use fixtures::linear;
+use hooks::{ChangesetHook, HookExecution, HookManager, HookRejectionInfo};
+use hooks_content_stores::{FileContentFetcher, InMemoryFileContentFetcher};
+use metaconfig_types::{BookmarkAttrs, BookmarkOrRegex, BookmarkParams, HookManagerParams};
+use mononoke_types::BonsaiChangeset;
+use regex::Regex;
+use scuba_ext::ScubaSampleBuilder;
+
+use super::{bookmark_target, create_changeset};
+use crate::{ChangesetId, CoreContext, Mononoke, MononokeError, SessionContainer};
+
+const MASTER_HASH: &str = "7785606eb1f26ff5722c831de402350cf97052dc44bc175da6ac0d715a3dbbf6";
+const ANCESTOR_HASH: &str = "2cb6d2d3052bfbdd6a95a61f2816d81130033b5f5a99e8d8fc24d9238d85bb48";
+
+#[fbinit::compat_test]
+async fn create_move_delete_bookmark(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), linear::getrepo(fb).await)],
+    )
+    .await?;
+    let repo = mononoke
+        .repo(ctx, "test")
+        .await?
+        .expect("repo exists")
+        .write()
+        .await?;
+    let master = ChangesetId::from_str(MASTER_HASH)?;
+    let ancestor = ChangesetId::from_str(ANCESTOR_HASH)?;
+
+    // Bookmarks can only be created at existing changesets, and only if they
+    // don't exist yet.
+    assert_matches!(
+        repo.create_bookmark("master", ancestor, None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_matches!(
+        repo.create_bookmark("newbook", ChangesetId::from_bytes(&[1; 32])?, None)
+            .await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    repo.create_bookmark("newbook", ancestor, None).await?;
+    assert_eq!(bookmark_target(&repo, "newbook").await?, Some(ancestor));
+
+    // Fast-forward moves are always permitted, other moves must be allowed
+    // explicitly.
+    repo.move_bookmark("newbook", master, false, None).await?;
+    assert_eq!(bookmark_target(&repo, "newbook").await?, Some(master));
+    assert_matches!(
+        repo.move_bookmark("newbook", ancestor, false, None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_eq!(bookmark_target(&repo, "newbook").await?, Some(master));
+    repo.move_bookmark("newbook", ancestor, true, None).await?;
+    assert_eq!(bookmark_target(&repo, "newbook").await?, Some(ancestor));
+    assert_matches!(
+        repo.move_bookmark("nobook", master, false, None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    // Deletions check the bookmark still points at the expected changeset.
+    assert_matches!(
+        repo.delete_bookmark("newbook", Some(master)).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    repo.delete_bookmark("newbook", Some(ancestor)).await?;
+    assert_eq!(bookmark_target(&repo, "newbook").await?, None);
+    assert_matches!(
+        repo.delete_bookmark("newbook", None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    Ok(())
+}
+
+/// A hook that rejects changesets that ask to be rejected.
+struct RejectMessageHook;
+
+#[async_trait]
+impl ChangesetHook for RejectMessageHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        changeset: &'cs BonsaiChangeset,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        if changeset.message().contains("REJECT") {
+            Ok(HookExecution::Rejected(HookRejectionInfo::new(
+                "Commit message asks for rejection",
+            )))
+        } else {
+            Ok(HookExecution::Accepted)
+        }
+    }
+}
+
+#[fbinit::compat_test]
+async fn bookmark_attrs_and_hooks(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let master_bookmark = BookmarkName::new("master")?;
+    let bookmark_attrs = BookmarkAttrs::new(vec![BookmarkParams {
+        bookmark: BookmarkOrRegex::Bookmark(master_bookmark.clone()),
+        hooks: vec!["reject_message".to_string()],
+        only_fast_forward: true,
+        rewrite_dates: None,
+        allowed_users: Some(Regex::new("^svc-")?),
+    }]);
+    let mut hook_manager = HookManager::new(
+        fb,
+        Box::new(InMemoryFileContentFetcher::new()),
+        HookManagerParams {
+            disable_acl_checker: true,
+        },
+        ScubaSampleBuilder::with_discard(),
+    )
+    .await?;
+    hook_manager.register_changeset_hook(
+        "reject_message",
+        Box::new(RejectMessageHook),
+        Default::default(),
+    );
+    hook_manager.set_hooks_for_bookmark(
+        BookmarkOrRegex::Bookmark(master_bookmark),
+        vec!["reject_message".to_string()],
+    );
+    let mononoke = Mononoke::new_test_bookmarks(
+        ctx.clone(),
+        "test".to_string(),
+        linear::getrepo(fb).await,
+        bookmark_attrs,
+        hook_manager,
+    )
+    .await?;
+
+    let user_ctx = |user: &str| {
+        SessionContainer::builder(fb)
+            .user_unix_name(user.to_string())
+            .build()
+            .new_context(ctx.logger().clone(), ScubaSampleBuilder::with_discard())
+    };
+    let repo = mononoke
+        .repo(user_ctx("svc-test"), "test")
+        .await?
+        .expect("repo exists")
+        .write()
+        .await?;
+    let other_repo = mononoke
+        .repo(user_ctx("someone"), "test")
+        .await?
+        .expect("repo exists")
+        .write()
+        .await?;
+    let master = ChangesetId::from_str(MASTER_HASH)?;
+    let ancestor = ChangesetId::from_str(ANCESTOR_HASH)?;
+
+    // Only permitted users can modify the bookmark.
+    let accepted = create_changeset(
+        &repo,
+        vec![master],
+        vec![("TEST_BOOKMARK", Some("Accepted"))],
+        "Accepted",
+    )
+    .await?
+    .id();
+    assert_matches!(
+        other_repo
+            .move_bookmark("master", accepted, false, None)
+            .await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_eq!(bookmark_target(&repo, "master").await?, Some(master));
+
+    // The bookmark is fast-forward only, so it can't be moved backwards or
+    // deleted.
+    assert_matches!(
+        repo.move_bookmark("master", ancestor, true, None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_matches!(
+        repo.delete_bookmark("master", None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    // Hooks run on all the new changesets.
+    let rejected = create_changeset(
+        &repo,
+        vec![master],
+        vec![("TEST_BOOKMARK", Some("Please REJECT this"))],
+        "Please REJECT this",
+    )
+    .await?
+    .id();
+    let child = create_changeset(
+        &repo,
+        vec![rejected],
+        vec![("TEST_BOOKMARK", Some("Accepted"))],
+        "Accepted",
+    )
+    .await?
+    .id();
+    assert_matches!(
+        repo.move_bookmark("master", child, false, None).await,
+        Err(MononokeError::HookFailure(rejections)) if rejections.len() == 1 && rejections[0].cs_id == rejected
+    );
+    assert_eq!(bookmark_target(&repo, "master").await?, Some(master));
+
+    repo.move_bookmark("master", accepted, false, None).await?;
+    assert_eq!(bookmark_target(&repo, "master").await?, Some(accepted));
+
+    // Other bookmarks are not affected by the hooks.
+    repo.create_bookmark("other", rejected, None).await?;
+    assert_eq!(bookmark_target(&repo, "other").await?, Some(rejected));
+
+    Ok(())
+}
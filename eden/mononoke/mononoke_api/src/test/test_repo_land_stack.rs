@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::convert::TryFrom;
+
+use anyhow::Error;
+use assert_matches::assert_matches;
+use fbinit::FacebookInit;
+use fixtures::linear;
+
+use super::{bookmark_target, create_changeset};
+use crate::{
+    ChangesetContext, ChangesetId, ChangesetSpecifier, CoreContext, Mononoke, MononokeError,
+    MononokePath, RepoWriteContext,
+};
+
+const MASTER_HASH: &str = "7785606eb1f26ff5722c831de402350cf97052dc44bc175da6ac0d715a3dbbf6";
+
+async fn changeset(
+    repo: &RepoWriteContext,
+    cs_id: ChangesetId,
+) -> Result<ChangesetContext, MononokeError> {
+    Ok(repo
+        .changeset(ChangesetSpecifier::Bonsai(cs_id))
+        .await?
+        .expect("changeset exists"))
+}
+
+#[fbinit::compat_test]
+async fn land_stack(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), linear::getrepo(fb).await)],
+    )
+    .await?;
+    let repo = mononoke
+        .repo(ctx, "test")
+        .await?
+        .expect("repo exists")
+        .write()
+        .await?;
+    let base = ChangesetId::from_str(MASTER_HASH)?;
+
+    // Move master on, so that the stack needs to be rebased.
+    let landed = create_changeset(
+        &repo,
+        vec![base],
+        vec![("landed", Some("landed"))],
+        "Set landed to landed",
+    )
+    .await?
+    .id();
+    repo.move_bookmark("master", landed, false, None).await?;
+
+    let first = create_changeset(
+        &repo,
+        vec![base],
+        vec![("stack", Some("first"))],
+        "Set stack to first",
+    )
+    .await?
+    .id();
+    let second = create_changeset(
+        &repo,
+        vec![first],
+        vec![("stack", Some("second"))],
+        "Set stack to second",
+    )
+    .await?
+    .id();
+    let outcome = repo.land_stack("master", second, base, None).await?;
+
+    assert_eq!(bookmark_target(&repo, "master").await?, Some(outcome.head));
+    assert_eq!(outcome.rebased_changesets.len(), 2);
+    let new_first = outcome.rebased_changesets[&first];
+    let new_second = outcome.rebased_changesets[&second];
+    assert_eq!(new_second, outcome.head);
+    assert_eq!(
+        changeset(&repo, new_first).await?.parents().await?,
+        vec![landed]
+    );
+    assert_eq!(
+        changeset(&repo, new_second).await?.parents().await?,
+        vec![new_first]
+    );
+    assert_eq!(
+        changeset(&repo, new_second).await?.message().await?,
+        "Set stack to second"
+    );
+
+    // Stacks must be non-empty, and the base must be an ancestor of the head.
+    let other = create_changeset(
+        &repo,
+        vec![base],
+        vec![("other", Some("other"))],
+        "Set other to other",
+    )
+    .await?
+    .id();
+    assert_matches!(
+        repo.land_stack("master", other, other, None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_matches!(
+        repo.land_stack("master", other, first, None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_eq!(bookmark_target(&repo, "master").await?, Some(outcome.head));
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn land_stack_conflicts(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), linear::getrepo(fb).await)],
+    )
+    .await?;
+    let repo = mononoke
+        .repo(ctx, "test")
+        .await?
+        .expect("repo exists")
+        .write()
+        .await?;
+    let base = ChangesetId::from_str(MASTER_HASH)?;
+
+    let landed = create_changeset(
+        &repo,
+        vec![base],
+        vec![("conflict", Some("landed"))],
+        "Set conflict to landed",
+    )
+    .await?
+    .id();
+    repo.move_bookmark("master", landed, false, None).await?;
+
+    // Stacks that change the same files as the commits they would be rebased
+    // over are not landed, and the conflicting paths are reported.
+    let first = create_changeset(
+        &repo,
+        vec![base],
+        vec![("stack", Some("first"))],
+        "Set stack to first",
+    )
+    .await?
+    .id();
+    let second = create_changeset(
+        &repo,
+        vec![first],
+        vec![("conflict", Some("second"))],
+        "Set conflict to second",
+    )
+    .await?
+    .id();
+    match repo.land_stack("master", second, base, None).await {
+        Err(MononokeError::PushrebaseConflicts(conflicts)) => {
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].left, MononokePath::try_from("conflict")?);
+            assert_eq!(conflicts[0].right, MononokePath::try_from("conflict")?);
+        }
+        result => panic!("expected pushrebase conflicts, got {:?}", result),
+    }
+    assert_eq!(bookmark_target(&repo, "master").await?, Some(landed));
+
+    Ok(())
+}
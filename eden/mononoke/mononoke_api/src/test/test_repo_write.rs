@@ -15,11 +15,14 @@ use chrono::{FixedOffset, TimeZone};
 use fbinit::FacebookInit;
 use fixtures::{linear, many_files_dirs};
 
+use super::create_changeset;
 use crate::{
     ChangesetContext, ChangesetId, CoreContext, CreateChange, FileType, Mononoke, MononokeError,
     MononokePath, RepoWriteContext,
 };
 
+const MESSAGE: &str = "Test Created Commit";
+
 #[fbinit::compat_test]
 async fn create_commit(fb: FacebookInit) -> Result<(), Error> {
     let ctx = CoreContext::test_mock(fb);
@@ -179,3 +182,175 @@ async fn create_commit_bad_changes(fb: FacebookInit) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[fbinit::compat_test]
+async fn create_merge_and_root_commits(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), linear::getrepo(fb).await)],
+    )
+    .await?;
+    let repo = mononoke
+        .repo(ctx, "test")
+        .await?
+        .expect("repo exists")
+        .write()
+        .await?;
+
+    async fn content(cs: &ChangesetContext, path: &str) -> Result<Option<Bytes>, MononokeError> {
+        match cs.path(path)?.file().await? {
+            Some(file) => Ok(Some(file.content_concat().await?)),
+            None => Ok(None),
+        }
+    }
+
+    // Root commits have no parents, so they cannot delete files.
+    assert_matches!(
+        create_changeset(&repo, vec![], vec![("file", None)], MESSAGE).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    let root = create_changeset(&repo, vec![], vec![("file", Some("root\n"))], MESSAGE).await?;
+    assert_eq!(root.parents().await?, vec![]);
+    assert_eq!(content(&root, "file").await?, Some(Bytes::from("root\n")));
+
+    // Merges of parents that do not conflict need no changes.
+    let left = create_changeset(
+        &repo,
+        vec![root.id()],
+        vec![("left", Some("left\n"))],
+        MESSAGE,
+    )
+    .await?;
+    let right = create_changeset(
+        &repo,
+        vec![root.id()],
+        vec![("right", Some("right\n"))],
+        MESSAGE,
+    )
+    .await?;
+    let merge = create_changeset(&repo, vec![left.id(), right.id()], vec![], MESSAGE).await?;
+    assert_eq!(merge.parents().await?, vec![left.id(), right.id()]);
+    assert_eq!(content(&merge, "file").await?, Some(Bytes::from("root\n")));
+    assert_eq!(content(&merge, "left").await?, Some(Bytes::from("left\n")));
+    assert_eq!(
+        content(&merge, "right").await?,
+        Some(Bytes::from("right\n"))
+    );
+
+    // Files that differ between the parents must be resolved, even if only
+    // one of the parents changed them.
+    let left = create_changeset(
+        &repo,
+        vec![root.id()],
+        vec![("file", Some("left\n"))],
+        MESSAGE,
+    )
+    .await?;
+    assert_matches!(
+        create_changeset(&repo, vec![left.id(), root.id()], vec![], MESSAGE).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    let right = create_changeset(
+        &repo,
+        vec![root.id()],
+        vec![("file", Some("right\n"))],
+        MESSAGE,
+    )
+    .await?;
+    assert_matches!(
+        create_changeset(&repo, vec![left.id(), right.id()], vec![], MESSAGE).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_matches!(
+        create_changeset(
+            &repo,
+            vec![left.id(), right.id()],
+            vec![("unrelated", Some("x\n"))],
+            MESSAGE
+        )
+        .await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    let merge = create_changeset(
+        &repo,
+        vec![left.id(), right.id()],
+        vec![("file", Some("both\n"))],
+        MESSAGE,
+    )
+    .await?;
+    assert_eq!(content(&merge, "file").await?, Some(Bytes::from("both\n")));
+    let merge = create_changeset(
+        &repo,
+        vec![left.id(), right.id()],
+        vec![("file", None)],
+        MESSAGE,
+    )
+    .await?;
+    assert_eq!(content(&merge, "file").await?, None);
+
+    // A file in one parent that is a directory in another must be resolved.
+    let file = create_changeset(
+        &repo,
+        vec![root.id()],
+        vec![("dir", Some("file\n"))],
+        MESSAGE,
+    )
+    .await?;
+    let dir = create_changeset(
+        &repo,
+        vec![root.id()],
+        vec![("dir/file", Some("dir\n"))],
+        MESSAGE,
+    )
+    .await?;
+    assert_matches!(
+        create_changeset(&repo, vec![file.id(), dir.id()], vec![], MESSAGE).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    let merge = create_changeset(
+        &repo,
+        vec![file.id(), dir.id()],
+        vec![("dir", None)],
+        MESSAGE,
+    )
+    .await?;
+    assert_eq!(
+        content(&merge, "dir/file").await?,
+        Some(Bytes::from("dir\n"))
+    );
+    let merge = create_changeset(
+        &repo,
+        vec![file.id(), dir.id()],
+        vec![("dir", Some("both\n"))],
+        MESSAGE,
+    )
+    .await?;
+    assert_eq!(content(&merge, "dir").await?, Some(Bytes::from("both\n")));
+
+    // Replacing a prefix directory with a file also resolves conflicts.
+    let left = create_changeset(
+        &repo,
+        vec![root.id()],
+        vec![("a/b", Some("left\n"))],
+        MESSAGE,
+    )
+    .await?;
+    let right = create_changeset(
+        &repo,
+        vec![root.id()],
+        vec![("a/b", Some("right\n"))],
+        MESSAGE,
+    )
+    .await?;
+    let merge = create_changeset(
+        &repo,
+        vec![left.id(), right.id()],
+        vec![("a", Some("a\n"))],
+        MESSAGE,
+    )
+    .await?;
+    assert_eq!(content(&merge, "a").await?, Some(Bytes::from("a\n")));
+
+    Ok(())
+}
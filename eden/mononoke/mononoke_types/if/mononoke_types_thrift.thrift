@@ -52,6 +52,7 @@ typedef IdType MPathHash (rust.newtype)
 typedef IdType ContentMetadataId (rust.newtype)
 typedef IdType FastlogBatchId (rust.newtype)
 typedef IdType BlameId (rust.newtype)
+typedef IdType BlameChunkId (rust.newtype)
 
 // mercurial_types defines Sha1, and it's most convenient to stick this in here.
 // This can be moved away in the future if necessary. Could also be used for
@@ -338,7 +339,15 @@ enum BlameRejected {
   Binary = 1,
 }
 
+// Blame for a file with many ranges, split into chunks that are stored
+// separately. Each chunk is a `Blame` whose offsets start at zero, and the
+// full blame is their concatenation.
+struct ChunkedBlame {
+  1: list<BlameChunkId> chunks,
+}
+
 union BlameMaybeRejected {
   1: Blame Blame (py3.name = "blame"),
   2: BlameRejected Rejected,
+  3: ChunkedBlame ChunkedBlame,
 }
@@ -6,32 +6,59 @@
  */
 
 use crate::{
+    blob::{BlameChunkBlob, Blob, BlobstoreValue},
+    errors::ErrorKind,
     thrift,
-    typed_hash::{FileUnodeId, MononokeId},
+    typed_hash::{BlameChunkId, BlameChunkIdContext, FileUnodeId, MononokeId},
     ChangesetId, MPath,
 };
-use anyhow::{bail, format_err, Error};
-use blobstore::{Blobstore, BlobstoreBytes, Loadable, LoadableError};
+use anyhow::{bail, format_err, Context, Error};
+use blobstore::{Blobstore, BlobstoreBytes, Loadable, LoadableError, Storable};
 use context::CoreContext;
 use fbthrift::compact_protocol;
-use futures::Future;
+use futures::{future, Future, IntoFuture};
 use futures_ext::{BoxFuture, FutureExt};
 use std::{collections::HashMap, convert::TryFrom};
 use thiserror::Error;
 use xdiff::{diff_hunks, Hunk};
 
+/// The copies that blame follows to attribute lines to the files they were copied from.
+///
+/// Blame is derived separately for each, as the blame of a file depends on them.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct BlameId(FileUnodeId);
+pub enum BlameCopies {
+    /// Only the copies recorded in bonsai changesets.
+    Recorded,
+    /// The recorded copies, and the copies detected in changesets that didn't record them.
+    Detected,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct BlameId(FileUnodeId, BlameCopies);
 
 impl BlameId {
+    pub fn new(file_unode_id: FileUnodeId, copies: BlameCopies) -> Self {
+        BlameId(file_unode_id, copies)
+    }
+
+    pub fn copies(&self) -> BlameCopies {
+        self.1
+    }
+
     pub fn blobstore_key(&self) -> String {
-        format!("blame.{}", self.0.blobstore_key())
+        match self.1 {
+            BlameCopies::Recorded => format!("blame.{}", self.0.blobstore_key()),
+            BlameCopies::Detected => {
+                format!("blame_detected_copies.{}", self.0.blobstore_key())
+            }
+        }
     }
 }
 
+/// Blame following the recorded copies only.
 impl From<FileUnodeId> for BlameId {
     fn from(file_unode_id: FileUnodeId) -> Self {
-        BlameId(file_unode_id)
+        BlameId(file_unode_id, BlameCopies::Recorded)
     }
 }
 
@@ -51,18 +78,104 @@ impl Loadable for BlameId {
     ) -> BoxFuture<Self::Value, LoadableError> {
         let blobstore_key = self.blobstore_key();
         blobstore
-            .get(ctx, blobstore_key.clone())
+            .get(ctx.clone(), blobstore_key.clone())
             .from_err()
             .and_then(move |bytes| {
                 let bytes = bytes.ok_or(LoadableError::Missing(blobstore_key))?;
-                let blame_t = compact_protocol::deserialize(bytes.as_raw_bytes().as_ref())?;
-                let blame = BlameMaybeRejected::from_thrift(blame_t)?;
-                Ok(blame)
+                let blame_t: thrift::BlameMaybeRejected =
+                    compact_protocol::deserialize(bytes.as_raw_bytes().as_ref())?;
+                Ok(blame_t)
+            })
+            .and_then({
+                let blobstore = blobstore.clone();
+                move |blame_t| match blame_t {
+                    thrift::BlameMaybeRejected::ChunkedBlame(chunked_t) => {
+                        load_chunked_blame(ctx, blobstore, chunked_t)
+                            .map(BlameMaybeRejected::Blame)
+                            .left_future()
+                    }
+                    blame_t => BlameMaybeRejected::from_thrift(blame_t)
+                        .map_err(LoadableError::from)
+                        .into_future()
+                        .right_future(),
+                }
             })
             .boxify()
     }
 }
 
+fn load_chunked_blame<B: Blobstore + Clone>(
+    ctx: CoreContext,
+    blobstore: B,
+    chunked_t: thrift::ChunkedBlame,
+) -> impl Future<Item = Blame, Error = LoadableError> {
+    chunked_t
+        .chunks
+        .into_iter()
+        .map(BlameChunkId::from_thrift)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(LoadableError::from)
+        .into_future()
+        .and_then(move |chunk_ids| {
+            future::join_all(
+                chunk_ids
+                    .into_iter()
+                    .map(move |chunk_id| chunk_id.load(ctx.clone(), &blobstore)),
+            )
+        })
+        .and_then(|chunks| {
+            Blame::concat(chunks.into_iter().map(BlameChunk::into_blame))
+                .map_err(LoadableError::from)
+        })
+}
+
+/// Blames with more ranges than this are stored as separate chunks, so that changing a few lines
+/// of a large file only writes the chunks around the change rather than its whole blame again.
+const BLAME_CHUNKING_THRESHOLD: usize = 4096;
+/// A chunk ends after about one range in this many (see `is_chunk_boundary`).
+const BLAME_CHUNK_AVERAGE_RANGES: u64 = 1024;
+/// Upper bound on the ranges in a chunk, should boundaries be unusually sparse.
+const BLAME_CHUNK_MAX_RANGES: usize = 8192;
+
+/// Whether a chunk ends after this range.
+///
+/// This only depends on the range itself, and a range keeps its changeset and origin offsets as
+/// lines around it are changed, so the boundaries (and so the chunks) away from a change stay the
+/// same between successive versions of a file.
+fn is_chunk_boundary(range: &BlameRange) -> bool {
+    let origin_end = u64::from(range.origin_offset + range.length);
+    let hash = (range.csid.sampling_fingerprint() ^ origin_end).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    (hash >> 32) % BLAME_CHUNK_AVERAGE_RANGES == 0
+}
+
+/// A part of a blame that is stored separately. Its ranges start from offset zero.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BlameChunk(Blame);
+
+impl BlameChunk {
+    pub fn into_blame(self) -> Blame {
+        self.0
+    }
+}
+
+impl BlobstoreValue for BlameChunk {
+    type Key = BlameChunkId;
+
+    fn into_blob(self) -> BlameChunkBlob {
+        let data = compact_protocol::serialize(&self.0.into_thrift());
+        let mut context = BlameChunkIdContext::new();
+        context.update(&data);
+        let id = context.finish();
+        Blob::new(id, data)
+    }
+
+    fn from_blob(blob: BlameChunkBlob) -> Result<Self, Error> {
+        let blame_t = compact_protocol::deserialize(blob.data().as_ref())
+            .with_context(|| ErrorKind::BlobDeserializeError("BlameChunk".into()))?;
+        Ok(BlameChunk(Blame::from_thrift(blame_t)?))
+    }
+}
+
 /// Store blame object as associated blame to provided FileUnodeId
 ///
 /// NOTE: `Blame` is not a `Storable` object and can only be assoicated with
@@ -70,16 +183,51 @@ impl Loadable for BlameId {
 pub fn store_blame<B: Blobstore + Clone>(
     ctx: CoreContext,
     blobstore: &B,
-    file_unode_id: FileUnodeId,
+    blame_id: BlameId,
     blame: BlameMaybeRejected,
 ) -> impl Future<Item = BlameId, Error = Error> {
-    let blame_t = blame.into_thrift();
-    let data = compact_protocol::serialize(&blame_t);
-    let data = BlobstoreBytes::from_bytes(data);
-    let blame_id = BlameId::from(file_unode_id);
-    blobstore
-        .put(ctx, blame_id.blobstore_key(), data)
-        .map(move |_| blame_id)
+    let blame_t = match blame {
+        BlameMaybeRejected::Blame(blame) if blame.ranges.len() > BLAME_CHUNKING_THRESHOLD => {
+            // Chunks are content-addressed, so the ones the previous version of the file shares
+            // with this one are already stored and needn't be written again.
+            let chunks = blame.into_chunks().into_iter().map(|chunk| {
+                let blob = chunk.into_blob();
+                let chunk_id = *blob.id();
+                let blobstore = blobstore.clone();
+                blobstore
+                    .is_present(ctx.clone(), chunk_id.blobstore_key())
+                    .and_then({
+                        let ctx = ctx.clone();
+                        move |is_present| {
+                            if is_present {
+                                future::ok(chunk_id).left_future()
+                            } else {
+                                blob.store(ctx, &blobstore).right_future()
+                            }
+                        }
+                    })
+            });
+            future::join_all(chunks)
+                .map(|chunk_ids| {
+                    thrift::BlameMaybeRejected::ChunkedBlame(thrift::ChunkedBlame {
+                        chunks: chunk_ids
+                            .into_iter()
+                            .map(|chunk_id| chunk_id.into_thrift())
+                            .collect(),
+                    })
+                })
+                .left_future()
+        }
+        blame => future::ok(blame.into_thrift()).right_future(),
+    };
+    let blobstore = blobstore.clone();
+    blame_t.and_then(move |blame_t| {
+        let data = compact_protocol::serialize(&blame_t);
+        let data = BlobstoreBytes::from_bytes(data);
+        blobstore
+            .put(ctx, blame_id.blobstore_key(), data)
+            .map(move |_| blame_id)
+    })
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Error)]
@@ -135,6 +283,9 @@ impl BlameMaybeRejected {
             thrift::BlameMaybeRejected::Blame(blame_t) => {
                 Ok(BlameMaybeRejected::Blame(Blame::from_thrift(blame_t)?))
             }
+            thrift::BlameMaybeRejected::ChunkedBlame(_) => Err(format_err!(
+                "BlameMaybeRejected contains a chunked blame, which must be loaded from a blobstore"
+            )),
             thrift::BlameMaybeRejected::UnknownField(id) => Err(format_err!(
                 "BlameMaybeRejected contains unknown variant with id: {}",
                 id
@@ -305,6 +456,49 @@ impl Blame {
         &self.ranges
     }
 
+    /// Split into chunks, at boundaries chosen by `is_chunk_boundary`.
+    fn into_chunks(self) -> Vec<BlameChunk> {
+        let mut chunks = Vec::new();
+        let mut ranges = Vec::new();
+        let mut chunk_offset = 0;
+        for range in self.ranges {
+            let boundary = is_chunk_boundary(&range);
+            let end = range.offset + range.length;
+            ranges.push(BlameRange {
+                offset: range.offset - chunk_offset,
+                ..range
+            });
+            if boundary || ranges.len() >= BLAME_CHUNK_MAX_RANGES {
+                chunk_offset = end;
+                chunks.push(BlameChunk(Blame {
+                    ranges: std::mem::replace(&mut ranges, Vec::new()),
+                }));
+            }
+        }
+        if !ranges.is_empty() {
+            chunks.push(BlameChunk(Blame { ranges }));
+        }
+        chunks
+    }
+
+    /// Join blames covering consecutive parts of a file.
+    fn concat(blames: impl IntoIterator<Item = Blame>) -> Result<Blame, Error> {
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        for blame in blames {
+            let mut length = 0;
+            for range in blame.ranges {
+                length = range.offset + range.length;
+                ranges.push(BlameRange {
+                    offset: offset + range.offset,
+                    ..range
+                });
+            }
+            offset += length;
+        }
+        Blame::new(ranges)
+    }
+
     pub fn from_parents<C>(
         csid: ChangesetId,
         content: C,
@@ -609,6 +803,9 @@ impl<'a> Iterator for BlameMergeLines<'a> {
 mod test {
     use super::*;
     use crate::hash::Blake2;
+    use fbinit::FacebookInit;
+    use fixtures::linear;
+    use tokio_compat::runtime::Runtime;
 
     const ONES_CSID: ChangesetId = ChangesetId::new(Blake2::from_byte_array([0x11; 32]));
     const TWOS_CSID: ChangesetId = ChangesetId::new(Blake2::from_byte_array([0x22; 32]));
@@ -823,6 +1020,90 @@ mod test {
         Ok(())
     }
 
+    // Blame with one range per line, large enough to be chunked
+    fn large_blame_ranges(path: &MPath) -> Vec<BlameRange> {
+        let csids = [ONES_CSID, TWOS_CSID, THREES_CSID, FOURS_CSID];
+        (0..20000u32)
+            .map(|index| BlameRange {
+                offset: index,
+                length: 1,
+                csid: csids[index as usize % csids.len()],
+                path: path.clone(),
+                origin_offset: index,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_blame_chunks() -> Result<(), Error> {
+        let path = MPath::new("path")?;
+        let ranges = large_blame_ranges(&path);
+        let blame = Blame::new(ranges.clone())?;
+
+        let chunks = blame.clone().into_chunks();
+        assert!(chunks.len() > 1);
+        for chunk in chunks.iter() {
+            assert_eq!(chunk.0.ranges[0].offset, 0);
+            assert!(chunk.0.ranges.len() <= BLAME_CHUNK_MAX_RANGES);
+        }
+        assert_eq!(
+            Blame::concat(chunks.clone().into_iter().map(BlameChunk::into_blame))?,
+            blame
+        );
+
+        // Adding a line at the start only changes the first chunk
+        let mut edited_ranges = vec![BlameRange {
+            offset: 0,
+            length: 1,
+            csid: FOURS_CSID,
+            path: path.clone(),
+            origin_offset: 0,
+        }];
+        edited_ranges.extend(ranges.into_iter().map(|range| BlameRange {
+            offset: range.offset + 1,
+            ..range
+        }));
+        let edited_chunks = Blame::new(edited_ranges)?.into_chunks();
+        let unchanged = chunks.len() - 1;
+        assert_eq!(
+            &edited_chunks[edited_chunks.len() - unchanged..],
+            &chunks[1..]
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    fn test_store_chunked_blame(fb: FacebookInit) -> Result<(), Error> {
+        let mut rt = Runtime::new()?;
+        let blobstore = rt.block_on_std(linear::getrepo(fb)).get_blobstore();
+        let ctx = CoreContext::test_mock(fb);
+
+        let path = MPath::new("path")?;
+        let blame = Blame::new(large_blame_ranges(&path))?;
+        let file_unode_id = FileUnodeId::from_byte_array([1; 32]);
+        let blame_id = rt.block_on(store_blame(
+            ctx.clone(),
+            &blobstore,
+            BlameId::from(file_unode_id),
+            blame.clone().into(),
+        ))?;
+
+        let blame_t = rt
+            .block_on(blobstore.get(ctx.clone(), blame_id.blobstore_key()))?
+            .ok_or_else(|| Error::msg("blame is missing"))?;
+        let blame_t = compact_protocol::deserialize(blame_t.as_raw_bytes().as_ref())?;
+        match blame_t {
+            thrift::BlameMaybeRejected::ChunkedBlame(_) => {}
+            _ => bail!("large blame should be stored in chunks"),
+        }
+
+        let loaded = rt.block_on(blame_id.load(ctx, &blobstore))?;
+        assert_eq!(loaded, BlameMaybeRejected::Blame(blame));
+
+        Ok(())
+    }
+
     #[test]
     fn test_blame_add_remove_add_whole_file() -> Result<(), Error> {
         let path = MPath::new("test/file")?;
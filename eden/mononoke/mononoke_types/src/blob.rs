@@ -12,8 +12,8 @@ use blobstore::BlobstoreBytes;
 use bytes::Bytes;
 
 use crate::typed_hash::{
-    ChangesetId, ContentChunkId, ContentId, ContentMetadataId, DeletedManifestId, FastlogBatchId,
-    FileUnodeId, FsnodeId, ManifestUnodeId, RawBundle2Id,
+    BlameChunkId, ChangesetId, ContentChunkId, ContentId, ContentMetadataId, DeletedManifestId,
+    FastlogBatchId, FileUnodeId, FsnodeId, ManifestUnodeId, RawBundle2Id,
 };
 
 /// A serialized blob in memory.
@@ -51,6 +51,7 @@ pub type DeletedManifestBlob = Blob<DeletedManifestId>;
 pub type FsnodeBlob = Blob<FsnodeId>;
 pub type ContentMetadataBlob = Blob<ContentMetadataId>;
 pub type FastlogBatchBlob = Blob<FastlogBatchId>;
+pub type BlameChunkBlob = Blob<BlameChunkId>;
 
 impl<Id> From<Blob<Id>> for BlobstoreBytes {
     #[inline]
@@ -31,7 +31,7 @@ pub mod sql_types;
 pub mod typed_hash;
 pub mod unode;
 
-pub use blame::{Blame, BlameId, BlameRange};
+pub use blame::{Blame, BlameChunk, BlameCopies, BlameId, BlameRange};
 pub use blob::{Blob, BlobstoreValue, ChangesetBlob, ContentBlob, RawBundle2Blob};
 pub use blobstore::BlobstoreBytes;
 pub use bonsai_changeset::{BonsaiChangeset, BonsaiChangesetMut};
@@ -45,9 +45,9 @@ pub use path::{check_case_conflicts, MPath, MPathElement, MPathHash, RepoPath};
 pub use rawbundle2::RawBundle2;
 pub use repo::RepositoryId;
 pub use typed_hash::{
-    ChangesetId, ChangesetIdPrefix, ChangesetIdsResolvedFromPrefix, ContentChunkId, ContentId,
-    ContentMetadataId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId, ManifestUnodeId,
-    MononokeId, RawBundle2Id,
+    BlameChunkId, ChangesetId, ChangesetIdPrefix, ChangesetIdsResolvedFromPrefix, ContentChunkId,
+    ContentId, ContentMetadataId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId,
+    ManifestUnodeId, MononokeId, RawBundle2Id,
 };
 
 mod macros;
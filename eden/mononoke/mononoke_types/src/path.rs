@@ -13,6 +13,7 @@ use std::fmt::{self, Display};
 use std::io::{self, Write};
 use std::iter::{once, FromIterator, Once};
 use std::slice::Iter;
+use std::str::FromStr;
 
 use anyhow::{bail, Context as _, Error, Result};
 use bytes::Bytes;
@@ -666,6 +667,14 @@ impl MPathHash {
     }
 }
 
+impl FromStr for MPathHash {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(MPathHash(Blake2::from_str(s)?))
+    }
+}
+
 /// Context for incrementally computing a hash.
 #[derive(Clone)]
 pub struct MPathHashContext(Context);
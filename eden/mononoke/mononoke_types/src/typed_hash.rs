@@ -18,6 +18,7 @@ use futures_ext::{BoxFuture, FutureExt};
 use quickcheck::{empty_shrinker, Arbitrary, Gen};
 
 use crate::{
+    blame::BlameChunk,
     blob::{Blob, BlobstoreValue},
     bonsai_changeset::BonsaiChangeset,
     content_chunk::ContentChunk,
@@ -110,6 +111,10 @@ pub struct FastlogBatchId(Blake2);
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub struct BlameId(Blake2);
 
+/// An identifier for a chunk of a large file's blame
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct BlameChunkId(Blake2);
+
 /// Implementations of typed hashes.
 macro_rules! impl_typed_hash_no_context {
     {
@@ -398,6 +403,13 @@ impl_typed_hash! {
     context_key => "fastlogbatch",
 }
 
+impl_typed_hash! {
+    hash_type => BlameChunkId,
+    value_type => BlameChunk,
+    context_type => BlameChunkIdContext,
+    context_key => "blamechunk",
+}
+
 impl ContentMetadataId {
     const PREFIX: &'static str = "content_metadata.blake2";
 }
@@ -522,5 +534,8 @@ mod test {
 
         let id = FastlogBatchId::from_byte_array([1; 32]);
         assert_eq!(id.blobstore_key(), format!("fastlogbatch.blake2.{}", id));
+
+        let id = BlameChunkId::from_byte_array([1; 32]);
+        assert_eq!(id.blobstore_key(), format!("blamechunk.blake2.{}", id));
     }
 }
@@ -177,8 +177,8 @@ impl HgReplayData {
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PushrebaseConflict {
-    left: MPath,
-    right: MPath,
+    pub left: MPath,
+    pub right: MPath,
 }
 
 impl PushrebaseConflict {
@@ -15,6 +15,7 @@ use source_control::services::source_control_service as service;
 pub(crate) enum ServiceError {
     Request(thrift::RequestError),
     Internal(thrift::InternalError),
+    PushrebaseConflicts(thrift::PushrebaseConflictsException),
 }
 
 impl From<thrift::RequestError> for ServiceError {
@@ -29,6 +30,12 @@ impl From<thrift::InternalError> for ServiceError {
     }
 }
 
+impl From<thrift::PushrebaseConflictsException> for ServiceError {
+    fn from(e: thrift::PushrebaseConflictsException) -> Self {
+        Self::PushrebaseConflicts(e)
+    }
+}
+
 impl From<MononokeError> for ServiceError {
     fn from(e: MononokeError) -> Self {
         match e {
@@ -44,6 +51,22 @@ impl From<MononokeError> for ServiceError {
                 kind: thrift::RequestErrorKind::NOT_AVAILABLE,
                 reason: error.to_string(),
             }),
+            error @ MononokeError::HookFailure(_) => Self::Request(thrift::RequestError {
+                kind: thrift::RequestErrorKind::INVALID_REQUEST,
+                reason: error.to_string(),
+            }),
+            MononokeError::PushrebaseConflicts(conflicts) => {
+                Self::PushrebaseConflicts(thrift::PushrebaseConflictsException {
+                    reason: format!("{} conflicts while pushrebasing", conflicts.len()),
+                    conflicts: conflicts
+                        .into_iter()
+                        .map(|conflict| thrift::PushrebaseConflict {
+                            left_path: conflict.left.to_string(),
+                            right_path: conflict.right.to_string(),
+                        })
+                        .collect(),
+                })
+            }
             MononokeError::InternalError(error) => {
                 let reason = error.to_string();
                 let backtrace = error
@@ -75,17 +98,32 @@ macro_rules! impl_into_thrift_error {
                 match e {
                     ServiceError::Request(e) => e.into(),
                     ServiceError::Internal(e) => e.into(),
+                    // Only methods that pushrebase can report conflicts.
+                    ServiceError::PushrebaseConflicts(e) => invalid_request(e.reason).into(),
                 }
             }
         }
     };
 }
 
+impl From<ServiceError> for service::RepoLandStackExn {
+    fn from(e: ServiceError) -> Self {
+        match e {
+            ServiceError::Request(e) => e.into(),
+            ServiceError::Internal(e) => e.into(),
+            ServiceError::PushrebaseConflicts(e) => e.into(),
+        }
+    }
+}
+
 impl_into_thrift_error!(service::ListReposExn);
 impl_into_thrift_error!(service::RepoResolveBookmarkExn);
 impl_into_thrift_error!(service::RepoResolveCommitPrefixExn);
 impl_into_thrift_error!(service::RepoListBookmarksExn);
 impl_into_thrift_error!(service::RepoCreateCommitExn);
+impl_into_thrift_error!(service::RepoCreateBookmarkExn);
+impl_into_thrift_error!(service::RepoMoveBookmarkExn);
+impl_into_thrift_error!(service::RepoDeleteBookmarkExn);
 impl_into_thrift_error!(service::RepoStackInfoExn);
 impl_into_thrift_error!(service::CommitFileDiffsExn);
 impl_into_thrift_error!(service::CommitLookupExn);
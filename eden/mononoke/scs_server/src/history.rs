@@ -23,14 +23,38 @@ pub(crate) async fn collect_history(
     format: thrift::HistoryFormat,
     identity_schemes: &BTreeSet<thrift::CommitIdentityScheme>,
 ) -> Result<thrift::History, errors::ServiceError> {
+    let (history, _) = collect_history_entries(
+        history_stream.map_ok(|changeset| (changeset, ())),
+        skip,
+        limit,
+        before_timestamp,
+        after_timestamp,
+        format,
+        identity_schemes,
+    )
+    .await?;
+    Ok(history)
+}
+
+/// Like `collect_history`, but each changeset comes with some extra data, which is
+/// returned for the changesets that end up in the history, in the same order.
+pub(crate) async fn collect_history_entries<T>(
+    history_stream: impl Stream<Item = Result<(ChangesetContext, T), MononokeError>>,
+    skip: usize,
+    limit: usize,
+    before_timestamp: Option<i64>,
+    after_timestamp: Option<i64>,
+    format: thrift::HistoryFormat,
+    identity_schemes: &BTreeSet<thrift::CommitIdentityScheme>,
+) -> Result<(thrift::History, Vec<T>), errors::ServiceError> {
     let history_stream = history_stream
         .map_err(errors::ServiceError::from)
         .skip(skip);
 
     let history = if before_timestamp.is_some() || after_timestamp.is_some() {
         history_stream
-                .map(move |changeset| async move {
-                    let changeset = changeset?;
+                .map(move |entry| async move {
+                    let (changeset, extra) = entry?;
                     let date = changeset.author_date().await?;
 
                     if let Some(after) = after_timestamp {
@@ -44,13 +68,13 @@ pub(crate) async fn collect_history(
                         }
                     }
 
-                    Ok(Some(changeset))
+                    Ok(Some((changeset, extra)))
                 })
                 // to check the date we need to fetch changeset first, that can be expensive
                 // better to try doing it in parallel
                 .buffered(100)
-                .try_filter_map(|maybe_changeset| async move {
-                    Ok::<_, errors::ServiceError>(maybe_changeset)
+                .try_filter_map(|maybe_entry| async move {
+                    Ok::<_, errors::ServiceError>(maybe_entry)
                 })
                 .take(limit)
                 .left_stream()
@@ -60,17 +84,17 @@ pub(crate) async fn collect_history(
 
     match format {
         thrift::HistoryFormat::COMMIT_INFO => {
-            let commit_infos: Vec<_> = history
-                .map(|changeset| async {
-                    match changeset {
-                        Ok(cs) => (cs, identity_schemes).into_response().await,
-                        Err(err) => Err(err),
-                    }
+            let entries: Vec<_> = history
+                .map(|entry| async {
+                    let (cs, extra) = entry?;
+                    let commit_info = (cs, identity_schemes).into_response().await?;
+                    Ok::<_, errors::ServiceError>((commit_info, extra))
                 })
                 .buffered(100)
                 .try_collect()
                 .await?;
-            Ok(thrift::History::commit_infos(commit_infos))
+            let (commit_infos, extras): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+            Ok((thrift::History::commit_infos(commit_infos), extras))
         }
         other_format => Err(errors::invalid_request(format!(
             "unsupported history format {}",
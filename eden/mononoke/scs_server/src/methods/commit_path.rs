@@ -8,6 +8,7 @@
 use context::CoreContext;
 use dedupmap::DedupMap;
 use futures::future;
+use futures::stream::TryStreamExt;
 use mononoke_api::{ChangesetSpecifier, MononokeError, PathEntry};
 use source_control as thrift;
 use std::borrow::Cow;
@@ -16,10 +17,13 @@ use std::collections::{BTreeSet, HashMap};
 use crate::commit_id::map_commit_identities;
 use crate::errors;
 use crate::from_request::{check_range_and_convert, validate_timestamp};
-use crate::history::collect_history;
+use crate::history::collect_history_entries;
 use crate::into_response::IntoResponse;
 use crate::source_control_impl::SourceControlServiceImpl;
 
+// History can be followed across at most this many copies and moves
+const MAX_COPY_HOPS: i64 = 100;
+
 impl SourceControlServiceImpl {
     /// Returns information about the file or directory at a path in a commit.
     pub(crate) async fn commit_path_info(
@@ -196,17 +200,21 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// Returns the commits that changed the file at a path, or any file under the
+    /// directory at a path, bounded by time and count.
     pub(crate) async fn commit_path_history(
         &self,
         ctx: CoreContext,
         commit_path: thrift::CommitPathSpecifier,
         params: thrift::CommitPathHistoryParams,
     ) -> Result<thrift::CommitPathHistoryResponse, errors::ServiceError> {
-        let (_repo, changeset) = self.repo_changeset(ctx, &commit_path.commit).await?;
+        let (repo, changeset) = self.repo_changeset(ctx, &commit_path.commit).await?;
         let path = changeset.path(&commit_path.path)?;
 
         let limit: usize = check_range_and_convert("limit", params.limit, 0..)?;
         let skip: usize = check_range_and_convert("skip", params.skip, 0..)?;
+        let max_copy_hops: usize =
+            check_range_and_convert("max_copy_hops", params.max_copy_hops, 0..=MAX_COPY_HOPS)?;
 
         // Time filter equal to zero might be mistaken by users for an unset, like None.
         // We will consider negative timestamps as invalid and zeros as unset.
@@ -230,13 +238,26 @@ impl SourceControlServiceImpl {
             .into());
         }
 
+        // Note the copies and moves that history is followed across, for the returned entries
         let history_stream = path
             .history(
                 after_timestamp.clone(),
                 params.follow_history_across_deletions,
+                max_copy_hops,
             )
-            .await?;
-        let history = collect_history(
+            .await?
+            .map_ok(|entry| {
+                let copy = entry.copied_from().map(|(from_path, from_cs_id)| {
+                    (
+                        entry.changeset().id(),
+                        entry.path().clone(),
+                        *from_cs_id,
+                        from_path.clone(),
+                    )
+                });
+                (entry.into_changeset(), copy)
+            });
+        let (history, copies) = collect_history_entries(
             history_stream,
             skip,
             limit,
@@ -247,6 +268,24 @@ impl SourceControlServiceImpl {
         )
         .await?;
 
-        Ok(thrift::CommitPathHistoryResponse { history })
+        let copies: Vec<_> = copies.into_iter().flatten().collect();
+        let csids = copies
+            .iter()
+            .flat_map(|(cs_id, _, from_cs_id, _)| vec![*cs_id, *from_cs_id])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let commit_ids = map_commit_identities(&repo, csids, &params.identity_schemes).await?;
+        let copies = copies
+            .into_iter()
+            .map(|(cs_id, path, from_cs_id, from_path)| thrift::PathCopy {
+                commit_ids: commit_ids.get(&cs_id).cloned().unwrap_or_default(),
+                path: path.to_string(),
+                from_commit_ids: commit_ids.get(&from_cs_id).cloned().unwrap_or_default(),
+                from_path: from_path.to_string(),
+            })
+            .collect();
+
+        Ok(thrift::CommitPathHistoryResponse { history, copies })
     }
 }
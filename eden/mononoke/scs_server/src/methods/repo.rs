@@ -5,7 +5,7 @@
  * GNU General Public License version 2.
  */
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::{identity, TryFrom};
 
 use bytes::Bytes;
@@ -17,8 +17,8 @@ use futures_util::stream::FuturesOrdered;
 use futures_util::TryStreamExt;
 use maplit::btreemap;
 use mononoke_api::{
-    ChangesetPrefixSpecifier, ChangesetSpecifier, ChangesetSpecifierPrefixResolution, CreateChange,
-    CreateCopyInfo, FileId, FileType, MononokePath,
+    ChangesetId, ChangesetPrefixSpecifier, ChangesetSpecifier, ChangesetSpecifierPrefixResolution,
+    CreateChange, CreateCopyInfo, FileId, FileType, MononokePath, RepoContext,
 };
 use mononoke_types::hash::{Sha1, Sha256};
 use source_control as thrift;
@@ -29,6 +29,29 @@ use crate::from_request::{check_range_and_convert, FromRequest};
 use crate::into_response::AsyncIntoResponse;
 use crate::source_control_impl::SourceControlServiceImpl;
 
+/// Resolve a commit id to the id of an existing changeset.
+async fn resolve_commit_id(
+    repo: &RepoContext,
+    commit: &thrift::CommitId,
+) -> Result<ChangesetId, errors::ServiceError> {
+    let changeset_specifier = ChangesetSpecifier::from_request(commit)?;
+    let changeset = repo
+        .changeset(changeset_specifier)
+        .await?
+        .ok_or_else(|| errors::commit_not_found(commit.to_string()))?;
+    Ok(changeset.id())
+}
+
+/// Convert pushvars, which may be used to bypass hooks.
+fn convert_pushvars(pushvars: Option<BTreeMap<String, Vec<u8>>>) -> Option<HashMap<String, Bytes>> {
+    pushvars.map(|pushvars| {
+        pushvars
+            .into_iter()
+            .map(|(name, value)| (name, Bytes::from(value)))
+            .collect()
+    })
+}
+
 impl SourceControlServiceImpl {
     /// Resolve a bookmark to a changeset.
     ///
@@ -167,29 +190,12 @@ impl SourceControlServiceImpl {
 
         let parents: Vec<_> = params
             .parents
-            .into_iter()
-            .map(|parent| {
-                let repo = &repo;
-                async move {
-                    let changeset_specifier = ChangesetSpecifier::from_request(&parent)?;
-                    let changeset = repo
-                        .changeset(changeset_specifier)
-                        .await?
-                        .ok_or_else(|| errors::commit_not_found(parent.to_string()))?;
-                    Ok::<_, errors::ServiceError>(changeset.id())
-                }
-            })
+            .iter()
+            .map(|parent| resolve_commit_id(&repo, parent))
             .collect::<FuturesOrdered<_>>()
             .try_collect()
             .await?;
 
-        if parents.len() != 1 {
-            return Err(errors::invalid_request(
-                "repo_create_commit can only create commits with a single parent",
-            )
-            .into());
-        }
-
         // Convert changes to actions
         let file_changes = params
             .changes
@@ -307,6 +313,105 @@ impl SourceControlServiceImpl {
         Ok(thrift::RepoCreateCommitResponse { ids })
     }
 
+    /// Create a new bookmark.
+    pub(crate) async fn repo_create_bookmark(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoCreateBookmarkParams,
+    ) -> Result<thrift::RepoCreateBookmarkResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?.write().await?;
+        let target = resolve_commit_id(&repo, &params.target).await?;
+        let pushvars = convert_pushvars(params.pushvars);
+
+        repo.create_bookmark(&params.bookmark, target, pushvars.as_ref())
+            .await?;
+        Ok(thrift::RepoCreateBookmarkResponse {})
+    }
+
+    /// Move a bookmark.
+    pub(crate) async fn repo_move_bookmark(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoMoveBookmarkParams,
+    ) -> Result<thrift::RepoMoveBookmarkResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?.write().await?;
+        let target = resolve_commit_id(&repo, &params.target).await?;
+        let pushvars = convert_pushvars(params.pushvars);
+
+        repo.move_bookmark(
+            &params.bookmark,
+            target,
+            params.allow_non_fast_forward_move,
+            pushvars.as_ref(),
+        )
+        .await?;
+        Ok(thrift::RepoMoveBookmarkResponse {})
+    }
+
+    /// Delete a bookmark.
+    pub(crate) async fn repo_delete_bookmark(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoDeleteBookmarkParams,
+    ) -> Result<thrift::RepoDeleteBookmarkResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?.write().await?;
+        let old_target = match &params.old_target {
+            Some(old_target) => Some(resolve_commit_id(&repo, old_target).await?),
+            None => None,
+        };
+
+        repo.delete_bookmark(&params.bookmark, old_target).await?;
+        Ok(thrift::RepoDeleteBookmarkResponse {})
+    }
+
+    /// Land a stack of commits by pushrebasing it onto a bookmark.
+    ///
+    /// Returns the IDs of the new head of the bookmark and of the rebased
+    /// commits in the requested identity schemes.
+    pub(crate) async fn repo_land_stack(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoLandStackParams,
+    ) -> Result<thrift::RepoLandStackResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?.write().await?;
+        let (head, base) = try_join(
+            resolve_commit_id(&repo, &params.head),
+            resolve_commit_id(&repo, &params.base),
+        )
+        .await?;
+        let pushvars = convert_pushvars(params.pushvars);
+
+        let outcome = repo
+            .land_stack(&params.bookmark, head, base, pushvars.as_ref())
+            .await?;
+
+        let ids = outcome
+            .rebased_changesets
+            .iter()
+            .flat_map(|(old_id, new_id)| vec![*old_id, *new_id])
+            .chain(Some(outcome.head))
+            .collect();
+        let ids = map_commit_identities(&repo, ids, &params.identity_schemes).await?;
+        let get_ids = |cs_id: &ChangesetId| ids.get(cs_id).cloned().unwrap_or_default();
+        let head = get_ids(&outcome.head);
+        let rebased_commits = outcome
+            .rebased_changesets
+            .iter()
+            .map(|(old_id, new_id)| thrift::RebasedCommit {
+                old_ids: get_ids(old_id),
+                new_ids: get_ids(new_id),
+            })
+            .collect();
+        Ok(thrift::RepoLandStackResponse {
+            head,
+            rebased_commits,
+        })
+    }
+
     /// Build stacks for the given list of heads.
     ///
     /// Returns the IDs of the changeset in the requested identity schemes.
@@ -36,6 +36,42 @@ impl AddScubaParams for thrift::ListReposParams {}
 
 impl AddScubaParams for thrift::RepoCreateCommitParams {}
 
+impl AddScubaParams for thrift::RepoCreateBookmarkParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark.as_str());
+        scuba.add("commit", self.target.to_string());
+    }
+}
+
+impl AddScubaParams for thrift::RepoMoveBookmarkParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark.as_str());
+        scuba.add("commit", self.target.to_string());
+        scuba.add(
+            "param_allow_non_fast_forward_move",
+            self.allow_non_fast_forward_move as i32,
+        );
+    }
+}
+
+impl AddScubaParams for thrift::RepoDeleteBookmarkParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark.as_str());
+        if let Some(old_target) = &self.old_target {
+            scuba.add("commit", old_target.to_string());
+        }
+    }
+}
+
+impl AddScubaParams for thrift::RepoLandStackParams {
+    fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark.as_str());
+        scuba.add("commit", self.head.to_string());
+        scuba.add("other_commit", self.base.to_string());
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::RepoListBookmarksParams {
     fn add_scuba_params(&self, scuba: &mut ScubaSampleBuilder) {
         scuba.add("param_include_scratch", self.include_scratch as i32);
@@ -282,6 +282,10 @@ fn log_result<T>(ctx: CoreContext, stats: &FutureStats, result: &Result<T, error
             internal_failure = 1;
             ("INTERNAL_ERROR", Some(format!("{:?}", e)))
         }
+        Err(errors::ServiceError::PushrebaseConflicts(e)) => {
+            invalid_request = 1;
+            ("PUSHREBASE_CONFLICTS", Some(format!("{:?}", e)))
+        }
     };
 
     STATS::total_request_success.add_value(success);
@@ -457,6 +461,26 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoCreateCommitParams,
         ) -> Result<thrift::RepoCreateCommitResponse, service::RepoCreateCommitExn>;
 
+        async fn repo_create_bookmark(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoCreateBookmarkParams,
+        ) -> Result<thrift::RepoCreateBookmarkResponse, service::RepoCreateBookmarkExn>;
+
+        async fn repo_move_bookmark(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoMoveBookmarkParams,
+        ) -> Result<thrift::RepoMoveBookmarkResponse, service::RepoMoveBookmarkExn>;
+
+        async fn repo_delete_bookmark(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoDeleteBookmarkParams,
+        ) -> Result<thrift::RepoDeleteBookmarkResponse, service::RepoDeleteBookmarkExn>;
+
+        async fn repo_land_stack(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoLandStackParams,
+        ) -> Result<thrift::RepoLandStackResponse, service::RepoLandStackExn>;
+
         async fn repo_stack_info(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoStackInfoParams,
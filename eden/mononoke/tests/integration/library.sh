@@ -1010,6 +1010,52 @@ function start_edenapi_server {
   return 1
 }
 
+function start_git_server {
+  local port log attempts timeout
+  port=$(get_free_socket)
+  log="$TESTTMP/git_server.out"
+
+  # Start the Git server, using test TLS credentials. Use the `sslgit`
+  # function to connect to it.
+  GLOG_minloglevel=5 "$GIT_SERVER" "$@" \
+    --debug \
+    --listen-host 127.0.0.1 \
+    --listen-port "$port" \
+    --mononoke-config-path "$TESTTMP/mononoke-config" \
+    --tls-ca "$TEST_CERTDIR/root-ca.crt" \
+    --tls-private-key "$TEST_CERTDIR/localhost.key" \
+    --tls-certificate "$TEST_CERTDIR/localhost.crt" \
+    --tls-ticket-seeds "$TEST_CERTDIR/server.pem.seeds" \
+    --trusted-proxy-identity USER:myusername0 \
+    "${COMMON_ARGS[@]}" >> "$log" 2>&1 &
+
+  echo "$!" >> "$DAEMON_PIDS"
+
+  export GIT_SERVER_URI="https://localhost:$port"
+
+  timeout="${MONONOKE_START_TIMEOUT:-"$MONONOKE_DEFAULT_START_TIMEOUT"}"
+  attempts="$((timeout * 10))"
+  for _ in $(seq 1 $attempts); do
+    if sslcurl -q "$GIT_SERVER_URI/health_check" > /dev/null 2>&1; then
+      truncate -s 0 "$log"
+      return 0
+    fi
+    sleep 0.1
+  done
+
+  echo "Git server failed to start" >&2
+  cat "$log" >&2
+  return 1
+}
+
+function sslgit {
+  git -c protocol.version=2 \
+    -c http.sslCAInfo="$TEST_CERTDIR/root-ca.crt" \
+    -c http.sslCert="$TEST_CERTDIR/localhost.crt" \
+    -c http.sslKey="$TEST_CERTDIR/localhost.key" \
+    "$@"
+}
+
 function edenapi_make_req {
 	"$EDENAPI_MAKE_REQ" "$@"
 }
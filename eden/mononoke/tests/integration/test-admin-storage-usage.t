@@ -0,0 +1,57 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+  $ hg init repo-hg --config format.usefncache=False
+
+# Init treemanifest and remotefilelog
+  $ cd repo-hg
+  $ cat >> .hg/hgrc <<EOF
+  > [extensions]
+  > treemanifest=
+  > [treemanifest]
+  > server=True
+  > EOF
+
+  $ mkdir dir1 dir2
+  $ printf '0123456789\n' > dir1/a.bin
+  $ printf 'hello\n' > README
+  $ hg commit -Aqm "commit 1" -u "alice <alice@example.com>"
+  $ cp dir1/a.bin dir2/b.bin
+  $ printf 'text\n' > dir2/c.txt
+  $ hg commit -Aqm "commit 2" -u "bob <bob@example.com>"
+  $ printf '0123456789abcdefghij\n' > dir1/a.bin
+  $ hg commit -Aqm "commit 3" -u "carol <carol@example.com>"
+  $ hg bookmark master
+  $ START=$(hg log -r 0 -T '{node}')
+
+  $ setup_mononoke_config blob_files
+
+  $ cd $TESTTMP
+  $ blobimport repo-hg/.hg repo
+
+dir2/b.bin has the same content as the first dir1/a.bin, so is only counted as a duplicate
+  $ mononoke_admin storage-usage "$START" master 2> /dev/null
+  kind,key,files,bytes
+  total,unique,4,43
+  total,duplicate,1,11
+  directory,dir1,2,32
+  directory,/,1,6
+  directory,dir2,1,5
+  extension,bin,2,32
+  extension,,1,6
+  extension,txt,1,5
+  author,carol <carol@example.com>,1,21
+  author,alice <alice@example.com>,2,17
+  author,bob <bob@example.com>,1,5
+
+  $ mononoke_admin storage-usage "$START" master --format json --limit 1 2> /dev/null | jq -c '.[]'
+  {"kind":"total","key":"unique","files":4,"bytes":43}
+  {"kind":"total","key":"duplicate","files":1,"bytes":11}
+  {"kind":"directory","key":"dir1","files":2,"bytes":32}
+  {"kind":"extension","key":"bin","files":2,"bytes":32}
+  {"kind":"author","key":"carol <carol@example.com>","files":1,"bytes":21}
@@ -0,0 +1,78 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+Set up local hgrc and Mononoke config, with Git data derived.
+  $ ENABLED_DERIVED_DATA='["git_trees", "git_commits"]' setup_common_config
+  $ cd $TESTTMP
+
+Initialize test repo.
+  $ hginit_treemanifest repo-hg
+  $ cd repo-hg
+  $ setup_hg_server
+  $ echo "this is file1" > file1
+  $ hg commit -Aqm "add file1"
+  $ mkdir dir
+  $ echo "this is file2" > dir/file2
+  $ hg commit -Aqm "add dir/file2"
+  $ hg bookmark master -r tip
+
+Blobimport test repo.
+  $ cd ..
+  $ blobimport repo-hg/.hg repo
+
+Start up the Git server.
+  $ setup_mononoke_config
+  $ start_git_server
+
+Check the capability advertisement.
+  $ sslcurl -s -H "Git-Protocol: version=2" "$GIT_SERVER_URI/repo/info/refs?service=git-upload-pack"
+  001e# service=git-upload-pack
+  0000000eversion 2
+  001eagent=mononoke-git-server
+  000bls-refs
+  000afetch
+  0000 (no-eol)
+
+Protocol version 0 is not supported.
+  $ sslcurl -s -o /dev/null -w "%{http_code}\n" "$GIT_SERVER_URI/repo/info/refs?service=git-upload-pack"
+  400
+
+List the refs.
+  $ sslgit ls-remote "$GIT_SERVER_URI/repo"
+  [0-9a-f]{40}\tHEAD (re)
+  [0-9a-f]{40}\trefs/heads/master (re)
+
+Clone the repo with a stock Git client.
+  $ sslgit clone -q "$GIT_SERVER_URI/repo" repo-git
+  $ cd repo-git
+  $ git log --format="%s" master
+  add dir/file2
+  add file1
+  $ cat file1 dir/file2
+  this is file1
+  this is file2
+  $ git fsck --no-progress
+  $ cd ..
+
+Add a commit and fetch it incrementally.
+  $ cd repo-hg
+  $ echo "this is file1, modified" > file1
+  $ hg commit -Aqm "modify file1"
+  $ hg bookmark -f master -r tip
+  $ cd ..
+  $ blobimport repo-hg/.hg repo
+  $ cd repo-git
+  $ sslgit fetch -q origin
+  $ git log --format="%s" origin/master
+  modify file1
+  add dir/file2
+  add file1
+  $ git merge -q --ff-only origin/master
+  $ cat file1
+  this is file1, modified
+  $ git fsck --no-progress
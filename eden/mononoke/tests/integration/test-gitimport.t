@@ -61,3 +61,32 @@
   $ hg --config extensions.hggit= git-updatemeta
   $ hg --config extensions.hggit= log -T '{gitnode}'
   8ce3eae44760b500bf3f2c3922a95dcd3c908e9e (no-eol)
+
+# Add a commit and a tag, and mirror them
+  $ cd "$GIT_REPO"
+  $ echo "this is file2" > file2
+  $ git add file2
+  $ git commit -qam "Add file2"
+  $ git tag -a -m "first release" v1.0
+  $ cd "$TESTTMP"
+  $ gitimport "$GIT_REPO" --hggit-compatibility mirror
+  * using repo "repo" repoid RepositoryId(0) (glob)
+  * Created * => ChangesetId(Blake2(*)) (glob)
+  * 1 bonsai changesets have been committed (glob)
+  * Bookmark master (refs/heads/master): Some(ChangesetId(Blake2(967b83a1a809dbd715163d6cbd5197b4733a09068c57251481c0bc76e6297ca0))) => ChangesetId(Blake2(*)) (glob)
+  * Bookmark tags/v1.0 (refs/tags/v1.0): None => ChangesetId(Blake2(*)) (glob)
+  * 2 bookmark(s) updated (glob)
+
+# Mirroring again imports nothing
+  $ gitimport "$GIT_REPO" --hggit-compatibility mirror
+  * using repo "repo" repoid RepositoryId(0) (glob)
+  * 0 bonsai changesets have been committed (glob)
+  * 0 bookmark(s) updated (glob)
+
+# Bookmark names can be transformed
+  $ gitimport "$GIT_REPO" --hggit-compatibility mirror --branch-bookmark-template "upstream/{}" --tag-bookmark-template "upstream/tags/{}"
+  * using repo "repo" repoid RepositoryId(0) (glob)
+  * 0 bonsai changesets have been committed (glob)
+  * Bookmark upstream/master (refs/heads/master): None => ChangesetId(Blake2(*)) (glob)
+  * Bookmark upstream/tags/v1.0 (refs/tags/v1.0): None => ChangesetId(Blake2(*)) (glob)
+  * 2 bookmark(s) updated (glob)
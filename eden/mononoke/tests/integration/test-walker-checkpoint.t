@@ -0,0 +1,54 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+setup configuration
+  $ default_setup_pre_blobimport "blob_files"
+  hg repo
+  o  C [draft;rev=2;26805aba1e60]
+  |
+  o  B [draft;rev=1;112478962961]
+  |
+  o  A [draft;rev=0;426bada5c675]
+  $
+  $ blobimport repo-hg/.hg repo
+
+scrub with a checkpoint, which is saved once the walk completes
+  $ mononoke_walker --storage-id=blobstore --readonly-storage scrub -q --bookmark master_bookmark -I bonsai --checkpoint-path=$TESTTMP/checkpoint 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types [BonsaiChangesetToBonsaiParent, BonsaiChangesetToFileContent, BookmarkToBonsaiChangeset]
+  Walking node types [BonsaiChangeset, Bookmark, FileContent]
+  Final count: (7, 7)
+  Bytes/s,* (glob)
+  * Type:Walked,Checks,Children BonsaiChangeset:3,* Bookmark:1,1,1 FileContent:3,3,0 (glob)
+  Saved checkpoint to $TESTTMP/checkpoint in *ms (glob)
+
+the completed walk has nothing queued
+  $ head -1 $TESTTMP/checkpoint
+  walker-checkpoint-v1 scrub repo
+  $ grep -c '^visited BonsaiChangeset ' $TESTTMP/checkpoint
+  3
+  $ grep -c '^visited FileContent ' $TESTTMP/checkpoint
+  3
+  $ grep -c '^pending ' $TESTTMP/checkpoint
+  0
+  [1]
+
+resuming from a completed walk only steps from the bookmark, as its changeset has been visited already
+  $ mononoke_walker --storage-id=blobstore --readonly-storage scrub -q --bookmark master_bookmark -I bonsai --checkpoint-path=$TESTTMP/checkpoint --resume 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types [BonsaiChangesetToBonsaiParent, BonsaiChangesetToFileContent, BookmarkToBonsaiChangeset]
+  Walking node types [BonsaiChangeset, Bookmark, FileContent]
+  Resuming from checkpoint $TESTTMP/checkpoint with 0 queued nodes
+  Final count: (1, 1)
+  Bytes/s,* (glob)
+  * Type:Walked,Checks,Children BonsaiChangeset:3,* Bookmark:2,* FileContent:3,* (glob)
+  Saved checkpoint to $TESTTMP/checkpoint in *ms (glob)
+
+a checkpoint can't be resumed by a different subcommand
+  $ mononoke_walker --storage-id=blobstore --readonly-storage validate -q --bookmark master_bookmark -I bonsai --checkpoint-path=$TESTTMP/checkpoint --resume 2>&1 | grep 'Checkpoint'
+  * Checkpoint $TESTTMP/checkpoint is for Some("walker-checkpoint-v1 scrub repo"), expected "walker-checkpoint-v1 validate repo" (glob)
@@ -75,3 +75,5 @@ Check scuba data
   1
   $ jq -r '.int * .normal | [ .check_fail, .check_type, .node_key, .node_path, .node_type, .repo, .walk_type ] | @csv' < scuba.json | sort
   1,"bonsai_phase_is_public","changeset.blake2.2b06a8547bfe6a3ac79392aef3fa7f3f45a82f4e0beb95c4fa2b914c34b5b215",,"BonsaiPhaseMapping","repo","validate"
+  $ jq -r '.normal | [ .check_expected, .check_actual ] | @csv' < scuba.json
+  "Some(Public)","None"
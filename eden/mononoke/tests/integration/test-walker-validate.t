@@ -27,6 +27,16 @@ validate, expecting all valid
   Walked* (glob)
   Nodes,Pass,Fail:40,3,0; EdgesChecked:9; CheckType:Pass,Fail Total:3,0 HgLinkNodePopulated:3,0
 
+validate, expecting all valid with the checks that aren't run by default
+  $ mononoke_walker --storage-id=blobstore --readonly-storage --cachelib-only-blobstore validate -I deep -q --bookmark master_bookmark -c BonsaiHgMappingRoundTrips -c BookmarkTargetExists -c ContentMetadataMatchesContent -c FsnodeSummaryMatchesEntries -c HgFileSizeMatchesMetadata 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types * (glob)
+  Walking node types * (glob)
+  Performing check types [BonsaiHgMappingRoundTrips, BookmarkTargetExists, ContentMetadataMatchesContent, FsnodeSummaryMatchesEntries, HgFileSizeMatchesMetadata]
+  Final count: * (glob)
+  Walked* (glob)
+  Nodes,Pass,Fail:*,*,0; EdgesChecked:*; CheckType:Pass,Fail Total:*,0 BonsaiHgMappingRoundTrips:3,0 BookmarkTargetExists:1,0 ContentMetadataMatchesContent:3,0 FsnodeSummaryMatchesEntries:*,0 HgFileSizeMatchesMetadata:3,0 (glob)
+
 Remove all filenodes
   $ sqlite3 "$TESTTMP/monsql/sqlite_dbs" "DELETE FROM filenodes where linknode=x'112478962961147124EDD43549AEDD1A335E44BF'";
 
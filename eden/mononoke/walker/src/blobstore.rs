@@ -125,7 +125,7 @@ fn get_blobconfig(
                 let seeked_id = BlobstoreId::new(inner_blobstore_id);
                 blobstores
                     .into_iter()
-                    .find_map(|(blobstore_id, blobstore)| {
+                    .find_map(|(blobstore_id, _, blobstore)| {
                         if blobstore_id == seeked_id {
                             Some(blobstore)
                         } else {
@@ -179,6 +179,7 @@ pub async fn open_blobstore(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                minimum_successful_writes,
                 scrub_action,
                 queue_db,
             },
@@ -187,7 +188,7 @@ pub async fn open_blobstore(
             // Without this the new stats only show up when a repair is needed (i.e. as they get incremented),
             // which makes them harder to monitor on (no datapoints rather than a zero datapoint at start).
             for s in &[STATS::scrub_repaired, STATS::scrub_repair_required] {
-                for (id, _config) in &blobstores {
+                for (id, _store_type, _config) in &blobstores {
                     s.add_value(0, (walk_stats_key, id.to_string(), repo_stats_key.clone()));
                 }
             }
@@ -199,6 +200,8 @@ pub async fn open_blobstore(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                minimum_successful_writes,
+                false,
                 Some((scrub_handler, scrub_action)),
                 mysql_options,
                 readonly_storage,
@@ -214,6 +217,8 @@ pub async fn open_blobstore(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                minimum_successful_writes,
+                read_repair,
                 queue_db,
             },
         ) => {
@@ -224,6 +229,8 @@ pub async fn open_blobstore(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                minimum_successful_writes,
+                read_repair,
                 None,
                 mysql_options,
                 readonly_storage,
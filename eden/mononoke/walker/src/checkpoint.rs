@@ -0,0 +1,184 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::progress::{ProgressStateCountByType, ProgressStateMutex, ProgressSummary};
+use crate::state::{StepStats, WalkState};
+use crate::walk::OutgoingEdge;
+
+use anyhow::{format_err, Error};
+use slog::{info, Logger};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const CHECKPOINT_VERSION: &str = "walker-checkpoint-v1";
+
+/// A visitor whose WalkState can be saved to and restored from a checkpoint
+pub trait Checkpointable {
+    fn walk_state(&self) -> &WalkState;
+}
+
+impl Checkpointable for WalkState {
+    fn walk_state(&self) -> &WalkState {
+        self
+    }
+}
+
+impl<V: Checkpointable> Checkpointable for Arc<V> {
+    fn walk_state(&self) -> &WalkState {
+        self.as_ref().walk_state()
+    }
+}
+
+type WalkProgressState = ProgressStateMutex<ProgressStateCountByType<StepStats, ProgressSummary>>;
+
+// Saves the visited and queued nodes plus the progress counts of a walk to a local file, so that
+// a later run can resume from there rather than from the walk roots.
+#[derive(Clone, Debug)]
+pub struct WalkCheckpoint {
+    path: PathBuf,
+    interval: Duration,
+    resume: bool,
+    // Identifies the walk, so that one subcommand or repo can't resume from another's checkpoint
+    header: String,
+}
+
+impl WalkCheckpoint {
+    pub fn new(
+        path: PathBuf,
+        interval: Duration,
+        resume: bool,
+        walk_stats_key: &str,
+        repo_name: &str,
+    ) -> Self {
+        Self {
+            path,
+            interval,
+            resume,
+            header: format!("{} {} {}", CHECKPOINT_VERSION, walk_stats_key, repo_name),
+        }
+    }
+
+    /// If resuming, restore the state saved by an earlier run. Returns the nodes that were still
+    /// queued at the time, which are empty if there is no checkpoint or its walk had finished.
+    pub fn load(
+        &self,
+        logger: &Logger,
+        walk_state: &WalkState,
+        progress_state: &WalkProgressState,
+    ) -> Result<Vec<OutgoingEdge>, Error> {
+        if !self.resume {
+            return Ok(vec![]);
+        }
+        if !self.path.exists() {
+            info!(
+                logger,
+                "No checkpoint at {}, walking from the roots",
+                self.path.display()
+            );
+            return Ok(vec![]);
+        }
+
+        let mut lines = BufReader::new(File::open(&self.path)?).lines();
+        match lines.next().transpose()? {
+            Some(header) if header == self.header => (),
+            header => {
+                return Err(format_err!(
+                    "Checkpoint {} is for {:?}, expected {:?}",
+                    self.path.display(),
+                    header,
+                    self.header
+                ));
+            }
+        }
+
+        let mut queued = vec![];
+        for line in lines {
+            let line = line?;
+            if line.starts_with("progress ") {
+                progress_state.restore_checkpoint_line(&line)?;
+            } else if let Some(node) = walk_state.restore_checkpoint_line(&line)? {
+                if let Some(label) = node.get_type().root_edge_type() {
+                    queued.push(OutgoingEdge::new(label, node));
+                }
+            }
+        }
+        info!(
+            logger,
+            "Resuming from checkpoint {} with {} queued nodes",
+            self.path.display(),
+            queued.len()
+        );
+        Ok(queued)
+    }
+
+    // Written to a temporary file first, so a crash while saving keeps the previous checkpoint
+    fn save(
+        &self,
+        walk_state: &WalkState,
+        progress_state: &WalkProgressState,
+    ) -> Result<(), Error> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut w = BufWriter::new(File::create(&tmp_path)?);
+        writeln!(w, "{}", self.header)?;
+        progress_state.write_checkpoint(&mut w)?;
+        walk_state.write_checkpoint(&mut w)?;
+        w.flush()?;
+        w.get_ref().sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Save the current state. This can take a while for a large walk, so runs off the executor.
+    pub async fn save_now<WS>(
+        &self,
+        logger: &Logger,
+        walk_state: WS,
+        progress_state: WalkProgressState,
+    ) -> Result<(), Error>
+    where
+        WS: 'static + Checkpointable + Send,
+    {
+        let start = Instant::now();
+        let checkpoint = self.clone();
+        tokio::task::spawn_blocking(move || {
+            checkpoint.save(walk_state.walk_state(), &progress_state)
+        })
+        .await??;
+        info!(
+            logger,
+            "Saved checkpoint to {} in {}ms",
+            self.path.display(),
+            start.elapsed().as_millis()
+        );
+        Ok(())
+    }
+
+    /// Save the state every interval. Only returns on error, so run alongside the walk.
+    pub async fn save_periodically<WS>(
+        &self,
+        logger: Logger,
+        walk_state: WS,
+        progress_state: WalkProgressState,
+    ) -> Result<(), Error>
+    where
+        WS: 'static + Checkpointable + Clone + Send,
+    {
+        loop {
+            tokio::time::delay_for(self.interval).await;
+            self.save_now(&logger, walk_state.clone(), progress_state.clone())
+                .await?;
+        }
+    }
+}
@@ -163,6 +163,16 @@ fn dump_with_extension(node_type: NodeType) -> bool {
         NodeType::AliasContentMapping => true,
         // Derived Data
         NodeType::Fsnode => false,
+        NodeType::ChangesetInfo => false,
+        NodeType::BonsaiUnodeMapping => false,
+        NodeType::UnodeManifest => false,
+        NodeType::UnodeFile => true,
+        NodeType::Blame => true,
+        NodeType::FastlogDir => false,
+        NodeType::FastlogFile => true,
+        NodeType::FastlogBatch => false,
+        NodeType::BonsaiDeletedManifestMapping => false,
+        NodeType::DeletedManifest => false,
     }
 }
 
@@ -0,0 +1,513 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::graph::{EdgeType, FileContentData, Node, NodeData, NodeType, OutgoingEdge};
+use crate::progress::{progress_stream, ProgressReporter};
+use crate::setup::{
+    setup_common, DRY_RUN_ARG, EXCLUDE_EDGE_TYPE_ARG, EXCLUDE_NODE_TYPE_ARG, GC,
+    GRACE_PERIOD_HOURS_ARG, INCLUDE_EDGE_TYPE_ARG, INCLUDE_NODE_TYPE_ARG, OUTPUT_REPORT_ARG,
+    RETENTION_DAYS_ARG, SWEEP_REPORT_ARG,
+};
+use crate::state::WalkState;
+use crate::tail::{walk_exact_tail, RepoWalkRun};
+
+use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use blobrepo_factory::Caching;
+use blobstore::{Blobstore, BlobstoreKeyRange};
+use bookmarks::{BookmarkPrefix, Freshness};
+use changesets::SqlChangesets;
+use clap::ArgMatches;
+use cloned::cloned;
+use cmdlib::args;
+use context::CoreContext;
+use dashmap::DashMap;
+use fbinit::FacebookInit;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    future::{self, FutureExt},
+    stream::TryStreamExt,
+};
+use mononoke_types::{
+    Blob, BlobstoreBytes, BlobstoreValue, BonsaiChangeset, ChangesetId, MononokeId,
+};
+use samplingblob::SamplingHandler;
+use slog::{info, Logger};
+use std::{
+    cmp::{max, min},
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const DEFAULT_GRACE_PERIOD_HOURS: u64 = 72;
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+const DELETE_CONCURRENCY: usize = 100;
+const RETENTION_SCAN_CONCURRENCY: usize = 100;
+const RETENTION_SCAN_CHUNK_SIZE: u64 = 10000;
+
+// Blobstore key kinds (the part of the key after the repo prefix) that are only written as the
+// payload of a node of the given type. Only kinds belonging to walked node types are swept, so
+// anything the walker doesn't know how to reach (e.g. derived data mappings) is never deleted.
+fn sweepable_key_kinds(node_type: NodeType) -> &'static [&'static str] {
+    match node_type {
+        NodeType::Root => &[],
+        // Bonsai
+        NodeType::Bookmark => &[],
+        // Changesets stay listed in the SQL changeset and hg mapping tables, which would be left
+        // pointing at nothing, so their blobs are kept.
+        NodeType::BonsaiChangeset => &[],
+        NodeType::BonsaiHgMapping => &[],
+        NodeType::BonsaiPhaseMapping => &[],
+        NodeType::PublishedBookmarks => &[],
+        NodeType::BonsaiFsnodeMapping => &[],
+        // Hg
+        NodeType::HgBonsaiMapping => &[],
+        NodeType::HgChangeset => &[],
+        NodeType::HgManifest => &["hgmanifest.sha1."],
+        NodeType::HgFileEnvelope => &["hgfilenode.sha1."],
+        NodeType::HgFileNode => &[],
+        // Content
+        NodeType::FileContent => &["content.blake2.", "chunk.blake2."],
+        NodeType::FileContentMetadata => &["content_metadata.blake2."],
+        NodeType::AliasContentMapping => &["alias."],
+        // Derived Data
+        NodeType::Fsnode => &["fsnode.blake2."],
+        NodeType::ChangesetInfo => &["changeset_info.blake2."],
+        NodeType::BonsaiUnodeMapping => &[],
+        NodeType::UnodeManifest => &["manifestunode.blake2."],
+        NodeType::UnodeFile => &["fileunode.blake2."],
+        // Chunks are shared with the blames following detected copies, which aren't walked
+        NodeType::Blame => &["blame.fileunode.blake2."],
+        NodeType::FastlogDir => &["fastlogbatch.manifestunode."],
+        NodeType::FastlogFile => &["fastlogbatch.fileunode."],
+        NodeType::FastlogBatch => &["fastlogbatch.blake2."],
+        NodeType::BonsaiDeletedManifestMapping => &[],
+        NodeType::DeletedManifest => &["deletedmanifest.blake2."],
+    }
+}
+
+// Marks every key the walk loads from the blobstore as reachable. Loads made while not marking,
+// e.g. when looking for the changesets to retain, don't count.
+#[derive(Debug)]
+struct GcMarker {
+    marking: AtomicBool,
+    reachable: DashMap<String, ()>,
+}
+
+impl GcMarker {
+    fn new() -> Self {
+        Self {
+            marking: AtomicBool::new(false),
+            reachable: DashMap::new(),
+        }
+    }
+
+    fn set_marking(&self, marking: bool) {
+        self.marking.store(marking, Ordering::SeqCst);
+    }
+
+    fn mark(&self, key: String) {
+        if self.marking.load(Ordering::SeqCst) {
+            self.reachable.insert(key, ());
+        }
+    }
+
+    fn is_reachable(&self, key: &str) -> bool {
+        self.reachable.contains_key(key)
+    }
+}
+
+impl SamplingHandler for GcMarker {
+    fn sample_get(
+        &self,
+        _ctx: CoreContext,
+        key: String,
+        value: Option<&BlobstoreBytes>,
+    ) -> Result<(), Error> {
+        if value.is_some() {
+            self.mark(key);
+        }
+        Ok(())
+    }
+
+    fn sample_is_present(&self, _ctx: CoreContext, key: String, value: bool) -> Result<(), Error> {
+        if value {
+            self.mark(key);
+        }
+        Ok(())
+    }
+}
+
+// Deleting a blob that is still in use is unrecoverable, so refuse any option that could make
+// the walk see less than everything reachable from its roots.
+fn check_complete_walk(
+    matches: &ArgMatches<'_>,
+    sub_m: &ArgMatches<'_>,
+    tail_secs: Option<u64>,
+    checkpoint: bool,
+    error_as_data: bool,
+) -> Result<(), Error> {
+    // Keys of node types the walk can't step through would be left unmarked
+    for arg in &[
+        INCLUDE_NODE_TYPE_ARG,
+        EXCLUDE_NODE_TYPE_ARG,
+        INCLUDE_EDGE_TYPE_ARG,
+        EXCLUDE_EDGE_TYPE_ARG,
+    ] {
+        if sub_m.is_present(arg) {
+            return Err(format_err!("--{} can't be used with {}", arg, GC));
+        }
+    }
+    if tail_secs.is_some() {
+        return Err(format_err!("{} can't tail", GC));
+    }
+    // The marks are only held in memory, so a resumed walk would miss what was marked before
+    if checkpoint {
+        return Err(format_err!("{} can't checkpoint or resume", GC));
+    }
+    if error_as_data {
+        return Err(format_err!("{} can't treat errors as data", GC));
+    }
+    // Blobs served from a shared cache would never reach the marker
+    if args::parse_caching(matches) == Caching::Enabled {
+        return Err(format_err!(
+            "{} needs --skip-caching or --cachelib-only-blobstore",
+            GC
+        ));
+    }
+    Ok(())
+}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+// Load the keys of an earlier report, checking it has aged past the grace period. Also returns
+// when the report was written, as every key in it was stored by then.
+fn read_sweep_report(path: &str, grace_period: Duration) -> Result<(HashSet<String>, i64), Error> {
+    let written = fs::metadata(path)?.modified()?;
+    let age = SystemTime::now()
+        .duration_since(written)
+        .unwrap_or_default();
+    if age < grace_period {
+        return Err(format_err!(
+            "Sweep report {} is {}h old, it must be at least {}h old",
+            path,
+            age.as_secs() / 3600,
+            grace_period.as_secs() / 3600,
+        ));
+    }
+    let keys = BufReader::new(File::open(path)?)
+        .lines()
+        .map(|line| Ok(line?))
+        .collect::<Result<_, Error>>()?;
+    Ok((keys, unix_secs(written)))
+}
+
+// Whether the changeset was committed, or its blob stored, since the cutoff.
+async fn is_recent_changeset(
+    ctx: &CoreContext,
+    blobstore: &Arc<dyn Blobstore>,
+    key: String,
+    cs_id: ChangesetId,
+    cutoff: i64,
+) -> Result<bool, Error> {
+    let data = match blobstore.get(ctx.clone(), key).compat().await? {
+        Some(data) => data,
+        None => return Ok(false),
+    };
+    if data
+        .as_meta()
+        .as_ctime()
+        .map_or(false, |ctime| ctime >= cutoff)
+    {
+        return Ok(true);
+    }
+    let bcs = BonsaiChangeset::from_blob(Blob::new(cs_id, data.into_raw_bytes()))?;
+    let committed = max(
+        bcs.author_date().timestamp_secs(),
+        bcs.committer_date()
+            .map_or(std::i64::MIN, |date| date.timestamp_secs()),
+    );
+    Ok(committed >= cutoff)
+}
+
+// Changesets that must be kept although the bookmarks walked may not reach them: the targets
+// of all bookmarks, including scratch ones, and every changeset committed or stored since the
+// cutoff, so recent drafts survive.
+async fn retained_changesets(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    blobstore: &Arc<dyn Blobstore>,
+    sql_changesets: &SqlChangesets,
+    cutoff: i64,
+) -> Result<HashSet<ChangesetId>, Error> {
+    let repo_id = repo.get_repoid();
+    let mut retained: HashSet<ChangesetId> = repo
+        .get_bookmarks_object()
+        .list_all_by_prefix(
+            ctx.clone(),
+            &BookmarkPrefix::empty(),
+            repo_id,
+            Freshness::MostRecent,
+            std::u64::MAX,
+        )
+        .compat()
+        .map_ok(|(_bookmark, cs_id)| cs_id)
+        .try_collect()
+        .await?;
+    let bookmarked = retained.len();
+
+    let (lower, upper) = match sql_changesets
+        .get_changesets_ids_bounds(repo_id)
+        .compat()
+        .await?
+    {
+        (Some(lower), Some(upper)) => (lower, upper),
+        _ => return Ok(retained),
+    };
+    let repo_prefix = repo_id.prefix();
+    let repo_prefix = repo_prefix.as_str();
+    let recent: Vec<ChangesetId> = futures::stream::iter(
+        (lower..=upper)
+            .step_by(RETENTION_SCAN_CHUNK_SIZE as usize)
+            .map(|start| {
+                Ok::<_, Error>((start, min(start + RETENTION_SCAN_CHUNK_SIZE, upper + 1)))
+            }),
+    )
+    .map_ok(|(start, end)| {
+        sql_changesets
+            .get_list_bs_cs_id_in_range_exclusive(repo_id, start, end)
+            .compat()
+    })
+    .try_flatten()
+    .map_ok(|cs_id| async move {
+        let key = format!("{}{}", repo_prefix, cs_id.blobstore_key());
+        let is_recent = is_recent_changeset(ctx, blobstore, key, cs_id, cutoff).await?;
+        Ok::<_, Error>(if is_recent { Some(cs_id) } else { None })
+    })
+    .try_buffer_unordered(RETENTION_SCAN_CONCURRENCY)
+    .try_filter_map(future::ok)
+    .try_collect()
+    .await?;
+
+    info!(
+        ctx.logger(),
+        "Retaining {} bookmarked and {} recent changesets",
+        bookmarked,
+        recent.len()
+    );
+    retained.extend(recent);
+    Ok(retained)
+}
+
+// Subcommand entry point for garbage collection
+pub async fn gc<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'a>,
+    sub_m: &'a ArgMatches<'a>,
+) -> Result<(), Error> {
+    let marker = Arc::new(GcMarker::new());
+
+    let (datasources, mut walk_params) =
+        setup_common(GC, fb, &logger, Some(marker.clone()), matches, sub_m).await?;
+
+    check_complete_walk(
+        matches,
+        sub_m,
+        walk_params.tail_secs,
+        walk_params.checkpoint.is_some(),
+        !walk_params.error_as_data_node_types.is_empty()
+            || !walk_params.error_as_data_edge_types.is_empty(),
+    )?;
+
+    let output_report = sub_m
+        .value_of(OUTPUT_REPORT_ARG)
+        .ok_or_else(|| format_err!("--{} is required", OUTPUT_REPORT_ARG))?
+        .to_string();
+    let grace_period = Duration::from_secs(
+        3600 * args::get_u64_opt(&sub_m, GRACE_PERIOD_HOURS_ARG)
+            .unwrap_or(DEFAULT_GRACE_PERIOD_HOURS),
+    );
+    let retention = Duration::from_secs(
+        86400 * args::get_u64_opt(&sub_m, RETENTION_DAYS_ARG).unwrap_or(DEFAULT_RETENTION_DAYS),
+    );
+    let cutoff = unix_secs(SystemTime::now() - retention);
+    let dry_run = sub_m.is_present(DRY_RUN_ARG);
+    // Check the report before the walk, rather than find out it's unusable hours later
+    let sweep_candidates = sub_m
+        .value_of(SWEEP_REPORT_ARG)
+        .map(|path| read_sweep_report(path, grace_period))
+        .transpose()?;
+    if sweep_candidates.is_some() && !dry_run && args::parse_readonly_storage(&matches).0 {
+        return Err(format_err!(
+            "Sweeping needs writable storage, pass --{} to only report",
+            DRY_RUN_ARG
+        ));
+    }
+
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    let blobstore = datasources.blobstore.clone();
+    let sql_changesets = args::open_sql::<SqlChangesets>(fb, &matches)
+        .compat()
+        .await?;
+    let retained = retained_changesets(
+        &ctx,
+        &datasources.blobrepo,
+        &blobstore,
+        &sql_changesets,
+        cutoff,
+    )
+    .await?;
+    walk_params
+        .walk_roots
+        .extend(retained.into_iter().map(|cs_id| {
+            OutgoingEdge::new(
+                EdgeType::RootToBonsaiChangeset,
+                Node::BonsaiChangeset(cs_id),
+            )
+        }));
+
+    let repo_prefix = datasources.blobrepo.get_repoid().prefix();
+    let key_kinds: Vec<_> = walk_params
+        .include_node_types
+        .iter()
+        .flat_map(|t| sweepable_key_kinds(*t).iter())
+        .map(|kind| format!("{}{}", repo_prefix, kind))
+        .collect();
+
+    // Mark: walk the graph, loading everything including the chunks of file contents
+    let make_sink = {
+        cloned!(
+            walk_params.progress_state,
+            walk_params.quiet,
+            walk_params.scheduled_max
+        );
+        move |_run: RepoWalkRun| {
+            async move |walk_output| {
+                progress_stream(quiet, &progress_state, walk_output)
+                    .map_ok(|(_n, nd, _progress_stats)| match nd {
+                        Some(NodeData::FileContent(FileContentData::ContentStream(
+                            file_bytes_stream,
+                        ))) => file_bytes_stream
+                            .try_for_each(|_file_bytes| future::ok(()))
+                            .left_future(),
+                        _ => future::ok(()).right_future(),
+                    })
+                    .try_buffer_unordered(scheduled_max)
+                    .try_for_each(|()| future::ok(()))
+                    .await?;
+                progress_state.report_progress();
+                Ok(())
+            }
+        }
+    };
+
+    let walk_state = Arc::new(WalkState::new(
+        walk_params.include_node_types.clone(),
+        walk_params.include_edge_types.clone(),
+    ));
+    marker.set_marking(true);
+    walk_exact_tail::<_, _, _, _, _, ()>(
+        fb,
+        logger.clone(),
+        datasources,
+        walk_params,
+        walk_state,
+        make_sink,
+        false,
+    )
+    .await?;
+    marker.set_marking(false);
+    info!(logger, "Marked {} reachable keys", marker.reachable.len());
+
+    // Sweep: anything of a sweepable kind the walk didn't load is unreachable
+    let unreachable: Vec<String> = blobstore
+        .enumerate(ctx.clone(), BlobstoreKeyRange::with_prefix(repo_prefix))
+        .compat()
+        .try_filter(|key| {
+            future::ready(
+                key_kinds.iter().any(|kind| key.starts_with(kind)) && !marker.is_reachable(key),
+            )
+        })
+        .try_collect()
+        .await?;
+
+    let mut report = BufWriter::new(File::create(&output_report)?);
+    let mut unreachable_by_kind = BTreeMap::new();
+    for key in &unreachable {
+        writeln!(report, "{}", key)?;
+        if let Some(kind) = key_kinds.iter().find(|kind| key.starts_with(kind.as_str())) {
+            *unreachable_by_kind.entry(kind.as_str()).or_insert(0) += 1;
+        }
+    }
+    report.flush()?;
+    info!(
+        logger,
+        "Found {} unreachable keys {:?}, written to {}",
+        unreachable.len(),
+        unreachable_by_kind,
+        output_report
+    );
+
+    let (sweep_candidates, report_written) = match sweep_candidates {
+        Some(sweep_candidates) => sweep_candidates,
+        None => return Ok(()),
+    };
+    // Only keys that have been unreachable for the whole grace period are deleted
+    let to_delete: Vec<_> = unreachable
+        .into_iter()
+        .filter(|key| sweep_candidates.contains(key))
+        .collect();
+
+    // Nor is anything stored within the retention window. Stores that don't report a ctime are
+    // taken to have stored the key when the sweep report was written, as it was listed there.
+    let deleted = AtomicUsize::new(0);
+    let retained = AtomicUsize::new(0);
+    futures::stream::iter(to_delete.into_iter().map(Ok))
+        .try_for_each_concurrent(DELETE_CONCURRENCY, |key| {
+            let (ctx, blobstore, deleted, retained) = (&ctx, &blobstore, &deleted, &retained);
+            async move {
+                let stored = match blobstore.get(ctx.clone(), key.clone()).compat().await? {
+                    Some(data) => data.as_meta().as_ctime().unwrap_or(report_written),
+                    None => return Ok(()),
+                };
+                if stored >= cutoff {
+                    retained.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                if !dry_run {
+                    blobstore.delete(ctx.clone(), key).compat().await?;
+                }
+                deleted.fetch_add(1, Ordering::Relaxed);
+                Ok::<_, Error>(())
+            }
+        })
+        .await?;
+
+    info!(
+        logger,
+        "{} {} keys, kept {} stored within the retention window",
+        if dry_run {
+            "Dry run, would delete"
+        } else {
+            "Deleted"
+        },
+        deleted.load(Ordering::Relaxed),
+        retained.load(Ordering::Relaxed)
+    );
+    Ok(())
+}
@@ -8,6 +8,7 @@
 use anyhow::{format_err, Error};
 use arc_interner::ArcIntern;
 use bookmarks::BookmarkName;
+use changeset_info::ChangesetInfo;
 use filenodes::FilenodeInfo;
 use filestore::Alias;
 use futures::stream::BoxStream;
@@ -15,7 +16,14 @@ use mercurial_types::{
     blobs::{BlobManifest, HgBlobChangeset},
     FileBytes, HgChangesetId, HgFileEnvelope, HgFileNodeId, HgManifestId,
 };
-use mononoke_types::{fsnode::Fsnode, FsnodeId};
+use mononoke_types::{
+    blame::BlameMaybeRejected,
+    deleted_files_manifest::DeletedManifest,
+    fastlog_batch::FastlogBatch,
+    fsnode::Fsnode,
+    unode::{FileUnode, ManifestUnode},
+    BlameId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId, ManifestUnodeId,
+};
 use mononoke_types::{
     BonsaiChangeset, ChangesetId, ContentId, ContentMetadata, MPath, MPathHash, MononokeId,
 };
@@ -96,6 +104,16 @@ enum NodeType {
     // Derived data
     BonsaiFsnodeMapping,
     Fsnode,
+    ChangesetInfo,
+    BonsaiUnodeMapping,
+    UnodeManifest,
+    UnodeFile,
+    Blame,
+    FastlogDir,
+    FastlogFile,
+    FastlogBatch,
+    BonsaiDeletedManifestMapping,
+    DeletedManifest,
 }
 }
 
@@ -128,6 +146,18 @@ impl NodeType {
             // Derived data
             NodeType::BonsaiFsnodeMapping => Some(EdgeType::RootToBonsaiFsnodeMapping),
             NodeType::Fsnode => Some(EdgeType::RootToFsnode),
+            NodeType::ChangesetInfo => Some(EdgeType::RootToChangesetInfo),
+            NodeType::BonsaiUnodeMapping => Some(EdgeType::RootToBonsaiUnodeMapping),
+            NodeType::UnodeManifest => Some(EdgeType::RootToUnodeManifest),
+            NodeType::UnodeFile => Some(EdgeType::RootToUnodeFile),
+            NodeType::Blame => Some(EdgeType::RootToBlame),
+            NodeType::FastlogDir => Some(EdgeType::RootToFastlogDir),
+            NodeType::FastlogFile => Some(EdgeType::RootToFastlogFile),
+            NodeType::FastlogBatch => Some(EdgeType::RootToFastlogBatch),
+            NodeType::BonsaiDeletedManifestMapping => {
+                Some(EdgeType::RootToBonsaiDeletedManifestMapping)
+            }
+            NodeType::DeletedManifest => Some(EdgeType::RootToDeletedManifest),
         }
     }
 }
@@ -239,6 +269,16 @@ pub enum Node {
     // Derived data
     BonsaiFsnodeMapping(ChangesetId),
     Fsnode((WrappedPath, FsnodeId)),
+    ChangesetInfo(ChangesetId),
+    BonsaiUnodeMapping(ChangesetId),
+    UnodeManifest((WrappedPath, ManifestUnodeId)),
+    UnodeFile((WrappedPath, FileUnodeId)),
+    Blame(BlameId),
+    FastlogDir((WrappedPath, ManifestUnodeId)),
+    FastlogFile((WrappedPath, FileUnodeId)),
+    FastlogBatch(FastlogBatchId),
+    BonsaiDeletedManifestMapping(ChangesetId),
+    DeletedManifest((WrappedPath, DeletedManifestId)),
 }
 
 // Some Node types are accessible by more than one type of edge, this allows us to restrict the paths
@@ -264,6 +304,16 @@ enum EdgeType {
     // Derived data Roots
     RootToBonsaiFsnodeMapping,
     RootToFsnode,
+    RootToChangesetInfo,
+    RootToBonsaiUnodeMapping,
+    RootToUnodeManifest,
+    RootToUnodeFile,
+    RootToBlame,
+    RootToFastlogDir,
+    RootToFastlogFile,
+    RootToFastlogBatch,
+    RootToBonsaiDeletedManifestMapping,
+    RootToDeletedManifest,
     // Bonsai
     BookmarkToBonsaiChangeset,
     BookmarkToBonsaiHgMapping,
@@ -275,6 +325,9 @@ enum EdgeType {
     PublishedBookmarksToBonsaiChangeset,
     PublishedBookmarksToBonsaiHgMapping,
     BonsaiChangesetToBonsaiFsnodeMapping,
+    BonsaiChangesetToChangesetInfo,
+    BonsaiChangesetToBonsaiUnodeMapping,
+    BonsaiChangesetToBonsaiDeletedManifestMapping,
     // Hg
     HgBonsaiMappingToBonsaiChangeset,
     HgChangesetToHgParent,
@@ -296,6 +349,24 @@ enum EdgeType {
     // Derived data
     BonsaiToRootFsnode,
     FsnodeToChildFsnode,
+    ChangesetInfoToChangesetInfoParent,
+    BonsaiToRootUnodeManifest,
+    UnodeManifestToUnodeManifestParent,
+    UnodeManifestToUnodeFile,
+    UnodeManifestToChildUnodeManifest,
+    UnodeManifestToLinkedBonsaiChangeset,
+    UnodeManifestToFastlogDir,
+    UnodeFileToUnodeFileParent,
+    UnodeFileToFileContent,
+    UnodeFileToLinkedBonsaiChangeset,
+    UnodeFileToBlame,
+    UnodeFileToFastlogFile,
+    FastlogDirToPreviousBatch,
+    FastlogFileToPreviousBatch,
+    FastlogBatchToPreviousBatch,
+    BonsaiToRootDeletedManifest,
+    DeletedManifestToDeletedManifestChild,
+    DeletedManifestToLinkedBonsaiChangeset,
 }
 }
 
@@ -329,6 +400,16 @@ impl EdgeType {
             // Derived data Roots
             EdgeType::RootToBonsaiFsnodeMapping => None,
             EdgeType::RootToFsnode => None,
+            EdgeType::RootToChangesetInfo => None,
+            EdgeType::RootToBonsaiUnodeMapping => None,
+            EdgeType::RootToUnodeManifest => None,
+            EdgeType::RootToUnodeFile => None,
+            EdgeType::RootToBlame => None,
+            EdgeType::RootToFastlogDir => None,
+            EdgeType::RootToFastlogFile => None,
+            EdgeType::RootToFastlogBatch => None,
+            EdgeType::RootToBonsaiDeletedManifestMapping => None,
+            EdgeType::RootToDeletedManifest => None,
             // Bonsai
             EdgeType::BookmarkToBonsaiChangeset => Some(NodeType::Bookmark),
             EdgeType::BookmarkToBonsaiHgMapping => Some(NodeType::Bookmark),
@@ -340,6 +421,11 @@ impl EdgeType {
             EdgeType::PublishedBookmarksToBonsaiChangeset => Some(NodeType::PublishedBookmarks),
             EdgeType::PublishedBookmarksToBonsaiHgMapping => Some(NodeType::PublishedBookmarks),
             EdgeType::BonsaiChangesetToBonsaiFsnodeMapping => Some(NodeType::BonsaiChangeset),
+            EdgeType::BonsaiChangesetToChangesetInfo => Some(NodeType::BonsaiChangeset),
+            EdgeType::BonsaiChangesetToBonsaiUnodeMapping => Some(NodeType::BonsaiChangeset),
+            EdgeType::BonsaiChangesetToBonsaiDeletedManifestMapping => {
+                Some(NodeType::BonsaiChangeset)
+            }
             // Hg
             EdgeType::HgBonsaiMappingToBonsaiChangeset => Some(NodeType::HgBonsaiMapping),
             EdgeType::HgChangesetToHgParent => Some(NodeType::HgChangeset),
@@ -361,6 +447,24 @@ impl EdgeType {
             // Derived data
             EdgeType::BonsaiToRootFsnode => Some(NodeType::BonsaiFsnodeMapping),
             EdgeType::FsnodeToChildFsnode => Some(NodeType::Fsnode),
+            EdgeType::ChangesetInfoToChangesetInfoParent => Some(NodeType::ChangesetInfo),
+            EdgeType::BonsaiToRootUnodeManifest => Some(NodeType::BonsaiUnodeMapping),
+            EdgeType::UnodeManifestToUnodeManifestParent => Some(NodeType::UnodeManifest),
+            EdgeType::UnodeManifestToUnodeFile => Some(NodeType::UnodeManifest),
+            EdgeType::UnodeManifestToChildUnodeManifest => Some(NodeType::UnodeManifest),
+            EdgeType::UnodeManifestToLinkedBonsaiChangeset => Some(NodeType::UnodeManifest),
+            EdgeType::UnodeManifestToFastlogDir => Some(NodeType::UnodeManifest),
+            EdgeType::UnodeFileToUnodeFileParent => Some(NodeType::UnodeFile),
+            EdgeType::UnodeFileToFileContent => Some(NodeType::UnodeFile),
+            EdgeType::UnodeFileToLinkedBonsaiChangeset => Some(NodeType::UnodeFile),
+            EdgeType::UnodeFileToBlame => Some(NodeType::UnodeFile),
+            EdgeType::UnodeFileToFastlogFile => Some(NodeType::UnodeFile),
+            EdgeType::FastlogDirToPreviousBatch => Some(NodeType::FastlogDir),
+            EdgeType::FastlogFileToPreviousBatch => Some(NodeType::FastlogFile),
+            EdgeType::FastlogBatchToPreviousBatch => Some(NodeType::FastlogBatch),
+            EdgeType::BonsaiToRootDeletedManifest => Some(NodeType::BonsaiDeletedManifestMapping),
+            EdgeType::DeletedManifestToDeletedManifestChild => Some(NodeType::DeletedManifest),
+            EdgeType::DeletedManifestToLinkedBonsaiChangeset => Some(NodeType::DeletedManifest),
         }
     }
     pub fn outgoing_type(&self) -> NodeType {
@@ -384,6 +488,16 @@ impl EdgeType {
             // Derived data Roots
             EdgeType::RootToBonsaiFsnodeMapping => NodeType::BonsaiFsnodeMapping,
             EdgeType::RootToFsnode => NodeType::Fsnode,
+            EdgeType::RootToChangesetInfo => NodeType::ChangesetInfo,
+            EdgeType::RootToBonsaiUnodeMapping => NodeType::BonsaiUnodeMapping,
+            EdgeType::RootToUnodeManifest => NodeType::UnodeManifest,
+            EdgeType::RootToUnodeFile => NodeType::UnodeFile,
+            EdgeType::RootToBlame => NodeType::Blame,
+            EdgeType::RootToFastlogDir => NodeType::FastlogDir,
+            EdgeType::RootToFastlogFile => NodeType::FastlogFile,
+            EdgeType::RootToFastlogBatch => NodeType::FastlogBatch,
+            EdgeType::RootToBonsaiDeletedManifestMapping => NodeType::BonsaiDeletedManifestMapping,
+            EdgeType::RootToDeletedManifest => NodeType::DeletedManifest,
             // Bonsai
             EdgeType::BookmarkToBonsaiChangeset => NodeType::BonsaiChangeset,
             EdgeType::BookmarkToBonsaiHgMapping => NodeType::BonsaiHgMapping,
@@ -395,6 +509,11 @@ impl EdgeType {
             EdgeType::PublishedBookmarksToBonsaiChangeset => NodeType::BonsaiChangeset,
             EdgeType::PublishedBookmarksToBonsaiHgMapping => NodeType::BonsaiHgMapping,
             EdgeType::BonsaiChangesetToBonsaiFsnodeMapping => NodeType::BonsaiFsnodeMapping,
+            EdgeType::BonsaiChangesetToChangesetInfo => NodeType::ChangesetInfo,
+            EdgeType::BonsaiChangesetToBonsaiUnodeMapping => NodeType::BonsaiUnodeMapping,
+            EdgeType::BonsaiChangesetToBonsaiDeletedManifestMapping => {
+                NodeType::BonsaiDeletedManifestMapping
+            }
             // Hg
             EdgeType::HgBonsaiMappingToBonsaiChangeset => NodeType::BonsaiChangeset,
             EdgeType::HgChangesetToHgParent => NodeType::HgChangeset,
@@ -416,6 +535,24 @@ impl EdgeType {
             // Derived data
             EdgeType::BonsaiToRootFsnode => NodeType::Fsnode,
             EdgeType::FsnodeToChildFsnode => NodeType::Fsnode,
+            EdgeType::ChangesetInfoToChangesetInfoParent => NodeType::ChangesetInfo,
+            EdgeType::BonsaiToRootUnodeManifest => NodeType::UnodeManifest,
+            EdgeType::UnodeManifestToUnodeManifestParent => NodeType::UnodeManifest,
+            EdgeType::UnodeManifestToUnodeFile => NodeType::UnodeFile,
+            EdgeType::UnodeManifestToChildUnodeManifest => NodeType::UnodeManifest,
+            EdgeType::UnodeManifestToLinkedBonsaiChangeset => NodeType::BonsaiChangeset,
+            EdgeType::UnodeManifestToFastlogDir => NodeType::FastlogDir,
+            EdgeType::UnodeFileToUnodeFileParent => NodeType::UnodeFile,
+            EdgeType::UnodeFileToFileContent => NodeType::FileContent,
+            EdgeType::UnodeFileToLinkedBonsaiChangeset => NodeType::BonsaiChangeset,
+            EdgeType::UnodeFileToBlame => NodeType::Blame,
+            EdgeType::UnodeFileToFastlogFile => NodeType::FastlogFile,
+            EdgeType::FastlogDirToPreviousBatch => NodeType::FastlogBatch,
+            EdgeType::FastlogFileToPreviousBatch => NodeType::FastlogBatch,
+            EdgeType::FastlogBatchToPreviousBatch => NodeType::FastlogBatch,
+            EdgeType::BonsaiToRootDeletedManifest => NodeType::DeletedManifest,
+            EdgeType::DeletedManifestToDeletedManifestChild => NodeType::DeletedManifest,
+            EdgeType::DeletedManifestToLinkedBonsaiChangeset => NodeType::BonsaiChangeset,
         }
     }
 }
@@ -455,6 +592,18 @@ pub enum NodeData {
     // Derived data
     BonsaiFsnodeMapping(Option<FsnodeId>),
     Fsnode(Fsnode),
+    ChangesetInfo(Option<ChangesetInfo>),
+    BonsaiUnodeMapping(Option<ManifestUnodeId>),
+    UnodeManifest(ManifestUnode),
+    UnodeFile(FileUnode),
+    // None if blame has not been derived for the file
+    Blame(Option<BlameMaybeRejected>),
+    // None if fastlog has not been derived for the unode
+    FastlogDir(Option<FastlogBatch>),
+    FastlogFile(Option<FastlogBatch>),
+    FastlogBatch(FastlogBatch),
+    BonsaiDeletedManifestMapping(Option<DeletedManifestId>),
+    DeletedManifest(DeletedManifest),
 }
 
 impl Node {
@@ -480,6 +629,16 @@ impl Node {
             // Derived data
             Node::BonsaiFsnodeMapping(_) => NodeType::BonsaiFsnodeMapping,
             Node::Fsnode(_) => NodeType::Fsnode,
+            Node::ChangesetInfo(_) => NodeType::ChangesetInfo,
+            Node::BonsaiUnodeMapping(_) => NodeType::BonsaiUnodeMapping,
+            Node::UnodeManifest(_) => NodeType::UnodeManifest,
+            Node::UnodeFile(_) => NodeType::UnodeFile,
+            Node::Blame(_) => NodeType::Blame,
+            Node::FastlogDir(_) => NodeType::FastlogDir,
+            Node::FastlogFile(_) => NodeType::FastlogFile,
+            Node::FastlogBatch(_) => NodeType::FastlogBatch,
+            Node::BonsaiDeletedManifestMapping(_) => NodeType::BonsaiDeletedManifestMapping,
+            Node::DeletedManifest(_) => NodeType::DeletedManifest,
         }
     }
 
@@ -505,6 +664,16 @@ impl Node {
             // Derived data
             Node::BonsaiFsnodeMapping(k) => k.blobstore_key(),
             Node::Fsnode((_, k)) => k.blobstore_key(),
+            Node::ChangesetInfo(k) => k.blobstore_key(),
+            Node::BonsaiUnodeMapping(k) => k.blobstore_key(),
+            Node::UnodeManifest((_, k)) => k.blobstore_key(),
+            Node::UnodeFile((_, k)) => k.blobstore_key(),
+            Node::Blame(k) => k.blobstore_key(),
+            Node::FastlogDir((_, k)) => k.blobstore_key(),
+            Node::FastlogFile((_, k)) => k.blobstore_key(),
+            Node::FastlogBatch(k) => k.blobstore_key(),
+            Node::BonsaiDeletedManifestMapping(k) => k.blobstore_key(),
+            Node::DeletedManifest((_, k)) => k.blobstore_key(),
         }
     }
 
@@ -530,6 +699,16 @@ impl Node {
             // Derived data
             Node::BonsaiFsnodeMapping(_) => None,
             Node::Fsnode((p, _)) => Some(&p),
+            Node::ChangesetInfo(_) => None,
+            Node::BonsaiUnodeMapping(_) => None,
+            Node::UnodeManifest((p, _)) => Some(&p),
+            Node::UnodeFile((p, _)) => Some(&p),
+            Node::Blame(_) => None,
+            Node::FastlogDir((p, _)) => Some(&p),
+            Node::FastlogFile((p, _)) => Some(&p),
+            Node::FastlogBatch(_) => None,
+            Node::BonsaiDeletedManifestMapping(_) => None,
+            Node::DeletedManifest((p, _)) => Some(&p),
         }
     }
 
@@ -556,6 +735,16 @@ impl Node {
             // Derived data
             Node::BonsaiFsnodeMapping(k) => Some(k.sampling_fingerprint()),
             Node::Fsnode((_, k)) => Some(k.sampling_fingerprint()),
+            Node::ChangesetInfo(k) => Some(k.sampling_fingerprint()),
+            Node::BonsaiUnodeMapping(k) => Some(k.sampling_fingerprint()),
+            Node::UnodeManifest((_, k)) => Some(k.sampling_fingerprint()),
+            Node::UnodeFile((_, k)) => Some(k.sampling_fingerprint()),
+            Node::Blame(k) => Some(FileUnodeId::from(*k).sampling_fingerprint()),
+            Node::FastlogDir((_, k)) => Some(k.sampling_fingerprint()),
+            Node::FastlogFile((_, k)) => Some(k.sampling_fingerprint()),
+            Node::FastlogBatch(k) => Some(k.sampling_fingerprint()),
+            Node::BonsaiDeletedManifestMapping(k) => Some(k.sampling_fingerprint()),
+            Node::DeletedManifest((_, k)) => Some(k.sampling_fingerprint()),
         }
     }
 }
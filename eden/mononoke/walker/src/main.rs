@@ -15,7 +15,9 @@ use futures::future::{self, FutureExt};
 use cmdlib::{args, helpers::block_execute};
 
 mod blobstore;
+mod checkpoint;
 mod corpus;
+mod gc;
 #[macro_use]
 mod graph;
 mod parse_node;
@@ -43,6 +45,7 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
             sizing::compression_benefit(fb, logger.clone(), &matches, sub_m).boxed()
         }
         (setup::CORPUS, Some(sub_m)) => corpus::corpus(fb, logger.clone(), &matches, sub_m).boxed(),
+        (setup::GC, Some(sub_m)) => gc::gc(fb, logger.clone(), &matches, sub_m).boxed(),
         (setup::SCRUB, Some(sub_m)) => {
             scrub::scrub_objects(fb, logger.clone(), &matches, sub_m).boxed()
         }
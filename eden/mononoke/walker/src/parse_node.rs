@@ -13,9 +13,10 @@ use filestore::Alias;
 use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId};
 use mononoke_types::{
     hash::{GitSha1, Sha1, Sha256},
-    ChangesetId, ContentId, FsnodeId, MPath,
+    BlameId, ChangesetId, ContentId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId,
+    MPath, ManifestUnodeId,
 };
-use std::str::FromStr;
+use std::{fmt::Display, str::FromStr};
 
 const NODE_SEP: &str = ":";
 
@@ -120,10 +121,104 @@ pub fn parse_node(s: &str) -> Result<Node, Error> {
             let id = FsnodeId::from_str(parts[0])?;
             Node::Fsnode((WrappedPath::from(mpath), id))
         }
+        NodeType::ChangesetInfo => {
+            Node::ChangesetInfo(ChangesetId::from_str(&parts.join(NODE_SEP))?)
+        }
+        NodeType::BonsaiUnodeMapping => {
+            Node::BonsaiUnodeMapping(ChangesetId::from_str(&parts.join(NODE_SEP))?)
+        }
+        NodeType::UnodeManifest => {
+            let mpath = check_and_build_mpath(node_type, parts)?;
+            let id = ManifestUnodeId::from_str(parts[0])?;
+            Node::UnodeManifest((WrappedPath::from(mpath), id))
+        }
+        NodeType::UnodeFile => {
+            let mpath = check_and_build_mpath(node_type, parts)?;
+            let id = FileUnodeId::from_str(parts[0])?;
+            Node::UnodeFile((WrappedPath::from(mpath), id))
+        }
+        NodeType::Blame => {
+            Node::Blame(BlameId::from(FileUnodeId::from_str(&parts.join(NODE_SEP))?))
+        }
+        NodeType::FastlogDir => {
+            let mpath = check_and_build_mpath(node_type, parts)?;
+            let id = ManifestUnodeId::from_str(parts[0])?;
+            Node::FastlogDir((WrappedPath::from(mpath), id))
+        }
+        NodeType::FastlogFile => {
+            let mpath = check_and_build_mpath(node_type, parts)?;
+            let id = FileUnodeId::from_str(parts[0])?;
+            Node::FastlogFile((WrappedPath::from(mpath), id))
+        }
+        NodeType::FastlogBatch => {
+            Node::FastlogBatch(FastlogBatchId::from_str(&parts.join(NODE_SEP))?)
+        }
+        NodeType::BonsaiDeletedManifestMapping => {
+            Node::BonsaiDeletedManifestMapping(ChangesetId::from_str(&parts.join(NODE_SEP))?)
+        }
+        NodeType::DeletedManifest => {
+            let mpath = check_and_build_mpath(node_type, parts)?;
+            let id = DeletedManifestId::from_str(parts[0])?;
+            Node::DeletedManifest((WrappedPath::from(mpath), id))
+        }
     };
     Ok(node)
 }
 
+fn format_with_path<K: Display>(node_type: NodeType, id: &K, path: &WrappedPath) -> String {
+    match path.as_ref() {
+        None => format!("{}{}{}{}/", node_type, NODE_SEP, id, NODE_SEP),
+        Some(mpath) => format!("{}{}{}{}{}", node_type, NODE_SEP, id, NODE_SEP, mpath),
+    }
+}
+
+fn format_with_id<K: Display>(node_type: NodeType, id: &K) -> String {
+    format!("{}{}{}", node_type, NODE_SEP, id)
+}
+
+/// The inverse of parse_node, e.g. for saving nodes to resume a walk from
+pub fn format_node(node: &Node) -> String {
+    let t = node.get_type();
+    match node {
+        Node::Root => t.to_string(),
+        // Bonsai
+        Node::Bookmark(k) => format_with_id(t, k),
+        Node::BonsaiChangeset(k) => format_with_id(t, k),
+        Node::BonsaiHgMapping(k) => format_with_id(t, k),
+        Node::BonsaiPhaseMapping(k) => format_with_id(t, k),
+        Node::PublishedBookmarks => t.to_string(),
+        // Hg
+        Node::HgBonsaiMapping(k) => format_with_id(t, k),
+        Node::HgChangeset(k) => format_with_id(t, k),
+        Node::HgManifest((p, k)) => format_with_path(t, k, p),
+        Node::HgFileEnvelope(k) => format_with_id(t, k),
+        Node::HgFileNode((p, k)) => format_with_path(t, k, p),
+        // Content
+        Node::FileContent(k) => format_with_id(t, k),
+        Node::FileContentMetadata(k) => format_with_id(t, k),
+        Node::AliasContentMapping(alias) => match alias {
+            Alias::GitSha1(k) => {
+                format!("{}{}{}{}{}", t, NODE_SEP, AliasType::GitSha1, NODE_SEP, k)
+            }
+            Alias::Sha1(k) => format!("{}{}{}{}{}", t, NODE_SEP, AliasType::Sha1, NODE_SEP, k),
+            Alias::Sha256(k) => format!("{}{}{}{}{}", t, NODE_SEP, AliasType::Sha256, NODE_SEP, k),
+        },
+        // Derived data
+        Node::BonsaiFsnodeMapping(k) => format_with_id(t, k),
+        Node::Fsnode((p, k)) => format_with_path(t, k, p),
+        Node::ChangesetInfo(k) => format_with_id(t, k),
+        Node::BonsaiUnodeMapping(k) => format_with_id(t, k),
+        Node::UnodeManifest((p, k)) => format_with_path(t, k, p),
+        Node::UnodeFile((p, k)) => format_with_path(t, k, p),
+        Node::Blame(k) => format_with_id(t, &FileUnodeId::from(*k)),
+        Node::FastlogDir((p, k)) => format_with_path(t, k, p),
+        Node::FastlogFile((p, k)) => format_with_path(t, k, p),
+        Node::FastlogBatch(k) => format_with_id(t, k),
+        Node::BonsaiDeletedManifestMapping(k) => format_with_id(t, k),
+        Node::DeletedManifest((p, k)) => format_with_path(t, k, p),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -240,6 +335,71 @@ mod tests {
                     .get_type()
                 );
             }
+            NodeType::ChangesetInfo => assert_eq!(
+                node_type,
+                &parse_node(&format!("ChangesetInfo{}{}", NODE_SEP, SAMPLE_BLAKE2))?.get_type()
+            ),
+            NodeType::BonsaiUnodeMapping => assert_eq!(
+                node_type,
+                &parse_node(&format!("BonsaiUnodeMapping{}{}", NODE_SEP, SAMPLE_BLAKE2))?
+                    .get_type()
+            ),
+            NodeType::UnodeManifest => assert_eq!(
+                node_type,
+                &parse_node(&format!(
+                    "UnodeManifest{}{}{}{}",
+                    NODE_SEP, SAMPLE_BLAKE2, NODE_SEP, SAMPLE_PATH
+                ))?
+                .get_type()
+            ),
+            NodeType::UnodeFile => assert_eq!(
+                node_type,
+                &parse_node(&format!(
+                    "UnodeFile{}{}{}{}",
+                    NODE_SEP, SAMPLE_BLAKE2, NODE_SEP, SAMPLE_PATH
+                ))?
+                .get_type()
+            ),
+            NodeType::Blame => assert_eq!(
+                node_type,
+                &parse_node(&format!("Blame{}{}", NODE_SEP, SAMPLE_BLAKE2))?.get_type()
+            ),
+            NodeType::FastlogDir => assert_eq!(
+                node_type,
+                &parse_node(&format!(
+                    "FastlogDir{}{}{}{}",
+                    NODE_SEP, SAMPLE_BLAKE2, NODE_SEP, SAMPLE_PATH
+                ))?
+                .get_type()
+            ),
+            NodeType::FastlogFile => assert_eq!(
+                node_type,
+                &parse_node(&format!(
+                    "FastlogFile{}{}{}{}",
+                    NODE_SEP, SAMPLE_BLAKE2, NODE_SEP, SAMPLE_PATH
+                ))?
+                .get_type()
+            ),
+            NodeType::FastlogBatch => assert_eq!(
+                node_type,
+                &parse_node(&format!("FastlogBatch{}{}", NODE_SEP, SAMPLE_BLAKE2))?.get_type()
+            ),
+            NodeType::BonsaiDeletedManifestMapping => assert_eq!(
+                node_type,
+                &parse_node(&format!(
+                    "BonsaiDeletedManifestMapping{}{}",
+                    NODE_SEP, SAMPLE_BLAKE2
+                ))?
+                .get_type()
+            ),
+            NodeType::DeletedManifest => assert_eq!(
+                node_type,
+                &parse_node(&format!(
+                    "DeletedManifest{}{}{}{}",
+                    NODE_SEP, SAMPLE_BLAKE2, NODE_SEP, SAMPLE_PATH
+                ))?
+                .get_type()
+            ),
         };
         Ok(v)
     }
@@ -251,4 +411,28 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn format_node_round_trips() -> Result<(), Error> {
+        let keyed = |t: &str, k: &str| format!("{}{}{}", t, NODE_SEP, k);
+        let with_path =
+            |t: &str, k: &str, p: &str| format!("{}{}{}{}{}", t, NODE_SEP, k, NODE_SEP, p);
+        let samples = vec![
+            "Root".to_string(),
+            "PublishedBookmarks".to_string(),
+            keyed("Bookmark", "foo"),
+            keyed("BonsaiChangeset", SAMPLE_BLAKE2),
+            keyed("HgChangeset", SAMPLE_SHA1),
+            with_path("HgManifest", SAMPLE_SHA1, "/"),
+            with_path("HgFileNode", SAMPLE_SHA1, SAMPLE_PATH),
+            keyed("AliasContentMapping", &keyed("Sha256", SAMPLE_SHA256)),
+            with_path("UnodeFile", SAMPLE_BLAKE2, "foo:bar"),
+            keyed("Blame", SAMPLE_BLAKE2),
+        ];
+        for s in samples {
+            let node = parse_node(&s)?;
+            assert_eq!(node, parse_node(&format_node(&node))?, "for {}", s);
+        }
+        Ok(())
+    }
 }
@@ -7,7 +7,7 @@
 
 use crate::graph::{Node, NodeType};
 use crate::state::StepStats;
-use anyhow::Error;
+use anyhow::{format_err, Error};
 use cloned::cloned;
 use context::CoreContext;
 use derive_more::{Add, Div, Mul, Sub};
@@ -21,7 +21,9 @@ use slog::{info, Logger};
 use stats::prelude::*;
 use std::{
     collections::{HashMap, HashSet},
+    io::Write,
     ops::Add,
+    str::FromStr,
     sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
@@ -181,6 +183,54 @@ where
 }
 
 impl ProgressStateCountByType<StepStats, ProgressSummary> {
+    /// Save the counts by type, one per line, so a resumed walk can carry on from them
+    pub fn write_checkpoint<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        for (t, (walked, ss)) in &self.work_stats.stats_by_type {
+            writeln!(
+                w,
+                "progress {} {} {} {} {} {} {}",
+                t,
+                walked,
+                ss.error_count,
+                ss.num_direct,
+                ss.num_direct_new,
+                ss.num_expanded_new,
+                ss.visited_of_type
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Restore a line saved by write_checkpoint
+    pub fn restore_checkpoint_line(&mut self, line: &str) -> Result<(), Error> {
+        let parts: Vec<_> = line.split(' ').collect();
+        if parts.len() != 8 || parts[0] != "progress" {
+            return Err(format_err!("Unexpected checkpoint progress line {}", line));
+        }
+        let node_type = NodeType::from_str(parts[1])?;
+        let counts = parts[2..]
+            .iter()
+            .map(|c| usize::from_str(c))
+            .collect::<Result<Vec<_>, _>>()?;
+        let walked = counts[0] as u64;
+        let ss = StepStats {
+            error_count: counts[1],
+            num_direct: counts[2],
+            num_direct_new: counts[3],
+            num_expanded_new: counts[4],
+            visited_of_type: counts[5],
+        };
+        self.work_stats.total_progress += walked;
+        let entry = self
+            .work_stats
+            .stats_by_type
+            .entry(node_type)
+            .or_insert((0, StepStats::default()));
+        entry.0 += walked;
+        entry.1 = entry.1 + ss;
+        Ok(())
+    }
+
     fn report_stats(&self, node_type: &NodeType, summary: &ProgressSummary) {
         STATS::walk_progress_walked_by_type.add_value(
             summary.walked as i64,
@@ -379,6 +429,16 @@ where
     }
 }
 
+impl ProgressStateMutex<ProgressStateCountByType<StepStats, ProgressSummary>> {
+    pub fn write_checkpoint<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        self.inner.lock().unwrap().write_checkpoint(w)
+    }
+
+    pub fn restore_checkpoint_line(&self, line: &str) -> Result<(), Error> {
+        self.inner.lock().unwrap().restore_checkpoint_line(line)
+    }
+}
+
 impl<Inner> Clone for ProgressStateMutex<Inner> {
     fn clone(&self) -> Self {
         Self {
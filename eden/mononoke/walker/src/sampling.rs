@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use crate::checkpoint::Checkpointable;
 use crate::graph::{EdgeType, Node, NodeData, NodeType, WrappedPath};
 use crate::state::{StepStats, WalkState};
 use crate::walk::{OutgoingEdge, WalkVisitor};
@@ -50,6 +51,12 @@ impl<T> SamplingWalkVisitor<T> {
     }
 }
 
+impl<T> Checkpointable for SamplingWalkVisitor<T> {
+    fn walk_state(&self) -> &WalkState {
+        &self.inner
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct PathTrackingRoute {
     // The path we reached this by
@@ -82,6 +89,16 @@ fn filter_repo_path(node_type: NodeType, path: Option<&'_ WrappedPath>) -> Optio
         NodeType::AliasContentMapping => path,
         // Derived Data
         NodeType::Fsnode => path,
+        NodeType::ChangesetInfo => None,
+        NodeType::BonsaiUnodeMapping => None,
+        NodeType::UnodeManifest => path,
+        NodeType::UnodeFile => path,
+        NodeType::Blame => path,
+        NodeType::FastlogDir => path,
+        NodeType::FastlogFile => path,
+        NodeType::FastlogBatch => path,
+        NodeType::BonsaiDeletedManifestMapping => None,
+        NodeType::DeletedManifest => path,
     }
 }
 
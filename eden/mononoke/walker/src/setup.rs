@@ -6,15 +6,17 @@
  */
 
 use crate::blobstore;
+use crate::checkpoint::WalkCheckpoint;
 use crate::graph::{EdgeType, Node, NodeType};
 use crate::parse_node::parse_node;
 use crate::progress::{
     sort_by_string, ProgressStateCountByType, ProgressStateMutex, ProgressSummary,
 };
 use crate::state::StepStats;
-use crate::validate::{CheckType, REPO, WALK_TYPE};
+use crate::validate::{CheckType, DEFAULT_CHECK_TYPES, REPO, WALK_TYPE};
 use crate::walk::OutgoingEdge;
 
+use ::blobstore::Blobstore;
 use anyhow::{format_err, Error};
 use blobrepo::BlobRepo;
 use blobrepo_factory::open_blobrepo_given_datasources;
@@ -32,10 +34,15 @@ use metaconfig_types::{Redaction, ScrubAction};
 use samplingblob::SamplingHandler;
 use scuba_ext::{ScubaSampleBuilder, ScubaSampleBuilderExt};
 use slog::{info, warn, Logger};
-use std::{collections::HashSet, iter::FromIterator, str::FromStr, sync::Arc, time::Duration};
+use std::{
+    collections::HashSet, iter::FromIterator, path::PathBuf, str::FromStr, sync::Arc,
+    time::Duration,
+};
 
 pub struct RepoWalkDatasources {
     pub blobrepo: BlobRepo,
+    // The blobstore under the repo, without repo prefix or caching
+    pub blobstore: Arc<dyn Blobstore>,
     pub scuba_builder: ScubaSampleBuilder,
 }
 
@@ -47,6 +54,7 @@ pub struct RepoWalkParams {
     pub include_node_types: HashSet<NodeType>,
     pub include_edge_types: HashSet<EdgeType>,
     pub tail_secs: Option<u64>,
+    pub checkpoint: Option<WalkCheckpoint>,
     pub quiet: bool,
     pub progress_state: ProgressStateMutex<ProgressStateCountByType<StepStats, ProgressSummary>>,
     pub error_as_data_node_types: HashSet<NodeType>,
@@ -55,24 +63,29 @@ pub struct RepoWalkParams {
 
 pub const PROGRESS_SAMPLE_RATE: u64 = 1000;
 pub const PROGRESS_SAMPLE_DURATION_S: u64 = 5;
+const DEFAULT_CHECKPOINT_INTERVAL_S: u64 = 600;
 
 // Sub commands
 pub const SCRUB: &str = "scrub";
 pub const COMPRESSION_BENEFIT: &str = "compression-benefit";
 pub const VALIDATE: &str = "validate";
 pub const CORPUS: &str = "corpus";
+pub const GC: &str = "gc";
 
 // Subcommand args
 const QUIET_ARG: &str = "quiet";
 const ENABLE_REDACTION_ARG: &str = "enable-redaction";
 const SCHEDULED_MAX_ARG: &str = "scheduled-max";
 const TAIL_INTERVAL_ARG: &str = "tail-interval";
+const CHECKPOINT_PATH_ARG: &str = "checkpoint-path";
+const CHECKPOINT_INTERVAL_ARG: &str = "checkpoint-interval";
+const RESUME_ARG: &str = "resume";
 const ERROR_AS_DATA_NODE_TYPE_ARG: &str = "error-as-data-node-type";
 const ERROR_AS_DATA_EDGE_TYPE_ARG: &str = "error-as-data-edge-type";
-const EXCLUDE_NODE_TYPE_ARG: &str = "exclude-node-type";
-const INCLUDE_NODE_TYPE_ARG: &str = "include-node-type";
-const EXCLUDE_EDGE_TYPE_ARG: &str = "exclude-edge-type";
-const INCLUDE_EDGE_TYPE_ARG: &str = "include-edge-type";
+pub const EXCLUDE_NODE_TYPE_ARG: &str = "exclude-node-type";
+pub const INCLUDE_NODE_TYPE_ARG: &str = "include-node-type";
+pub const EXCLUDE_EDGE_TYPE_ARG: &str = "exclude-edge-type";
+pub const INCLUDE_EDGE_TYPE_ARG: &str = "include-edge-type";
 const BOOKMARK_ARG: &str = "bookmark";
 const WALK_ROOT_ARG: &str = "walk-root";
 const INNER_BLOBSTORE_ID_ARG: &str = "inner-blobstore-id";
@@ -90,6 +103,11 @@ pub const SAMPLE_PATH_REGEX_ARG: &str = "sample-path-regex";
 pub const EXCLUDE_SAMPLE_NODE_TYPE_ARG: &str = "exclude-sample-node-type";
 pub const INCLUDE_SAMPLE_NODE_TYPE_ARG: &str = "include-sample-node-type";
 pub const OUTPUT_DIR_ARG: &str = "output-dir";
+pub const OUTPUT_REPORT_ARG: &str = "output-report";
+pub const SWEEP_REPORT_ARG: &str = "sweep-report";
+pub const GRACE_PERIOD_HOURS_ARG: &str = "grace-period-hours";
+pub const RETENTION_DAYS_ARG: &str = "retention-days";
+pub const DRY_RUN_ARG: &str = "dry-run";
 const SCUBA_TABLE_ARG: &str = "scuba-table";
 const SCUBA_LOG_FILE_ARG: &str = "scuba-log-file";
 
@@ -99,6 +117,8 @@ const MARKER_VALUE_ARG: &str = "marker";
 const HG_VALUE_ARG: &str = "hg";
 const BONSAI_VALUE_ARG: &str = "bonsai";
 const CONTENT_META_VALUE_ARG: &str = "contentmeta";
+const DEFAULT_VALUE_ARG: &str = "default";
+const DERIVED_VALUE_ARG: &str = "derived";
 
 // Toplevel args - healer and populate healer have this one at top level
 // so keeping it there for consistency
@@ -122,6 +142,22 @@ pub const DEFAULT_INCLUDE_NODE_TYPES: &[NodeType] = &[
     NodeType::BonsaiFsnodeMapping,
 ];
 
+// All the derived data types the walker can step to
+const DERIVED_NODE_TYPES: &[NodeType] = &[
+    NodeType::BonsaiFsnodeMapping,
+    NodeType::Fsnode,
+    NodeType::ChangesetInfo,
+    NodeType::BonsaiUnodeMapping,
+    NodeType::UnodeManifest,
+    NodeType::UnodeFile,
+    NodeType::Blame,
+    NodeType::FastlogDir,
+    NodeType::FastlogFile,
+    NodeType::FastlogBatch,
+    NodeType::BonsaiDeletedManifestMapping,
+    NodeType::DeletedManifest,
+];
+
 // Goes as far into history as it can
 const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     // Bonsai
@@ -133,6 +169,9 @@ const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::PublishedBookmarksToBonsaiChangeset,
     EdgeType::PublishedBookmarksToBonsaiHgMapping,
     EdgeType::BonsaiChangesetToBonsaiFsnodeMapping,
+    EdgeType::BonsaiChangesetToChangesetInfo,
+    EdgeType::BonsaiChangesetToBonsaiUnodeMapping,
+    EdgeType::BonsaiChangesetToBonsaiDeletedManifestMapping,
     // Hg
     EdgeType::HgBonsaiMappingToBonsaiChangeset,
     EdgeType::HgChangesetToHgParent,
@@ -154,6 +193,24 @@ const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     // Derived data
     EdgeType::BonsaiToRootFsnode,
     EdgeType::FsnodeToChildFsnode,
+    EdgeType::ChangesetInfoToChangesetInfoParent,
+    EdgeType::BonsaiToRootUnodeManifest,
+    EdgeType::UnodeManifestToUnodeManifestParent,
+    EdgeType::UnodeManifestToUnodeFile,
+    EdgeType::UnodeManifestToChildUnodeManifest,
+    EdgeType::UnodeManifestToLinkedBonsaiChangeset,
+    EdgeType::UnodeManifestToFastlogDir,
+    EdgeType::UnodeFileToUnodeFileParent,
+    EdgeType::UnodeFileToFileContent,
+    EdgeType::UnodeFileToLinkedBonsaiChangeset,
+    EdgeType::UnodeFileToBlame,
+    EdgeType::UnodeFileToFastlogFile,
+    EdgeType::FastlogDirToPreviousBatch,
+    EdgeType::FastlogFileToPreviousBatch,
+    EdgeType::FastlogBatchToPreviousBatch,
+    EdgeType::BonsaiToRootDeletedManifest,
+    EdgeType::DeletedManifestToDeletedManifestChild,
+    EdgeType::DeletedManifestToLinkedBonsaiChangeset,
 ];
 
 // Does not recurse into history, edges to parents excluded
@@ -166,6 +223,9 @@ const SHALLOW_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::PublishedBookmarksToBonsaiChangeset,
     EdgeType::PublishedBookmarksToBonsaiHgMapping,
     EdgeType::BonsaiChangesetToBonsaiFsnodeMapping,
+    EdgeType::BonsaiChangesetToChangesetInfo,
+    EdgeType::BonsaiChangesetToBonsaiUnodeMapping,
+    EdgeType::BonsaiChangesetToBonsaiDeletedManifestMapping,
     // Hg
     EdgeType::HgBonsaiMappingToBonsaiChangeset,
     EdgeType::HgChangesetToHgManifest,
@@ -182,6 +242,18 @@ const SHALLOW_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     // Derived data
     EdgeType::BonsaiToRootFsnode,
     EdgeType::FsnodeToChildFsnode,
+    EdgeType::BonsaiToRootUnodeManifest,
+    EdgeType::UnodeManifestToUnodeFile,
+    EdgeType::UnodeManifestToChildUnodeManifest,
+    EdgeType::UnodeManifestToFastlogDir,
+    EdgeType::UnodeFileToFileContent,
+    EdgeType::UnodeFileToBlame,
+    EdgeType::UnodeFileToFastlogFile,
+    EdgeType::FastlogDirToPreviousBatch,
+    EdgeType::FastlogFileToPreviousBatch,
+    EdgeType::FastlogBatchToPreviousBatch,
+    EdgeType::BonsaiToRootDeletedManifest,
+    EdgeType::DeletedManifestToDeletedManifestChild,
 ];
 
 // Types that can result in loading hg data.  Useful for excludes.
@@ -225,17 +297,18 @@ const MARKER_EDGE_TYPES: &[EdgeType] = &[EdgeType::BonsaiChangesetToBonsaiPhaseM
 
 lazy_static! {
     static ref INCLUDE_CHECK_TYPE_HELP: String = format!(
-        "Check types to include, defaults to: {:?}",
+        "Check types to include, defaults to: {:?}. All check types: {:?}",
+        DEFAULT_CHECK_TYPES,
         CheckType::ALL_VARIANTS,
     );
 
     static ref INCLUDE_NODE_TYPE_HELP: String = format!(
-        "Graph node types we want to step to in the walk. Defaults to core Mononoke and Hg types: {:?}",
+        "Graph node types we want to step to in the walk. Can pass pre-configured sets via default, derived, as well as individual types. Defaults to core Mononoke and Hg types: {:?}",
         DEFAULT_INCLUDE_NODE_TYPES
     );
 
     static ref EXCLUDE_NODE_TYPE_HELP: String = format!(
-        "Graph node types to exclude from walk. They are removed from the include node types. Specific any of: {:?}",
+        "Graph node types to exclude from walk. They are removed from the include node types. Can pass pre-configured sets via default, derived, as well as any of: {:?}",
         NodeType::ALL_VARIANTS,
     );
 
@@ -331,6 +404,45 @@ pub fn setup_toplevel_app<'a, 'b>(app_name: &str) -> App<'a, 'b> {
     );
     let corpus = add_sampling_args(corpus);
 
+    let gc = setup_subcommand_args(
+        SubCommand::with_name(GC).about("mark and sweep garbage collection of blobs not reachable from the walk roots"),
+    )
+    .arg(
+        Arg::with_name(OUTPUT_REPORT_ARG)
+            .long(OUTPUT_REPORT_ARG)
+            .takes_value(true)
+            .required(true)
+            .help("File to write the unreachable keys found by this run to, one per line."),
+    )
+    .arg(
+        Arg::with_name(SWEEP_REPORT_ARG)
+            .long(SWEEP_REPORT_ARG)
+            .takes_value(true)
+            .required(false)
+            .help("Report written by an earlier run. Keys listed in it that are still unreachable are deleted. Without this nothing is deleted."),
+    )
+    .arg(
+        Arg::with_name(GRACE_PERIOD_HOURS_ARG)
+            .long(GRACE_PERIOD_HOURS_ARG)
+            .takes_value(true)
+            .required(false)
+            .help("Minimum age of the sweep report before its keys can be deleted, so that in-flight writes have time to become reachable. Default 72."),
+    )
+    .arg(
+        Arg::with_name(RETENTION_DAYS_ARG)
+            .long(RETENTION_DAYS_ARG)
+            .takes_value(true)
+            .required(false)
+            .help("Changesets committed or stored within this many days are kept even if no bookmark reaches them, and no key stored within it is deleted. Default 30."),
+    )
+    .arg(
+        Arg::with_name(DRY_RUN_ARG)
+            .long(DRY_RUN_ARG)
+            .takes_value(false)
+            .required(false)
+            .help("Log what would be deleted from the sweep report, but do not delete anything."),
+    );
+
     let validate = setup_subcommand_args(
         SubCommand::with_name(VALIDATE).about("estimate compression benefit"),
     )
@@ -367,6 +479,7 @@ pub fn setup_toplevel_app<'a, 'b>(app_name: &str) -> App<'a, 'b> {
         )
         .subcommand(compression_benefit)
         .subcommand(corpus)
+        .subcommand(gc)
         .subcommand(scrub_objects)
         .subcommand(validate)
 }
@@ -404,6 +517,29 @@ fn setup_subcommand_args<'a, 'b>(subcmd: App<'a, 'b>) -> App<'a, 'b> {
                 .required(false)
                 .help("Tail by polling the entry points at interval of TAIL seconds"),
         )
+        .arg(
+            Arg::with_name(CHECKPOINT_PATH_ARG)
+                .long(CHECKPOINT_PATH_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Save the walk state to this file at intervals and when the walk completes, so that it can be resumed."),
+        )
+        .arg(
+            Arg::with_name(CHECKPOINT_INTERVAL_ARG)
+                .long(CHECKPOINT_INTERVAL_ARG)
+                .takes_value(true)
+                .required(false)
+                .requires(CHECKPOINT_PATH_ARG)
+                .help("Minimum interval between checkpoints in seconds. Default 600."),
+        )
+        .arg(
+            Arg::with_name(RESUME_ARG)
+                .long(RESUME_ARG)
+                .takes_value(false)
+                .required(false)
+                .requires(CHECKPOINT_PATH_ARG)
+                .help("Resume from the checkpoint if there is one. An interrupted walk carries on from where it got to, a completed one only walks what has become reachable since."),
+        )
         .arg(
             Arg::with_name(PROGRESS_INTERVAL_ARG)
                 .long(PROGRESS_INTERVAL_ARG)
@@ -543,18 +679,40 @@ pub fn parse_node_types(
     exclude_arg_name: &str,
     default: &[NodeType],
 ) -> Result<HashSet<NodeType>, Error> {
-    let mut include_node_types: HashSet<NodeType> = match sub_m.values_of(include_arg_name) {
-        None => Ok(HashSet::from_iter(default.iter().cloned())),
-        Some(values) => values.map(NodeType::from_str).collect(),
-    }?;
-    let exclude_node_types: HashSet<NodeType> = match sub_m.values_of(exclude_arg_name) {
-        None => Ok(HashSet::new()),
-        Some(values) => values.map(NodeType::from_str).collect(),
-    }?;
+    let mut include_node_types = parse_node_values(sub_m.values_of(include_arg_name), default)?;
+    let exclude_node_types = parse_node_values(sub_m.values_of(exclude_arg_name), &[])?;
     include_node_types.retain(|x| !exclude_node_types.contains(x));
     Ok(include_node_types)
 }
 
+// parse the pre-defined groups we have for default and derived node types
+fn parse_node_value(arg: &str) -> Result<HashSet<NodeType>, Error> {
+    match arg {
+        DEFAULT_VALUE_ARG => Ok(HashSet::from_iter(
+            DEFAULT_INCLUDE_NODE_TYPES.iter().cloned(),
+        )),
+        DERIVED_VALUE_ARG => Ok(HashSet::from_iter(DERIVED_NODE_TYPES.iter().cloned())),
+        _ => NodeType::from_str(arg).map(|n| {
+            let mut h = HashSet::new();
+            h.insert(n);
+            h
+        }),
+    }
+}
+
+fn parse_node_values(
+    values: Option<Values>,
+    default: &[NodeType],
+) -> Result<HashSet<NodeType>, Error> {
+    match values {
+        None => Ok(HashSet::from_iter(default.iter().cloned())),
+        Some(values) => values
+            .map(parse_node_value)
+            .collect::<Result<Vec<HashSet<NodeType>>, Error>>()
+            .map(|m| m.into_iter().flatten().collect::<HashSet<NodeType>>()),
+    }
+}
+
 // parse the pre-defined groups we have for deep, shallow, hg, bonsai etc.
 fn parse_edge_value(arg: &str) -> Result<HashSet<EdgeType>, Error> {
     match arg {
@@ -648,6 +806,7 @@ pub fn setup_common<'a>(
         let scheduled_max = args::get_usize_opt(&sub_m, SCHEDULED_MAX_ARG).unwrap_or(4096) as usize;
         let inner_blobstore_id = args::get_u64_opt(&sub_m, INNER_BLOBSTORE_ID_ARG);
         let tail_secs = args::get_u64_opt(&sub_m, TAIL_INTERVAL_ARG);
+        let checkpoint_interval_secs = args::get_u64_opt(&sub_m, CHECKPOINT_INTERVAL_ARG);
         let progress_interval_secs = args::get_u64_opt(&sub_m, PROGRESS_INTERVAL_ARG);
         let progress_sample_rate = args::get_u64_opt(&sub_m, PROGRESS_SAMPLE_RATE_ARG);
 
@@ -825,7 +984,7 @@ pub fn setup_common<'a>(
 
         let blobrepo = open_blobrepo_given_datasources(
             fb,
-            blobstore,
+            blobstore.clone(),
             sql_factory,
             config.repoid,
             caching,
@@ -839,6 +998,18 @@ pub fn setup_common<'a>(
         )
         .await?;
 
+        let checkpoint = sub_m.value_of(CHECKPOINT_PATH_ARG).map(|path| {
+            WalkCheckpoint::new(
+                PathBuf::from(path),
+                Duration::from_secs(
+                    checkpoint_interval_secs.unwrap_or(DEFAULT_CHECKPOINT_INTERVAL_S),
+                ),
+                sub_m.is_present(RESUME_ARG),
+                walk_stats_key,
+                &repo_name,
+            )
+        });
+
         let mut progress_node_types = include_node_types.clone();
         for e in &walk_roots {
             progress_node_types.insert(e.target.get_type());
@@ -857,6 +1028,7 @@ pub fn setup_common<'a>(
         Ok((
             RepoWalkDatasources {
                 blobrepo,
+                blobstore,
                 scuba_builder,
             },
             RepoWalkParams {
@@ -866,6 +1038,7 @@ pub fn setup_common<'a>(
                 include_node_types,
                 include_edge_types,
                 tail_secs,
+                checkpoint,
                 quiet,
                 progress_state,
                 error_as_data_node_types,
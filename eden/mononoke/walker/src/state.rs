@@ -6,19 +6,30 @@
  */
 
 use crate::graph::{EdgeType, Node, NodeData, NodeType, WrappedPath};
+use crate::parse_node::{format_node, parse_node};
 use crate::walk::{expand_checked_nodes, OutgoingEdge, WalkVisitor};
+use anyhow::{format_err, Error};
 use array_init::array_init;
 use context::CoreContext;
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
 use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId};
-use mononoke_types::{ChangesetId, ContentId, FsnodeId, MPathHash};
+use mononoke_types::{
+    ChangesetId, ContentId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId, MPathHash,
+    ManifestUnodeId,
+};
 use phases::Phase;
 use std::{
     cmp,
     collections::HashSet,
+    fmt::Display,
     hash::Hash,
+    io::Write,
     ops::Add,
-    sync::atomic::{AtomicUsize, Ordering},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        RwLock,
+    },
 };
 
 #[derive(Clone, Copy, Default, Debug, PartialEq)]
@@ -59,7 +70,18 @@ pub struct WalkState {
     visited_hg_filenode: DashMap<(Option<MPathHash>, HgFileNodeId), ()>,
     visited_hg_manifest: DashMap<(Option<MPathHash>, HgManifestId), ()>,
     visited_fsnode: DashMap<(Option<MPathHash>, FsnodeId), ()>,
+    visited_changeset_info: DashMap<ChangesetId, ()>,
+    visited_unode_manifest: DashMap<(Option<MPathHash>, ManifestUnodeId), ()>,
+    visited_unode_file: DashMap<(Option<MPathHash>, FileUnodeId), ()>,
+    visited_fastlog_batch: DashMap<FastlogBatchId, ()>,
+    visited_deleted_manifest: DashMap<(Option<MPathHash>, DeletedManifestId), ()>,
     visit_count: [AtomicUsize; NodeType::MAX_ORDINAL + 1],
+    // Only maintained once checkpointing is enabled.
+    checkpointing: AtomicBool,
+    // Visits hold this for read, so that a checkpoint can see a consistent state.
+    checkpoint_lock: RwLock<()>,
+    // Nodes queued to be visited, with the number of times they are queued.
+    pending: DashMap<Node, usize>,
 }
 
 /// If the state did not have this value present, true is returned.
@@ -75,6 +97,78 @@ where
     !visited_with_path.insert((mpathhash_opt, *id), ()).is_some()
 }
 
+fn forget<K: Eq + Hash>(visited: &DashMap<K, ()>, k: &K) {
+    visited.remove(k);
+}
+
+fn forget_with_path<K>(
+    visited_with_path: &DashMap<(Option<MPathHash>, K), ()>,
+    k: &(WrappedPath, K),
+) where
+    K: Eq + Hash + Copy,
+{
+    let (path, id) = k;
+    visited_with_path.remove(&(path.get_path_hash().cloned(), *id));
+}
+
+fn write_visited<K, W>(
+    w: &mut W,
+    node_type: NodeType,
+    visited: &DashMap<K, ()>,
+) -> Result<(), Error>
+where
+    K: Eq + Hash + Display,
+    W: Write,
+{
+    for entry in visited.iter() {
+        writeln!(w, "visited {} {}", node_type, entry.key())?;
+    }
+    Ok(())
+}
+
+fn write_visited_with_path<K, W>(
+    w: &mut W,
+    node_type: NodeType,
+    visited_with_path: &DashMap<(Option<MPathHash>, K), ()>,
+) -> Result<(), Error>
+where
+    K: Eq + Hash + Display,
+    W: Write,
+{
+    for entry in visited_with_path.iter() {
+        let (mpathhash_opt, id) = entry.key();
+        match mpathhash_opt {
+            Some(mpathhash) => writeln!(w, "visited {} {} {}", node_type, id, mpathhash.to_hex())?,
+            None => writeln!(w, "visited {} {}", node_type, id)?,
+        }
+    }
+    Ok(())
+}
+
+fn restore_visited<K>(visited: &DashMap<K, ()>, key: &str) -> Result<(), Error>
+where
+    K: Eq + Hash + FromStr,
+    Error: From<K::Err>,
+{
+    visited.insert(K::from_str(key)?, ());
+    Ok(())
+}
+
+fn restore_visited_with_path<K>(
+    visited_with_path: &DashMap<(Option<MPathHash>, K), ()>,
+    key: &str,
+) -> Result<(), Error>
+where
+    K: Eq + Hash + FromStr,
+    Error: From<K::Err>,
+{
+    let mut parts = key.splitn(2, ' ');
+    let id = K::from_str(parts.next().unwrap_or_default())?;
+    let mpathhash_opt = parts.next().map(MPathHash::from_str).transpose()?;
+    visited_with_path.insert((mpathhash_opt, id), ());
+    Ok(())
+}
+
 impl WalkState {
     pub fn new(
         include_node_types: HashSet<NodeType>,
@@ -93,7 +187,143 @@ impl WalkState {
             visited_hg_filenode: DashMap::new(),
             visited_hg_manifest: DashMap::new(),
             visited_fsnode: DashMap::new(),
+            visited_changeset_info: DashMap::new(),
+            visited_unode_manifest: DashMap::new(),
+            visited_unode_file: DashMap::new(),
+            visited_fastlog_batch: DashMap::new(),
+            visited_deleted_manifest: DashMap::new(),
             visit_count: array_init(|_i| AtomicUsize::new(0)),
+            checkpointing: AtomicBool::new(false),
+            checkpoint_lock: RwLock::new(()),
+            pending: DashMap::new(),
+        }
+    }
+
+    /// Start tracking the queued nodes, so that write_checkpoint can save them.
+    pub fn enable_checkpoints(&self) {
+        self.checkpointing.store(true, Ordering::Release);
+    }
+
+    /// Save the visited nodes, visit counts and queued nodes, one per line. Visits are paused
+    /// while this runs.
+    pub fn write_checkpoint<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        let _guard = self.checkpoint_lock.write().expect("lock poisoned");
+        write_visited(w, NodeType::BonsaiChangeset, &self.visited_bcs)?;
+        write_visited(w, NodeType::BonsaiHgMapping, &self.visited_bcs_mapping)?;
+        write_visited(w, NodeType::BonsaiPhaseMapping, &self.visited_bcs_phase)?;
+        write_visited(w, NodeType::FileContent, &self.visited_file)?;
+        write_visited(w, NodeType::HgChangeset, &self.visited_hg_cs)?;
+        write_visited(w, NodeType::HgBonsaiMapping, &self.visited_hg_cs_mapping)?;
+        write_visited(w, NodeType::HgFileEnvelope, &self.visited_hg_file_envelope)?;
+        write_visited_with_path(w, NodeType::HgFileNode, &self.visited_hg_filenode)?;
+        write_visited_with_path(w, NodeType::HgManifest, &self.visited_hg_manifest)?;
+        write_visited_with_path(w, NodeType::Fsnode, &self.visited_fsnode)?;
+        write_visited(w, NodeType::ChangesetInfo, &self.visited_changeset_info)?;
+        write_visited_with_path(w, NodeType::UnodeManifest, &self.visited_unode_manifest)?;
+        write_visited_with_path(w, NodeType::UnodeFile, &self.visited_unode_file)?;
+        write_visited(w, NodeType::FastlogBatch, &self.visited_fastlog_batch)?;
+        write_visited_with_path(w, NodeType::DeletedManifest, &self.visited_deleted_manifest)?;
+        for t in NodeType::ALL_VARIANTS {
+            writeln!(w, "visit_count {} {}", t, self.get_visit_count(t))?;
+        }
+        for entry in self.pending.iter() {
+            writeln!(w, "pending {}", format_node(entry.key()))?;
+        }
+        Ok(())
+    }
+
+    /// Restore one line saved by write_checkpoint. Returns the node if it was queued at the
+    /// time of the checkpoint, and so needs to be walked again.
+    pub fn restore_checkpoint_line(&self, line: &str) -> Result<Option<Node>, Error> {
+        let mut parts = line.splitn(2, ' ');
+        let kind = parts.next().unwrap_or_default();
+        let rest = parts.next().unwrap_or_default();
+        match kind {
+            "visited" => {
+                let mut parts = rest.splitn(2, ' ');
+                let node_type = NodeType::from_str(parts.next().unwrap_or_default())?;
+                let key = parts.next().unwrap_or_default();
+                match node_type {
+                    NodeType::BonsaiChangeset => restore_visited(&self.visited_bcs, key),
+                    NodeType::BonsaiHgMapping => restore_visited(&self.visited_bcs_mapping, key),
+                    NodeType::BonsaiPhaseMapping => restore_visited(&self.visited_bcs_phase, key),
+                    NodeType::FileContent => restore_visited(&self.visited_file, key),
+                    NodeType::HgChangeset => restore_visited(&self.visited_hg_cs, key),
+                    NodeType::HgBonsaiMapping => restore_visited(&self.visited_hg_cs_mapping, key),
+                    NodeType::HgFileEnvelope => {
+                        restore_visited(&self.visited_hg_file_envelope, key)
+                    }
+                    NodeType::HgFileNode => {
+                        restore_visited_with_path(&self.visited_hg_filenode, key)
+                    }
+                    NodeType::HgManifest => {
+                        restore_visited_with_path(&self.visited_hg_manifest, key)
+                    }
+                    NodeType::Fsnode => restore_visited_with_path(&self.visited_fsnode, key),
+                    NodeType::ChangesetInfo => restore_visited(&self.visited_changeset_info, key),
+                    NodeType::UnodeManifest => {
+                        restore_visited_with_path(&self.visited_unode_manifest, key)
+                    }
+                    NodeType::UnodeFile => restore_visited_with_path(&self.visited_unode_file, key),
+                    NodeType::FastlogBatch => restore_visited(&self.visited_fastlog_batch, key),
+                    NodeType::DeletedManifest => {
+                        restore_visited_with_path(&self.visited_deleted_manifest, key)
+                    }
+                    _ => Err(format_err!("Visits to {} are not tracked", node_type)),
+                }?;
+                Ok(None)
+            }
+            "visit_count" => {
+                let mut parts = rest.splitn(2, ' ');
+                let node_type = NodeType::from_str(parts.next().unwrap_or_default())?;
+                let count = usize::from_str(parts.next().unwrap_or_default())?;
+                self.visit_count[node_type as usize].fetch_add(count, Ordering::Release);
+                Ok(None)
+            }
+            "pending" => {
+                let node = parse_node(rest)?;
+                // So that it is queued again when walked from
+                self.forget_visit(&node);
+                Ok(Some(node))
+            }
+            _ => Err(format_err!("Unexpected checkpoint line {}", line)),
+        }
+    }
+
+    fn forget_visit(&self, node: &Node) {
+        match node {
+            Node::BonsaiChangeset(bcs_id) => forget(&self.visited_bcs, bcs_id),
+            Node::BonsaiHgMapping(bcs_id) => forget(&self.visited_bcs_mapping, bcs_id),
+            Node::BonsaiPhaseMapping(bcs_id) => forget(&self.visited_bcs_phase, bcs_id),
+            Node::HgBonsaiMapping(hg_cs_id) => forget(&self.visited_hg_cs_mapping, hg_cs_id),
+            Node::HgChangeset(hg_cs_id) => forget(&self.visited_hg_cs, hg_cs_id),
+            Node::HgManifest(k) => forget_with_path(&self.visited_hg_manifest, k),
+            Node::HgFileNode(k) => forget_with_path(&self.visited_hg_filenode, k),
+            Node::HgFileEnvelope(id) => forget(&self.visited_hg_file_envelope, id),
+            Node::FileContent(content_id) => forget(&self.visited_file, content_id),
+            Node::Fsnode(k) => forget_with_path(&self.visited_fsnode, k),
+            Node::ChangesetInfo(bcs_id) => forget(&self.visited_changeset_info, bcs_id),
+            Node::UnodeManifest(k) => forget_with_path(&self.visited_unode_manifest, k),
+            Node::UnodeFile(k) => forget_with_path(&self.visited_unode_file, k),
+            Node::FastlogBatch(id) => forget(&self.visited_fastlog_batch, id),
+            Node::DeletedManifest(k) => forget_with_path(&self.visited_deleted_manifest, k),
+            _ => (),
+        }
+    }
+
+    fn record_pending<'a, I>(&self, resolved: &Node, queued: I)
+    where
+        I: IntoIterator<Item = &'a Node>,
+    {
+        // The resolved node goes first, as walk roots queue themselves
+        if let Entry::Occupied(mut entry) = self.pending.entry(resolved.clone()) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+        for node in queued {
+            *self.pending.entry(node.clone()).or_insert(0) += 1;
         }
     }
 
@@ -123,6 +353,13 @@ impl WalkState {
             Node::HgFileEnvelope(id) => self.visited_hg_file_envelope.insert(*id, ()).is_none(),
             Node::FileContent(content_id) => self.visited_file.insert(*content_id, ()).is_none(),
             Node::Fsnode(k) => record_with_path(&self.visited_fsnode, k),
+            Node::ChangesetInfo(bcs_id) => {
+                self.visited_changeset_info.insert(*bcs_id, ()).is_none()
+            }
+            Node::UnodeManifest(k) => record_with_path(&self.visited_unode_manifest, k),
+            Node::UnodeFile(k) => record_with_path(&self.visited_unode_file, k),
+            Node::FastlogBatch(id) => self.visited_fastlog_batch.insert(*id, ()).is_none(),
+            Node::DeletedManifest(k) => record_with_path(&self.visited_deleted_manifest, k),
             _ => true,
         }
     }
@@ -179,6 +416,12 @@ impl WalkVisitor<(Node, Option<NodeData>, Option<StepStats>), ()> for WalkState
         (),
         Vec<OutgoingEdge>,
     ) {
+        let checkpoint_guard = if self.checkpointing.load(Ordering::Acquire) {
+            Some(self.checkpoint_lock.read().expect("lock poisoned"))
+        } else {
+            None
+        };
+
         // Filter things we don't want to enter the WalkVisitor at all.
         outgoing.retain(|e| self.retain_edge(e));
         let num_direct = outgoing.len();
@@ -191,6 +434,10 @@ impl WalkVisitor<(Node, Option<NodeData>, Option<StepStats>), ()> for WalkState
         outgoing.retain(|e| self.retain_edge(e));
 
         self.record_resolved_visit(&resolved, node_data.as_ref());
+        if checkpoint_guard.is_some() {
+            self.record_pending(&resolved.target, outgoing.iter().map(|e| &e.target));
+        }
+        drop(checkpoint_guard);
 
         // Stats
         let num_expanded_new = outgoing.len();
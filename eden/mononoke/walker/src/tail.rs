@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use crate::checkpoint::Checkpointable;
 use crate::setup::{RepoWalkDatasources, RepoWalkParams};
 use crate::walk::{walk_exact, WalkVisitor};
 
@@ -12,7 +13,10 @@ use anyhow::Error;
 use cloned::cloned;
 use context::CoreContext;
 use fbinit::FacebookInit;
-use futures::{future::Future, stream::BoxStream};
+use futures::{
+    future::{self, Either, Future, FutureExt},
+    stream::BoxStream,
+};
 use scuba_ext::ScubaSampleBuilder;
 use slog::Logger;
 use tokio::time::{Duration, Instant};
@@ -36,15 +40,31 @@ where
     RunFac: 'static + Clone + Send + Sync + FnOnce(RepoWalkRun) -> SinkFac,
     SinkFac: 'static + FnOnce(BoxStream<'static, Result<VOut, Error>>) -> SinkOut + Clone + Send,
     SinkOut: Future<Output = Result<(), Error>> + 'static + Send,
-    WS: 'static + Clone + WalkVisitor<VOut, Route> + Send,
+    WS: 'static + Clone + WalkVisitor<VOut, Route> + Checkpointable + Send,
     VOut: 'static + Send,
     Route: 'static + Send + Clone,
 {
     let scuba_builder = datasources.scuba_builder;
     let repo = datasources.blobrepo;
     let tail_secs = walk_params.tail_secs.clone();
+    let checkpoint = walk_params.checkpoint.clone();
+
+    let mut walk_roots = walk_params.walk_roots.clone();
+    if let Some(checkpoint) = &checkpoint {
+        walk_state.walk_state().enable_checkpoints();
+        let queued = checkpoint.load(
+            &logger,
+            walk_state.walk_state(),
+            &walk_params.progress_state,
+        )?;
+        // Only walk on from where the interrupted run got to
+        if !queued.is_empty() {
+            walk_roots = queued;
+        }
+    }
+
     loop {
-        cloned!(make_run, repo, mut scuba_builder, walk_state,);
+        cloned!(make_run, repo, mut scuba_builder);
 
         let ctx = CoreContext::new_with_logger(fb, logger.clone());
         scuba_builder.add("session", ctx.session().session_id().to_string());
@@ -57,8 +77,8 @@ where
             ctx,
             repo,
             walk_params.enable_derive,
-            walk_params.walk_roots.clone(),
-            walk_state,
+            walk_roots,
+            walk_state.clone(),
             walk_params.scheduled_max,
             walk_params.error_as_data_node_types.clone(),
             walk_params.error_as_data_edge_types.clone(),
@@ -67,7 +87,31 @@ where
         );
 
         let make_sink = make_run(walk_run);
-        make_sink(walk_output).await?;
+        let walk = make_sink(walk_output);
+        match &checkpoint {
+            Some(checkpoint) => {
+                let save_periodically = checkpoint.save_periodically(
+                    logger.clone(),
+                    walk_state.clone(),
+                    walk_params.progress_state.clone(),
+                );
+                match future::select(walk.boxed(), save_periodically.boxed()).await {
+                    Either::Left((res, _)) => res?,
+                    Either::Right((res, _)) => res?,
+                }
+                // Nothing is queued once the walk completes, so resuming from this only walks
+                // what has become reachable since.
+                checkpoint
+                    .save_now(
+                        &logger,
+                        walk_state.clone(),
+                        walk_params.progress_state.clone(),
+                    )
+                    .await?;
+            }
+            None => walk.await?,
+        }
+        walk_roots = walk_params.walk_roots.clone();
 
         match tail_secs {
             Some(interval) => {
@@ -8,10 +8,12 @@
 // This module allows the implementation of validating checks over the mononoke graph
 // Currently checks are added by
 //  1. Add a CheckType variant
-//  2. Add CheckType::node_type() and CheckType::enum_type() cases for the new variant
-//  3. Add a new validation method
+//  2. Add CheckType::node_type() and CheckType::stats_key() cases for the new variant
+//  3. Add a new validation method. Checks that need only the node's data and outgoing edges
+//     return a CheckResult, checks that need to look up more data return a CheckFuture.
 //  4. Add the method to the match/case in ValidatingVisitor::visit()
 
+use crate::checkpoint::Checkpointable;
 use crate::graph::{EdgeType, Node, NodeData, NodeType};
 use crate::progress::{
     progress_stream, report_state, sort_by_string, ProgressRecorder, ProgressRecorderUnprotected,
@@ -26,15 +28,22 @@ use crate::tail::{walk_exact_tail, RepoWalkRun};
 use crate::walk::{OutgoingEdge, WalkVisitor};
 
 use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
 use clap::ArgMatches;
 use cloned::cloned;
 use cmdlib::args;
 use context::CoreContext;
 use derive_more::AddAssign;
+use derived_data::{BonsaiDerived, BonsaiDerivedMapping};
 use fbinit::FacebookInit;
-use futures::{future::TryFutureExt, stream::TryStreamExt};
-use itertools::Itertools;
-use mononoke_types::MPath;
+use filestore::FetchKey;
+use futures::{
+    compat::Future01CompatExt,
+    future::{self, BoxFuture, FutureExt, TryFutureExt},
+    stream::TryStreamExt,
+};
+use mononoke_types::{fsnode::FsnodeEntry, MPath};
 use phases::Phase;
 use scuba_ext::ScubaSampleBuilder;
 use slog::{info, warn, Logger};
@@ -50,6 +59,7 @@ use std::{
     sync::Arc,
     time::{Duration, Instant},
 };
+use unodes::RootUnodeManifestId;
 
 const STATS_PREFIX: &str = "mononoke.walker.validate";
 pub const NODES: &'static str = "nodes";
@@ -63,6 +73,8 @@ pub const NODE_PATH: &'static str = "node_path";
 pub const EDGE_TYPE: &'static str = "edge_type";
 pub const CHECK_TYPE: &'static str = "check_type";
 pub const CHECK_FAIL: &'static str = "check_fail";
+pub const CHECK_EXPECTED: &'static str = "check_expected";
+pub const CHECK_ACTUAL: &'static str = "check_actual";
 pub const WALK_TYPE: &'static str = "walk_type";
 pub const REPO: &'static str = "repo";
 const SRC_NODE_KEY: &'static str = "src_node_key";
@@ -90,6 +102,12 @@ define_type_enum! {
 enum CheckType {
     BonsaiChangesetPhaseIsPublic,
     HgLinkNodePopulated,
+    BonsaiHgMappingRoundTrips,
+    FsnodeSummaryMatchesEntries,
+    ContentMetadataMatchesContent,
+    UnodeParentsMatchBonsai,
+    HgFileSizeMatchesMetadata,
+    BookmarkTargetExists,
 }
 }
 
@@ -98,12 +116,24 @@ impl CheckType {
         match self {
             CheckType::BonsaiChangesetPhaseIsPublic => "bonsai_phase_is_public",
             CheckType::HgLinkNodePopulated => "hg_link_node_populated",
+            CheckType::BonsaiHgMappingRoundTrips => "bonsai_hg_mapping_round_trips",
+            CheckType::FsnodeSummaryMatchesEntries => "fsnode_summary_matches_entries",
+            CheckType::ContentMetadataMatchesContent => "content_metadata_matches_content",
+            CheckType::UnodeParentsMatchBonsai => "unode_parents_match_bonsai",
+            CheckType::HgFileSizeMatchesMetadata => "hg_file_size_matches_metadata",
+            CheckType::BookmarkTargetExists => "bookmark_target_exists",
         }
     }
     pub fn node_type(&self) -> NodeType {
         match self {
             CheckType::BonsaiChangesetPhaseIsPublic => NodeType::BonsaiPhaseMapping,
             CheckType::HgLinkNodePopulated => NodeType::HgFileNode,
+            CheckType::BonsaiHgMappingRoundTrips => NodeType::BonsaiHgMapping,
+            CheckType::FsnodeSummaryMatchesEntries => NodeType::Fsnode,
+            CheckType::ContentMetadataMatchesContent => NodeType::FileContentMetadata,
+            CheckType::UnodeParentsMatchBonsai => NodeType::BonsaiUnodeMapping,
+            CheckType::HgFileSizeMatchesMetadata => NodeType::HgFileEnvelope,
+            CheckType::BookmarkTargetExists => NodeType::Bookmark,
         }
     }
 }
@@ -114,20 +144,62 @@ impl fmt::Display for CheckType {
     }
 }
 
+// What a failed check expected to find, and what it found instead
+#[derive(Debug)]
+struct CheckFailure {
+    expected: String,
+    actual: String,
+}
+
+impl CheckFailure {
+    fn new(expected: String, actual: String) -> Self {
+        Self { expected, actual }
+    }
+}
+
+type CheckResult = Result<(), CheckFailure>;
+
+// For checks that need to load more than the node being checked
+type CheckFuture = BoxFuture<'static, Result<CheckResult, Error>>;
+
+fn check_eq<T: PartialEq + fmt::Debug>(expected: T, actual: T) -> CheckResult {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(CheckFailure::new(
+            format!("{:?}", expected),
+            format!("{:?}", actual),
+        ))
+    }
+}
+
 #[derive(Debug)]
 struct CheckOutput {
     check: CheckType,
     status: CheckStatus,
+    failure: Option<CheckFailure>,
 }
 
 impl CheckOutput {
-    fn new(check: CheckType, status: CheckStatus) -> Self {
-        Self { check, status }
+    fn new(check: CheckType, result: CheckResult) -> Self {
+        match result {
+            Ok(()) => Self {
+                check,
+                status: CheckStatus::Pass,
+                failure: None,
+            },
+            Err(failure) => Self {
+                check,
+                status: CheckStatus::Fail,
+                failure: Some(failure),
+            },
+        }
     }
 }
 
 struct ValidatingVisitor {
     repo_stats_key: String,
+    repo: BlobRepo,
     inner: WalkState,
     checks_by_node_type: HashMap<NodeType, HashSet<CheckType>>,
 }
@@ -135,39 +207,216 @@ struct ValidatingVisitor {
 impl ValidatingVisitor {
     pub fn new(
         repo_stats_key: String,
+        repo: BlobRepo,
         include_node_types: HashSet<NodeType>,
         include_edge_types: HashSet<EdgeType>,
         include_checks: HashSet<CheckType>,
     ) -> Self {
+        let mut checks_by_node_type = HashMap::new();
+        for check in include_checks {
+            checks_by_node_type
+                .entry(check.node_type())
+                .or_insert_with(HashSet::new)
+                .insert(check);
+        }
         Self {
             repo_stats_key,
+            repo,
             inner: WalkState::new(include_node_types, include_edge_types),
-            checks_by_node_type: include_checks
-                .into_iter()
-                .group_by(|c| c.node_type())
-                .into_iter()
-                .map(|(key, group)| (key, HashSet::from_iter(group)))
-                .collect(),
+            checks_by_node_type,
         }
     }
 }
 
-fn check_bonsai_phase_is_public(node_data: Option<&NodeData>) -> CheckStatus {
-    match node_data {
-        Some(NodeData::BonsaiPhaseMapping(Some(Phase::Public))) => CheckStatus::Pass,
-        _ => CheckStatus::Fail,
+impl Checkpointable for ValidatingVisitor {
+    fn walk_state(&self) -> &WalkState {
+        &self.inner
     }
 }
 
-fn check_linknode_populated(outgoing: &[OutgoingEdge]) -> CheckStatus {
+fn check_bonsai_phase_is_public(node_data: Option<&NodeData>) -> CheckResult {
+    let phase = match node_data {
+        Some(NodeData::BonsaiPhaseMapping(phase)) => *phase,
+        _ => None,
+    };
+    check_eq(Some(Phase::Public), phase)
+}
+
+fn check_linknode_populated(outgoing: &[OutgoingEdge]) -> CheckResult {
     if outgoing
         .iter()
         .any(|e| e.label == EdgeType::HgLinkNodeToHgChangeset)
     {
-        CheckStatus::Pass
+        Ok(())
     } else {
-        CheckStatus::Fail
+        Err(CheckFailure::new(
+            format!("{:?}", EdgeType::HgLinkNodeToHgChangeset),
+            format!("{:?}", outgoing.iter().map(|e| e.label).collect::<Vec<_>>()),
+        ))
+    }
+}
+
+// The hg changeset a bonsai maps to should map back to the same bonsai
+fn check_bonsai_hg_mapping_round_trips(
+    ctx: CoreContext,
+    repo: &BlobRepo,
+    node: &Node,
+    node_data: Option<&NodeData>,
+) -> CheckFuture {
+    match (node, node_data) {
+        (Node::BonsaiHgMapping(bcs_id), Some(NodeData::BonsaiHgMapping(Some(hg_cs_id)))) => {
+            let expected = Some(*bcs_id);
+            repo.get_bonsai_from_hg(ctx, *hg_cs_id)
+                .compat()
+                .map_ok(move |actual| check_eq(expected, actual))
+                .boxed()
+        }
+        // Nothing mapped yet, so nothing to round trip
+        _ => future::ok(Ok(())).boxed(),
+    }
+}
+
+// The counts and sizes in an fsnode's summary should add up from its entries
+fn check_fsnode_summary_matches_entries(node_data: Option<&NodeData>) -> CheckResult {
+    let fsnode = match node_data {
+        Some(NodeData::Fsnode(fsnode)) => fsnode,
+        _ => return Ok(()),
+    };
+    let stored = fsnode.summary();
+    let mut computed = stored.clone();
+    computed.child_files_count = 0;
+    computed.child_files_total_size = 0;
+    computed.child_dirs_count = 0;
+    computed.descendant_files_count = 0;
+    computed.descendant_files_total_size = 0;
+    for (_elem, entry) in fsnode.list() {
+        match entry {
+            FsnodeEntry::File(file) => {
+                computed.child_files_count += 1;
+                computed.child_files_total_size += file.size();
+                computed.descendant_files_count += 1;
+                computed.descendant_files_total_size += file.size();
+            }
+            FsnodeEntry::Directory(dir) => {
+                let dir_summary = dir.summary();
+                computed.child_dirs_count += 1;
+                computed.descendant_files_count += dir_summary.descendant_files_count;
+                computed.descendant_files_total_size += dir_summary.descendant_files_total_size;
+            }
+        }
     }
+    check_eq(&computed, stored)
+}
+
+// The stored metadata should match the hashes recomputed from the content's bytes
+fn check_content_metadata_matches_content(
+    ctx: CoreContext,
+    repo: &BlobRepo,
+    node: &Node,
+    node_data: Option<&NodeData>,
+) -> CheckFuture {
+    match (node, node_data) {
+        (Node::FileContentMetadata(content_id), Some(NodeData::FileContentMetadata(Some(m)))) => {
+            let expected = Some(m.clone());
+            filestore::compute_metadata(repo.blobstore(), ctx, &FetchKey::Canonical(*content_id))
+                .compat()
+                .map_ok(move |actual| check_eq(expected, actual))
+                .boxed()
+        }
+        // Metadata not computed yet, so nothing to compare
+        _ => future::ok(Ok(())).boxed(),
+    }
+}
+
+// A root unode is either reused unchanged from a parent, or is new in this changeset with the
+// parents' root unodes as its parents
+fn check_unode_parents_match_bonsai(
+    ctx: CoreContext,
+    repo: &BlobRepo,
+    node: &Node,
+    node_data: Option<&NodeData>,
+) -> CheckFuture {
+    let (bcs_id, root_unode_id) = match (node, node_data) {
+        (Node::BonsaiUnodeMapping(bcs_id), Some(NodeData::BonsaiUnodeMapping(Some(id)))) => {
+            (*bcs_id, *id)
+        }
+        // Not derived yet, so nothing to compare
+        _ => return future::ok(Ok(())).boxed(),
+    };
+    let repo = repo.clone();
+    async move {
+        let bcs = bcs_id.load(ctx.clone(), repo.blobstore()).compat().await?;
+        let parents: Vec<_> = bcs.parents().collect();
+        let parent_roots = RootUnodeManifestId::mapping(&ctx, &repo)
+            .get(ctx.clone(), parents.clone())
+            .compat()
+            .await?;
+        let mut expected = vec![];
+        for parent in parents {
+            match parent_roots.get(&parent) {
+                Some(root) => expected.push(*root.manifest_unode_id()),
+                None => {
+                    return Ok(Err(CheckFailure::new(
+                        format!("unodes derived for parent {}", parent),
+                        "not derived".to_string(),
+                    )))
+                }
+            }
+        }
+        if expected.contains(&root_unode_id) {
+            return Ok(Ok(()));
+        }
+        expected.sort();
+        expected.dedup();
+
+        let unode = root_unode_id.load(ctx, repo.blobstore()).compat().await?;
+        let mut actual = unode.parents().clone();
+        actual.sort();
+        actual.dedup();
+        Ok(check_eq((bcs_id, expected), (*unode.linknode(), actual)))
+    }
+    .boxed()
+}
+
+// The size in the hg envelope should match the size of the content it points to
+fn check_hg_file_size_matches_metadata(
+    ctx: CoreContext,
+    repo: &BlobRepo,
+    node_data: Option<&NodeData>,
+) -> CheckFuture {
+    let envelope = match node_data {
+        Some(NodeData::HgFileEnvelope(envelope)) => envelope,
+        _ => return future::ok(Ok(())).boxed(),
+    };
+    let expected = Some(envelope.content_size());
+    filestore::get_metadata_readonly(
+        repo.blobstore(),
+        ctx,
+        &FetchKey::Canonical(envelope.content_id()),
+    )
+    .compat()
+    .map_ok(move |metadata| match metadata {
+        // Content exists but metadata not computed yet, so nothing to compare
+        Some(None) => Ok(()),
+        metadata => check_eq(expected, metadata.flatten().map(|m| m.total_size)),
+    })
+    .boxed()
+}
+
+// A bookmark should point at a changeset that is in the changesets table
+fn check_bookmark_target_exists(
+    ctx: CoreContext,
+    repo: &BlobRepo,
+    node_data: Option<&NodeData>,
+) -> CheckFuture {
+    let bcs_id = match node_data {
+        Some(NodeData::Bookmark(bcs_id)) => *bcs_id,
+        _ => return future::ok(Ok(())).boxed(),
+    };
+    repo.changeset_exists_by_bonsai(ctx, bcs_id)
+        .compat()
+        .map_ok(move |exists| check_eq(Some(bcs_id), if exists { Some(bcs_id) } else { None }))
+        .boxed()
 }
 
 #[derive(AddAssign, Clone, Copy, Default, Debug)]
@@ -183,7 +432,34 @@ struct CheckData {
     stats: CheckStats,
 }
 
-impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), Node> for ValidatingVisitor {
+// Checks for a node, some of which may still need to load data to complete
+struct PendingCheckData {
+    source_node: Option<Node>,
+    checks: Vec<BoxFuture<'static, Result<CheckOutput, Error>>>,
+    edges: u64,
+}
+
+impl PendingCheckData {
+    async fn resolve(self) -> Result<CheckData, Error> {
+        let checked = future::try_join_all(self.checks).await?;
+        let pass = checked
+            .iter()
+            .filter(|c| c.status == CheckStatus::Pass)
+            .count() as u64;
+        let fail = checked.len() as u64 - pass;
+        Ok(CheckData {
+            source_node: self.source_node,
+            checked,
+            stats: CheckStats {
+                pass,
+                fail,
+                edges: self.edges,
+            },
+        })
+    }
+}
+
+impl WalkVisitor<(Node, Option<PendingCheckData>, Option<StepStats>), Node> for ValidatingVisitor {
     fn start_step(
         &self,
         ctx: CoreContext,
@@ -201,7 +477,7 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), Node> for Validat
         route: Option<Node>,
         outgoing: Vec<OutgoingEdge>,
     ) -> (
-        (Node, Option<CheckData>, Option<StepStats>),
+        (Node, Option<PendingCheckData>, Option<StepStats>),
         Node,
         Vec<OutgoingEdge>,
     ) {
@@ -209,27 +485,60 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), Node> for Validat
             self.checks_by_node_type.get(&resolved.target.get_type());
         // The incoming resolved edge counts as one
         let mut num_edges: u64 = 1;
-        let mut pass = 0;
-        let mut fail = 0;
-        let checked: Vec<_> = checks_to_do
+        let node = &resolved.target;
+        let node_data_ref = node_data.as_ref();
+        let checks: Vec<_> = checks_to_do
             .map(|set| {
-                set.iter().filter_map(|check| {
+                set.iter().map(|check| {
+                    let check = *check;
                     // Lets check!
-                    let status = match check {
+                    let result = match check {
                         CheckType::BonsaiChangesetPhaseIsPublic => {
-                            check_bonsai_phase_is_public(node_data.as_ref())
+                            future::ok(check_bonsai_phase_is_public(node_data_ref)).boxed()
                         }
                         CheckType::HgLinkNodePopulated => {
                             num_edges += outgoing.len() as u64;
-                            check_linknode_populated(&outgoing)
+                            future::ok(check_linknode_populated(&outgoing)).boxed()
+                        }
+                        CheckType::BonsaiHgMappingRoundTrips => {
+                            check_bonsai_hg_mapping_round_trips(
+                                ctx.clone(),
+                                &self.repo,
+                                node,
+                                node_data_ref,
+                            )
+                        }
+                        CheckType::FsnodeSummaryMatchesEntries => {
+                            future::ok(check_fsnode_summary_matches_entries(node_data_ref)).boxed()
+                        }
+                        CheckType::ContentMetadataMatchesContent => {
+                            check_content_metadata_matches_content(
+                                ctx.clone(),
+                                &self.repo,
+                                node,
+                                node_data_ref,
+                            )
+                        }
+                        CheckType::UnodeParentsMatchBonsai => check_unode_parents_match_bonsai(
+                            ctx.clone(),
+                            &self.repo,
+                            node,
+                            node_data_ref,
+                        ),
+                        CheckType::HgFileSizeMatchesMetadata => {
+                            check_hg_file_size_matches_metadata(
+                                ctx.clone(),
+                                &self.repo,
+                                node_data_ref,
+                            )
+                        }
+                        CheckType::BookmarkTargetExists => {
+                            check_bookmark_target_exists(ctx.clone(), &self.repo, node_data_ref)
                         }
                     };
-                    if status == CheckStatus::Pass {
-                        pass += 1;
-                    } else {
-                        fail += 1;
-                    }
-                    Some(CheckOutput::new(*check, status))
+                    result
+                        .map_ok(move |result| CheckOutput::new(check, result))
+                        .boxed()
                 })
             })
             .into_iter()
@@ -252,17 +561,13 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), Node> for Validat
 
         let vout = (
             node.clone(),
-            if checked.is_empty() {
+            if checks.is_empty() {
                 None
             } else {
-                Some(CheckData {
+                Some(PendingCheckData {
                     source_node: route,
-                    checked,
-                    stats: CheckStats {
-                        pass,
-                        fail,
-                        edges: num_edges,
-                    },
+                    checks,
+                    edges: num_edges,
                 })
             },
             opt_stats,
@@ -445,13 +750,16 @@ impl ProgressRecorderUnprotected<CheckData> for ValidateProgressState {
                     // For failures log immediately
                     let mut scuba = self.scuba_builder.clone();
                     add_node_to_scuba(source_node.as_ref(), n, &mut scuba);
-                    scuba
-                        .add(CHECK_TYPE, k.stats_key())
-                        .add(
-                            CHECK_FAIL,
-                            if c.status == CheckStatus::Pass { 0 } else { 1 },
-                        )
-                        .log();
+                    scuba.add(CHECK_TYPE, k.stats_key()).add(
+                        CHECK_FAIL,
+                        if c.status == CheckStatus::Pass { 0 } else { 1 },
+                    );
+                    if let Some(failure) = &c.failure {
+                        scuba
+                            .add(CHECK_EXPECTED, failure.expected.as_str())
+                            .add(CHECK_ACTUAL, failure.actual.as_str());
+                    }
+                    scuba.log();
                     for json in scuba.get_sample().to_json() {
                         warn!(self.logger, "Validation failed: {}", json)
                     }
@@ -513,6 +821,7 @@ pub async fn validate<'a>(
 
     let stateful_visitor = Arc::new(ValidatingVisitor::new(
         repo_stats_key.clone(),
+        datasources.blobrepo.clone(),
         include_node_types,
         include_edge_types,
         include_check_types.clone(),
@@ -528,17 +837,26 @@ pub async fn validate<'a>(
         Duration::from_secs(PROGRESS_SAMPLE_DURATION_S),
     ));
 
-    cloned!(walk_params.progress_state, walk_params.quiet);
+    cloned!(
+        walk_params.progress_state,
+        walk_params.quiet,
+        walk_params.scheduled_max
+    );
     let make_sink = move |run: RepoWalkRun| {
         cloned!(run.ctx);
         validate_progress_state.set_sample_builder(run.scuba_builder);
         async move |walk_output| {
             cloned!(ctx, progress_state, validate_progress_state);
             let walk_progress = progress_stream(quiet, &progress_state.clone(), walk_output)
-                .map_ok(|(n, d, s)| {
-                    // swap stats and data round
-                    (n, s, d)
-                });
+                .map_ok(|(n, d, s)| async move {
+                    // finish any checks that load data, then swap stats and data round
+                    let d = match d {
+                        Some(d) => Some(PendingCheckData::resolve(d).await?),
+                        None => None,
+                    };
+                    Ok::<_, Error>((n, s, d))
+                })
+                .try_buffered(scheduled_max);
 
             let validate_progress =
                 progress_stream(quiet, &validate_progress_state.clone(), walk_progress);
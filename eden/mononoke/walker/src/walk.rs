@@ -10,13 +10,16 @@ use crate::validate::{add_node_to_scuba, CHECK_FAIL, CHECK_TYPE, EDGE_TYPE};
 
 use anyhow::{format_err, Context, Error};
 use blobrepo::BlobRepo;
-use blobstore::Loadable;
+use blobstore::{Blobstore, Loadable, LoadableError};
 use bookmarks::{BookmarkName, BookmarkPrefix, Freshness};
 use bounded_traversal::bounded_traversal_stream;
+use changeset_info::ChangesetInfo;
 use cloned::cloned;
 use context::CoreContext;
+use deleted_files_manifest::RootDeletedManifestId;
 use derived_data::BonsaiDerived;
 use derived_data_filenodes::FilenodesOnlyPublic;
+use fastlog::fetch_fastlog_batch_by_unode_id;
 use filestore::{self, Alias};
 use fsnodes::RootFsnodeId;
 use futures::{
@@ -28,10 +31,14 @@ use futures::{
 use futures_ext::{FutureExt as Future01Ext, StreamExt as Stream01Ext};
 use futures_old::{future as old_future, Future as Future01, Stream as Stream01};
 use itertools::{Either, Itertools};
+use manifest::Entry;
 use mercurial_types::{
     FileBytes, HgChangesetId, HgEntryId, HgFileNodeId, HgManifest, HgManifestId, RepoPath,
 };
-use mononoke_types::{fsnode::FsnodeEntry, ChangesetId, ContentId, FsnodeId, MPath};
+use mononoke_types::{
+    fastlog_batch::FastlogBatch, fsnode::FsnodeEntry, unode::UnodeEntry, BlameId, ChangesetId,
+    ContentId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId, MPath, ManifestUnodeId,
+};
 use phases::{HeadsFetcher, Phase, Phases};
 use scuba_ext::ScubaSampleBuilder;
 use slog::warn;
@@ -41,6 +48,7 @@ use std::{
     sync::Arc,
 };
 use thiserror::Error;
+use unodes::RootUnodeManifestId;
 
 // Holds type of edge and target Node that we want to load in next step(s)
 // Combined with current node, this forms an complegte edge.
@@ -239,6 +247,18 @@ async fn bonsai_changeset_step(
         EdgeType::BonsaiChangesetToBonsaiFsnodeMapping,
         Node::BonsaiFsnodeMapping(*bcs_id),
     ));
+    recurse.push(OutgoingEdge::new(
+        EdgeType::BonsaiChangesetToChangesetInfo,
+        Node::ChangesetInfo(*bcs_id),
+    ));
+    recurse.push(OutgoingEdge::new(
+        EdgeType::BonsaiChangesetToBonsaiUnodeMapping,
+        Node::BonsaiUnodeMapping(*bcs_id),
+    ));
+    recurse.push(OutgoingEdge::new(
+        EdgeType::BonsaiChangesetToBonsaiDeletedManifestMapping,
+        Node::BonsaiDeletedManifestMapping(*bcs_id),
+    ));
     Ok(StepOutput(NodeData::BonsaiChangeset(bcs), recurse))
 }
 
@@ -629,6 +649,261 @@ async fn fsnode_step(
     Ok(StepOutput(NodeData::Fsnode(fsnode), edges))
 }
 
+async fn changeset_info_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bcs_id: &ChangesetId,
+    enable_derive: bool,
+) -> Result<StepOutput, Error> {
+    let is_derived = ChangesetInfo::is_derived(&ctx, &repo, &bcs_id).await?;
+
+    if is_derived || enable_derive {
+        let info = ChangesetInfo::derive(ctx.clone(), repo.clone(), *bcs_id)
+            .map_err(Error::from)
+            .compat()
+            .await?;
+
+        let edges = info
+            .parents()
+            .map(|parent_id| {
+                OutgoingEdge::new(
+                    EdgeType::ChangesetInfoToChangesetInfoParent,
+                    Node::ChangesetInfo(parent_id),
+                )
+            })
+            .collect();
+        Ok(StepOutput(NodeData::ChangesetInfo(Some(info)), edges))
+    } else {
+        Ok(StepOutput(NodeData::ChangesetInfo(None), vec![]))
+    }
+}
+
+async fn bonsai_to_unode_mapping_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bcs_id: &ChangesetId,
+    enable_derive: bool,
+) -> Result<StepOutput, Error> {
+    let is_derived = RootUnodeManifestId::is_derived(&ctx, &repo, &bcs_id).await?;
+
+    if is_derived || enable_derive {
+        let root_unode_id = RootUnodeManifestId::derive(ctx.clone(), repo.clone(), *bcs_id)
+            .map_err(Error::from)
+            .compat()
+            .await?;
+
+        Ok(StepOutput(
+            NodeData::BonsaiUnodeMapping(Some(*root_unode_id.manifest_unode_id())),
+            vec![OutgoingEdge::new(
+                EdgeType::BonsaiToRootUnodeManifest,
+                Node::UnodeManifest((WrappedPath::Root, *root_unode_id.manifest_unode_id())),
+            )],
+        ))
+    } else {
+        Ok(StepOutput(NodeData::BonsaiUnodeMapping(None), vec![]))
+    }
+}
+
+async fn unode_manifest_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    path: WrappedPath,
+    unode_id: &ManifestUnodeId,
+) -> Result<StepOutput, Error> {
+    let unode = unode_id
+        .load(ctx.clone(), &repo.get_blobstore())
+        .map_err(Error::from)
+        .compat()
+        .await?;
+
+    let mut edges = vec![];
+    for parent in unode.parents() {
+        edges.push(OutgoingEdge::new(
+            EdgeType::UnodeManifestToUnodeManifestParent,
+            Node::UnodeManifest((path.clone(), *parent)),
+        ));
+    }
+    edges.push(OutgoingEdge::new(
+        EdgeType::UnodeManifestToLinkedBonsaiChangeset,
+        Node::BonsaiChangeset(*unode.linknode()),
+    ));
+    edges.push(OutgoingEdge::new(
+        EdgeType::UnodeManifestToFastlogDir,
+        Node::FastlogDir((path.clone(), *unode_id)),
+    ));
+    for (child, unode_entry) in unode.list() {
+        let mpath_opt = WrappedPath::from(MPath::join_element_opt(path.as_ref(), Some(child)));
+        match unode_entry {
+            UnodeEntry::Directory(id) => edges.push(OutgoingEdge::new(
+                EdgeType::UnodeManifestToChildUnodeManifest,
+                Node::UnodeManifest((mpath_opt, *id)),
+            )),
+            UnodeEntry::File(id) => edges.push(OutgoingEdge::new(
+                EdgeType::UnodeManifestToUnodeFile,
+                Node::UnodeFile((mpath_opt, *id)),
+            )),
+        }
+    }
+
+    Ok(StepOutput(NodeData::UnodeManifest(unode), edges))
+}
+
+async fn unode_file_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    path: WrappedPath,
+    unode_id: &FileUnodeId,
+) -> Result<StepOutput, Error> {
+    let unode = unode_id
+        .load(ctx.clone(), &repo.get_blobstore())
+        .map_err(Error::from)
+        .compat()
+        .await?;
+
+    let mut edges = vec![];
+    for parent in unode.parents() {
+        edges.push(OutgoingEdge::new(
+            EdgeType::UnodeFileToUnodeFileParent,
+            Node::UnodeFile((path.clone(), *parent)),
+        ));
+    }
+    edges.push(OutgoingEdge::new(
+        EdgeType::UnodeFileToLinkedBonsaiChangeset,
+        Node::BonsaiChangeset(*unode.linknode()),
+    ));
+    edges.push(OutgoingEdge::new(
+        EdgeType::UnodeFileToFileContent,
+        Node::FileContent(*unode.content_id()),
+    ));
+    edges.push(OutgoingEdge::new(
+        EdgeType::UnodeFileToBlame,
+        Node::Blame(BlameId::from(*unode_id)),
+    ));
+    edges.push(OutgoingEdge::new(
+        EdgeType::UnodeFileToFastlogFile,
+        Node::FastlogFile((path, *unode_id)),
+    ));
+
+    Ok(StepOutput(NodeData::UnodeFile(unode), edges))
+}
+
+// Blame is derived on demand, so not finding it is not an error
+async fn blame_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    blame_id: &BlameId,
+) -> Result<StepOutput, Error> {
+    match blame_id.load(ctx.clone(), repo.blobstore()).compat().await {
+        Ok(blame) => Ok(StepOutput(NodeData::Blame(Some(blame)), vec![])),
+        Err(LoadableError::Missing(_)) => Ok(StepOutput(NodeData::Blame(None), vec![])),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn fastlog_batch_edges(edge_type: EdgeType, batch: Option<&FastlogBatch>) -> Vec<OutgoingEdge> {
+    batch
+        .into_iter()
+        .flat_map(|batch| batch.previous_batches().iter())
+        .map(|id| OutgoingEdge::new(edge_type, Node::FastlogBatch(*id)))
+        .collect()
+}
+
+// Fastlog is derived on demand, so not finding it is not an error
+async fn fastlog_dir_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    unode_id: &ManifestUnodeId,
+) -> Result<StepOutput, Error> {
+    let blobstore: Arc<dyn Blobstore> = Arc::new(repo.get_blobstore());
+    let batch = fetch_fastlog_batch_by_unode_id(ctx, &blobstore, Entry::Tree(*unode_id)).await?;
+    let edges = fastlog_batch_edges(EdgeType::FastlogDirToPreviousBatch, batch.as_ref());
+    Ok(StepOutput(NodeData::FastlogDir(batch), edges))
+}
+
+async fn fastlog_file_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    unode_id: &FileUnodeId,
+) -> Result<StepOutput, Error> {
+    let blobstore: Arc<dyn Blobstore> = Arc::new(repo.get_blobstore());
+    let batch = fetch_fastlog_batch_by_unode_id(ctx, &blobstore, Entry::Leaf(*unode_id)).await?;
+    let edges = fastlog_batch_edges(EdgeType::FastlogFileToPreviousBatch, batch.as_ref());
+    Ok(StepOutput(NodeData::FastlogFile(batch), edges))
+}
+
+async fn fastlog_batch_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: &FastlogBatchId,
+) -> Result<StepOutput, Error> {
+    let batch = id
+        .load(ctx.clone(), repo.blobstore())
+        .map_err(Error::from)
+        .compat()
+        .await?;
+    let edges = fastlog_batch_edges(EdgeType::FastlogBatchToPreviousBatch, Some(&batch));
+    Ok(StepOutput(NodeData::FastlogBatch(batch), edges))
+}
+
+async fn bonsai_to_deleted_manifest_mapping_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    bcs_id: &ChangesetId,
+    enable_derive: bool,
+) -> Result<StepOutput, Error> {
+    let is_derived = RootDeletedManifestId::is_derived(&ctx, &repo, &bcs_id).await?;
+
+    if is_derived || enable_derive {
+        let root_id = RootDeletedManifestId::derive(ctx.clone(), repo.clone(), *bcs_id)
+            .map_err(Error::from)
+            .compat()
+            .await?;
+
+        Ok(StepOutput(
+            NodeData::BonsaiDeletedManifestMapping(Some(*root_id.deleted_manifest_id())),
+            vec![OutgoingEdge::new(
+                EdgeType::BonsaiToRootDeletedManifest,
+                Node::DeletedManifest((WrappedPath::Root, *root_id.deleted_manifest_id())),
+            )],
+        ))
+    } else {
+        Ok(StepOutput(
+            NodeData::BonsaiDeletedManifestMapping(None),
+            vec![],
+        ))
+    }
+}
+
+async fn deleted_manifest_step(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    path: WrappedPath,
+    id: &DeletedManifestId,
+) -> Result<StepOutput, Error> {
+    let manifest = id
+        .load(ctx.clone(), &repo.get_blobstore())
+        .map_err(Error::from)
+        .compat()
+        .await?;
+
+    let mut edges = vec![];
+    if let Some(linknode) = manifest.linknode() {
+        edges.push(OutgoingEdge::new(
+            EdgeType::DeletedManifestToLinkedBonsaiChangeset,
+            Node::BonsaiChangeset(*linknode),
+        ));
+    }
+    for (child, id) in manifest.list() {
+        let mpath_opt = WrappedPath::from(MPath::join_element_opt(path.as_ref(), Some(child)));
+        edges.push(OutgoingEdge::new(
+            EdgeType::DeletedManifestToDeletedManifestChild,
+            Node::DeletedManifest((mpath_opt, *id)),
+        ));
+    }
+
+    Ok(StepOutput(NodeData::DeletedManifest(manifest), edges))
+}
+
 /// Expand nodes where check for a type is used as a check for other types.
 /// e.g. to make sure metadata looked up/considered for files.
 pub fn expand_checked_nodes(children: &mut Vec<OutgoingEdge>) -> () {
@@ -830,6 +1105,24 @@ where
             bonsai_to_fsnode_mapping_step(&ctx, &repo, &cs_id, enable_derive).await
         }
         Node::Fsnode((path, fsnode_id)) => fsnode_step(&ctx, &repo, path, &fsnode_id).await,
+        Node::ChangesetInfo(bcs_id) => {
+            changeset_info_step(&ctx, &repo, &bcs_id, enable_derive).await
+        }
+        Node::BonsaiUnodeMapping(bcs_id) => {
+            bonsai_to_unode_mapping_step(&ctx, &repo, &bcs_id, enable_derive).await
+        }
+        Node::UnodeManifest((path, unode_id)) => {
+            unode_manifest_step(&ctx, &repo, path, &unode_id).await
+        }
+        Node::UnodeFile((path, unode_id)) => unode_file_step(&ctx, &repo, path, &unode_id).await,
+        Node::Blame(blame_id) => blame_step(&ctx, &repo, &blame_id).await,
+        Node::FastlogDir((_path, unode_id)) => fastlog_dir_step(&ctx, &repo, &unode_id).await,
+        Node::FastlogFile((_path, unode_id)) => fastlog_file_step(&ctx, &repo, &unode_id).await,
+        Node::FastlogBatch(id) => fastlog_batch_step(&ctx, &repo, &id).await,
+        Node::BonsaiDeletedManifestMapping(bcs_id) => {
+            bonsai_to_deleted_manifest_mapping_step(&ctx, &repo, &bcs_id, enable_derive).await
+        }
+        Node::DeletedManifest((path, id)) => deleted_manifest_step(&ctx, &repo, path, &id).await,
     };
 
     let edge_label = walk_item.label;